@@ -0,0 +1,128 @@
+use crate::fonts::FontSet;
+use crate::{ImageCombinerError, TextCardStyle};
+use image::{ImageBuffer, Rgba};
+use rusttype::{point, Scale};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ArcDirection {
+    //reads left to right over the top of the circle, glyph tops point outward
+    Clockwise,
+    //reads left to right along the bottom of the circle, glyph tops point to the center
+    CounterClockwise,
+}
+
+//the circle the baseline follows, angles are degrees clockwise from the top
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ArcText {
+    radius: f32,
+    center_angle: f32,
+    direction: ArcDirection,
+}
+
+impl ArcText {
+    pub fn new(radius: f32, center_angle: f32, direction: ArcDirection) -> Self {
+        Self {
+            radius,
+            center_angle,
+            direction,
+        }
+    }
+}
+
+//text along a circle on a transparent square overlay with the circle centered in it,
+//every glyph is rotated to the tangent and sampled bilinearly so its edges stay anti-aliased
+pub fn render_arc_text(
+    text: &str,
+    style: &TextCardStyle,
+    arc: ArcText,
+) -> Result<ImageBuffer<Rgba<u8>, Vec<u8>>, ImageCombinerError> {
+    if !(arc.radius.is_finite() && arc.radius > 0.0) {
+        return Err(ImageCombinerError::InvalidOptions(format!(
+            "arc text radius must be positive radius:{}",
+            arc.radius
+        )));
+    }
+    let fonts = FontSet::new(style)?;
+    let font_size = style.font_size.unwrap_or(arc.radius * 0.25).max(1.0);
+    let scale = Scale::uniform(font_size);
+    let glyphs = fonts.glyphs(text, scale);
+    //glyphs reach at most a font size past the baseline on either side of the circle
+    let size = ((arc.radius + font_size) * 2.0).ceil() as u32 + 2;
+    let center = size as f32 * 0.5;
+    let mut overlay = ImageBuffer::from_pixel(size, size, Rgba([0, 0, 0, 0]));
+    let sign = match arc.direction {
+        ArcDirection::Clockwise => 1.0,
+        ArcDirection::CounterClockwise => -1.0,
+    };
+    let advances: Vec<f32> = glyphs
+        .iter()
+        .map(|glyph| glyph.h_metrics().advance_width)
+        .collect();
+    let span = advances.iter().sum::<f32>() / arc.radius;
+    let mut along = 0.0;
+    let mut coverage = vec![0f32; (size * size) as usize];
+    for (glyph, advance) in glyphs.into_iter().zip(advances) {
+        let theta = arc.center_angle.to_radians()
+            + sign * ((along + advance * 0.5) / arc.radius - span * 0.5);
+        along += advance;
+        let glyph = glyph.positioned(point(0.0, 0.0));
+        let Some(bounds) = glyph.pixel_bounding_box() else {
+            continue;
+        };
+        let (glyph_width, glyph_height) = (bounds.width() as usize, bounds.height() as usize);
+        let mut bitmap = vec![0f32; glyph_width * glyph_height];
+        glyph.draw(|x, y, v| bitmap[y as usize * glyph_width + x as usize] = v);
+        let sample = |x: f32, y: f32| -> f32 {
+            let (x0, y0) = (x.floor(), y.floor());
+            let (fx, fy) = (x - x0, y - y0);
+            let at = |x: f32, y: f32| {
+                if x < 0.0 || y < 0.0 || x >= glyph_width as f32 || y >= glyph_height as f32 {
+                    return 0.0;
+                }
+                bitmap[y as usize * glyph_width + x as usize]
+            };
+            at(x0, y0) * (1.0 - fx) * (1.0 - fy)
+                + at(x0 + 1.0, y0) * fx * (1.0 - fy)
+                + at(x0, y0 + 1.0) * (1.0 - fx) * fy
+                + at(x0 + 1.0, y0 + 1.0) * fx * fy
+        };
+        //baseline point, the tangent the glyph advances along and the direction below its baseline
+        let (sin, cos) = theta.sin_cos();
+        let origin = (center + arc.radius * sin, center - arc.radius * cos);
+        let tangent = (sign * cos, sign * sin);
+        let down = (-sign * sin, sign * cos);
+        let reach = (glyph_width + glyph_height) as f32 + advance;
+        let (min_x, max_x) = (
+            (origin.0 - reach).max(0.0) as u32,
+            ((origin.0 + reach).ceil() as u32).min(size),
+        );
+        let (min_y, max_y) = (
+            (origin.1 - reach).max(0.0) as u32,
+            ((origin.1 + reach).ceil() as u32).min(size),
+        );
+        for y in min_y..max_y {
+            for x in min_x..max_x {
+                let relative = (x as f32 + 0.5 - origin.0, y as f32 + 0.5 - origin.1);
+                let u = relative.0 * tangent.0 + relative.1 * tangent.1;
+                let v = relative.0 * down.0 + relative.1 * down.1;
+                let value = sample(
+                    u + advance * 0.5 - bounds.min.x as f32 - 0.5,
+                    v - bounds.min.y as f32 - 0.5,
+                );
+                if value > 0.0 {
+                    let cell = &mut coverage[(y * size + x) as usize];
+                    *cell += value * (1.0 - *cell);
+                }
+            }
+        }
+    }
+    let color = style.color;
+    for (x, y, pixel) in overlay.enumerate_pixels_mut() {
+        let value = coverage[(y * size + x) as usize];
+        if value > 0.0 {
+            let alpha = (color[3] as f32 * value.min(1.0)).round() as u8;
+            *pixel = Rgba([color[0], color[1], color[2], alpha]);
+        }
+    }
+    Ok(overlay)
+}
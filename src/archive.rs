@@ -0,0 +1,232 @@
+use crate::{
+    create_bundled_image_from_bytes, probe_dimensions, render_bundle_with_placements,
+    CreateBundledImageOptions, ImageCombinerError, ImageSource, IntoImageSource, TilePlacement,
+};
+use log::debug;
+use std::cmp::Ordering;
+use std::collections::VecDeque;
+use std::io::{Cursor, Read, Seek, Write};
+
+pub async fn create_bundled_image_from_zip(
+    zip_bytes: Vec<u8>,
+    options: CreateBundledImageOptions,
+) -> Result<Vec<u8>, ImageCombinerError> {
+    let source = ZipImageSource::from_reader(Cursor::new(zip_bytes))?;
+    create_bundled_image_from_bytes(source, options).await
+}
+
+//the bundle, its thumbnails and sidecars describing the layout in one zip:
+//bundle.{ext}, bundle-w{width}.{ext}, layout.json, alt.txt and sprites.css
+pub async fn create_bundled_image_archive(
+    buffers: impl IntoImageSource,
+    options: CreateBundledImageOptions,
+) -> Result<Vec<u8>, ImageCombinerError> {
+    let (bundled, placements) = render_bundle_with_placements(buffers, &options).await?;
+    let extension = options.output_format.extension();
+    let image_name = format!("bundle.{}", extension);
+    let (width, height, _) = probe_dimensions(&bundled.bytes)?;
+    let mut files = vec![(image_name.clone(), bundled.bytes.clone())];
+    for (thumbnail_width, bytes) in &bundled.thumbnails {
+        files.push((
+            format!("bundle-w{}.{}", thumbnail_width, extension),
+            bytes.clone(),
+        ));
+    }
+    files.push((
+        "layout.json".to_string(),
+        layout_json(&image_name, (width, height), &placements, &bundled.skipped).into_bytes(),
+    ));
+    files.push((
+        "alt.txt".to_string(),
+        alt_text(&placements, &bundled.skipped).into_bytes(),
+    ));
+    files.push((
+        "sprites.css".to_string(),
+        sprite_css(&image_name, &placements).into_bytes(),
+    ));
+    let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    for (name, bytes) in files {
+        debug!("write zip entry {}", name);
+        writer
+            .start_file(name, zip::write::SimpleFileOptions::default())
+            .map_err(zip_error)?;
+        writer.write_all(&bytes)?;
+    }
+    Ok(writer.finish().map_err(zip_error)?.into_inner())
+}
+
+fn layout_json(
+    image_name: &str,
+    dimension: (u32, u32),
+    placements: &[(usize, TilePlacement)],
+    skipped: &[usize],
+) -> String {
+    let tiles: Vec<String> = placements
+        .iter()
+        .enumerate()
+        .map(|(i, (index, tile))| {
+            format!(
+                "{{\"index\":{},\"position\":{},\"row\":{},\"column\":{},\"x\":{},\"y\":{},\"width\":{},\"height\":{}}}",
+                index, i, tile.row, tile.column, tile.x, tile.y, tile.width, tile.height
+            )
+        })
+        .collect();
+    let skipped: Vec<String> = skipped.iter().map(|i| i.to_string()).collect();
+    format!(
+        "{{\"image\":\"{}\",\"width\":{},\"height\":{},\"tiles\":[{}],\"skipped\":[{}]}}",
+        image_name,
+        dimension.0,
+        dimension.1,
+        tiles.join(","),
+        skipped.join(",")
+    )
+}
+
+//images are numbered by input, a placeholder keeps its number and is described as such
+fn alt_text(placements: &[(usize, TilePlacement)], skipped: &[usize]) -> String {
+    let rows = placements
+        .iter()
+        .map(|(_, tile)| tile.row + 1)
+        .max()
+        .unwrap_or(0);
+    let columns = placements
+        .iter()
+        .map(|(_, tile)| tile.column + 1)
+        .max()
+        .unwrap_or(0);
+    let mut text = format!(
+        "{} images arranged in {} rows and {} columns.\n",
+        placements
+            .iter()
+            .filter(|(index, _)| !skipped.contains(index))
+            .count(),
+        rows,
+        columns
+    );
+    for (index, tile) in placements {
+        let placeholder = if skipped.contains(index) {
+            ", failed to load"
+        } else {
+            ""
+        };
+        text.push_str(&format!(
+            "Image {}: row {}, column {}{}.\n",
+            index + 1,
+            tile.row + 1,
+            tile.column + 1,
+            placeholder
+        ));
+    }
+    text
+}
+
+fn sprite_css(image_name: &str, placements: &[(usize, TilePlacement)]) -> String {
+    let mut css = format!(
+        ".bundle-tile {{\n  background-image: url(\"{}\");\n  background-repeat: no-repeat;\n}}\n",
+        image_name
+    );
+    for (index, tile) in placements {
+        css.push_str(&format!(
+            ".bundle-tile-{} {{\n  width: {}px;\n  height: {}px;\n  background-position: -{}px -{}px;\n}}\n",
+            index + 1,
+            tile.width,
+            tile.height,
+            tile.x,
+            tile.y
+        ));
+    }
+    css
+}
+
+pub struct ZipImageSource {
+    entries: VecDeque<Vec<u8>>,
+}
+
+impl ZipImageSource {
+    pub fn from_reader<R: Read + Seek>(reader: R) -> Result<Self, ImageCombinerError> {
+        let mut archive = zip::ZipArchive::new(reader).map_err(zip_error)?;
+        let mut names: Vec<(usize, String)> = Vec::new();
+        for i in 0..archive.len() {
+            let entry = archive.by_index(i).map_err(zip_error)?;
+            if entry.is_dir() || !is_image_entry(entry.name()) {
+                debug!("skip zip entry {}", entry.name());
+                continue;
+            }
+            names.push((i, entry.name().to_string()));
+        }
+        names.sort_by(|a, b| natural_cmp(&a.1, &b.1));
+        let mut entries = VecDeque::new();
+        for (i, name) in names {
+            debug!("extract zip entry {}", name);
+            let mut entry = archive.by_index(i).map_err(zip_error)?;
+            let mut bytes = Vec::new();
+            entry.read_to_end(&mut bytes)?;
+            entries.push_back(bytes);
+        }
+        Ok(Self { entries })
+    }
+}
+
+impl ImageSource for ZipImageSource {
+    async fn next_image(&mut self) -> Option<Result<Vec<u8>, ImageCombinerError>> {
+        self.entries.pop_front().map(Ok)
+    }
+}
+
+fn zip_error(e: zip::result::ZipError) -> ImageCombinerError {
+    ImageCombinerError::InvalidArchive(e.to_string())
+}
+
+fn is_image_entry(name: &str) -> bool {
+    let file_name = name.rsplit('/').next().unwrap_or(name);
+    //skip macOS resource forks and other hidden files
+    if name.starts_with("__MACOSX/") || file_name.starts_with('.') {
+        return false;
+    }
+    image::ImageFormat::from_path(file_name).is_ok()
+}
+
+//compare names so that "item_2.jpeg" sorts before "item_10.jpeg"
+fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let mut a_chars = a.chars().peekable();
+    let mut b_chars = b.chars().peekable();
+    loop {
+        match (a_chars.peek().copied(), b_chars.peek().copied()) {
+            (None, None) => return Ordering::Equal,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(x), Some(y)) if x.is_ascii_digit() && y.is_ascii_digit() => {
+                let x_number = take_number(&mut a_chars);
+                let y_number = take_number(&mut b_chars);
+                let ordering = x_number
+                    .len()
+                    .cmp(&y_number.len())
+                    .then_with(|| x_number.cmp(&y_number));
+                if ordering != Ordering::Equal {
+                    return ordering;
+                }
+            }
+            (Some(x), Some(y)) => {
+                if x != y {
+                    return x.cmp(&y);
+                }
+                a_chars.next();
+                b_chars.next();
+            }
+        }
+    }
+}
+
+fn take_number(chars: &mut std::iter::Peekable<std::str::Chars<'_>>) -> String {
+    let mut number = String::new();
+    while let Some(c) = chars.peek().copied().filter(char::is_ascii_digit) {
+        number.push(c);
+        chars.next();
+    }
+    let trimmed = number.trim_start_matches('0');
+    if trimmed.is_empty() {
+        "0".to_string()
+    } else {
+        trimmed.to_string()
+    }
+}
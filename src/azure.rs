@@ -0,0 +1,110 @@
+use crate::storage::encode_key;
+use crate::{ImageCombinerError, Storage, UploadOptions};
+use log::debug;
+
+//blobs in one container, sas is a shared access signature query string with read and write permissions
+#[derive(Clone)]
+pub struct AzureBlobStorage {
+    client: reqwest::Client,
+    account: String,
+    container: String,
+    sas: String,
+}
+
+impl AzureBlobStorage {
+    pub fn new(account: String, container: String, sas: String) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            account,
+            container,
+            sas: sas.trim_start_matches('?').to_string(),
+        }
+    }
+
+    pub fn with_client(mut self, client: reqwest::Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    fn blob_url(&self, key: &str) -> String {
+        //slashes stay as they are so keys keep their virtual directories
+        let blob = key.split('/').map(encode_key).collect::<Vec<_>>().join("/");
+        format!(
+            "https://{}.blob.core.windows.net/{}/{}?{}",
+            self.account, self.container, blob, self.sas
+        )
+    }
+}
+
+impl Storage for AzureBlobStorage {
+    async fn get(&self, key: &str) -> Result<Vec<u8>, ImageCombinerError> {
+        let res = self
+            .client
+            .get(self.blob_url(key))
+            .send()
+            .await
+            .and_then(|res| res.error_for_status())
+            .map_err(|e| ImageCombinerError::Fetch(e.to_string()))?;
+        let bytes = res
+            .bytes()
+            .await
+            .map_err(|e| ImageCombinerError::Fetch(e.to_string()))?;
+        Ok(bytes.to_vec())
+    }
+
+    async fn put(
+        &self,
+        key: &str,
+        bytes: Vec<u8>,
+        options: &UploadOptions,
+    ) -> Result<(), ImageCombinerError> {
+        debug!("put azure {}/{}/{}", self.account, self.container, key);
+        let mut request = self
+            .client
+            .put(self.blob_url(key))
+            .header("x-ms-blob-type", "BlockBlob");
+        if let Some(content_type) = &options.content_type {
+            request = request.header("x-ms-blob-content-type", content_type);
+        }
+        if let Some(cache_control) = &options.cache_control {
+            request = request.header("x-ms-blob-cache-control", cache_control);
+        }
+        for (name, value) in options.metadata.iter() {
+            request = request.header(format!("x-ms-meta-{}", name), value);
+        }
+        request
+            .body(bytes)
+            .send()
+            .await
+            .and_then(|res| res.error_for_status())
+            .map_err(|e| ImageCombinerError::Upload(e.to_string()))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_blob_url() {
+        let storage = AzureBlobStorage::new(
+            "account".to_string(),
+            "images".to_string(),
+            "?sv=2022&sig=abc".to_string(),
+        );
+        assert_eq!(
+            storage.blob_url("bundles/a b.jpg"),
+            "https://account.blob.core.windows.net/images/bundles/a%20b.jpg?sv=2022&sig=abc"
+        );
+        assert_eq!(
+            storage.blob_url("写真/#1.png"),
+            "https://account.blob.core.windows.net/images/%E5%86%99%E7%9C%9F/%231.png?sv=2022&sig=abc"
+        );
+    }
+
+    #[test]
+    fn test_encode_key() {
+        assert_eq!(encode_key("a/b?c"), "a%2Fb%3Fc");
+    }
+}
@@ -0,0 +1,205 @@
+use crate::cache::CacheKey;
+use crate::{
+    decode, draw_table, fit_logo, render_text_card, Anchor, CreateBundledImageOptions,
+    DecodeOptions, Fit, ImageCombinerError, OverlayPosition, TableBase, TextCardStyle,
+};
+use image::{GenericImage, GenericImageView, ImageBuffer, Rgba};
+use log::debug;
+use rusttype::Font;
+use std::sync::Arc;
+
+//draws into a canvas the size of the band, already filled with its background
+pub type BandDraw = Arc<
+    dyn Fn(&mut ImageBuffer<Rgba<u8>, Vec<u8>>) -> Result<(), ImageCombinerError> + Send + Sync,
+>;
+
+//what fills a header or footer band
+#[derive(Clone)]
+pub enum BandContent {
+    //placed by the band position and scaled down to fit inside the band, never enlarged
+    Logo(Vec<u8>),
+    //wrapped and centered, half the band height when the style has no font size
+    Text(String, TextCardStyle),
+    //font bytes of the table text, drawn in the theme colors and shrunk to fit the band
+    Table(TableBase, Vec<u8>),
+    Custom(BandDraw),
+}
+
+//a strip across the whole canvas above or below the images
+#[derive(Clone)]
+pub struct Band {
+    height: u32,
+    content: BandContent,
+    background: Option<Rgba<u8>>,
+    position: Option<OverlayPosition>,
+}
+
+impl Band {
+    pub fn new(height: u32, content: BandContent) -> Self {
+        Self {
+            height,
+            content,
+            background: None,
+            position: None,
+        }
+    }
+
+    //the canvas background by default
+    pub fn with_background(mut self, background: Rgba<u8>) -> Self {
+        self.background = Some(background);
+        self
+    }
+
+    //where a logo sits in the band, centered by default
+    pub fn with_position(mut self, position: OverlayPosition) -> Self {
+        self.position = Some(position);
+        self
+    }
+
+    pub(crate) fn fingerprint(&self) -> String {
+        let content = match &self.content {
+            BandContent::Logo(_) => "logo".to_string(),
+            BandContent::Text(text, style) => {
+                format!("text {:?} {:?} {}", style.color, style.font_size, text)
+            }
+            BandContent::Table(table, _) => format!(
+                "table {:?} {:?} {} {:?} {:?} {:?} {:?} {:?} {:?}",
+                table.head,
+                table.body,
+                table.border_width,
+                table.style,
+                table.alignments,
+                table.widths,
+                table.spans,
+                table.title,
+                table.footnote
+            ),
+            //a closure can only be told apart by its address, so it never hits across runs
+            BandContent::Custom(draw) => format!("custom {:p}", Arc::as_ptr(draw)),
+        };
+        format!(
+            "{}|{:?}|{:?}|{}",
+            self.height, self.background, self.position, content
+        )
+    }
+
+    pub(crate) fn hash_content(&self, key: &mut CacheKey) {
+        match &self.content {
+            BandContent::Logo(buf) => key.update(buf),
+            BandContent::Text(_, style) => style.hash_style(key),
+            BandContent::Table(_, font_bytes) => key.update(font_bytes),
+            BandContent::Custom(_) => {}
+        }
+    }
+
+    fn render(
+        &self,
+        width: u32,
+        options: &CreateBundledImageOptions,
+    ) -> Result<ImageBuffer<Rgba<u8>, Vec<u8>>, ImageCombinerError> {
+        let background = self.background.unwrap_or(options.background);
+        let mut canvas = ImageBuffer::from_pixel(width, self.height, background);
+        let inset = self.height / 10;
+        match &self.content {
+            BandContent::Logo(buf) => {
+                let logo = decode(buf, &DecodeOptions::new(options.exif_orientation))?;
+                let logo = fit_logo(
+                    logo,
+                    width.saturating_sub(inset * 2).max(1),
+                    self.height.saturating_sub(inset * 2).max(1),
+                    Fit::Contain,
+                );
+                let position = self
+                    .position
+                    .unwrap_or_else(|| OverlayPosition::inset(Anchor::Center, inset));
+                let (x, y) = position.place((width, self.height), logo.dimensions());
+                canvas.copy_from(&logo.to_rgba8(), x, y)?;
+            }
+            BandContent::Text(text, style) => {
+                let mut style = style.clone();
+                style.font_size = style.font_size.or(Some(self.height as f32 * 0.5));
+                let card = render_text_card(
+                    text,
+                    style,
+                    background,
+                    width,
+                    self.height,
+                    options.antialias(),
+                )?;
+                canvas.copy_from(&card.to_rgba8(), 0, 0)?;
+            }
+            BandContent::Table(table_base, font_bytes) => {
+                let font = Font::try_from_vec(font_bytes.clone()).ok_or_else(|| {
+                    ImageCombinerError::InvalidText("invalid font bytes".to_string())
+                })?;
+                //the size a table above the images gets, smaller until it fits the band
+                let padding = width as f32 * 0.05;
+                let mut font_size = (width as f32 - padding * 2.0) * 0.03;
+                loop {
+                    let table = table_base.clone().build(
+                        font_size * 0.75,
+                        font_size * 0.25,
+                        font_size,
+                        Some(width as f32 - padding * 2.0),
+                    );
+                    let table_height = table.outer_height() + table.border_width as f32;
+                    if table.table_width() <= width as f32 && table_height <= self.height as f32 {
+                        let top = (self.height as f32 - table_height) * 0.5;
+                        draw_table(
+                            &mut canvas,
+                            &table,
+                            &font,
+                            top,
+                            options.theme.text,
+                            options.theme.border,
+                            options.antialias(),
+                        );
+                        break;
+                    }
+                    font_size *= 0.9;
+                    if font_size < 4.0 {
+                        return Err(ImageCombinerError::InvalidTable(format!(
+                            "table does not fit the band width:{},height:{}",
+                            width, self.height
+                        )));
+                    }
+                }
+                debug!("band table font size is {}", font_size);
+            }
+            BandContent::Custom(draw) => draw(&mut canvas)?,
+        }
+        Ok(canvas)
+    }
+}
+
+//height of the header band, 0 without one
+pub(crate) fn header_height(options: &CreateBundledImageOptions) -> u32 {
+    options.header.as_ref().map_or(0, |band| band.height)
+}
+
+pub(crate) fn footer_height(options: &CreateBundledImageOptions) -> u32 {
+    options.footer.as_ref().map_or(0, |band| band.height)
+}
+
+//grows the canvas by the header and footer bands and renders them
+pub(crate) fn attach_bands(
+    canvas: &mut ImageBuffer<Rgba<u8>, Vec<u8>>,
+    options: &CreateBundledImageOptions,
+) -> Result<(), ImageCombinerError> {
+    let header = header_height(options);
+    let footer = footer_height(options);
+    if header == 0 && footer == 0 {
+        return Ok(());
+    }
+    let (width, height) = canvas.dimensions();
+    let mut banded = ImageBuffer::from_pixel(width, header + height + footer, options.background);
+    banded.copy_from(canvas, 0, header)?;
+    if let Some(band) = &options.header {
+        banded.copy_from(&band.render(width, options)?, 0, 0)?;
+    }
+    if let Some(band) = &options.footer {
+        banded.copy_from(&band.render(width, options)?, 0, header + height)?;
+    }
+    *canvas = banded;
+    Ok(())
+}
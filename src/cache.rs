@@ -0,0 +1,65 @@
+use crate::ImageCombinerError;
+use log::debug;
+use sha2::{Digest, Sha256};
+use std::path::PathBuf;
+
+#[derive(Debug, Clone)]
+pub struct CacheDir {
+    path: PathBuf,
+}
+
+impl CacheDir {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    pub(crate) async fn get(&self, key: &str) -> Option<Vec<u8>> {
+        let bytes = tokio::fs::read(self.path.join(key)).await.ok()?;
+        debug!("cache hit {}", key);
+        Some(bytes)
+    }
+
+    pub(crate) async fn put(&self, key: &str, bytes: &[u8]) -> Result<(), ImageCombinerError> {
+        tokio::fs::create_dir_all(&self.path).await?;
+        //write aside and rename so a concurrent reader never sees a partial file
+        let temp_path = self
+            .path
+            .join(format!("{}.{}.tmp", key, std::process::id()));
+        tokio::fs::write(&temp_path, bytes).await?;
+        tokio::fs::rename(&temp_path, self.path.join(key)).await?;
+        debug!("cache put {}", key);
+        Ok(())
+    }
+}
+
+#[derive(Default)]
+pub(crate) struct CacheKey {
+    hasher: Sha256,
+}
+
+impl CacheKey {
+    pub(crate) fn new() -> Self {
+        let mut key = Self::default();
+        //outputs may change between versions so they never share entries
+        key.update(env!("CARGO_PKG_VERSION").as_bytes());
+        key
+    }
+
+    //length prefixed so that ("ab","c") and ("a","bc") do not collide
+    pub(crate) fn update(&mut self, bytes: &[u8]) {
+        self.hasher.update((bytes.len() as u64).to_le_bytes());
+        self.hasher.update(bytes);
+    }
+
+    pub(crate) fn finish(self, extension: &str) -> String {
+        format!("{}.{}", hex(&self.hasher.finalize()), extension)
+    }
+}
+
+pub(crate) fn sha256_hex(bytes: &[u8]) -> String {
+    hex(&Sha256::digest(bytes))
+}
+
+fn hex(digest: &[u8]) -> String {
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
@@ -0,0 +1,616 @@
+use crate::ImageCombinerError;
+use image::error::{ImageFormatHint, UnsupportedError, UnsupportedErrorKind};
+use image::{DynamicImage, GenericImageView, ImageError, ImageFormat};
+use jpeg_encoder::{ColorType, Encoder, SamplingFactor};
+use log::debug;
+use std::io::{Cursor, Write};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum OutputFormat {
+    Jpeg,
+    Png,
+}
+
+impl OutputFormat {
+    pub(crate) fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Jpeg => "jpeg",
+            OutputFormat::Png => "png",
+        }
+    }
+
+    pub fn mime_type(&self) -> &'static str {
+        match self {
+            OutputFormat::Jpeg => "image/jpeg",
+            OutputFormat::Png => "image/png",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Subsampling {
+    Yuv444,
+    Yuv422,
+    Yuv420,
+}
+
+#[derive(Debug, Clone)]
+pub struct EncodeOptions {
+    quality: u8,
+    subsampling: Subsampling,
+    icc_profile: Option<Vec<u8>>,
+    restart_interval: Option<u16>,
+}
+
+impl EncodeOptions {
+    pub fn new(quality: u8, subsampling: Subsampling, icc_profile: Option<Vec<u8>>) -> Self {
+        Self {
+            quality,
+            subsampling,
+            icc_profile,
+            restart_interval: None,
+        }
+    }
+
+    pub(crate) fn with_max_quality(mut self, max_quality: u8) -> Self {
+        self.quality = self.quality.min(max_quality);
+        self
+    }
+
+    pub(crate) fn quality(&self) -> u8 {
+        self.quality
+    }
+}
+
+impl Default for EncodeOptions {
+    fn default() -> Self {
+        EncodeOptionsBuilder::new().build()
+    }
+}
+
+#[derive(Default)]
+pub struct EncodeOptionsBuilder {
+    quality: Option<u8>,
+    subsampling: Option<Subsampling>,
+    icc_profile: Option<Vec<u8>>,
+    restart_interval: Option<u16>,
+}
+
+impl EncodeOptionsBuilder {
+    pub fn new() -> Self {
+        Self {
+            quality: None,
+            subsampling: None,
+            icc_profile: None,
+            restart_interval: None,
+        }
+    }
+
+    //jpeg quality 1-100, ignored for png
+    pub fn set_quality(mut self, quality: u8) -> Self {
+        self.quality = Some(quality);
+        self
+    }
+
+    pub fn set_subsampling(mut self, subsampling: Subsampling) -> Self {
+        self.subsampling = Some(subsampling);
+        self
+    }
+
+    //embed this ICC profile into jpeg output, see `icc_profile` to read one from an input
+    pub fn set_icc_profile(mut self, icc_profile: Vec<u8>) -> Self {
+        self.icc_profile = Some(icc_profile);
+        self
+    }
+
+    //write a jpeg restart marker every this many MCUs so tiling viewers can seek into wide images
+    pub fn set_restart_interval(mut self, restart_interval: u16) -> Self {
+        self.restart_interval = Some(restart_interval);
+        self
+    }
+
+    pub fn build(&self) -> EncodeOptions {
+        let quality = self.quality.unwrap_or(100);
+        let subsampling = self.subsampling.unwrap_or(Subsampling::Yuv444);
+        let mut options = EncodeOptions::new(quality, subsampling, self.icc_profile.clone());
+        options.restart_interval = self.restart_interval;
+        options
+    }
+}
+
+//rotations are clockwise
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Transform {
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    FlipHorizontal,
+    FlipVertical,
+}
+
+impl Transform {
+    fn apply(self, image: DynamicImage) -> DynamicImage {
+        match self {
+            Transform::Rotate90 => image.rotate90(),
+            Transform::Rotate180 => image.rotate180(),
+            Transform::Rotate270 => image.rotate270(),
+            Transform::FlipHorizontal => image.fliph(),
+            Transform::FlipVertical => image.flipv(),
+        }
+    }
+
+    fn swaps_axes(self) -> bool {
+        matches!(self, Transform::Rotate90 | Transform::Rotate270)
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct DecodeOptions {
+    exif_orientation: bool,
+    transform: Option<Transform>,
+    crop_aspect: Option<(u32, u32)>,
+    trim_background: Option<u8>,
+}
+
+impl DecodeOptions {
+    pub fn new(exif_orientation: bool) -> Self {
+        Self {
+            exif_orientation,
+            transform: None,
+            crop_aspect: None,
+            trim_background: None,
+        }
+    }
+
+    //applied after the EXIF orientation
+    pub fn with_transform(mut self, transform: Option<Transform>) -> Self {
+        self.transform = transform;
+        self
+    }
+
+    //center crop to width:height after the orientation and transform are applied
+    pub fn with_crop_aspect(mut self, crop_aspect: Option<(u32, u32)>) -> Self {
+        self.crop_aspect = crop_aspect;
+        self
+    }
+
+    //trim borders of the top left pixel color before the aspect crop, within tolerance on every channel
+    pub fn with_trim_background(mut self, tolerance: Option<u8>) -> Self {
+        self.trim_background = tolerance;
+        self
+    }
+
+    //logos are never trimmed or cropped to the image aspect ratio
+    pub(crate) fn for_logo(self) -> Self {
+        self.with_trim_background(None).with_crop_aspect(None)
+    }
+
+    //the centered (x, y, width, height) kept by crop_aspect
+    fn crop(&self, width: u32, height: u32) -> Option<(u32, u32, u32, u32)> {
+        //a zero side has no aspect ratio to crop to
+        let (aspect_width, aspect_height) = self.crop_aspect.filter(|&(w, h)| w > 0 && h > 0)?;
+        let (crop_width, crop_height) =
+            if width as u64 * aspect_height as u64 > height as u64 * aspect_width as u64 {
+                let crop_width = (height as u64 * aspect_width as u64 + aspect_height as u64 / 2)
+                    / aspect_height as u64;
+                ((crop_width as u32).clamp(1, width), height)
+            } else {
+                let crop_height = (width as u64 * aspect_height as u64 + aspect_width as u64 / 2)
+                    / aspect_width as u64;
+                (width, (crop_height as u32).clamp(1, height))
+            };
+        Some((
+            (width - crop_width) / 2,
+            (height - crop_height) / 2,
+            crop_width,
+            crop_height,
+        ))
+    }
+
+    //whether the decoded width and height are swapped from the stored ones
+    fn swaps_axes(&self, bytes: &[u8]) -> bool {
+        let exif = self.exif_orientation && (5..=8).contains(&exif_orientation(bytes));
+        exif != self.transform.is_some_and(Transform::swaps_axes)
+    }
+}
+
+impl Default for DecodeOptions {
+    fn default() -> Self {
+        Self::new(true)
+    }
+}
+
+pub fn encode(
+    image: &DynamicImage,
+    format: OutputFormat,
+    options: &EncodeOptions,
+) -> Result<Vec<u8>, ImageCombinerError> {
+    let mut image_bytes = Vec::new();
+    encode_to(image, format, options, &mut image_bytes)?;
+    Ok(image_bytes)
+}
+
+//re-encodes jpeg at lower quality until the output fits in max_bytes, png can only be checked
+//returns the bytes with the quality they were encoded at
+pub(crate) fn encode_within(
+    image: &DynamicImage,
+    format: OutputFormat,
+    options: &EncodeOptions,
+    max_bytes: Option<u64>,
+    target_bytes: Option<u64>,
+) -> Result<(Vec<u8>, u8), ImageCombinerError> {
+    let mut options = options.clone();
+    let mut image_bytes = match target_bytes {
+        Some(target_bytes) if format == OutputFormat::Jpeg => {
+            let (image_bytes, quality) = search_quality(image, &options, target_bytes)?;
+            options.quality = quality;
+            image_bytes
+        }
+        _ => encode(image, format, &options)?,
+    };
+    let Some(max_bytes) = max_bytes else {
+        return Ok((image_bytes, options.quality));
+    };
+    while image_bytes.len() as u64 > max_bytes {
+        if format != OutputFormat::Jpeg || options.quality <= MIN_SHRINK_QUALITY {
+            return Err(ImageCombinerError::Encode(format!(
+                "output does not fit in {} bytes size:{},quality:{}",
+                max_bytes,
+                image_bytes.len(),
+                options.quality
+            )));
+        }
+        options.quality = options
+            .quality
+            .saturating_sub(SHRINK_QUALITY_STEP)
+            .max(MIN_SHRINK_QUALITY);
+        debug!(
+            "{} bytes exceed {}, encode again at quality {}",
+            image_bytes.len(),
+            max_bytes,
+            options.quality
+        );
+        image_bytes = encode(image, format, &options)?;
+    }
+    Ok((image_bytes, options.quality))
+}
+
+const SHRINK_QUALITY_STEP: u8 = 10;
+const MIN_SHRINK_QUALITY: u8 = 10;
+//enough to narrow 1-100 down to a single quality
+const MAX_QUALITY_SEARCH_STEPS: u32 = 8;
+
+//highest jpeg quality up to the configured one whose output is at most target_bytes
+fn search_quality(
+    image: &DynamicImage,
+    options: &EncodeOptions,
+    target_bytes: u64,
+) -> Result<(Vec<u8>, u8), ImageCombinerError> {
+    let mut options = options.clone();
+    options.quality = options.quality.clamp(1, 100);
+    let image_bytes = encode(image, OutputFormat::Jpeg, &options)?;
+    if image_bytes.len() as u64 <= target_bytes {
+        return Ok((image_bytes, options.quality));
+    }
+    let (mut low, mut high) = (1, options.quality - 1);
+    let mut best = None;
+    for _ in 0..MAX_QUALITY_SEARCH_STEPS {
+        if low > high {
+            break;
+        }
+        options.quality = low + (high - low).div_ceil(2);
+        let image_bytes = encode(image, OutputFormat::Jpeg, &options)?;
+        debug!(
+            "quality {} gives {} bytes for target {}",
+            options.quality,
+            image_bytes.len(),
+            target_bytes
+        );
+        if image_bytes.len() as u64 <= target_bytes {
+            best = Some((image_bytes, options.quality));
+            low = options.quality + 1;
+        } else if options.quality == 1 {
+            break;
+        } else {
+            high = options.quality - 1;
+        }
+    }
+    best.ok_or_else(|| {
+        ImageCombinerError::Encode(format!("no jpeg quality fits in {} bytes", target_bytes))
+    })
+}
+
+//writes the encoded image into writer as it is produced instead of collecting it first
+pub fn encode_to<W: Write>(
+    image: &DynamicImage,
+    format: OutputFormat,
+    options: &EncodeOptions,
+    writer: W,
+) -> Result<u64, ImageCombinerError> {
+    let mut writer = CountingWriter {
+        inner: writer,
+        count: 0,
+    };
+    match format {
+        OutputFormat::Jpeg => {
+            let mut encoder = Encoder::new(&mut writer, options.quality.clamp(1, 100));
+            encoder.set_sampling_factor(match options.subsampling {
+                Subsampling::Yuv444 => SamplingFactor::R_4_4_4,
+                Subsampling::Yuv422 => SamplingFactor::R_4_2_2,
+                Subsampling::Yuv420 => SamplingFactor::R_4_2_0,
+            });
+            if let Some(restart_interval) = options.restart_interval {
+                encoder.set_restart_interval(restart_interval);
+            }
+            if let Some(icc_profile) = &options.icc_profile {
+                encoder.add_icc_profile(icc_profile).map_err(encode_error)?;
+            }
+            let (width, height) = image.dimensions();
+            if width > u16::MAX as u32 || height > u16::MAX as u32 {
+                return Err(ImageCombinerError::Encode(format!(
+                    "jpeg dimension is limited to {} width:{},height:{}",
+                    u16::MAX,
+                    width,
+                    height
+                )));
+            }
+            let rgba = image.to_rgba8();
+            encoder
+                .encode(&rgba, width as u16, height as u16, ColorType::Rgba)
+                .map_err(encode_error)?;
+        }
+        OutputFormat::Png => {
+            image.write_to(&mut writer, image::ImageOutputFormat::Png)?;
+        }
+    }
+    writer.flush()?;
+    debug!("encoded {:?} {} bytes", format, writer.count);
+    Ok(writer.count)
+}
+
+struct CountingWriter<W> {
+    inner: W,
+    count: u64,
+}
+
+impl<W: Write> Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.count += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+pub fn decode(bytes: &[u8], options: &DecodeOptions) -> Result<DynamicImage, ImageError> {
+    let mut image = image::load_from_memory(bytes)?;
+    if options.exif_orientation {
+        image = apply_exif_orientation(image, bytes);
+    }
+    if let Some(transform) = options.transform {
+        image = transform.apply(image);
+    }
+    if let Some(tolerance) = options.trim_background {
+        image = trim_background(image, tolerance);
+    }
+    if let Some((x, y, width, height)) = options.crop(image.width(), image.height()) {
+        image = image.crop_imm(x, y, width, height);
+    }
+    Ok(image)
+}
+
+//width, height and format read from the header only, EXIF orientation is not applied
+pub fn probe_dimensions(bytes: &[u8]) -> Result<(u32, u32, ImageFormat), ImageError> {
+    let reader = image::io::Reader::new(Cursor::new(bytes)).with_guessed_format()?;
+    let format = reader.format().ok_or_else(|| {
+        ImageError::Unsupported(UnsupportedError::from_format_and_kind(
+            ImageFormatHint::Unknown,
+            UnsupportedErrorKind::Format(ImageFormatHint::Unknown),
+        ))
+    })?;
+    let (width, height) = reader.into_dimensions()?;
+    Ok((width, height, format))
+}
+
+//dimensions the image will have once decoded with these options,
+//with trim_background this is a full decode so callers that decode anyway skip it
+pub(crate) fn probe_decoded_dimensions(
+    bytes: &[u8],
+    options: &DecodeOptions,
+) -> Option<(u32, u32)> {
+    //the trimmed size is only known from the pixels
+    if options.trim_background.is_some() {
+        return decode(bytes, options).ok().map(|image| image.dimensions());
+    }
+    let (mut width, mut height, _) = probe_dimensions(bytes).ok()?;
+    if options.swaps_axes(bytes) {
+        (width, height) = (height, width);
+    }
+    match options.crop(width, height) {
+        Some((_, _, width, height)) => Some((width, height)),
+        None => Some((width, height)),
+    }
+}
+
+//horizontal and vertical pixels per inch from EXIF, a JFIF header or a png pHYs chunk
+pub fn source_dpi(bytes: &[u8]) -> Option<(f32, f32)> {
+    exif_dpi(bytes)
+        .or_else(|| jfif_dpi(bytes))
+        .or_else(|| png_dpi(bytes))
+        .filter(|&(x, y)| x > 0.0 && y > 0.0)
+}
+
+//dpi along the axes of the image once decoded with these options
+pub(crate) fn decoded_dpi(bytes: &[u8], options: &DecodeOptions) -> Option<(f32, f32)> {
+    let (x, y) = source_dpi(bytes)?;
+    if options.swaps_axes(bytes) {
+        return Some((y, x));
+    }
+    Some((x, y))
+}
+
+fn exif_dpi(bytes: &[u8]) -> Option<(f32, f32)> {
+    let exif = exif::Reader::new()
+        .read_from_container(&mut Cursor::new(bytes))
+        .ok()?;
+    let resolution = |tag| match &exif.get_field(tag, exif::In::PRIMARY)?.value {
+        exif::Value::Rational(values) => values.first().map(|v| v.to_f64() as f32),
+        _ => None,
+    };
+    let (x, y) = (
+        resolution(exif::Tag::XResolution)?,
+        resolution(exif::Tag::YResolution)?,
+    );
+    let unit = exif
+        .get_field(exif::Tag::ResolutionUnit, exif::In::PRIMARY)
+        .and_then(|field| field.value.get_uint(0))
+        .unwrap_or(2);
+    match unit {
+        2 => Some((x, y)),
+        3 => Some((x * 2.54, y * 2.54)),
+        _ => None,
+    }
+}
+
+//APP0 has to directly follow SOI, units 0 is only an aspect ratio
+fn jfif_dpi(bytes: &[u8]) -> Option<(f32, f32)> {
+    let app0 = bytes.get(..18)?;
+    if app0[..4] != [0xFF, 0xD8, 0xFF, 0xE0] || &app0[6..11] != b"JFIF\0" {
+        return None;
+    }
+    let x = u16::from_be_bytes([app0[14], app0[15]]) as f32;
+    let y = u16::from_be_bytes([app0[16], app0[17]]) as f32;
+    match app0[13] {
+        1 => Some((x, y)),
+        2 => Some((x * 2.54, y * 2.54)),
+        _ => None,
+    }
+}
+
+fn png_dpi(bytes: &[u8]) -> Option<(f32, f32)> {
+    if bytes.get(..8)? != b"\x89PNG\r\n\x1a\n" {
+        return None;
+    }
+    let mut offset = 8;
+    while let Some(header) = bytes.get(offset..offset + 8) {
+        let length = u32::from_be_bytes([header[0], header[1], header[2], header[3]]) as usize;
+        let data = bytes.get(offset + 8..offset + 8 + length)?;
+        match &header[4..] {
+            //pixels per metre when the unit is 1, otherwise only an aspect ratio
+            b"pHYs" if length == 9 && data[8] == 1 => {
+                let x = u32::from_be_bytes([data[0], data[1], data[2], data[3]]) as f32;
+                let y = u32::from_be_bytes([data[4], data[5], data[6], data[7]]) as f32;
+                return Some((x * 0.0254, y * 0.0254));
+            }
+            b"IDAT" | b"IEND" => return None,
+            _ => {}
+        }
+        //chunk data is followed by a 4 byte crc
+        offset += 12 + length;
+    }
+    None
+}
+
+//ICC profile embedded in a jpeg, None for other formats
+pub fn icc_profile(bytes: &[u8]) -> Option<Vec<u8>> {
+    let mut decoder = jpeg_decoder::Decoder::new(Cursor::new(bytes));
+    decoder.read_info().ok()?;
+    decoder.icc_profile()
+}
+
+fn encode_error(e: jpeg_encoder::EncodingError) -> ImageCombinerError {
+    ImageCombinerError::Encode(e.to_string())
+}
+
+fn exif_orientation(buf: &[u8]) -> u32 {
+    exif::Reader::new()
+        .read_from_container(&mut Cursor::new(buf))
+        .ok()
+        .and_then(|exif| {
+            exif.get_field(exif::Tag::Orientation, exif::In::PRIMARY)
+                .and_then(|field| field.value.get_uint(0))
+        })
+        .unwrap_or(1)
+}
+
+//crops rows and columns at the edges that only hold the top left pixel color,
+//an image made of that color alone is kept as it is
+fn trim_background(image: DynamicImage, tolerance: u8) -> DynamicImage {
+    let (width, height) = image.dimensions();
+    if width == 0 || height == 0 {
+        return image;
+    }
+    let rgba = image.to_rgba8();
+    let background = *rgba.get_pixel(0, 0);
+    let is_background = |x: u32, y: u32| {
+        rgba.get_pixel(x, y)
+            .0
+            .iter()
+            .zip(background.0.iter())
+            .all(|(a, b)| a.abs_diff(*b) <= tolerance)
+    };
+    let row_is_background = |y: u32| (0..width).all(|x| is_background(x, y));
+    let Some(top) = (0..height).find(|&y| !row_is_background(y)) else {
+        return image;
+    };
+    let bottom = (top..height)
+        .rev()
+        .find(|&y| !row_is_background(y))
+        .unwrap();
+    let column_is_background = |x: u32| (top..=bottom).all(|y| is_background(x, y));
+    let left = (0..width).find(|&x| !column_is_background(x)).unwrap();
+    let right = (left..width)
+        .rev()
+        .find(|&x| !column_is_background(x))
+        .unwrap();
+    if (left, top, right, bottom) != (0, 0, width - 1, height - 1) {
+        debug!(
+            "trim background {}x{} to {}x{}",
+            width,
+            height,
+            right - left + 1,
+            bottom - top + 1
+        );
+    }
+    image.crop_imm(left, top, right - left + 1, bottom - top + 1)
+}
+
+fn apply_exif_orientation(image: DynamicImage, buf: &[u8]) -> DynamicImage {
+    let orientation = exif_orientation(buf);
+    if orientation != 1 {
+        debug!("apply exif orientation {}", orientation);
+    }
+    match orientation {
+        2 => image.fliph(),
+        3 => image.rotate180(),
+        4 => image.flipv(),
+        5 => image.rotate90().fliph(),
+        6 => image.rotate90(),
+        7 => image.rotate270().fliph(),
+        8 => image.rotate270(),
+        _ => image,
+    }
+}
+
+//each page becomes one RGBA image directory of the file
+#[cfg(feature = "tiff")]
+pub fn encode_tiff_pages(pages: &[DynamicImage]) -> Result<Vec<u8>, ImageCombinerError> {
+    let tiff_error = |e: tiff::TiffError| ImageCombinerError::Encode(e.to_string());
+    let mut image_bytes = Cursor::new(Vec::new());
+    let mut encoder = tiff::encoder::TiffEncoder::new(&mut image_bytes).map_err(tiff_error)?;
+    for page in pages {
+        let rgba = page.to_rgba8();
+        encoder
+            .write_image::<tiff::encoder::colortype::RGBA8>(rgba.width(), rgba.height(), &rgba)
+            .map_err(tiff_error)?;
+    }
+    debug!(
+        "encoded tiff {} pages {} bytes",
+        pages.len(),
+        image_bytes.get_ref().len()
+    );
+    Ok(image_bytes.into_inner())
+}
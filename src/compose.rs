@@ -0,0 +1,125 @@
+use crate::tasks::TileTasks;
+use crate::{
+    decode_images, decorate_canvas, finish_bundle, open_source, place_tile, placeholder_image,
+    resize_member, BundledImage, CellDecoration, CreateBundledImageOptions, DecodeErrorPolicy,
+    ImageCombinerError, ImageSource, IntoImageSource, Stage,
+};
+use image::{DynamicImage, ImageBuffer};
+use log::debug;
+
+//the image at image_index in source order resized into a rectangle of the canvas
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Placement {
+    pub image_index: usize,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Placement {
+    pub fn new(image_index: usize, x: u32, y: u32, width: u32, height: u32) -> Self {
+        Self {
+            image_index,
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+}
+
+//draws each placement with the fit, alignment and cell decoration of the options, later placements
+//over earlier ones, an image may be placed more than once, the canvas ends at the furthest placement
+//plus the outer margin and placements are offset by the top left margin,
+//placements of an input skipped under DecodeErrorPolicy::Skip are left out
+pub async fn compose(
+    buffers: impl IntoImageSource,
+    placements: Vec<Placement>,
+    options: CreateBundledImageOptions,
+) -> Result<BundledImage, ImageCombinerError> {
+    if let Some(placement) = placements.iter().find(|p| p.width == 0 || p.height == 0) {
+        return Err(ImageCombinerError::InvalidOptions(format!(
+            "placement has no area {:?}",
+            placement
+        )));
+    }
+    let mut source = open_source(buffers, &options);
+    let mut collected = Vec::new();
+    while let Some(buffer) = source.next_image().await {
+        collected.push(buffer?);
+    }
+    let count = collected.len();
+    if let Some(placement) = placements.iter().find(|p| p.image_index >= count) {
+        return Err(ImageCombinerError::InvalidOptions(format!(
+            "placement refers to a missing image images:{},placement:{:?}",
+            count, placement
+        )));
+    }
+    let (decoded, skipped) = decode_images(collected, &options).await?;
+    //skipped inputs are missing from decoded, line the rest up with their input index again
+    let mut decoded = decoded.into_iter();
+    let images: Vec<Option<Option<DynamicImage>>> = (0..count)
+        .map(|index| {
+            if options.on_decode_error == DecodeErrorPolicy::Skip && skipped.contains(&index) {
+                None
+            } else {
+                decoded.next()
+            }
+        })
+        .collect();
+    let width = placements.iter().map(|p| p.x + p.width).max().unwrap_or(0);
+    let height = placements.iter().map(|p| p.y + p.height).max().unwrap_or(0);
+    let (_, margin) = options.spacing((width, height));
+    let (width, height) = (
+        width + margin.left + margin.right,
+        height + margin.top + margin.bottom,
+    );
+    debug!("create composed image buf {}x{}", width, height);
+    let mut canvas = ImageBuffer::from_pixel(width, height, options.background);
+    let decoration = CellDecoration {
+        border: options.cell_border,
+        corner_radius: options.corner_radius,
+        drop_shadow: options.drop_shadow,
+        antialias: options.antialias(),
+        //placements leave no gap of their own
+        gap: (0, 0),
+    };
+    let placements: Vec<(Placement, Option<DynamicImage>)> = placements
+        .into_iter()
+        .filter_map(|placement| Some((placement, images[placement.image_index].clone()?)))
+        .collect();
+    let mut tasks = TileTasks::new(Stage::Resize);
+    for (i, (placement, image)) in placements.iter().cloned().enumerate() {
+        let (placement_width, placement_height) = (placement.width, placement.height);
+        let fit = options.fit;
+        let allow_upscale = options.allow_upscale;
+        let resampling = options.resampling();
+        tasks.spawn(i, async move {
+            Ok(match image {
+                Some(image) => resize_member(
+                    image,
+                    i,
+                    placement_width,
+                    placement_height,
+                    fit,
+                    allow_upscale,
+                    resampling,
+                ),
+                None => placeholder_image(placement_width, placement_height),
+            })
+        });
+    }
+    for (image, (placement, _)) in tasks.join_all().await?.into_iter().zip(placements.iter()) {
+        place_tile(
+            &mut canvas,
+            &image.to_rgba8(),
+            options.cell_alignment,
+            (margin.left + placement.x, margin.top + placement.y),
+            (placement.width, placement.height),
+            &decoration,
+        )?;
+    }
+    decorate_canvas(&mut canvas, &options)?;
+    finish_bundle(DynamicImage::ImageRgba8(canvas), skipped, &options).await
+}
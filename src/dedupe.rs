@@ -0,0 +1,160 @@
+use crate::{
+    decode, early_resize_dimension, limit_member_dimension, pace_decode, prepare_decoded,
+    render_bundle, render_tiles, resize_member, sort_order, spawn_decode, spawn_job, BundledImage,
+    CreateBundledImageOptions, DecodeJob, DecodedTile, ImageCombinerError, LayoutMode, OriginImage,
+    SortOrder, Stage, Tile,
+};
+use image::imageops::FilterType;
+
+//the tiles left after dedupe with their input indices and the indices that were dropped,
+//decoded holds the decode of each kept image tile so it is not decoded a second time
+pub(crate) struct Deduped {
+    pub(crate) tiles: Vec<Tile>,
+    pub(crate) decoded: Vec<Option<DecodedTile>>,
+    pub(crate) kept: Vec<usize>,
+    pub(crate) duplicates: Vec<usize>,
+}
+
+//drops images whose difference hash is within max_distance bits of an earlier image,
+//images are decoded and hashed on the decode workers and kept resized to the member size when it is known
+pub(crate) async fn dedupe_tiles(
+    tiles: Vec<Tile>,
+    options: &CreateBundledImageOptions,
+    max_distance: u32,
+) -> Result<Deduped, ImageCombinerError> {
+    //a canvas limit scales the member size by how many tiles are kept, which is only known afterwards
+    let resize_to = early_resize_dimension(
+        options.dimension.filter(|_| options.max_canvas.is_none()),
+        options,
+    );
+    let mut jobs = Vec::with_capacity(tiles.len());
+    for (index, tile) in tiles.into_iter().enumerate() {
+        let decode_options = options.decode_options(index);
+        let spill = options.low_memory;
+        let fit = options.fit;
+        let allow_upscale = options.allow_upscale;
+        let resampling = options.resampling();
+        let job = spawn_job(options, Stage::Decode, index, move || match tile {
+            Tile::Image(buf) => {
+                let decoded = decode(&buf, &decode_options);
+                let hash = decoded.as_ref().ok().map(difference_hash);
+                let size = buf.len();
+                let decoded = match decoded {
+                    Ok(image) => {
+                        let image = match resize_to {
+                            Some((width, height)) => resize_member(
+                                image,
+                                index,
+                                width,
+                                height,
+                                fit,
+                                allow_upscale,
+                                resampling,
+                            ),
+                            None => image,
+                        };
+                        Ok(OriginImage::new(image, spill)?)
+                    }
+                    Err(e) => Err(e),
+                };
+                Ok((
+                    Tile::Image(buf),
+                    Some(DecodedTile::Image(decoded, size)),
+                    hash,
+                ))
+            }
+            tile => Ok::<_, ImageCombinerError>((tile, None, None)),
+        });
+        let job = Box::pin(async move { job.await? });
+        jobs.push(pace_decode(job, options).await?);
+    }
+    let mut hashes: Vec<u64> = Vec::new();
+    let mut deduped = Deduped {
+        tiles: Vec::new(),
+        decoded: Vec::new(),
+        kept: Vec::new(),
+        duplicates: Vec::new(),
+    };
+    for (index, job) in jobs.into_iter().enumerate() {
+        let (tile, decoded, hash) = job.await?;
+        //inputs that fail to decode are left to the decode error policy
+        if let Some(hash) = hash {
+            if hashes
+                .iter()
+                .any(|other| (other ^ hash).count_ones() <= max_distance)
+            {
+                deduped.duplicates.push(index);
+                continue;
+            }
+            hashes.push(hash);
+        }
+        deduped.tiles.push(tile);
+        deduped.decoded.push(decoded);
+        deduped.kept.push(index);
+    }
+    Ok(deduped)
+}
+
+//the grid takes the images decoded for the hash, masonry and physical scale read the tile bytes again
+pub(crate) async fn render_deduped(
+    tiles: Vec<Tile>,
+    decoded: Vec<Option<DecodedTile>>,
+    options: &CreateBundledImageOptions,
+) -> Result<BundledImage, ImageCombinerError> {
+    if options.layout_mode == LayoutMode::Masonry || options.physical_scale {
+        return render_tiles(tiles, options).await;
+    }
+    let mut options = options.clone();
+    if options.sort != SortOrder::InputOrder {
+        options.order = Some(sort_order(&tiles, &options)?);
+    }
+    let member_dimension = options
+        .dimension
+        .map(|member_dimension| limit_member_dimension(tiles.len(), member_dimension, &options))
+        .transpose()?;
+    let resize_to = early_resize_dimension(member_dimension, &options);
+    let mut jobs = Vec::with_capacity(tiles.len());
+    for (index, (tile, decoded)) in tiles.into_iter().zip(decoded).enumerate() {
+        jobs.push(match decoded {
+            Some(decoded) => Box::pin(std::future::ready(Ok(decoded))) as DecodeJob,
+            None => pace_decode(spawn_decode(index, tile, &options, resize_to), &options).await?,
+        });
+    }
+    let bundle = prepare_decoded(jobs, member_dimension, &options).await?;
+    render_bundle(bundle, &options).await
+}
+
+//per input options are moved to the positions of the kept tiles, those of dropped tiles are discarded
+pub(crate) fn remap_options(
+    options: &CreateBundledImageOptions,
+    kept: &[usize],
+) -> CreateBundledImageOptions {
+    let position = |index: usize| kept.iter().position(|&i| i == index);
+    let mut remapped = options.clone();
+    remapped.transforms = options
+        .transforms
+        .iter()
+        .filter_map(|(index, transform)| Some((position(*index)?, *transform)))
+        .collect();
+    remapped.order = options
+        .order
+        .as_ref()
+        .map(|order| order.iter().filter_map(|&index| position(index)).collect());
+    remapped
+}
+
+//one bit per neighbouring pair of an 9x8 gray thumbnail, set where the left pixel is brighter,
+//robust to rescaling and recompression
+fn difference_hash(image: &image::DynamicImage) -> u64 {
+    let gray = image.resize_exact(9, 8, FilterType::Triangle).to_luma8();
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            hash <<= 1;
+            if gray.get_pixel(x, y)[0] > gray.get_pixel(x + 1, y)[0] {
+                hash |= 1;
+            }
+        }
+    }
+    hash
+}
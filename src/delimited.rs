@@ -0,0 +1,55 @@
+use crate::ImageCombinerError;
+
+//splits csv-style text into rows, fields may be quoted with "" as an escaped quote
+//and quoted fields may hold the delimiter or line breaks, blank lines are dropped
+pub(crate) fn parse_delimited(
+    text: &str,
+    delimiter: char,
+) -> Result<Vec<Vec<String>>, ImageCombinerError> {
+    //spreadsheet exports often start with a byte order mark
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    let mut rows = Vec::new();
+    let mut row = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if quoted {
+            match c {
+                '"' if chars.peek() == Some(&'"') => {
+                    chars.next();
+                    field.push('"');
+                }
+                '"' => quoted = false,
+                _ => field.push(c),
+            }
+            continue;
+        }
+        match c {
+            '"' if field.is_empty() => quoted = true,
+            '\r' if chars.peek() == Some(&'\n') => {}
+            '\n' | '\r' => {
+                row.push(std::mem::take(&mut field));
+                push_row(&mut rows, std::mem::take(&mut row));
+            }
+            c if c == delimiter => row.push(std::mem::take(&mut field)),
+            _ => field.push(c),
+        }
+    }
+    if quoted {
+        return Err(ImageCombinerError::InvalidTable(format!(
+            "unterminated quote in row:{}",
+            rows.len() + 1
+        )));
+    }
+    row.push(field);
+    push_row(&mut rows, row);
+    Ok(rows)
+}
+
+fn push_row(rows: &mut Vec<Vec<String>>, row: Vec<String>) {
+    if row.len() == 1 && row[0].trim().is_empty() {
+        return;
+    }
+    rows.push(row);
+}
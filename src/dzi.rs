@@ -0,0 +1,131 @@
+use crate::{
+    compose_tiles, encode, open_source, CreateBundledImageOptions, ImageCombinerError, ImageSource,
+    IntoImageSource, Tile,
+};
+use image::GenericImageView;
+use log::debug;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DeepZoomOptions {
+    tile_size: u32,
+    overlap: u32,
+}
+
+impl DeepZoomOptions {
+    pub fn new(tile_size: u32, overlap: u32) -> Self {
+        Self { tile_size, overlap }
+    }
+}
+
+//the sizes most DeepZoom viewers expect
+impl Default for DeepZoomOptions {
+    fn default() -> Self {
+        Self::new(254, 1)
+    }
+}
+
+#[derive(Debug)]
+pub struct DeepZoomImage {
+    //path of the .dzi descriptor, tiles are in the sibling {name}_files folder
+    pub descriptor: PathBuf,
+    pub width: u32,
+    pub height: u32,
+    pub skipped: Vec<usize>,
+}
+
+//writes {name}.dzi and {name}_files/{level}/{column}_{row}.{ext} under output_dir,
+//level 0 is a single pixel and the last level is the full size bundle
+pub async fn create_bundled_image_dzi(
+    buffers: impl IntoImageSource,
+    options: CreateBundledImageOptions,
+    deep_zoom: DeepZoomOptions,
+    output_dir: impl AsRef<Path>,
+    name: &str,
+) -> Result<DeepZoomImage, ImageCombinerError> {
+    if deep_zoom.tile_size == 0 {
+        return Err(ImageCombinerError::InvalidOptions(
+            "deep zoom tile size must be at least 1".to_string(),
+        ));
+    }
+    let mut source = open_source(buffers, &options);
+    let mut tiles = Vec::new();
+    while let Some(buffer) = source.next_image().await {
+        tiles.push(Tile::Image(buffer?));
+    }
+    let (canvas, skipped) = compose_tiles(tiles, &options).await?;
+    let (width, height) = canvas.dimensions();
+    let output_dir = output_dir.as_ref();
+    let extension = options.output_format.extension();
+    let files_dir = output_dir.join(format!("{}_files", name));
+    let max_level = max_level(width, height);
+    let mut level_image = canvas;
+    for level in (0..=max_level).rev() {
+        let level_dir = files_dir.join(level.to_string());
+        tokio::fs::create_dir_all(&level_dir)
+            .await
+            .map_err(ImageCombinerError::Io)?;
+        let (level_width, level_height) = level_image.dimensions();
+        debug!(
+            "write deep zoom level {} {}x{}",
+            level, level_width, level_height
+        );
+        for (column, x, tile_width) in tile_spans(level_width, deep_zoom) {
+            for (row, y, tile_height) in tile_spans(level_height, deep_zoom) {
+                let tile = level_image.crop_imm(x, y, tile_width, tile_height);
+                let bytes = encode(&tile, options.output_format, &options.encode_options)?;
+                tokio::fs::write(
+                    level_dir.join(format!("{}_{}.{}", column, row, extension)),
+                    bytes,
+                )
+                .await
+                .map_err(ImageCombinerError::Io)?;
+            }
+        }
+        if level > 0 {
+            level_image = level_image.resize_exact(
+                level_width.div_ceil(2),
+                level_height.div_ceil(2),
+                image::imageops::FilterType::Lanczos3,
+            );
+        }
+    }
+    let descriptor = output_dir.join(format!("{}.dzi", name));
+    tokio::fs::write(
+        &descriptor,
+        descriptor_xml(width, height, deep_zoom, extension),
+    )
+    .await
+    .map_err(ImageCombinerError::Io)?;
+    Ok(DeepZoomImage {
+        descriptor,
+        width,
+        height,
+        skipped,
+    })
+}
+
+//halving the largest side this many times reaches one pixel
+fn max_level(width: u32, height: u32) -> u32 {
+    let largest = width.max(height).max(1);
+    u32::BITS - (largest - 1).leading_zeros()
+}
+
+//(index, start, length) of each tile along one side, tiles reach overlap pixels into their neighbours
+fn tile_spans(length: u32, deep_zoom: DeepZoomOptions) -> Vec<(u32, u32, u32)> {
+    let DeepZoomOptions { tile_size, overlap } = deep_zoom;
+    (0..length.div_ceil(tile_size))
+        .map(|index| {
+            let start = (index * tile_size).saturating_sub(overlap);
+            let end = ((index + 1) * tile_size + overlap).min(length);
+            (index, start, end - start)
+        })
+        .collect()
+}
+
+fn descriptor_xml(width: u32, height: u32, deep_zoom: DeepZoomOptions, extension: &str) -> String {
+    format!(
+        "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<Image xmlns=\"http://schemas.microsoft.com/deepzoom/2008\" Format=\"{}\" Overlap=\"{}\" TileSize=\"{}\">\n  <Size Width=\"{}\" Height=\"{}\"/>\n</Image>\n",
+        extension, deep_zoom.overlap, deep_zoom.tile_size, width, height
+    )
+}
@@ -0,0 +1,323 @@
+use crate::rng::SeededRng;
+use crate::ImageCombinerError;
+use log::debug;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::future::Future;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
+
+pub struct FetchOptions {
+    concurrency: usize,
+    retry_policy: RetryPolicy,
+    pub(crate) rate_limiter: Option<Arc<RateLimiter>>,
+    //the seed of the job the source is read for, set through ImageSource::seed
+    pub(crate) seed: Option<u64>,
+}
+
+impl FetchOptions {
+    pub fn new(concurrency: usize, retries: u32) -> Self {
+        Self {
+            concurrency,
+            retry_policy: RetryPolicy {
+                max_attempts: retries + 1,
+                ..RetryPolicy::default()
+            },
+            rate_limiter: None,
+            seed: None,
+        }
+    }
+
+    pub fn with_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+}
+
+//delays double from base_delay after every failed attempt,
+//jitter is the fraction of each delay that is randomized so clients retrying together spread out
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub jitter: f32,
+}
+
+impl RetryPolicy {
+    pub fn new(max_attempts: u32, base_delay: Duration) -> Self {
+        Self {
+            max_attempts: max_attempts.max(1),
+            base_delay,
+            jitter: 0.0,
+        }
+    }
+
+    pub fn with_jitter(mut self, jitter: f32) -> Self {
+        self.jitter = jitter.clamp(0.0, 1.0);
+        self
+    }
+
+    pub(crate) fn delay(&self, rng: &mut SeededRng, attempt: u32) -> Duration {
+        let delay = self.base_delay.saturating_mul(2u32.saturating_pow(attempt));
+        if self.jitter <= 0.0 {
+            return delay;
+        }
+        delay.mul_f64(1.0 - self.jitter as f64 * rng.next_unit())
+    }
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self::new(3, Duration::from_millis(100))
+    }
+}
+
+//a failed fetch, retryable is false when asking again cannot succeed such as a missing object
+#[derive(Debug)]
+pub(crate) struct FetchError {
+    message: String,
+    retryable: bool,
+}
+
+impl FetchError {
+    pub(crate) fn new(message: impl Display, retryable: bool) -> Self {
+        Self {
+            message: message.to_string(),
+            retryable,
+        }
+    }
+}
+
+impl Display for FetchError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.message)
+    }
+}
+
+//a token bucket, burst requests go out at once and then per_second more each second
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    pub per_second: f64,
+    pub burst: u32,
+}
+
+impl RateLimit {
+    pub fn new(per_second: f64, burst: u32) -> Self {
+        Self {
+            per_second: per_second.max(0.001),
+            burst: burst.max(1),
+        }
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    //the tokio clock, so a paused runtime drives the refill as well as the wait
+    updated: tokio::time::Instant,
+}
+
+//one bucket per host, shared by every fetch made with the same options,
+//hosts are compared ignoring ascii case like they are in urls
+pub(crate) struct RateLimiter {
+    default: Option<RateLimit>,
+    hosts: HashMap<String, RateLimit>,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl RateLimiter {
+    pub(crate) async fn acquire(&self, host: &str) {
+        let host = host.to_ascii_lowercase();
+        let Some(limit) = self.hosts.get(&host).or(self.default.as_ref()) else {
+            return;
+        };
+        let wait = {
+            let mut buckets = self.buckets.lock().unwrap();
+            let now = tokio::time::Instant::now();
+            let bucket = buckets.entry(host.clone()).or_insert(Bucket {
+                tokens: limit.burst as f64,
+                updated: now,
+            });
+            let refill = now.duration_since(bucket.updated).as_secs_f64() * limit.per_second;
+            bucket.tokens = (bucket.tokens + refill).min(limit.burst as f64);
+            bucket.updated = now;
+            //the token is taken now, a negative balance is the wait for it
+            bucket.tokens -= 1.0;
+            (bucket.tokens < 0.0)
+                .then(|| Duration::from_secs_f64(-bucket.tokens / limit.per_second))
+        };
+        if let Some(wait) = wait {
+            debug!("rate limit {} wait {:?}", host, wait);
+            tokio::time::sleep(wait).await;
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct FetchOptionsBuilder {
+    concurrency: Option<usize>,
+    retries: Option<u32>,
+    retry_policy: Option<RetryPolicy>,
+    rate_limit: Option<RateLimit>,
+    host_rate_limits: Vec<(String, RateLimit)>,
+}
+
+impl FetchOptionsBuilder {
+    pub fn new() -> Self {
+        Self {
+            concurrency: None,
+            retries: None,
+            retry_policy: None,
+            rate_limit: None,
+            host_rate_limits: Vec::new(),
+        }
+    }
+
+    pub fn set_concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = Some(concurrency);
+        self
+    }
+
+    pub fn set_retries(mut self, retries: u32) -> Self {
+        self.retries = Some(retries);
+        self
+    }
+
+    //replaces set_retries when both are given
+    pub fn set_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = Some(retry_policy);
+        self
+    }
+
+    //applies to every host without its own limit, retries take a token too
+    pub fn set_rate_limit(mut self, rate_limit: RateLimit) -> Self {
+        self.rate_limit = Some(rate_limit);
+        self
+    }
+
+    //hosts as they appear in the url with the port when it has one, or bucket names for s3,
+    //matched ignoring ascii case
+    pub fn set_host_rate_limits(mut self, host_rate_limits: Vec<(String, RateLimit)>) -> Self {
+        self.host_rate_limits = host_rate_limits;
+        self
+    }
+
+    pub fn build(&self) -> FetchOptions {
+        let concurrency = self.concurrency.unwrap_or(4);
+        let retries = self.retries.unwrap_or(2);
+        let mut options = FetchOptions::new(concurrency, retries);
+        if let Some(retry_policy) = self.retry_policy {
+            options = options.with_retry_policy(retry_policy);
+        }
+        if self.rate_limit.is_some() || !self.host_rate_limits.is_empty() {
+            options.rate_limiter = Some(Arc::new(RateLimiter {
+                default: self.rate_limit,
+                hosts: self
+                    .host_rate_limits
+                    .iter()
+                    .map(|(host, limit)| (host.to_ascii_lowercase(), *limit))
+                    .collect(),
+                buckets: Mutex::new(HashMap::new()),
+            }));
+        }
+        options
+    }
+}
+
+//the bytes of every target in order with the attempts each one took,
+//host names the rate limit bucket a target is fetched under, the first failure aborts the others
+pub(crate) async fn fetch_all<T, H, F, Fut>(
+    targets: Vec<T>,
+    fetch_options: &FetchOptions,
+    host: H,
+    fetch: F,
+) -> Result<(Vec<Vec<u8>>, Vec<u32>), ImageCombinerError>
+where
+    T: Display + Clone + Send + 'static,
+    H: Fn(&T) -> String,
+    F: Fn(T) -> Fut + Clone + Send + 'static,
+    Fut: Future<Output = Result<Vec<u8>, FetchError>> + Send,
+{
+    let semaphore = Arc::new(Semaphore::new(fetch_options.concurrency.max(1)));
+    let rng = SeededRng::new(fetch_options.seed);
+    let mut set = JoinSet::new();
+    for (index, target) in targets.into_iter().enumerate() {
+        let rng = rng.fork(index as u64);
+        let semaphore = Arc::clone(&semaphore);
+        let retry_policy = fetch_options.retry_policy;
+        let rate_limiter = fetch_options.rate_limiter.clone();
+        let host = host(&target);
+        let fetch = fetch.clone();
+        let task = async move {
+            let _permit = semaphore
+                .acquire_owned()
+                .await
+                .map_err(|e| ImageCombinerError::Fetch(e.to_string()))?;
+            let fetch = move |target: T| {
+                let rate_limiter = rate_limiter.clone();
+                let host = host.clone();
+                let fetch = fetch.clone();
+                async move {
+                    if let Some(rate_limiter) = rate_limiter {
+                        rate_limiter.acquire(&host).await;
+                    }
+                    fetch(target).await
+                }
+            };
+            fetch_with_retry(target, &retry_policy, rng, fetch).await
+        };
+        set.spawn(async move { (index, task.await) });
+    }
+    let mut results = Vec::with_capacity(set.len());
+    while let Some(joined) = set.join_next().await {
+        let (index, result) = match joined {
+            Ok(joined) => joined,
+            Err(e) => {
+                set.abort_all();
+                return Err(e.into());
+            }
+        };
+        match result {
+            Ok(fetched) => results.push((index, fetched)),
+            Err(e) => {
+                set.abort_all();
+                return Err(e);
+            }
+        }
+    }
+    results.sort_by_key(|&(index, _)| index);
+    Ok(results.into_iter().map(|(_, fetched)| fetched).unzip())
+}
+
+async fn fetch_with_retry<T, F, Fut>(
+    target: T,
+    retry_policy: &RetryPolicy,
+    mut rng: SeededRng,
+    fetch: F,
+) -> Result<(Vec<u8>, u32), ImageCombinerError>
+where
+    T: Display + Clone,
+    F: Fn(T) -> Fut,
+    Fut: Future<Output = Result<Vec<u8>, FetchError>>,
+{
+    let mut attempt = 0;
+    loop {
+        match fetch(target.clone()).await {
+            Ok(bytes) => return Ok((bytes, attempt + 1)),
+            Err(e) if e.retryable && attempt + 1 < retry_policy.max_attempts => {
+                debug!("fetch {} failed attempt {}: {}", target, attempt + 1, e);
+                tokio::time::sleep(retry_policy.delay(&mut rng, attempt)).await;
+                attempt += 1;
+            }
+            Err(e) => {
+                return Err(ImageCombinerError::Fetch(format!(
+                    "fetch {} failed after {} attempts: {}",
+                    target,
+                    attempt + 1,
+                    e
+                )))
+            }
+        }
+    }
+}
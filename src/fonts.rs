@@ -0,0 +1,321 @@
+use crate::{
+    blend_pixel, contrast_color, corner_coverage, snap_coverage, ImageCombinerError, TextCardStyle,
+};
+use image::{GenericImage, ImageBuffer, Rgba};
+use imageproc::drawing::draw_text_mut;
+use rusttype::{point, Font, Scale, ScaledGlyph};
+
+//writing systems that can be given their own font in a TextCardStyle,
+//runs are always laid out left to right in the order of the text
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Script {
+    Latin,
+    //Han, kana and hangul
+    Cjk,
+    //drawn as given without shaping or right to left reordering,
+    //so Arabic text has to be passed already shaped and in visual order
+    Arabic,
+}
+
+impl Script {
+    //None for spaces, digits and punctuation, which stay with the run around them
+    fn of(c: char) -> Option<Self> {
+        match c as u32 {
+            0x0600..=0x06ff
+            | 0x0750..=0x077f
+            | 0x08a0..=0x08ff
+            | 0xfb50..=0xfdff
+            | 0xfe70..=0xfeff => Some(Script::Arabic),
+            0x1100..=0x11ff
+            | 0x3040..=0x30ff
+            | 0x3400..=0x4dbf
+            | 0x4e00..=0x9fff
+            | 0xac00..=0xd7af
+            | 0xf900..=0xfaff => Some(Script::Cjk),
+            _ if c.is_alphabetic() && (c.is_ascii() || ('\u{c0}'..='\u{24f}').contains(&c)) => {
+                Some(Script::Latin)
+            }
+            _ => None,
+        }
+    }
+}
+
+//fills text from the style color to end_color, angle is degrees clockwise from left to right
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextGradient {
+    end_color: Rgba<u8>,
+    angle: f32,
+}
+
+impl TextGradient {
+    pub fn new(end_color: Rgba<u8>, angle: f32) -> Self {
+        Self { end_color, angle }
+    }
+}
+
+//rounded box behind every line of text, sized to the text with padding on each side,
+//without a color it takes the contrast color of the text
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TextPill {
+    padding: u32,
+    color: Option<Rgba<u8>>,
+}
+
+impl TextPill {
+    pub fn new(padding: u32) -> Self {
+        Self {
+            padding,
+            color: None,
+        }
+    }
+
+    pub fn with_color(mut self, color: Rgba<u8>) -> Self {
+        self.color = Some(color);
+        self
+    }
+}
+
+//the style font and the fonts registered for single scripts
+pub(crate) struct FontSet {
+    primary: Font<'static>,
+    scripts: Vec<(Script, Font<'static>)>,
+    gradient: Option<TextGradient>,
+    pill: Option<TextPill>,
+    antialias: bool,
+}
+
+impl FontSet {
+    pub(crate) fn new(style: &TextCardStyle) -> Result<Self, ImageCombinerError> {
+        let parse = |bytes: &Vec<u8>| {
+            Font::try_from_vec(bytes.clone())
+                .ok_or_else(|| ImageCombinerError::InvalidText("invalid font bytes".to_string()))
+        };
+        Ok(Self {
+            primary: parse(&style.font_bytes)?,
+            scripts: style
+                .script_fonts
+                .iter()
+                .map(|(script, bytes)| Ok((*script, parse(bytes)?)))
+                .collect::<Result<_, ImageCombinerError>>()?,
+            gradient: style.gradient,
+            pill: style.pill,
+            antialias: true,
+        })
+    }
+
+    //without antialiasing every glyph and pill pixel is either drawn in full or left alone
+    pub(crate) fn with_antialias(mut self, antialias: bool) -> Self {
+        self.antialias = antialias;
+        self
+    }
+
+    fn font(&self, script: Option<Script>) -> &Font<'static> {
+        self.scripts
+            .iter()
+            .find(|(s, _)| Some(*s) == script)
+            .map_or(&self.primary, |(_, font)| font)
+    }
+
+    //glyphs of every character in order, each from the font of its script run
+    pub(crate) fn glyphs(&self, text: &str, scale: Scale) -> Vec<ScaledGlyph<'static>> {
+        script_runs(text)
+            .into_iter()
+            .flat_map(|(script, run)| {
+                let font = self.font(script);
+                run.chars().map(move |c| font.glyph(c).scaled(scale))
+            })
+            .collect()
+    }
+
+    //each run of one script is drawn with its own font, right after the previous run,
+    //right to left runs are not reordered, see Script::Arabic
+    pub(crate) fn draw(
+        &self,
+        canvas: &mut ImageBuffer<Rgba<u8>, Vec<u8>>,
+        color: Rgba<u8>,
+        x: u32,
+        y: u32,
+        scale: Scale,
+        text: &str,
+    ) {
+        if let Some(pill) = self.pill {
+            let width = self.width(text, scale);
+            self.draw_pill(canvas, pill, color, (x, y), (width, scale.y));
+        }
+        if let Some(gradient) = self.gradient {
+            return self.draw_gradient(canvas, (color, gradient), x, y, scale, text);
+        }
+        let mut left = x as f32;
+        for (script, run) in script_runs(text) {
+            let font = self.font(script);
+            draw_text(
+                canvas,
+                color,
+                (left as u32, y),
+                scale,
+                font,
+                run,
+                self.antialias,
+            );
+            left += run_width(font, run, scale);
+        }
+    }
+
+    //width the text takes when drawn, every run measured with the font it is drawn with
+    pub(crate) fn width(&self, text: &str, scale: Scale) -> f32 {
+        script_runs(text)
+            .into_iter()
+            .map(|(script, run)| run_width(self.font(script), run, scale))
+            .sum()
+    }
+
+    //fully rounded ends, clipped to the canvas
+    fn draw_pill(
+        &self,
+        canvas: &mut ImageBuffer<Rgba<u8>, Vec<u8>>,
+        pill: TextPill,
+        text_color: Rgba<u8>,
+        (x, y): (u32, u32),
+        (width, height): (f32, f32),
+    ) {
+        let color = pill.color.unwrap_or_else(|| contrast_color(text_color));
+        let left = x as i64 - pill.padding as i64;
+        let top = y as i64 - pill.padding as i64;
+        let pill_width = width.ceil() as u32 + pill.padding * 2;
+        let pill_height = height.ceil() as u32 + pill.padding * 2;
+        for pill_y in 0..pill_height {
+            for pill_x in 0..pill_width {
+                let (canvas_x, canvas_y) = (left + pill_x as i64, top + pill_y as i64);
+                if canvas_x < 0
+                    || canvas_y < 0
+                    || canvas_x >= canvas.width() as i64
+                    || canvas_y >= canvas.height() as i64
+                {
+                    continue;
+                }
+                let coverage = snap_coverage(
+                    corner_coverage(pill_x, pill_y, pill_width, pill_height, pill_height / 2),
+                    self.antialias,
+                );
+                if coverage > 0.0 {
+                    let pixel = canvas.get_pixel_mut(canvas_x as u32, canvas_y as u32);
+                    *pixel = blend_pixel(*pixel, color, coverage);
+                }
+            }
+        }
+    }
+
+    //the gradient spans the box of the whole line, so every glyph takes the color at its own place
+    fn draw_gradient(
+        &self,
+        canvas: &mut ImageBuffer<Rgba<u8>, Vec<u8>>,
+        (start_color, gradient): (Rgba<u8>, TextGradient),
+        x: u32,
+        y: u32,
+        scale: Scale,
+        text: &str,
+    ) {
+        let width = self.width(text, scale);
+        let (sin, cos) = gradient.angle.to_radians().sin_cos();
+        //the line box projected on the gradient direction
+        let start = (width * cos).min(0.0) + (scale.y * sin).min(0.0);
+        let extent = ((width * cos).abs() + (scale.y * sin).abs()).max(1.0);
+        let (canvas_width, canvas_height) = canvas.dimensions();
+        let mut left = x as f32;
+        for (script, run) in script_runs(text) {
+            let font = self.font(script);
+            let ascent = font.v_metrics(scale).ascent;
+            for glyph in font.layout(run, scale, point(left, y as f32 + ascent)) {
+                let Some(bounds) = glyph.pixel_bounding_box() else {
+                    continue;
+                };
+                glyph.draw(|gx, gy, coverage| {
+                    let coverage = snap_coverage(coverage, self.antialias);
+                    let (px, py) = (gx as i32 + bounds.min.x, gy as i32 + bounds.min.y);
+                    if px < 0 || py < 0 || px >= canvas_width as i32 || py >= canvas_height as i32 {
+                        return;
+                    }
+                    let along = (px as f32 - x as f32) * cos + (py as f32 - y as f32) * sin;
+                    let t = ((along - start) / extent).clamp(0.0, 1.0);
+                    let pixel = canvas.get_pixel_mut(px as u32, py as u32);
+                    for channel in 0..4 {
+                        let color = start_color[channel] as f32 * (1.0 - t)
+                            + gradient.end_color[channel] as f32 * t;
+                        pixel[channel] = (pixel[channel] as f32 * (1.0 - coverage)
+                            + color * coverage)
+                            .round() as u8;
+                    }
+                });
+            }
+            left += run_width(font, run, scale);
+        }
+    }
+}
+
+//advance of a run laid out in one font, kerning included
+fn run_width(font: &Font<'_>, run: &str, scale: Scale) -> f32 {
+    font.layout(run, scale, point(0.0, 0.0))
+        .last()
+        .map_or(0.0, |glyph| {
+            glyph.position().x + glyph.unpositioned().h_metrics().advance_width
+        })
+}
+
+//text in one font with its top left at position, glyph edges are blended only with antialias
+pub(crate) fn draw_text<I: GenericImage<Pixel = Rgba<u8>>>(
+    canvas: &mut I,
+    color: Rgba<u8>,
+    (x, y): (u32, u32),
+    scale: Scale,
+    font: &Font<'_>,
+    text: &str,
+    antialias: bool,
+) {
+    if antialias {
+        return draw_text_mut(canvas, color, x, y, scale, font, text);
+    }
+    let (width, height) = canvas.dimensions();
+    let ascent = font.v_metrics(scale).ascent;
+    for glyph in font.layout(text, scale, point(x as f32, y as f32 + ascent)) {
+        let Some(bounds) = glyph.pixel_bounding_box() else {
+            continue;
+        };
+        glyph.draw(|gx, gy, coverage| {
+            let (px, py) = (gx as i32 + bounds.min.x, gy as i32 + bounds.min.y);
+            if snap_coverage(coverage, false) == 0.0
+                || px < 0
+                || py < 0
+                || px >= width as i32
+                || py >= height as i32
+            {
+                return;
+            }
+            canvas.put_pixel(px as u32, py as u32, color);
+        });
+    }
+}
+
+//neutral characters join the run before them, or the first run when the text starts with them
+pub(crate) fn script_runs(text: &str) -> Vec<(Option<Script>, &str)> {
+    let mut runs: Vec<(Option<Script>, &str)> = Vec::new();
+    let mut start = 0;
+    let mut current = None;
+    for (i, c) in text.char_indices() {
+        let Some(script) = Script::of(c) else {
+            continue;
+        };
+        match current {
+            None => current = Some(script),
+            Some(previous) if previous != script => {
+                runs.push((current, &text[start..i]));
+                start = i;
+                current = Some(script);
+            }
+            _ => {}
+        }
+    }
+    if start < text.len() {
+        runs.push((current, &text[start..]));
+    }
+    runs
+}
@@ -0,0 +1,174 @@
+use crate::storage::encode_key;
+use crate::{ImageCombinerError, Storage, UploadOptions};
+use log::debug;
+
+const BOUNDARY: &str = "image_combiner_upload_boundary";
+
+//objects in one bucket through the JSON API, token is an OAuth2 access token with storage scope
+#[derive(Clone)]
+pub struct GcsStorage {
+    client: reqwest::Client,
+    bucket: String,
+    token: String,
+}
+
+impl GcsStorage {
+    pub fn new(bucket: String, token: String) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            bucket,
+            token,
+        }
+    }
+
+    pub fn with_client(mut self, client: reqwest::Client) -> Self {
+        self.client = client;
+        self
+    }
+}
+
+impl Storage for GcsStorage {
+    async fn get(&self, key: &str) -> Result<Vec<u8>, ImageCombinerError> {
+        let url = format!(
+            "https://storage.googleapis.com/storage/v1/b/{}/o/{}?alt=media",
+            encode_key(&self.bucket),
+            encode_key(key)
+        );
+        let res = self
+            .client
+            .get(url)
+            .bearer_auth(&self.token)
+            .send()
+            .await
+            .and_then(|res| res.error_for_status())
+            .map_err(|e| ImageCombinerError::Fetch(e.to_string()))?;
+        let bytes = res
+            .bytes()
+            .await
+            .map_err(|e| ImageCombinerError::Fetch(e.to_string()))?;
+        Ok(bytes.to_vec())
+    }
+
+    async fn put(
+        &self,
+        key: &str,
+        bytes: Vec<u8>,
+        options: &UploadOptions,
+    ) -> Result<(), ImageCombinerError> {
+        debug!("put gs://{}/{}", self.bucket, key);
+        let url = format!(
+            "https://storage.googleapis.com/upload/storage/v1/b/{}/o?uploadType=multipart",
+            encode_key(&self.bucket)
+        );
+        self.client
+            .post(url)
+            .bearer_auth(&self.token)
+            .header(
+                "Content-Type",
+                format!("multipart/related; boundary={}", BOUNDARY),
+            )
+            .body(multipart_body(key, bytes, options))
+            .send()
+            .await
+            .and_then(|res| res.error_for_status())
+            .map_err(|e| ImageCombinerError::Upload(e.to_string()))?;
+        Ok(())
+    }
+}
+
+//a multipart upload carries the object metadata in the same request as the bytes
+fn multipart_body(key: &str, bytes: Vec<u8>, options: &UploadOptions) -> Vec<u8> {
+    let content_type = options
+        .content_type
+        .as_deref()
+        .unwrap_or("application/octet-stream");
+    let mut resource = vec![
+        format!("\"name\":{}", json_string(key)),
+        format!("\"contentType\":{}", json_string(content_type)),
+    ];
+    if let Some(cache_control) = &options.cache_control {
+        resource.push(format!("\"cacheControl\":{}", json_string(cache_control)));
+    }
+    if !options.metadata.is_empty() {
+        let metadata: Vec<String> = options
+            .metadata
+            .iter()
+            .map(|(name, value)| format!("{}:{}", json_string(name), json_string(value)))
+            .collect();
+        resource.push(format!("\"metadata\":{{{}}}", metadata.join(",")));
+    }
+    let mut body = format!(
+        "--{}\r\nContent-Type: application/json; charset=UTF-8\r\n\r\n{{{}}}\r\n--{}\r\nContent-Type: {}\r\n\r\n",
+        BOUNDARY,
+        resource.join(","),
+        BOUNDARY,
+        content_type
+    )
+    .into_bytes();
+    body.extend(bytes);
+    body.extend(format!("\r\n--{}--\r\n", BOUNDARY).into_bytes());
+    body
+}
+
+fn json_string(value: &str) -> String {
+    let mut json = String::from("\"");
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::UploadOptionsBuilder;
+    use std::collections::BTreeMap;
+
+    #[test]
+    fn test_encode_key() {
+        assert_eq!(encode_key("bundles/a b+c.jpg"), "bundles%2Fa%20b%2Bc.jpg");
+        assert_eq!(encode_key("Az09-._~"), "Az09-._~");
+        assert_eq!(encode_key("写真"), "%E5%86%99%E7%9C%9F");
+    }
+
+    #[test]
+    fn test_json_string() {
+        assert_eq!(json_string("plain"), "\"plain\"");
+        assert_eq!(json_string("a\"b\\c"), "\"a\\\"b\\\\c\"");
+        assert_eq!(json_string("line\nbreak\t"), "\"line\\u000abreak\\u0009\"");
+        assert_eq!(json_string("写真"), "\"写真\"");
+    }
+
+    #[test]
+    fn test_multipart_body() {
+        let body = multipart_body("a.png", vec![1, 2], &UploadOptionsBuilder::new().build());
+        let mut expected = format!(
+            "--{b}\r\nContent-Type: application/json; charset=UTF-8\r\n\r\n{{\"name\":\"a.png\",\"contentType\":\"application/octet-stream\"}}\r\n--{b}\r\nContent-Type: application/octet-stream\r\n\r\n",
+            b = BOUNDARY
+        )
+        .into_bytes();
+        expected.extend([1, 2]);
+        expected.extend(format!("\r\n--{}--\r\n", BOUNDARY).into_bytes());
+        assert_eq!(body, expected);
+
+        let options = UploadOptionsBuilder::new()
+            .set_content_type("image/png")
+            .set_cache_control("max-age=60")
+            .set_metadata(BTreeMap::from([
+                ("sku".to_string(), "a\"1".to_string()),
+                ("color".to_string(), "red".to_string()),
+            ]))
+            .build();
+        let body = String::from_utf8(multipart_body("a.png", Vec::new(), &options)).unwrap();
+        assert!(body.contains(
+            "{\"name\":\"a.png\",\"contentType\":\"image/png\",\"cacheControl\":\"max-age=60\",\"metadata\":{\"color\":\"red\",\"sku\":\"a\\\"1\"}}\r\n"
+        ));
+        assert!(body.contains("\r\nContent-Type: image/png\r\n\r\n\r\n--"));
+    }
+}
@@ -0,0 +1,23 @@
+//rounding rules for every length that decides where pixels land, shared so that renders of the same
+//options at any quality, and anything drawing from plan_layout_geometry, agree to the pixel:
+//a length taken as a fraction of another rounds to the nearest pixel with halves away from zero,
+//a length scaled down to fit a bound is truncated so it never overshoots,
+//and free space split on both sides leaves the odd pixel on the right or bottom
+
+pub(crate) fn round_px(length: f64) -> u32 {
+    if length.is_nan() || length <= 0.0 {
+        return 0;
+    }
+    length.round() as u32
+}
+
+pub(crate) fn fit_px(length: f64) -> u32 {
+    if length.is_nan() || length <= 0.0 {
+        return 0;
+    }
+    length as u32
+}
+
+pub(crate) fn center_px(free: u32) -> u32 {
+    free / 2
+}
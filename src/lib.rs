@@ -1,16 +1,109 @@
+mod arc;
+#[cfg(feature = "zip")]
+mod archive;
+#[cfg(feature = "azure")]
+pub mod azure;
+mod band;
+mod cache;
+mod codec;
+mod compose;
+mod dedupe;
+mod delimited;
+mod dzi;
+#[cfg(any(feature = "url", feature = "s3"))]
+mod fetch;
+mod fonts;
+#[cfg(feature = "gcs")]
+pub mod gcs;
+mod geometry;
+mod masonry;
+mod matrix;
+mod metrics;
+mod overlay;
+mod physical;
+mod pool;
+#[cfg(feature = "project")]
+mod project;
+#[cfg(feature = "serde")]
+mod records;
+#[cfg(any(feature = "url", feature = "s3"))]
+mod rng;
+#[cfg(feature = "s3")]
+pub mod s3;
+mod signature;
+mod source;
+mod spill;
+mod stamp;
+mod storage;
+mod strip;
+mod tasks;
+mod template;
 mod test;
+#[cfg(feature = "url")]
+mod url;
 
+pub use arc::{render_arc_text, ArcDirection, ArcText};
+#[cfg(feature = "zip")]
+pub use archive::{create_bundled_image_archive, create_bundled_image_from_zip, ZipImageSource};
+pub use band::{Band, BandContent, BandDraw};
+pub use cache::CacheDir;
+#[cfg(feature = "tiff")]
+pub use codec::encode_tiff_pages;
+pub use codec::{
+    decode, encode, encode_to, icc_profile, probe_dimensions, source_dpi, DecodeOptions,
+    EncodeOptions, EncodeOptionsBuilder, OutputFormat, Subsampling, Transform,
+};
+pub use compose::{compose, Placement};
+pub use dzi::{create_bundled_image_dzi, DeepZoomImage, DeepZoomOptions};
+#[cfg(any(feature = "url", feature = "s3"))]
+pub use fetch::{FetchOptions, FetchOptionsBuilder, RateLimit, RetryPolicy};
+pub use fonts::{Script, TextGradient, TextPill};
+pub use matrix::{create_labeled_matrix, MatrixLabels};
+pub use metrics::{MetricsExporter, PrometheusExporter};
+pub use overlay::{BlendMode, OverlayLayer};
+pub use pool::WorkerPool;
+#[cfg(feature = "project")]
+pub use project::{Project, ProjectOverlay, ProjectRender};
+pub use signature::verify_signature;
+pub use source::{FileImageSource, ImageSource, IntoImageSource, StreamImageSource};
+pub use stamp::{create_stamp, StampShape, StampStyle};
+pub use storage::{
+    create_bundled_image_in_storage, FsStorage, MemoryStorage, Storage, StorageImageSource,
+    UploadOptions, UploadOptionsBuilder,
+};
+pub use strip::{
+    combine_horizontal, combine_vertical, create_labeled_strip, StripDirection, StripLabels,
+};
+pub use template::{create_bundled_image_with_template, LayoutTemplate, TemplateCell};
+#[cfg(feature = "url")]
+pub use url::{create_bundled_image_from_urls, UrlImageSource};
+
+use cache::{sha256_hex, CacheKey};
+use codec::{encode_within, probe_decoded_dimensions};
+use fonts::{draw_text, FontSet};
 use image::error::ImageError;
-use image::{DynamicImage, GenericImage, GenericImageView, ImageBuffer, Rgba};
-use imageproc::drawing::{draw_line_segment_mut, draw_text_mut};
+use image::{DynamicImage, GenericImage, GenericImageView, ImageBuffer, Luma, Rgba};
+use imageproc::drawing::{
+    draw_filled_circle_mut, draw_filled_rect_mut, draw_line_segment_mut, draw_polygon_mut,
+    draw_text_mut,
+};
+use imageproc::point::Point;
+use imageproc::rect::Rect;
 use log::debug;
 use rusttype::{Font, Scale};
-use std::collections::HashMap;
+use source::open_source;
+use spill::SpilledTile;
+use std::collections::{BTreeMap, HashMap};
 use std::error::Error;
+use std::future::Future;
+use std::pin::Pin;
+use tasks::{join_error, TileTasks};
 
 use std::sync::Arc;
-use tokio::task::JoinError;
-use tokio::{sync::Mutex, task::JoinHandle};
+use tokio::{
+    sync::{Mutex, Semaphore},
+    task::JoinError,
+};
 
 #[derive(Debug)]
 pub enum ImageCombinerError {
@@ -18,6 +111,41 @@ pub enum ImageCombinerError {
     Runtime(JoinError),
     InvalidTable(String),
     InvalidText(String),
+    Fetch(String),
+    //writing an output to storage failed
+    Upload(String),
+    Io(std::io::Error),
+    InvalidOptions(String),
+    InvalidArchive(String),
+    Decode(Vec<DecodeFailure>),
+    Encode(String),
+    WorkerPool(String),
+    //a panic caught while working on a bundle, image_index is the tile position when known
+    InternalPanic {
+        stage: Stage,
+        image_index: Option<usize>,
+        message: String,
+    },
+    //position of the tile in the bundle whose task failed
+    Tile {
+        index: usize,
+        error: Box<ImageCombinerError>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Stage {
+    Decode,
+    Resize,
+    Draw,
+    Encode,
+}
+
+#[derive(Debug)]
+pub struct DecodeFailure {
+    pub index: usize,
+    pub size: usize,
+    pub error: ImageError,
 }
 
 impl std::fmt::Display for ImageCombinerError {
@@ -28,6 +156,39 @@ impl std::fmt::Display for ImageCombinerError {
 
 impl Error for ImageCombinerError {}
 
+impl ImageCombinerError {
+    //stable name of the error kind for metrics and logs, a failed tile reports the error inside it
+    pub fn code(&self) -> &'static str {
+        match self {
+            Self::ImageProcess(_) => "image_process",
+            Self::Runtime(_) => "runtime",
+            Self::InvalidTable(_) => "invalid_table",
+            Self::InvalidText(_) => "invalid_text",
+            Self::Fetch(_) => "fetch",
+            Self::Upload(_) => "upload",
+            Self::Io(_) => "io",
+            Self::InvalidOptions(_) => "invalid_options",
+            Self::InvalidArchive(_) => "invalid_archive",
+            Self::Decode(_) => "decode",
+            Self::Encode(_) => "encode",
+            Self::WorkerPool(_) => "worker_pool",
+            Self::InternalPanic { .. } => "internal_panic",
+            Self::Tile { error, .. } => error.code(),
+        }
+    }
+}
+
+impl Stage {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Decode => "decode",
+            Self::Resize => "resize",
+            Self::Draw => "draw",
+            Self::Encode => "encode",
+        }
+    }
+}
+
 impl From<ImageError> for ImageCombinerError {
     fn from(e: ImageError) -> Self {
         Self::ImageProcess(e)
@@ -40,255 +201,622 @@ impl From<JoinError> for ImageCombinerError {
     }
 }
 
+impl From<std::io::Error> for ImageCombinerError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
 const BLACK_COLOR: Rgba<u8> = image::Rgba([0u8, 0u8, 0u8, 255u8]);
 const WHITE_COLOR: Rgba<u8> = image::Rgba([255u8, 255u8, 255u8, 0u8]);
 const GRAY_COLOR: Rgba<u8> = image::Rgba([219u8, 219u8, 219u8, 255u8]);
+const DARK_GRAY_COLOR: Rgba<u8> = image::Rgba([160u8, 160u8, 160u8, 255u8]);
 
 pub async fn create_bundled_image_from_bytes(
-    buffers: Vec<Vec<u8>>,
+    buffers: impl IntoImageSource,
     options: CreateBundledImageOptions,
 ) -> Result<Vec<u8>, ImageCombinerError> {
-    debug!("process {} images into 1", buffers.len());
-    let origin_images = load_images_from_vec(buffers)?;
-    let (width, height) = match options.dimension {
-        Some(user_setting_dimension) => user_setting_dimension,
-        None => find_optical_dimension(&origin_images),
-    };
-    let resize_images = resize_images(origin_images, width, height).await?;
-    let row = (resize_images.len() as f32 / options.column as f32).ceil() as u32;
-    let canvas_height = if row >= 1 {
-        height + options.padding
-    } else {
-        height
-    };
-    let canvas_width = if options.column >= 1 {
-        width + options.padding
-    } else {
-        width
-    };
+    Ok(create_bundled_image(buffers, options).await?.bytes)
+}
 
-    let bundled_image_canvas_height = row * canvas_height;
-    let bundled_image_canvas_width = options.column * canvas_width;
-    debug!(
-        "create image buf {}x{}",
-        bundled_image_canvas_width, bundled_image_canvas_height
-    );
-    let image_buf = ImageBuffer::from_fn(
-        bundled_image_canvas_width,
-        bundled_image_canvas_height,
-        |_, _| WHITE_COLOR,
-    );
-    let image_buf_threaded = Arc::new(Mutex::new(image_buf));
-    draw_bundled_image(
-        Arc::clone(&image_buf_threaded),
-        resize_images,
-        options.column,
-        height,
-        canvas_width,
-        canvas_height,
-        0,
-    )
-    .await?;
-    let dyn_image = DynamicImage::ImageRgba8(image_buf_threaded.lock_owned().await.to_owned());
-    let mut image_bytes = Vec::new();
-    dyn_image.write_to(&mut image_bytes, image::ImageOutputFormat::Jpeg(100))?;
-    Ok(image_bytes)
+pub async fn create_bundled_image(
+    buffers: impl IntoImageSource,
+    options: CreateBundledImageOptions,
+) -> Result<BundledImage, ImageCombinerError> {
+    let metrics = options.metrics.clone();
+    let started = std::time::Instant::now();
+    let bundled = bundle_image(buffers, options).await;
+    if let Some(metrics) = metrics {
+        metrics.job_finished(started.elapsed(), bundled.as_ref().err());
+    }
+    bundled
 }
 
-pub async fn add_table(
-    buffer: Vec<u8>,
-    table_base: TableBase,
-    font_bytes: &'_ [u8],
+async fn bundle_image(
+    buffers: impl IntoImageSource,
+    options: CreateBundledImageOptions,
+) -> Result<BundledImage, ImageCombinerError> {
+    if options.cache_dir.is_some()
+        || options.layout_mode == LayoutMode::Masonry
+        || options.physical_scale
+        || options.sort != SortOrder::InputOrder
+        || options.max_canvas.is_some()
+        || options.dedupe.is_some()
+    {
+        //the key, masonry columns, the physical scale, sorting, the canvas limit and dedupe all depend on every input,
+        //so the whole batch is read before rendering
+        let mut source = open_source(buffers, &options);
+        let mut tiles = Vec::new();
+        while let Some(buffer) = source.next_image().await {
+            tiles.push(Tile::Image(buffer?));
+        }
+        let mut bundled = if let Some(max_distance) = options.dedupe {
+            let deduped = dedupe::dedupe_tiles(tiles, &options, max_distance).await?;
+            let mut options = dedupe::remap_options(&options, &deduped.kept);
+            options.dedupe = None;
+            let mut bundled = match &options.cache_dir {
+                Some(cache_dir) => {
+                    let key = cache_key(&deduped.tiles, &options);
+                    let render = dedupe::render_deduped(deduped.tiles, deduped.decoded, &options);
+                    render_cached(key, &options, cache_dir, render).await?
+                }
+                None => dedupe::render_deduped(deduped.tiles, deduped.decoded, &options).await?,
+            };
+            //skipped indices are reported as input indices
+            bundled.skipped = bundled.skipped.iter().map(|&i| deduped.kept[i]).collect();
+            bundled.duplicates = deduped.duplicates;
+            bundled
+        } else if let Some(cache_dir) = &options.cache_dir {
+            let key = cache_key(&tiles, &options);
+            render_cached(key, &options, cache_dir, render_tiles(tiles, &options)).await?
+        } else {
+            render_tiles(tiles, &options).await?
+        };
+        bundled.fetch_attempts = source.fetch_attempts();
+        return Ok(bundled);
+    }
+    let mut source = open_source(buffers, &options);
+    let bundle = prepare_bundle(&mut source, &options).await?;
+    let mut bundled = render_bundle(bundle, &options).await?;
+    bundled.fetch_attempts = source.fetch_attempts();
+    Ok(bundled)
+}
+
+//plans the canvas the options render to and where each input lands, in input order,
+//with the same code the render uses so a preview or another renderer can draw the same layout,
+//headers move tiles down by their height, only the plain grid in input order is planned
+pub async fn plan_layout_geometry(
+    buffers: impl IntoImageSource,
+    options: &CreateBundledImageOptions,
+) -> Result<LayoutGeometry, ImageCombinerError> {
+    if !options.keeps_input_cells() {
+        return Err(ImageCombinerError::InvalidOptions(
+            "geometry is only planned for the grid in input order".to_string(),
+        ));
+    }
+    let mut source = open_source(buffers, options);
+    let mut tiles = Vec::new();
+    while let Some(buffer) = source.next_image().await {
+        tiles.push(Tile::Image(buffer?));
+    }
+    let layout = prepare_tiles(tiles, options).await?.layout;
+    let header = band::header_height(options);
+    let footer = band::footer_height(options);
+    Ok(LayoutGeometry {
+        width: layout.width,
+        height: header + layout.height + footer,
+        tiles: layout
+            .placements()
+            .into_iter()
+            .map(|placement| TilePlacement {
+                y: placement.y + header,
+                ..placement
+            })
+            .collect(),
+    })
+}
+
+pub async fn create_bundled_image_from_tiles(
+    tiles: Vec<Tile>,
+    options: CreateBundledImageOptions,
 ) -> Result<Vec<u8>, ImageCombinerError> {
-    let origin_image = image::load_from_memory(&buffer)?;
-    let padding = origin_image.width() as f32 * 0.05;
-    let font_size = (origin_image.width() as f32 - padding * 2.0) * 0.03;
-    debug!("font size is {}", font_size);
-    let cell_padding_x = font_size * 0.75;
-    let cell_padding_y = font_size * 0.25;
-    let table = table_base.build(cell_padding_x, cell_padding_y, font_size);
-
-    debug!("table width is {}", table.table_width());
-    if table.table_width() > origin_image.width() as f32 {
-        debug!("table width would be bigger than origin image width return error");
-        return Err(ImageCombinerError::InvalidTable(format!(
-            "table size over table width is {},canvas width is {}",
-            table.table_width(),
-            origin_image.width()
-        )));
+    if let Some(cache_dir) = &options.cache_dir {
+        let key = cache_key(&tiles, &options);
+        return Ok(
+            render_cached(key, &options, cache_dir, render_tiles(tiles, &options))
+                .await?
+                .bytes,
+        );
+    }
+    Ok(render_tiles(tiles, &options).await?.bytes)
+}
+
+async fn render_tiles(
+    tiles: Vec<Tile>,
+    options: &CreateBundledImageOptions,
+) -> Result<BundledImage, ImageCombinerError> {
+    let (dyn_image, skipped) = compose_tiles(tiles, options).await?;
+    finish_bundle(dyn_image, skipped, options).await
+}
+
+//the whole canvas before it is encoded, in any layout mode
+async fn compose_tiles(
+    tiles: Vec<Tile>,
+    options: &CreateBundledImageOptions,
+) -> Result<(DynamicImage, Vec<usize>), ImageCombinerError> {
+    let sorted;
+    let options = if options.sort != SortOrder::InputOrder {
+        let mut options_with_order = options.clone();
+        options_with_order.order = Some(sort_order(&tiles, options)?);
+        sorted = options_with_order;
+        &sorted
+    } else {
+        options
     };
+    if options.layout_mode == LayoutMode::Masonry {
+        return masonry::compose_masonry(tiles, options).await;
+    }
+    if options.physical_scale {
+        return compose_bundle(physical::prepare_physical(tiles, options).await?, options).await;
+    }
+    compose_bundle(prepare_tiles(tiles, options).await?, options).await
+}
 
-    let table_canvas_height = table.table_height().ceil() as u32 + padding as u32 * 2;
-    debug!("table height is {}", table.table_height());
-    let mut full_canvas = ImageBuffer::from_fn(
-        origin_image.width(),
-        origin_image.height() + table_canvas_height,
-        |_, _| image::Rgba([255, 255, 255, 0] as [u8; 4]),
-    );
-    debug!(
-        "full canvas size is width:{} height: {}",
-        origin_image.width(),
-        origin_image.height() + table_canvas_height
-    );
-    //draw table
+//render only runs when key misses
+async fn render_cached(
+    key: String,
+    options: &CreateBundledImageOptions,
+    cache_dir: &CacheDir,
+    render: impl Future<Output = Result<BundledImage, ImageCombinerError>>,
+) -> Result<BundledImage, ImageCombinerError> {
+    if let Some(bundled) = cached_bundle(cache_dir, &key, options).await {
+        return Ok(bundled);
+    }
+    let bundled = render.await?;
+    //a bundle missing broken inputs is not stored, the next render may succeed
+    if bundled.skipped.is_empty() {
+        //thumbnails first so a hit on the bundle always finds them
+        let entries = bundled
+            .thumbnails
+            .iter()
+            .map(|(&width, bytes)| (thumbnail_key(&key, width), bytes))
+            .chain(std::iter::once((key.clone(), &bundled.bytes)));
+        for (entry_key, bytes) in entries {
+            if let Err(e) = cache_dir.put(&entry_key, bytes).await {
+                debug!("failed to write cache {}: {}", entry_key, e);
+                break;
+            }
+        }
+    }
+    Ok(bundled)
+}
+
+async fn cached_bundle(
+    cache_dir: &CacheDir,
+    key: &str,
+    options: &CreateBundledImageOptions,
+) -> Option<BundledImage> {
+    let bytes = cache_dir.get(key).await?;
+    let mut thumbnails = BTreeMap::new();
+    if !options.thumbnail_widths.is_empty() {
+        let (canvas_width, _, _) = probe_dimensions(&bytes).ok()?;
+        for width in thumbnail_widths(options, canvas_width) {
+            thumbnails.insert(width, cache_dir.get(&thumbnail_key(key, width)).await?);
+        }
+    }
+    Some(BundledImage {
+        sha256: options.sha256.then(|| sha256_hex(&bytes)),
+        //not stored with the entry, known only when the bytes are encoded
+        quality: None,
+        seed: options.seed,
+        bytes,
+        skipped: Vec::new(),
+        duplicates: Vec::new(),
+        fetch_attempts: Vec::new(),
+        thumbnails,
+    })
+}
+
+//"{hash}.jpeg" becomes "{hash}-w400.jpeg"
+fn thumbnail_key(key: &str, width: u32) -> String {
+    key.replacen('.', &format!("-w{}.", width), 1)
+}
+
+fn cache_key(tiles: &[Tile], options: &CreateBundledImageOptions) -> String {
+    let mut key = CacheKey::new();
+    key.update(options.cache_fingerprint().as_bytes());
+    if let CellStyle::Frame {
+        caption: Some(caption),
+        ..
+    } = &options.cell_style
     {
-        let mut table_canvas =
-            full_canvas.sub_image(0, 0, origin_image.width(), table_canvas_height);
-        let font: Font<'_> = Font::try_from_bytes(font_bytes).unwrap();
-        for (top, left, text) in
-            table.text_top_left_position(padding, table_canvas.width() as f32, cell_padding_y)
-        {
-            draw_text_mut(
-                &mut table_canvas,
-                BLACK_COLOR,
-                left.ceil() as u32,
-                top.ceil() as u32,
-                Scale::uniform(font_size),
-                &font,
+        caption.style.hash_style(&mut key);
+    }
+    if let Some(badge) = &options.badge {
+        key.update(&badge.style.font_bytes);
+    }
+    if let Some(ruler) = &options.ruler {
+        key.update(&ruler.style.font_bytes);
+    }
+    for band in options.header.iter().chain(options.footer.iter()) {
+        band.hash_content(&mut key);
+    }
+    for overlay in options.overlays.iter() {
+        key.update(overlay.image());
+    }
+    //a signed render must not be served from an unsigned entry, or one signed with another key
+    if let Some(signing_key) = &options.signing_key {
+        key.update(b"signed");
+        key.update(signing_key);
+    }
+    for tile in tiles {
+        match tile {
+            Tile::Image(buf) => {
+                key.update(b"image");
+                key.update(buf);
+            }
+            Tile::Logo(buf, fit) => {
+                key.update(format!("logo {:?}", fit).as_bytes());
+                key.update(buf);
+            }
+            Tile::TextCard {
                 text,
-            );
+                style,
+                background,
+            } => {
+                key.update(
+                    format!(
+                        "text card {:?} {:?} {:?}",
+                        style.color, style.font_size, background
+                    )
+                    .as_bytes(),
+                );
+                key.update(text.as_bytes());
+                style.hash_style(&mut key);
+            }
+            Tile::Empty => key.update(b"empty"),
+            Tile::Placeholder => key.update(b"placeholder"),
         }
-        for (start, end) in table.table_line_position(padding, origin_image.width() as f32) {
-            draw_line_segment_mut(&mut table_canvas, start, end, BLACK_COLOR);
+    }
+    key.finish(options.output_format.extension())
+}
+
+//re-encode an existing bundle in another format without going through the layout again
+pub async fn transcode(
+    buffer: Vec<u8>,
+    output_format: OutputFormat,
+    mut options: CreateBundledImageOptions,
+) -> Result<Vec<u8>, ImageCombinerError> {
+    let decode_options = DecodeOptions::new(options.exif_orientation);
+    let job = move || decode(&buffer, &decode_options);
+    let dyn_image = match &options.worker_pool {
+        Some(worker_pool) => worker_pool.spawn(Stage::Decode, None, job).await??,
+        None => tokio::spawn(async move { job() })
+            .await
+            .map_err(|e| join_error(e, Stage::Decode, None))??,
+    };
+    options.output_format = output_format;
+    encode_bundle(dyn_image, &options).await
+}
+
+//one encoded image per max_rows_per_page rows instead of one canvas holding every row
+pub async fn create_bundled_image_pages(
+    buffers: impl IntoImageSource,
+    options: CreateBundledImageOptions,
+    max_rows_per_page: u32,
+) -> Result<Vec<Vec<u8>>, ImageCombinerError> {
+    let mut source = open_source(buffers, &options);
+    let mut tiles = Vec::new();
+    while let Some(buffer) = source.next_image().await {
+        tiles.push(Tile::Image(buffer?));
+    }
+    let mut pages = Vec::new();
+    for (image, _) in compose_pages(tiles, &options, max_rows_per_page).await? {
+        pages.push(encode_bundle(image, &options).await?);
+    }
+    Ok(pages)
+}
+
+//one page per max_rows_per_page rows, written as a single multi-page TIFF
+#[cfg(feature = "tiff")]
+pub async fn create_bundled_image_tiff(
+    buffers: impl IntoImageSource,
+    options: CreateBundledImageOptions,
+    max_rows_per_page: u32,
+) -> Result<Vec<u8>, ImageCombinerError> {
+    let mut source = open_source(buffers, &options);
+    let mut tiles = Vec::new();
+    while let Some(buffer) = source.next_image().await {
+        tiles.push(Tile::Image(buffer?));
+    }
+    let pages: Vec<DynamicImage> = compose_pages(tiles, &options, max_rows_per_page)
+        .await?
+        .into_iter()
+        .map(|(image, _)| image)
+        .collect();
+    match &options.worker_pool {
+        Some(worker_pool) => {
+            worker_pool
+                .spawn(Stage::Encode, None, move || encode_tiff_pages(&pages))
+                .await?
         }
+        None => encode_tiff_pages(&pages),
     }
-    //draw origin image
-    {
-        debug!("draw origin image");
-        let mut origin_canvas = full_canvas.sub_image(
-            0,
-            table_canvas_height,
-            origin_image.width(),
-            origin_image.height(),
-        );
-        origin_canvas.copy_from(&origin_image, 0, 0)?;
+}
+
+//the bundle with the placement of every tile keyed by its input index, for outputs that describe the layout
+#[cfg(feature = "zip")]
+async fn render_bundle_with_placements(
+    buffers: impl IntoImageSource,
+    options: &CreateBundledImageOptions,
+) -> Result<(BundledImage, Vec<(usize, TilePlacement)>), ImageCombinerError> {
+    let mut source = open_source(buffers, options);
+    let mut tiles = Vec::new();
+    while let Some(buffer) = source.next_image().await {
+        tiles.push(Tile::Image(buffer?));
     }
+    let count = tiles.len();
+    let bundle = prepare_tiles(tiles, options).await?;
+    //skipped inputs give up their cell, placeholders keep it
+    let dropped = |index: &usize| {
+        options.on_decode_error == DecodeErrorPolicy::Skip && bundle.skipped.contains(index)
+    };
+    let inputs: Vec<usize> = match &options.order {
+        Some(order) => order
+            .iter()
+            .copied()
+            .filter(|index| !dropped(index))
+            .collect(),
+        None => (0..count).filter(|index| !dropped(index)).collect(),
+    };
+    let header = band::header_height(options);
+    let placements = inputs
+        .into_iter()
+        .zip(bundle.layout.placements())
+        .map(|(index, placement)| {
+            (
+                index,
+                TilePlacement {
+                    y: placement.y + header,
+                    ..placement
+                },
+            )
+        })
+        .collect();
+    Ok((render_bundle(bundle, options).await?, placements))
+}
+
+pub struct BundledImage {
+    pub bytes: Vec<u8>,
+    //input indices that failed to decode and were skipped or replaced by a placeholder
+    pub skipped: Vec<usize>,
+    //input indices dropped as near duplicates of an earlier input when set_dedupe is enabled
+    pub duplicates: Vec<usize>,
+    //width to encoded bytes for each requested thumbnail width
+    pub thumbnails: BTreeMap<u32, Vec<u8>>,
+    //hex SHA-256 of bytes when set_sha256 is enabled
+    pub sha256: Option<String>,
+    //jpeg quality the bytes were encoded at, lowered by max_output_bytes or target_file_size
+    pub quality: Option<u8>,
+    //seed given with set_seed, recorded as is
+    pub seed: Option<u64>,
+    //attempts each input took to download, empty when the source does not fetch
+    pub fetch_attempts: Vec<u32>,
+}
 
+pub async fn add_table(
+    buffer: Vec<u8>,
+    table_base: TableBase,
+    font_bytes: &'_ [u8],
+) -> Result<Vec<u8>, ImageCombinerError> {
+    add_table_with_theme(buffer, table_base, font_bytes, Theme::light()).await
+}
+
+//the table is drawn in the text color, lines included
+pub async fn add_table_with_theme(
+    buffer: Vec<u8>,
+    table_base: TableBase,
+    font_bytes: &'_ [u8],
+    theme: Theme,
+) -> Result<Vec<u8>, ImageCombinerError> {
+    add_tables_with_theme(buffer, vec![table_base], font_bytes, theme).await
+}
+
+//tables are stacked top to bottom in the placement of the first one
+pub async fn add_tables(
+    buffer: Vec<u8>,
+    table_bases: Vec<TableBase>,
+    font_bytes: &'_ [u8],
+) -> Result<Vec<u8>, ImageCombinerError> {
+    add_tables_with_theme(buffer, table_bases, font_bytes, Theme::light()).await
+}
+
+pub async fn add_tables_with_theme(
+    buffer: Vec<u8>,
+    table_bases: Vec<TableBase>,
+    font_bytes: &'_ [u8],
+    theme: Theme,
+) -> Result<Vec<u8>, ImageCombinerError> {
+    let origin_image = image::load_from_memory(&buffer)?.to_rgba8();
+    let full_canvas = attach_tables(
+        &origin_image,
+        table_bases,
+        font_bytes,
+        theme.background,
+        (theme.text, theme.text),
+    )?;
     let dyn_image = DynamicImage::ImageRgba8(full_canvas);
-    let mut image_bytes = Vec::new();
-    dyn_image.write_to(&mut image_bytes, image::ImageOutputFormat::Jpeg(100))?;
+    let image_bytes = encode(&dyn_image, OutputFormat::Jpeg, &EncodeOptions::default())?;
     Ok(image_bytes)
 }
 
 pub async fn create_bundled_image_from_bytes_with_table(
-    buffers: Vec<Vec<u8>>,
+    buffers: impl IntoImageSource,
     table_base: TableBase,
     options: CreateBundledImageOptions,
     font_bytes: &'_ [u8],
 ) -> Result<Vec<u8>, ImageCombinerError> {
-    debug!("process {} images into 1", buffers.len());
-    let origin_images = load_images_from_vec(buffers)?;
-    let (width, height) = match options.dimension {
-        Some(user_setting_dimension) => user_setting_dimension,
-        None => find_optical_dimension(&origin_images),
-    };
-    let resize_images = resize_images(origin_images, width, height).await?;
-    let row = (resize_images.len() as f32 / options.column as f32).ceil() as u32;
-    let canvas_height = if row >= 1 {
-        height + options.padding
-    } else {
-        height
-    };
-    let canvas_width = if options.column >= 1 {
-        width + options.padding
-    } else {
-        width
-    };
+    create_bundled_image_from_bytes_with_tables(buffers, vec![table_base], options, font_bytes)
+        .await
+}
 
-    let bundled_image_canvas_height = row * canvas_height;
-    let bundled_image_canvas_width = options.column * canvas_width;
-    let padding = bundled_image_canvas_width as f32 * 0.05;
-    let font_size = (bundled_image_canvas_width as f32 - padding * 2.0) * 0.03;
+//tables are stacked top to bottom in the placement of the first one
+pub async fn create_bundled_image_from_bytes_with_tables(
+    buffers: impl IntoImageSource,
+    table_bases: Vec<TableBase>,
+    options: CreateBundledImageOptions,
+    font_bytes: &'_ [u8],
+) -> Result<Vec<u8>, ImageCombinerError> {
+    let bundle = prepare_bundle(buffers, &options).await?;
+    let (dyn_image, _) = compose_bundle(bundle, &options).await?;
+    let full_canvas = attach_tables(
+        &dyn_image.to_rgba8(),
+        table_bases,
+        font_bytes,
+        options.background,
+        (options.theme.text, options.theme.border),
+    )?;
+    encode_bundle(DynamicImage::ImageRgba8(full_canvas), &options).await
+}
+
+//where add_table and create_bundled_image_from_bytes_with_table put the table, Top by default
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TablePlacement {
+    Top,
+    Bottom,
+    //a sidebar as tall as the image
+    Left,
+    Right,
+    //drawn over the image on a panel of the background color, opacity 255 hides the image below
+    Overlay {
+        position: OverlayPosition,
+        opacity: u8,
+    },
+}
+
+//the image with the tables beside or over it, the font size follows the image width
+fn attach_tables(
+    image: &ImageBuffer<Rgba<u8>, Vec<u8>>,
+    table_bases: Vec<TableBase>,
+    font_bytes: &[u8],
+    background: Rgba<u8>,
+    colors: (Rgba<u8>, Rgba<u8>),
+) -> Result<ImageBuffer<Rgba<u8>, Vec<u8>>, ImageCombinerError> {
+    let (width, height) = image.dimensions();
+    let padding = width as f32 * 0.05;
+    let font_size = (width as f32 - padding * 2.0) * 0.03;
     debug!("font size is {}", font_size);
-    let cell_padding_x = font_size * 0.75;
-    let cell_padding_y = font_size * 0.25;
-    let table = table_base.build(cell_padding_x, cell_padding_y, font_size);
-    let table_canvas_height = table.table_height().ceil() as u32 + padding.ceil() as u32 * 2;
-    let table_canvas_width = table.table_width() + padding * 2.0;
-    if table_canvas_width.ceil() as u32 > bundled_image_canvas_width {
-        debug!("table width would be bigger than origin image width return error");
-        return Err(ImageCombinerError::InvalidTable(format!(
-            "table size over table width is {},canvas width is {}",
-            table_canvas_width.ceil() as u32,
-            table_canvas_width.ceil()
-        )));
+    let placement = table_bases
+        .first()
+        .ok_or_else(|| ImageCombinerError::InvalidTable("no table to attach".to_string()))?
+        .placement;
+    //beside the image there is no width to fill
+    let available_width = match placement {
+        TablePlacement::Left | TablePlacement::Right => None,
+        _ => Some(width as f32 - padding * 2.0),
     };
-
-    let full_canvas_height = bundled_image_canvas_height + table_canvas_height;
-    debug!(
-        "create image buf {}x{}",
-        bundled_image_canvas_width, full_canvas_height
-    );
-    let image_buf = ImageBuffer::from_fn(bundled_image_canvas_width, full_canvas_height, |_, _| {
-        WHITE_COLOR
-    });
-    let image_buf_threaded = Arc::new(Mutex::new(image_buf));
-    draw_bundled_image(
-        Arc::clone(&image_buf_threaded),
-        resize_images,
-        options.column,
-        height,
-        canvas_width,
-        canvas_height,
-        table_canvas_height,
-    )
-    .await?;
-    {
-        let mut image_buf_lock = image_buf_threaded.lock().await;
-        let mut table_canvas =
-            image_buf_lock.sub_image(0, 0, bundled_image_canvas_width, table_canvas_height);
-        let font: Font<'_> = Font::try_from_bytes(font_bytes).unwrap();
-        for (top, left, text) in
-            table.text_top_left_position(padding, bundled_image_canvas_width as f32, cell_padding_y)
-        {
-            draw_text_mut(
-                &mut table_canvas,
-                BLACK_COLOR,
-                left.ceil() as u32,
-                top.ceil() as u32,
-                Scale::uniform(font_size),
-                &font,
-                text,
-            );
+    let tables: Vec<Table> = table_bases
+        .into_iter()
+        .map(|table_base| {
+            table_base.build(
+                font_size * 0.75,
+                font_size * 0.25,
+                font_size,
+                available_width,
+            )
+        })
+        .collect();
+    let font = Font::try_from_bytes(font_bytes)
+        .ok_or_else(|| ImageCombinerError::InvalidText("invalid font bytes".to_string()))?;
+    let widest = tables.iter().map(Table::table_width).fold(0.0, f32::max);
+    //tables are a padding apart from each other as well as from the edges
+    let stack_height =
+        tables.iter().map(Table::outer_height).sum::<f32>() + padding * (tables.len() - 1) as f32;
+    let table_width = (widest + padding * 2.0).ceil() as u32;
+    let table_height = stack_height.ceil() as u32 + padding.ceil() as u32 * 2;
+    let draw_stack = |canvas: &mut image::SubImage<&mut ImageBuffer<Rgba<u8>, Vec<u8>>>,
+                      top: f32| {
+        let mut top = top;
+        for table in tables.iter() {
+            draw_table(canvas, table, &font, top, colors.0, colors.1, true);
+            top += table.outer_height() + padding;
+        }
+    };
+    let too_large = |table: (u32, u32), room: (u32, u32)| {
+        debug!("table would be bigger than the room for it return error");
+        Err(ImageCombinerError::InvalidTable(format!(
+            "table size over room table:{}x{},room:{}x{}",
+            table.0, table.1, room.0, room.1
+        )))
+    };
+    match placement {
+        //fits when the table itself fits the width, the padding on each side shrinks when it does not fit
+        TablePlacement::Top | TablePlacement::Bottom => {
+            if widest.ceil() as u32 > width {
+                return too_large((widest.ceil() as u32, table_height), (width, table_height));
+            }
+            let mut canvas = ImageBuffer::from_pixel(width, height + table_height, background);
+            let (table_top, image_top) = match placement {
+                TablePlacement::Top => (0, table_height),
+                _ => (height, 0),
+            };
+            canvas.copy_from(image, 0, image_top)?;
+            let mut table_canvas = canvas.sub_image(0, table_top, width, table_height);
+            draw_stack(&mut table_canvas, padding);
+            Ok(canvas)
         }
-        for (start, end) in table.table_line_position(padding, bundled_image_canvas_width as f32) {
-            draw_line_segment_mut(&mut table_canvas, start, end, GRAY_COLOR);
+        TablePlacement::Left | TablePlacement::Right => {
+            if table_height > height {
+                return too_large((table_width, table_height), (table_width, height));
+            }
+            let mut canvas = ImageBuffer::from_pixel(width + table_width, height, background);
+            let (table_left, image_left) = match placement {
+                TablePlacement::Left => (0, table_width),
+                _ => (width, 0),
+            };
+            canvas.copy_from(image, image_left, 0)?;
+            let mut table_canvas = canvas.sub_image(table_left, 0, table_width, height);
+            //vertically centered in the sidebar
+            draw_stack(&mut table_canvas, (height as f32 - stack_height) * 0.5);
+            Ok(canvas)
+        }
+        TablePlacement::Overlay { position, opacity } => {
+            if table_width > width || table_height > height {
+                return too_large((table_width, table_height), (width, height));
+            }
+            let mut canvas = image.clone();
+            let (x, y) = position.place((width, height), (table_width, table_height));
+            let mut panel = canvas.sub_image(x, y, table_width, table_height);
+            let panel_color = Rgba([background[0], background[1], background[2], 255]);
+            for panel_y in 0..table_height {
+                for panel_x in 0..table_width {
+                    let pixel = panel.get_pixel(panel_x, panel_y);
+                    panel.put_pixel(
+                        panel_x,
+                        panel_y,
+                        blend_pixel(pixel, panel_color, opacity as f32 / 255.0),
+                    );
+                }
+            }
+            draw_stack(&mut panel, padding);
+            Ok(canvas)
         }
     }
-
-    let dyn_image = DynamicImage::ImageRgba8(image_buf_threaded.lock_owned().await.to_owned());
-    let mut image_bytes = Vec::new();
-    dyn_image.write_to(&mut image_bytes, image::ImageOutputFormat::Jpeg(100))?;
-    Ok(image_bytes)
 }
 
 pub async fn create_bundled_image_from_bytes_with_text<'a>(
-    buffers: Vec<Vec<u8>>,
+    buffers: impl IntoImageSource,
     text: &'a str,
     options: CreateBundledImageOptions,
     font_bytes: &'a [u8],
 ) -> Result<Vec<u8>, ImageCombinerError> {
-    debug!("process {} images into 1", buffers.len());
-    let origin_images = load_images_from_vec(buffers)?;
-    let (width, height) = match options.dimension {
-        Some(user_setting_dimension) => user_setting_dimension,
-        None => find_optical_dimension(&origin_images),
-    };
-    let resize_images = resize_images(origin_images, width, height).await?;
-    let row = (resize_images.len() as f32 / options.column as f32).ceil() as u32;
-    let canvas_height = if row >= 1 {
-        height + options.padding
-    } else {
-        height
-    };
-    let canvas_width = if options.column >= 1 {
-        width + options.padding
-    } else {
-        width
-    };
-
-    let bundled_image_canvas_height = row * canvas_height;
-    let bundled_image_canvas_width = options.column * canvas_width;
+    let PreparedBundle {
+        tiles: resize_images,
+        layout,
+        ..
+    } = prepare_bundle(buffers, &options).await?;
+    let bundled_image_canvas_height = layout.height;
+    let bundled_image_canvas_width = layout.width;
     let padding = bundled_image_canvas_width as f32 * 0.05;
     let font_size = (bundled_image_canvas_width as f32 - padding * 2.0) * 0.03;
     debug!("font size is {}", font_size);
@@ -307,18 +835,17 @@ pub async fn create_bundled_image_from_bytes_with_text<'a>(
         "create image buf {}x{}",
         bundled_image_canvas_width, full_canvas_height
     );
+    let background = options.background;
     let image_buf = ImageBuffer::from_fn(bundled_image_canvas_width, full_canvas_height, |_, _| {
-        WHITE_COLOR
+        background
     });
     let image_buf_threaded = Arc::new(Mutex::new(image_buf));
     draw_bundled_image(
         Arc::clone(&image_buf_threaded),
         resize_images,
-        options.column,
-        height,
-        canvas_width,
-        canvas_height,
+        &layout,
         text_canvas_height,
+        &options,
     )
     .await?;
     {
@@ -330,25 +857,44 @@ pub async fn create_bundled_image_from_bytes_with_text<'a>(
             bundled_image_canvas_width,
             text_canvas_height,
         );
-        draw_text_mut(
+        draw_text(
             &mut text_canvas,
-            BLACK_COLOR,
-            padding.ceil() as u32,
-            padding.ceil() as u32,
+            options.theme.text,
+            (padding.ceil() as u32, padding.ceil() as u32),
             Scale::uniform(font_size),
             &font,
             text,
+            options.antialias(),
         );
     }
     let dyn_image = DynamicImage::ImageRgba8(image_buf_threaded.lock_owned().await.to_owned());
-    let mut image_bytes = Vec::new();
-    dyn_image.write_to(&mut image_bytes, image::ImageOutputFormat::Jpeg(100))?;
+    let image_bytes = encode_bundle(dyn_image, &options).await?;
     Ok(image_bytes)
 }
 
 pub async fn create_table_image(
     table_base: TableBase,
     font_bytes: &'_ [u8],
+) -> Result<Vec<u8>, ImageCombinerError> {
+    create_table_image_with_background(table_base, font_bytes, WHITE_COLOR).await
+}
+
+pub async fn create_table_image_with_background(
+    table_base: TableBase,
+    font_bytes: &'_ [u8],
+    background: Rgba<u8>,
+) -> Result<Vec<u8>, ImageCombinerError> {
+    let theme = Theme {
+        background,
+        ..Theme::light()
+    };
+    create_table_image_with_theme(table_base, font_bytes, theme).await
+}
+
+pub async fn create_table_image_with_theme(
+    table_base: TableBase,
+    font_bytes: &'_ [u8],
+    theme: Theme,
 ) -> Result<Vec<u8>, ImageCombinerError> {
     let mut canvas_width = 960u32;
 
@@ -357,40 +903,58 @@ pub async fn create_table_image(
     debug!("font size is {}", font_size);
     let cell_padding_x = font_size * 0.75;
     let cell_padding_y = font_size * 0.25;
-    let table = table_base.build(cell_padding_x, cell_padding_y, font_size);
-    let table_canvas_height = table.table_height().ceil() as u32 + padding.ceil() as u32 * 2;
+    let table = table_base.build(
+        cell_padding_x,
+        cell_padding_y,
+        font_size,
+        Some(canvas_width as f32 - padding * 2.0),
+    );
+    let table_canvas_height = table.outer_height().ceil() as u32 + padding.ceil() as u32 * 2;
     let table_canvas_width = table.table_width() + padding * 2.0;
     if table_canvas_width.ceil() as u32 > canvas_width {
         canvas_width = table_canvas_width.ceil() as u32 + 100
     }
-    let mut image_buf = ImageBuffer::from_fn(canvas_width, table_canvas_height, |_, _| WHITE_COLOR);
+    let mut image_buf =
+        ImageBuffer::from_fn(canvas_width, table_canvas_height, |_, _| theme.background);
     let font: Font<'_> = Font::try_from_bytes(font_bytes).unwrap();
-    for (top, left, text) in
-        table.text_top_left_position(padding, canvas_width as f32, cell_padding_y)
-    {
-        draw_text_mut(
-            &mut image_buf,
-            BLACK_COLOR,
-            left.ceil() as u32,
-            top.ceil() as u32,
-            Scale::uniform(font_size),
-            &font,
-            text,
-        );
-    }
-    for (start, end) in table.table_line_position(padding, canvas_width as f32) {
-        draw_line_segment_mut(&mut image_buf, start, end, GRAY_COLOR);
-    }
+    draw_table(
+        &mut image_buf,
+        &table,
+        &font,
+        padding,
+        theme.text,
+        theme.border,
+        true,
+    );
 
     let dyn_image = DynamicImage::ImageRgba8(image_buf);
-    let mut image_bytes = Vec::new();
-    dyn_image.write_to(&mut image_bytes, image::ImageOutputFormat::Jpeg(100))?;
+    let image_bytes = encode(&dyn_image, OutputFormat::Jpeg, &EncodeOptions::default())?;
     Ok(image_bytes)
 }
 
 pub async fn create_text_image<'a>(
     text: &'a str,
     font_bytes: &'a [u8],
+) -> Result<Vec<u8>, ImageCombinerError> {
+    create_text_image_with_background(text, font_bytes, WHITE_COLOR).await
+}
+
+pub async fn create_text_image_with_background<'a>(
+    text: &'a str,
+    font_bytes: &'a [u8],
+    background: Rgba<u8>,
+) -> Result<Vec<u8>, ImageCombinerError> {
+    let theme = Theme {
+        background,
+        ..Theme::light()
+    };
+    create_text_image_with_theme(text, font_bytes, theme).await
+}
+
+pub async fn create_text_image_with_theme<'a>(
+    text: &'a str,
+    font_bytes: &'a [u8],
+    theme: Theme,
 ) -> Result<Vec<u8>, ImageCombinerError> {
     let mut canvas_width = 960u32;
 
@@ -403,12 +967,12 @@ pub async fn create_text_image<'a>(
     }
     let text_canvas_height = (font_size + padding * 2.0).ceil() as u32;
     let mut text_canvas =
-        ImageBuffer::from_fn(canvas_width, text_canvas_height, |_, _| WHITE_COLOR);
+        ImageBuffer::from_fn(canvas_width, text_canvas_height, |_, _| theme.background);
 
     let font: Font<'a> = Font::try_from_bytes(font_bytes).unwrap();
     draw_text_mut(
         &mut text_canvas,
-        BLACK_COLOR,
+        theme.text,
         padding.ceil() as u32,
         padding.ceil() as u32,
         Scale::uniform(font_size),
@@ -417,8 +981,7 @@ pub async fn create_text_image<'a>(
     );
 
     let dyn_image = DynamicImage::ImageRgba8(text_canvas);
-    let mut image_bytes = Vec::new();
-    dyn_image.write_to(&mut image_bytes, image::ImageOutputFormat::Jpeg(100))?;
+    let image_bytes = encode(&dyn_image, OutputFormat::Jpeg, &EncodeOptions::default())?;
     Ok(image_bytes)
 }
 #[derive(Clone)]
@@ -426,6 +989,14 @@ pub struct TableBase {
     head: Vec<String>,
     body: Vec<Vec<String>>,
     border_width: u32,
+    placement: TablePlacement,
+    style: TableStyle,
+    alignments: Vec<ColumnAlignment>,
+    widths: Vec<ColumnWidth>,
+    spans: Vec<CellSpan>,
+    title: Option<String>,
+    footnote: Option<String>,
+    transposed: bool,
 }
 
 impl TableBase {
@@ -448,62 +1019,406 @@ impl TableBase {
             head,
             body,
             border_width,
+            placement: TablePlacement::Top,
+            style: TableStyle::default(),
+            alignments: Vec::new(),
+            widths: Vec::new(),
+            spans: Vec::new(),
+            title: None,
+            footnote: None,
+            transposed: false,
         })
     }
 
-    fn build(self, cell_padding_x: f32, cell_padding_y: f32, cell_font_size: f32) -> Table {
-        let mut head: Vec<TableCell> = Vec::new();
-        let cell_height = cell_padding_y * 2.0 + cell_font_size + self.border_width as f32;
-        for (i, column) in self.head.iter().enumerate() {
-            let longest_column_len =
-                (0..self.body.len()).fold(calc_chars_len(column), |acc, body_row_index| {
-                    let body_row_len = calc_chars_len(self.body[body_row_index][i].as_str());
-                    if body_row_len > acc {
-                        return body_row_len;
-                    }
-                    acc
-                });
-            let text_len = cell_font_size * longest_column_len as f32;
-            let width = cell_padding_x * 2.0 + self.border_width as f32 + text_len;
-            let cell = TableCell::new(width, cell_height, column, cell_font_size);
-            head.push(cell);
-        }
-
-        //draw table body
-        let mut body: Vec<Vec<TableCell>> = Vec::new();
-        for row_string in self.body.into_iter() {
-            let mut row: Vec<TableCell> = Vec::new();
-            for (j, column) in row_string.into_iter().enumerate() {
-                let width = head[j].width;
-                row.push(TableCell::new(
-                    width,
-                    cell_height,
-                    column.as_str(),
-                    cell_font_size,
-                ));
-            }
-            body.push(row);
-        }
+    //the first row is the head, every other row must have as many columns
+    pub fn from_csv(
+        reader: impl std::io::Read,
+        border_width: u32,
+    ) -> Result<Self, ImageCombinerError> {
+        Self::from_delimited(reader, ',', border_width)
+    }
 
-        Table::new(head, body, self.border_width)
+    pub fn from_tsv(
+        reader: impl std::io::Read,
+        border_width: u32,
+    ) -> Result<Self, ImageCombinerError> {
+        Self::from_delimited(reader, '\t', border_width)
     }
-}
+
+    //one body row per record of a struct or map, the head is the field names in declaration order
+    #[cfg(feature = "serde")]
+    pub fn from_serde<T: serde::Serialize>(
+        records: &[T],
+        border_width: u32,
+    ) -> Result<Self, ImageCombinerError> {
+        let (head, body) = records::record_rows(records)?;
+        Self::new(head, body, border_width)
+    }
+
+    fn from_delimited(
+        mut reader: impl std::io::Read,
+        delimiter: char,
+        border_width: u32,
+    ) -> Result<Self, ImageCombinerError> {
+        let mut text = String::new();
+        reader.read_to_string(&mut text)?;
+        let mut rows = delimited::parse_delimited(&text, delimiter)?.into_iter();
+        let head = rows
+            .next()
+            .ok_or_else(|| ImageCombinerError::InvalidTable("table has no head row".to_string()))?;
+        Self::new(head, rows.collect(), border_width)
+    }
+
+    pub fn with_placement(mut self, placement: TablePlacement) -> Self {
+        self.placement = placement;
+        self
+    }
+
+    //a line width in the style replaces the border width
+    pub fn with_style(mut self, style: TableStyle) -> Self {
+        if let Some(line_width) = style.line_width {
+            self.border_width = line_width;
+        }
+        self.style = style;
+        self
+    }
+
+    //one alignment per column from the left, columns without one stay centered
+    pub fn with_column_alignments(mut self, alignments: Vec<ColumnAlignment>) -> Self {
+        self.alignments = alignments;
+        self
+    }
+
+    //one width per column from the left, columns without one are sized to their longest text
+    pub fn with_column_widths(mut self, widths: Vec<ColumnWidth>) -> Self {
+        self.widths = widths;
+        self
+    }
+
+    //centered above the grid in a larger font
+    pub fn with_title(mut self, title: String) -> Self {
+        self.title = Some(title);
+        self
+    }
+
+    //left aligned below the grid in a smaller font
+    pub fn with_footnote(mut self, footnote: String) -> Self {
+        self.footnote = Some(footnote);
+        self
+    }
+
+    //the head runs down the first column and each record is a column, which suits narrow canvases,
+    //alignments, widths and spans then count the columns and rows as drawn
+    pub fn with_transposed(mut self, transposed: bool) -> Self {
+        self.transposed = transposed;
+        self
+    }
+
+    //merged cells, a span overlapping an earlier one or starting outside the table is ignored
+    pub fn with_spans(mut self, spans: Vec<CellSpan>) -> Self {
+        self.spans = spans;
+        self
+    }
+
+    //row i is the head cell of column i followed by that column of every record
+    fn transpose(self) -> Self {
+        let mut rows: Vec<Vec<String>> = self.head.into_iter().map(|cell| vec![cell]).collect();
+        for record in self.body {
+            for (row, cell) in rows.iter_mut().zip(record) {
+                row.push(cell);
+            }
+        }
+        let mut rows = rows.into_iter();
+        Self {
+            head: rows.next().unwrap_or_default(),
+            body: rows.collect(),
+            transposed: false,
+            ..self
+        }
+    }
+
+    //per column of a row, 1 for a plain cell, the span for a merged cell and 0 for cells it covers
+    fn row_spans(&self, row: usize) -> Vec<usize> {
+        let column_count = self.head.len();
+        let mut spans = vec![1; column_count];
+        for span in self.spans.iter().filter(|s| s.row == row && s.span > 1) {
+            let end = (span.column + span.span).min(column_count);
+            if span.column >= column_count || spans[span.column..end].iter().any(|s| *s != 1) {
+                continue;
+            }
+            spans[span.column] = end - span.column;
+            for covered in spans[span.column + 1..end].iter_mut() {
+                *covered = 0;
+            }
+        }
+        spans
+    }
+
+    //fill columns share what is left of available_width, without it they are sized like auto
+    fn build(
+        self,
+        cell_padding_x: f32,
+        cell_padding_y: f32,
+        cell_font_size: f32,
+        available_width: Option<f32>,
+    ) -> Table {
+        if self.transposed {
+            let mut table = self.transpose().build(
+                cell_padding_x,
+                cell_padding_y,
+                cell_font_size,
+                available_width,
+            );
+            table.transposed = true;
+            return table;
+        }
+        let alignment = |column: usize| {
+            let alignment = self.alignments.get(column).copied();
+            (alignment.unwrap_or(ColumnAlignment::Center), cell_padding_x)
+        };
+        let rows: Vec<&Vec<String>> = std::iter::once(&self.head).chain(&self.body).collect();
+        let row_spans: Vec<Vec<usize>> = (0..rows.len()).map(|row| self.row_spans(row)).collect();
+        let mut widths: Vec<f32> = Vec::new();
+        for i in 0..self.head.len() {
+            //merged cells are fitted after the columns they cover
+            let longest_column_len = (0..rows.len())
+                .filter(|row| row_spans[*row][i] == 1)
+                .map(|row| calc_chars_len(rows[row][i].as_str()))
+                .max()
+                .unwrap_or(0);
+            let text_len = cell_font_size * longest_column_len as f32;
+            let width = cell_padding_x * 2.0 + self.border_width as f32 + text_len;
+            widths.push(match self.widths.get(i) {
+                Some(ColumnWidth::Fixed(fixed)) => *fixed as f32,
+                Some(ColumnWidth::Min(min)) => width.max(*min as f32),
+                Some(ColumnWidth::Max(max)) => width.min(*max as f32),
+                _ => width,
+            });
+        }
+        for (row, spans) in row_spans.iter().enumerate() {
+            for (i, span) in spans.iter().enumerate().filter(|(_, span)| **span > 1) {
+                let text_len = cell_font_size * calc_chars_len(rows[row][i].as_str()) as f32;
+                let needed = cell_padding_x * 2.0 + self.border_width as f32 + text_len;
+                let merged: f32 = widths[i..i + span].iter().sum();
+                if needed > merged && !matches!(self.widths.get(i), Some(ColumnWidth::Max(_))) {
+                    widths[i + span - 1] += needed - merged;
+                }
+            }
+        }
+        let fill_columns: Vec<usize> = (0..widths.len())
+            .filter(|i| self.widths.get(*i) == Some(&ColumnWidth::Fill))
+            .collect();
+        if let (Some(available_width), false) = (available_width, fill_columns.is_empty()) {
+            let used = widths.iter().sum::<f32>() + self.border_width as f32;
+            let extra = (available_width - used).max(0.0) / fill_columns.len() as f32;
+            for i in fill_columns {
+                widths[i] += extra;
+            }
+        }
+        //auto columns give way when the table is wider than available_width, the widest first,
+        //down to one character each
+        let mut narrowed = Vec::new();
+        if let Some(available_width) = available_width {
+            let auto: Vec<usize> = (0..widths.len())
+                .filter(|i| matches!(self.widths.get(*i), None | Some(ColumnWidth::Auto)))
+                .collect();
+            let border = self.border_width as f32;
+            let others: f32 = (0..widths.len())
+                .filter(|i| !auto.contains(i))
+                .map(|i| widths[i])
+                .sum();
+            if widths.iter().sum::<f32>() + border > available_width && !auto.is_empty() {
+                let mut sorted: Vec<f32> = auto.iter().map(|&i| widths[i]).collect();
+                sorted.sort_by(f32::total_cmp);
+                let mut room = available_width - border - others;
+                let mut cap = f32::MAX;
+                for (n, width) in sorted.iter().enumerate() {
+                    let remaining = (sorted.len() - n) as f32;
+                    if width * remaining > room {
+                        cap = room / remaining;
+                        break;
+                    }
+                    room -= width;
+                }
+                let cap = cap.max(cell_padding_x * 2.0 + border + cell_font_size);
+                for i in auto {
+                    if widths[i] > cap {
+                        widths[i] = cap;
+                        narrowed.push(i);
+                    }
+                }
+            }
+        }
+        //fixed, max and narrowed auto columns wrap their text, the row grows to its tallest cell
+        let wraps: Vec<bool> = (0..widths.len())
+            .map(|i| {
+                narrowed.contains(&i)
+                    || matches!(
+                        self.widths.get(i),
+                        Some(ColumnWidth::Fixed(_)) | Some(ColumnWidth::Max(_))
+                    )
+            })
+            .collect();
+        let build_row = |row: usize| {
+            let mut row: Vec<TableCell> = rows[row]
+                .iter()
+                .zip(row_spans[row].iter())
+                .enumerate()
+                .map(|(i, (text, span))| {
+                    let span_width: f32 = widths[i..i + span].iter().sum();
+                    let lines = if *span == 0 {
+                        Vec::new()
+                    } else if wraps[i] {
+                        let text_width =
+                            span_width - cell_padding_x * 2.0 - self.border_width as f32;
+                        wrap_text(text, cell_font_size, text_width.max(cell_font_size))
+                    } else {
+                        vec![text.clone()]
+                    };
+                    let mut cell = TableCell::new(widths[i], lines, cell_font_size, alignment(i));
+                    cell.span = *span;
+                    cell.span_width = span_width;
+                    cell
+                })
+                .collect();
+            let line_count = row.iter().map(|cell| cell.lines.len()).max().unwrap_or(0);
+            let line_count = line_count.max(1);
+            let height = cell_padding_y * 2.0
+                + cell_font_size * line_count as f32
+                + self.border_width as f32;
+            for cell in row.iter_mut() {
+                cell.height = height;
+            }
+            row
+        };
+        let head = build_row(0);
+        let body = (1..rows.len()).map(build_row).collect();
+
+        let mut table = Table::new(
+            head,
+            body,
+            self.border_width,
+            cell_padding_y,
+            cell_font_size,
+            self.style,
+        );
+        table.title = self.title;
+        table.footnote = self.footnote;
+        table
+    }
+}
+
+//colors of a table, unset colors come from the theme and cells are left unfilled
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct TableStyle {
+    line_color: Option<Rgba<u8>>,
+    text_color: Option<Rgba<u8>>,
+    header_fill: Option<Rgba<u8>>,
+    body_fill: Option<Rgba<u8>>,
+    stripe_fill: Option<Rgba<u8>>,
+    line_width: Option<u32>,
+}
+
+impl TableStyle {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_line_color(mut self, line_color: Rgba<u8>) -> Self {
+        self.line_color = Some(line_color);
+        self
+    }
+
+    pub fn with_text_color(mut self, text_color: Rgba<u8>) -> Self {
+        self.text_color = Some(text_color);
+        self
+    }
+
+    pub fn with_header_fill(mut self, header_fill: Rgba<u8>) -> Self {
+        self.header_fill = Some(header_fill);
+        self
+    }
+
+    pub fn with_body_fill(mut self, body_fill: Rgba<u8>) -> Self {
+        self.body_fill = Some(body_fill);
+        self
+    }
+
+    //every second body row, drawn over the body fill
+    pub fn with_stripe_fill(mut self, stripe_fill: Rgba<u8>) -> Self {
+        self.stripe_fill = Some(stripe_fill);
+        self
+    }
+
+    pub fn with_line_width(mut self, line_width: u32) -> Self {
+        self.line_width = Some(line_width.max(1));
+        self
+    }
+}
 
 pub struct Table {
     head: Vec<TableCell>,
     body: Vec<Vec<TableCell>>,
     border_width: u32,
+    cell_padding_y: f32,
+    font_size: f32,
+    style: TableStyle,
+    title: Option<String>,
+    footnote: Option<String>,
+    //the head is the first column and every record a column
+    transposed: bool,
 }
 
 impl Table {
-    fn new(head: Vec<TableCell>, body: Vec<Vec<TableCell>>, border_width: u32) -> Self {
+    fn new(
+        head: Vec<TableCell>,
+        body: Vec<Vec<TableCell>>,
+        border_width: u32,
+        cell_padding_y: f32,
+        font_size: f32,
+        style: TableStyle,
+    ) -> Self {
         Self {
             head,
             body,
             border_width,
+            cell_padding_y,
+            font_size,
+            style,
+            title: None,
+            footnote: None,
+            transposed: false,
+        }
+    }
+
+    fn title_font_size(&self) -> f32 {
+        self.font_size * 1.25
+    }
+
+    fn footnote_font_size(&self) -> f32 {
+        self.font_size * 0.75
+    }
+
+    //room above the grid taken by the title
+    fn title_height(&self) -> f32 {
+        match self.title {
+            Some(_) => self.title_font_size() + self.cell_padding_y * 2.0,
+            None => 0.0,
+        }
+    }
+
+    fn footnote_height(&self) -> f32 {
+        match self.footnote {
+            Some(_) => self.footnote_font_size() + self.cell_padding_y * 2.0,
+            None => 0.0,
         }
     }
 
+    //the grid with the title and footnote around it
+    fn outer_height(&self) -> f32 {
+        self.title_height() + self.table_height() + self.footnote_height()
+    }
+
     fn table_width(&self) -> f32 {
         self.head
             .iter()
@@ -525,24 +1440,22 @@ impl Table {
         cell_padding_y: f32,
     ) -> Vec<(f32, f32, &String)> {
         let mut res = Vec::new();
-        let head_text_top = padding + cell_padding_y + self.border_width as f32;
-        //handle table head
-        let mut current_cell_x = full_canvas_width * 0.5 - self.table_width() * 0.5;
-        for cell in self.head.iter() {
-            let head_text_left = current_cell_x + cell.width * 0.5 - cell.text_len * 0.5;
-            res.push((head_text_top, head_text_left, &cell.text));
-            current_cell_x += cell.width;
-        }
-        //draw table body
-        for (i, row) in self.body.iter().enumerate() {
-            let body_cell_top = padding + row[0].height + i as f32 * row[0].height;
-            let body_text_top = body_cell_top + cell_padding_y + self.border_width as f32;
+        let mut cell_top = padding;
+        for row in std::iter::once(&self.head).chain(self.body.iter()) {
+            let text_top = cell_top + cell_padding_y + self.border_width as f32;
             let mut current_cell_x = full_canvas_width * 0.5 - self.table_width() * 0.5;
             for cell in row.iter() {
-                let body_text_left = current_cell_x + cell.width * 0.5 - cell.text_len * 0.5;
-                res.push((body_text_top, body_text_left, &cell.text));
+                for (line_index, line) in cell.lines.iter().enumerate() {
+                    let line_top = text_top + line_index as f32 * self.font_size;
+                    res.push((
+                        line_top,
+                        cell.text_left(line, current_cell_x, self.border_width),
+                        line,
+                    ));
+                }
                 current_cell_x += cell.width;
             }
+            cell_top += row[0].height;
         }
         res
     }
@@ -572,25 +1485,34 @@ impl Table {
         }
         let column_line_start_y = padding;
         let column_line_end_y = (self.border_width - 1) as f32 + padding + self.table_height();
+        //drawn row by row so no line runs through a merged cell
         for border_shift in 0..self.border_width {
-            let mut current_column_line_x = border_shift as f32 + row_line_start_x;
-            for column in self.head.iter() {
-                let column_top_line = (
-                    (current_column_line_x, column_line_start_y),
-                    (current_column_line_x, column_line_end_y),
-                );
-                res.push(column_top_line);
-                current_column_line_x += column.width;
+            let mut row_top = column_line_start_y;
+            for row in std::iter::once(&self.head).chain(self.body.iter()) {
+                let row_bottom = (self.border_width - 1) as f32 + row_top + row[0].height;
+                let mut current_column_line_x = border_shift as f32 + row_line_start_x;
+                for cell in row.iter() {
+                    if cell.span > 0 {
+                        let column_line = (
+                            (current_column_line_x, row_top),
+                            (current_column_line_x, row_bottom),
+                        );
+                        res.push(column_line);
+                    }
+                    current_column_line_x += cell.width;
+                }
+                row_top += row[0].height;
             }
         }
 
         for border_shift in 0..self.border_width {
-            for (i, row) in self.body.iter().enumerate() {
-                let row_line_y =
-                    border_shift as f32 + column_line_start_y + (i + 1) as f32 * row[0].height;
+            let mut row_top = column_line_start_y + self.head[0].height;
+            for row in self.body.iter() {
+                let row_line_y = border_shift as f32 + row_top;
                 let body_row_top_line =
                     ((row_line_start_x, row_line_y), (row_line_end_x, row_line_y));
-                res.push(body_row_top_line)
+                res.push(body_row_top_line);
+                row_top += row[0].height;
             }
         }
         for border_shift in 0..self.border_width {
@@ -606,191 +1528,4192 @@ impl Table {
     }
 }
 
+//the table centered in the canvas width, padding is the space above it,
+//text_color and line_color are used where the table style sets none
+fn draw_table<I: GenericImage<Pixel = Rgba<u8>>>(
+    canvas: &mut I,
+    table: &Table,
+    font: &Font<'_>,
+    padding: f32,
+    text_color: Rgba<u8>,
+    line_color: Rgba<u8>,
+    antialias: bool,
+) {
+    let canvas_width = canvas.width() as f32;
+    let text_color = table.style.text_color.unwrap_or(text_color);
+    let line_color = table.style.line_color.unwrap_or(line_color);
+    let table_left = canvas_width * 0.5 - table.table_width() * 0.5;
+    if let Some(title) = &table.title {
+        let title_len = calc_chars_len(title) as f32 * table.title_font_size();
+        draw_text(
+            canvas,
+            text_color,
+            (
+                (canvas_width * 0.5 - title_len * 0.5).max(0.0).ceil() as u32,
+                padding.ceil() as u32,
+            ),
+            Scale::uniform(table.title_font_size()),
+            font,
+            title,
+            antialias,
+        );
+    }
+    if let Some(footnote) = &table.footnote {
+        let footnote_top = padding
+            + table.title_height()
+            + table.table_height()
+            + table.border_width as f32
+            + table.cell_padding_y;
+        draw_text(
+            canvas,
+            text_color,
+            (
+                table_left.max(0.0).ceil() as u32,
+                footnote_top.ceil() as u32,
+            ),
+            Scale::uniform(table.footnote_font_size()),
+            font,
+            footnote,
+            antialias,
+        );
+    }
+    //the grid starts below the title
+    let padding = padding + table.title_height();
+    //fills go under the lines and text
+    let left = table_left.round();
+    let width = table.table_width().round() + table.border_width as f32 - 1.0;
+    let height = table.table_height();
+    //(fill, left, top, width, height)
+    let mut fills = Vec::new();
+    if table.transposed {
+        //the head column and every other record column
+        let head_width = table.head[0].width;
+        fills.push((table.style.header_fill, left, padding, head_width, height));
+        fills.push((
+            table.style.body_fill,
+            left + head_width,
+            padding,
+            width - head_width,
+            height,
+        ));
+        let mut column_left = left + head_width;
+        for (index, cell) in table.head.iter().skip(1).enumerate() {
+            if index % 2 == 1 {
+                fills.push((
+                    table.style.stripe_fill,
+                    column_left,
+                    padding,
+                    cell.width,
+                    height,
+                ));
+            }
+            column_left += cell.width;
+        }
+    } else {
+        let head_height = table.head[0].height;
+        fills.push((table.style.header_fill, left, padding, width, head_height));
+        fills.push((
+            table.style.body_fill,
+            left,
+            padding + head_height,
+            width,
+            height - head_height,
+        ));
+        let mut row_top = padding + head_height;
+        for (index, row) in table.body.iter().enumerate() {
+            if index % 2 == 1 {
+                fills.push((table.style.stripe_fill, left, row_top, width, row[0].height));
+            }
+            row_top += row[0].height;
+        }
+    }
+    for (fill, left, top, width, height) in fills {
+        if let (Some(fill), true) = (fill, height >= 1.0 && width >= 1.0) {
+            draw_filled_rect_mut(
+                canvas,
+                Rect::at(left as i32, top.round() as i32)
+                    .of_size(width.round() as u32, height.round() as u32),
+                fill,
+            );
+        }
+    }
+    for (top, left, text) in
+        table.text_top_left_position(padding, canvas_width, table.cell_padding_y)
+    {
+        draw_text(
+            canvas,
+            text_color,
+            (left.ceil() as u32, top.ceil() as u32),
+            Scale::uniform(table.font_size),
+            font,
+            text,
+            antialias,
+        );
+    }
+    for (start, end) in table.table_line_position(padding, canvas_width) {
+        draw_line_segment_mut(canvas, start, end, line_color);
+    }
+}
+
+//a cell at row and column merged with the span - 1 cells to its right, row 0 is the header
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CellSpan {
+    pub row: usize,
+    pub column: usize,
+    pub span: usize,
+}
+
+impl CellSpan {
+    pub fn new(row: usize, column: usize, span: usize) -> Self {
+        Self { row, column, span }
+    }
+}
+
+//widths in pixels including the cell padding, text longer than a fixed or max width wraps,
+//auto columns wrap too when the table has a width to fit, like above or below an image
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColumnWidth {
+    Auto,
+    Fixed(u32),
+    Min(u32),
+    Max(u32),
+    //takes the width the other columns leave over
+    Fill,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ColumnAlignment {
+    Left,
+    Center,
+    Right,
+}
+
 pub struct TableCell {
     width: f32,
     height: f32,
-    text: String,
-    text_len: f32,
+    lines: Vec<String>,
+    font_size: f32,
+    alignment: ColumnAlignment,
+    padding_x: f32,
+    //columns this cell covers, 0 when merged into a cell to its left
+    span: usize,
+    span_width: f32,
 }
 
 impl TableCell {
-    fn new(width: f32, height: f32, text: &str, font_size: f32) -> Self {
-        let chars_count = calc_chars_len(text);
+    //height is set once the whole row is known
+    fn new(
+        width: f32,
+        lines: Vec<String>,
+        font_size: f32,
+        (alignment, padding_x): (ColumnAlignment, f32),
+    ) -> Self {
         Self {
             width,
-            height,
-            text: text.to_owned(),
-            text_len: chars_count as f32 * font_size,
+            height: 0.0,
+            lines,
+            font_size,
+            alignment,
+            padding_x,
+            span: 1,
+            span_width: width,
+        }
+    }
+
+    fn text_left(&self, line: &str, cell_left: f32, border_width: u32) -> f32 {
+        let text_len = calc_chars_len(line) as f32 * self.font_size;
+        match self.alignment {
+            ColumnAlignment::Left => cell_left + border_width as f32 + self.padding_x,
+            ColumnAlignment::Center => cell_left + self.span_width * 0.5 - text_len * 0.5,
+            ColumnAlignment::Right => cell_left + self.span_width - self.padding_x - text_len,
         }
     }
 }
 
-pub struct CreateBundledImageOptions {
-    dimension: Option<(u32, u32)>,
-    padding: u32,
-    column: u32,
+pub enum Tile {
+    Image(Vec<u8>),
+    //placed like an image but never upscaled and always centered in its cell
+    Logo(Vec<u8>, Fit),
+    //a block of text wrapped within a cell the size of an image tile
+    TextCard {
+        text: String,
+        style: TextCardStyle,
+        background: Rgba<u8>,
+    },
+    //a cell intentionally left blank
+    Empty,
+    //a cell filled with the same crossed out graphic as images that failed to decode
+    Placeholder,
 }
 
-impl CreateBundledImageOptions {
-    pub fn new(dimension: Option<(u32, u32)>, padding: u32, column: u32) -> Self {
+#[derive(Clone)]
+pub struct TextCardStyle {
+    font_bytes: Vec<u8>,
+    color: Rgba<u8>,
+    font_size: Option<f32>,
+    script_fonts: Vec<(Script, Vec<u8>)>,
+    gradient: Option<TextGradient>,
+    pill: Option<TextPill>,
+}
+
+impl TextCardStyle {
+    pub fn new(font_bytes: Vec<u8>, color: Rgba<u8>, font_size: Option<f32>) -> Self {
         Self {
-            dimension,
-            padding,
-            column,
+            font_bytes,
+            color,
+            font_size,
+            script_fonts: Vec::new(),
+            gradient: None,
+            pill: None,
+        }
+    }
+
+    //runs of this script are drawn with font_bytes instead of the style font,
+    //so mixed language text keeps the proper typeface for each script
+    pub fn with_script_font(mut self, script: Script, font_bytes: Vec<u8>) -> Self {
+        self.script_fonts.push((script, font_bytes));
+        self
+    }
+
+    //text is filled with a linear gradient from the style color instead of the flat color
+    pub fn with_gradient(mut self, gradient: TextGradient) -> Self {
+        self.gradient = Some(gradient);
+        self
+    }
+
+    //a rounded box behind the text so it stays readable over busy photos
+    pub fn with_pill(mut self, pill: TextPill) -> Self {
+        self.pill = Some(pill);
+        self
+    }
+
+    //fonts and fill, the parts of the style not in the option fingerprint
+    fn hash_style(&self, key: &mut CacheKey) {
+        key.update(format!("{:?}{:?}", self.gradient, self.pill).as_bytes());
+        key.update(&self.font_bytes);
+        for (script, font_bytes) in self.script_fonts.iter() {
+            key.update(format!("{:?}", script).as_bytes());
+            key.update(font_bytes);
         }
     }
 }
-pub struct CreateBundledImageOptionsBuilder {
-    member_dimension: Option<(u32, u32)>,
-    column: Option<u32>,
-    padding: Option<u32>,
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum DecodeErrorPolicy {
+    FailFast,
+    Skip,
+    Placeholder,
 }
 
-impl Default for CreateBundledImageOptionsBuilder {
-    fn default() -> Self {
-        Self {
-            member_dimension: None,
-            column: None,
-            padding: None,
-        }
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Fit {
+    Contain,
+    Cover,
+    Stretch,
+    CropCenter,
+    //Cover and CropCenter that keep the part of the image with the most edges instead of its center
+    SmartCover,
+    SmartCrop,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum CellAlignment {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight,
+}
+
+impl CellAlignment {
+    //offset of a tile inside its cell given the free space left around it
+    fn offset(self, free_width: u32, free_height: u32) -> (u32, u32) {
+        let x = match self {
+            Self::TopLeft | Self::Left | Self::BottomLeft => 0,
+            Self::Top | Self::Center | Self::Bottom => geometry::center_px(free_width),
+            Self::TopRight | Self::Right | Self::BottomRight => free_width,
+        };
+        let y = match self {
+            Self::TopLeft | Self::Top | Self::TopRight => 0,
+            Self::Left | Self::Center | Self::Right => geometry::center_px(free_height),
+            Self::BottomLeft | Self::Bottom | Self::BottomRight => free_height,
+        };
+        (x, y)
     }
 }
 
-impl CreateBundledImageOptionsBuilder {
-    pub fn new() -> Self {
+//corner, edge or center an overlay is pinned to, the same nine positions a tile aligns to in its cell
+pub type Anchor = CellAlignment;
+
+//anchor plus an offset in pixels, positive offsets move the overlay in from the edges it is anchored to,
+//on a centered axis they move it right or down
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OverlayPosition {
+    pub anchor: Anchor,
+    pub offset_x: i32,
+    pub offset_y: i32,
+}
+
+impl OverlayPosition {
+    pub fn new(anchor: Anchor) -> Self {
         Self {
-            member_dimension: None,
-            column: None,
-            padding: None,
+            anchor,
+            offset_x: 0,
+            offset_y: 0,
         }
     }
 
-    pub fn set_member_dimension(mut self, width: u32, height: u32) -> Self {
-        self.member_dimension = Some((width, height));
+    pub fn with_offset(mut self, offset_x: i32, offset_y: i32) -> Self {
+        self.offset_x = offset_x;
+        self.offset_y = offset_y;
         self
     }
 
-    pub fn set_column(mut self, column: u32) -> Self {
-        self.column = Some(column);
-        self
+    //the same inset from every anchored edge, none along a centered axis
+    fn inset(anchor: Anchor, inset: u32) -> Self {
+        let (x, y) = anchor.offset(2, 2);
+        let inward = |side: u32| if side == 1 { 0 } else { inset as i32 };
+        Self::new(anchor).with_offset(inward(x), inward(y))
     }
 
-    pub fn set_padding(mut self, padding: u32) -> Self {
-        self.padding = Some(padding);
-        self
+    //top left of an overlay of size inside area, kept inside the area
+    fn place(self, area: (u32, u32), size: (u32, u32)) -> (u32, u32) {
+        let free = (area.0.saturating_sub(size.0), area.1.saturating_sub(size.1));
+        let (x, y) = self.anchor.offset(free.0, free.1);
+        //anchored to the far edge the offset moves left or up
+        let (side_x, side_y) = self.anchor.offset(2, 2);
+        let shift = |at: u32, side: u32, offset: i32, free: u32| {
+            let offset = if side == 2 { -offset } else { offset };
+            (at as i64 + offset as i64).clamp(0, free as i64) as u32
+        };
+        (
+            shift(x, side_x, self.offset_x, free.0),
+            shift(y, side_y, self.offset_y, free.1),
+        )
     }
+}
 
-    pub fn build(&self) -> CreateBundledImageOptions {
-        let padding = self.padding.unwrap_or(20);
-        let column = self.column.unwrap_or(1);
-        CreateBundledImageOptions::new(self.member_dimension, padding, column)
+impl From<Anchor> for OverlayPosition {
+    fn from(anchor: Anchor) -> Self {
+        Self::new(anchor)
     }
 }
 
-fn calc_chars_len(s: &str) -> usize {
-    s.chars().fold(0.0, |acc, c| {
-        if c.is_ascii() {
-            return acc + 0.5;
-        }
-        acc + 1.0
-    }) as usize
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum FillOrder {
+    RowMajor,
+    ColumnMajor,
 }
 
-fn load_images_from_vec(buffers: Vec<Vec<u8>>) -> Result<Vec<DynamicImage>, ImageCombinerError> {
-    let mut origin_images: Vec<DynamicImage> = Vec::new();
-    for buf in buffers {
-        let origin_image = image::load_from_memory(&buf)?;
-        origin_images.push(origin_image);
-    }
-    Ok(origin_images)
+//ByHeight and ByWidth put the largest first, ByAspect the tallest first,
+//tiles without a readable size keep their input order after the images
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SortOrder {
+    InputOrder,
+    ByHeight,
+    ByWidth,
+    ByAspect,
 }
 
-async fn resize_images(
-    images: Vec<DynamicImage>,
-    target_image_width: u32,
-    target_image_height: u32,
-) -> Result<Vec<DynamicImage>, ImageCombinerError> {
-    let mut resized_images_handles: Vec<JoinHandle<DynamicImage>> = Vec::new();
-    for (i, mut origin_image) in images.into_iter().enumerate() {
-        let handle = tokio::spawn(async move {
-            if origin_image.height() != target_image_height {
-                debug!("resize image no {}", i + 1);
-                origin_image = origin_image.resize(
-                    target_image_width,
-                    target_image_height,
-                    image::imageops::FilterType::Lanczos3,
-                );
-            }
-            origin_image
-        });
-        resized_images_handles.push(handle);
+#[derive(Clone)]
+pub enum CellStyle {
+    Plain,
+    //white frame of border width with a bottom_band tall band below the image
+    Frame {
+        border: u32,
+        bottom_band: u32,
+        caption: Option<FrameCaption>,
+    },
+}
+
+impl CellStyle {
+    fn frame_padding(&self) -> Padding {
+        match self {
+            CellStyle::Plain => Padding::default(),
+            CellStyle::Frame {
+                border,
+                bottom_band,
+                ..
+            } => Padding::new(*border, *border, *bottom_band, *border),
+        }
     }
-    let mut resize_images = Vec::new();
-    for handle in resized_images_handles {
-        resize_images.push(handle.await?)
+
+    fn fingerprint(&self) -> String {
+        match self {
+            CellStyle::Plain => "plain".to_string(),
+            CellStyle::Frame {
+                border,
+                bottom_band,
+                caption,
+            } => format!(
+                "frame {} {} {:?}",
+                border,
+                bottom_band,
+                caption.as_ref().map(|caption| (
+                    &caption.captions,
+                    caption.style.color,
+                    caption.style.font_size
+                ))
+            ),
+        }
     }
-    Ok(resize_images)
 }
 
-async fn draw_bundled_image(
-    image_buf_threaded: Arc<Mutex<ImageBuffer<Rgba<u8>, Vec<u8>>>>,
-    images: Vec<DynamicImage>,
-    column: u32,
-    image_height: u32,
-    image_canvas_width: u32,
-    image_canvas_height: u32,
-    bundled_image_canvas_y: u32,
-) -> Result<(), ImageCombinerError> {
-    let mut handles: Vec<JoinHandle<Result<(), ImageCombinerError>>> = Vec::new();
-    for (i, image) in images.into_iter().enumerate() {
-        let cloned_image_buf = Arc::clone(&image_buf_threaded);
-        let handle = tokio::spawn(async move {
-            let current_column = (i as u32 % column) as u32;
-            let current_row = (i as u32 / column) as u32;
-            debug!("write image no {}", i);
-            let image = image.to_rgba8();
-            let mut buf = 0;
-            if image.height() <= image_height {
-                let sub = image_height - image.height();
-                buf = sub / 2;
-            }
-            let mut image_buf = cloned_image_buf.lock().await;
-            image_buf.copy_from(
-                &image,
-                current_column * image_canvas_width,
-                current_row * image_canvas_height + buf + bundled_image_canvas_y,
-            )?;
-            Ok(())
-        });
-        handles.push(handle)
+//number of each image in placement order, drawn over the image at position
+#[derive(Clone)]
+pub struct NumberBadge {
+    style: TextCardStyle,
+    //the theme accent when None
+    background: Option<Rgba<u8>>,
+    position: Anchor,
+    offset: Option<(i32, i32)>,
+}
+
+impl NumberBadge {
+    pub fn new(style: TextCardStyle, background: Rgba<u8>, position: Anchor) -> Self {
+        Self {
+            style,
+            background: Some(background),
+            position,
+            offset: None,
+        }
+    }
+
+    //the circle takes the accent color of the options theme
+    pub fn themed(style: TextCardStyle, position: Anchor) -> Self {
+        Self {
+            style,
+            background: None,
+            position,
+            offset: None,
+        }
     }
 
-    for handle in handles {
-        handle.await??;
+    //in from the anchored edges of the image, half the font size by default
+    pub fn with_offset(mut self, offset_x: i32, offset_y: i32) -> Self {
+        self.offset = Some((offset_x, offset_y));
+        self
     }
-    Ok(())
 }
 
-fn find_optical_dimension(origin_images: &[DynamicImage]) -> (u32, u32) {
-    let mut dimension_map: HashMap<(u32, u32), u8> = std::collections::HashMap::new();
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RulerUnit {
+    Centimeter,
+    Inch,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Edge {
+    Top,
+    Bottom,
+    Left,
+    Right,
+}
+
+//scale drawn over one edge of the canvas, pixels_per_cm ties it to the physical size of the photos
+#[derive(Clone)]
+pub struct Ruler {
+    pixels_per_cm: f32,
+    unit: RulerUnit,
+    edge: Edge,
+    style: TextCardStyle,
+    //the theme banner when None
+    background: Option<Rgba<u8>>,
+}
+
+impl Ruler {
+    pub fn new(
+        pixels_per_cm: f32,
+        unit: RulerUnit,
+        edge: Edge,
+        style: TextCardStyle,
+        background: Rgba<u8>,
+    ) -> Self {
+        Self {
+            pixels_per_cm,
+            unit,
+            edge,
+            style,
+            background: Some(background),
+        }
+    }
+
+    //the strip behind the ticks takes the banner color of the options theme
+    pub fn themed(pixels_per_cm: f32, unit: RulerUnit, edge: Edge, style: TextCardStyle) -> Self {
+        Self {
+            pixels_per_cm,
+            unit,
+            edge,
+            style,
+            background: None,
+        }
+    }
+}
+
+//arrows from each tile to the next in placement order, drawn through the gaps between cells
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ArrowStyle {
+    //the theme accent when None
+    pub color: Option<Rgba<u8>>,
+    pub thickness: u32,
+    pub head_size: u32,
+}
+
+impl ArrowStyle {
+    pub fn new(color: Rgba<u8>, thickness: u32, head_size: u32) -> Self {
+        Self {
+            color: Some(color),
+            thickness,
+            head_size,
+        }
+    }
+
+    //arrows in the accent color of the options theme
+    pub fn themed(thickness: u32, head_size: u32) -> Self {
+        Self {
+            color: None,
+            thickness,
+            head_size,
+        }
+    }
+}
+
+//captions[i] is drawn into the band of the image at position i
+#[derive(Clone)]
+pub struct FrameCaption {
+    captions: Vec<String>,
+    style: TextCardStyle,
+}
+
+impl FrameCaption {
+    pub fn new(captions: Vec<String>, style: TextCardStyle) -> Self {
+        Self { captions, style }
+    }
+}
+
+//colors shared by every section of an asset, switch the theme to render its dark variant
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Theme {
+    pub background: Rgba<u8>,
+    pub text: Rgba<u8>,
+    pub border: Rgba<u8>,
+    //highlights a single element, such as a selected cell or an emphasis mark
+    pub accent: Rgba<u8>,
+    //behind frames and bands drawn around the images
+    pub banner: Rgba<u8>,
+}
+
+impl Theme {
+    pub fn new(
+        background: Rgba<u8>,
+        text: Rgba<u8>,
+        border: Rgba<u8>,
+        accent: Rgba<u8>,
+        banner: Rgba<u8>,
+    ) -> Self {
+        Self {
+            background,
+            text,
+            border,
+            accent,
+            banner,
+        }
+    }
+
+    //the colors used before themes existed
+    pub fn light() -> Self {
+        Self::new(
+            WHITE_COLOR,
+            BLACK_COLOR,
+            GRAY_COLOR,
+            Rgba([220, 40, 40, 255]),
+            Rgba([255, 255, 255, 255]),
+        )
+    }
+
+    pub fn dark() -> Self {
+        Self::new(
+            Rgba([24, 24, 24, 255]),
+            Rgba([236, 236, 236, 255]),
+            Rgba([88, 88, 88, 255]),
+            Rgba([255, 110, 90, 255]),
+            Rgba([44, 44, 44, 255]),
+        )
+    }
+}
+
+//black or white, whichever reads better on color by its relative luminance
+pub fn contrast_color(color: Rgba<u8>) -> Rgba<u8> {
+    let linear = |channel: u8| {
+        let value = channel as f32 / 255.0;
+        if value <= 0.04045 {
+            value / 12.92
+        } else {
+            ((value + 0.055) / 1.055).powf(2.4)
+        }
+    };
+    let luminance =
+        0.2126 * linear(color[0]) + 0.7152 * linear(color[1]) + 0.0722 * linear(color[2]);
+    //where the contrast ratio against black and against white is equal
+    if luminance > 0.179 {
+        BLACK_COLOR
+    } else {
+        Rgba([255, 255, 255, 255])
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::light()
+    }
+}
+
+//Fast keeps the cheap defaults, Quality also resizes in linear light
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Preset {
+    Fast,
+    Quality,
+}
+
+//Preview resizes with nearest neighbor in sRGB, draws text, rounded corners and unblurred shadows
+//without antialiasing and encodes jpeg at low quality for interactive editing,
+//everything is placed exactly where the Final render places it
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum RenderQuality {
+    Final,
+    Preview,
+}
+
+//jpeg quality preview renders are capped at
+const PREVIEW_QUALITY: u8 = 50;
+
+//how members are resampled to their cell
+#[derive(Debug, Clone, Copy)]
+struct Resampling {
+    filter: image::imageops::FilterType,
+    linear_light: bool,
+}
+
+//Grid gives every image the same cell, Masonry keeps each aspect ratio in fixed width columns
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LayoutMode {
+    Grid,
+    Masonry,
+}
+
+//soft shadow under every placed image, offset to the right and down for positive values
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct DropShadow {
+    pub blur_radius: u32,
+    pub offset_x: i32,
+    pub offset_y: i32,
+    pub color: Rgba<u8>,
+}
+
+impl DropShadow {
+    pub fn new(blur_radius: u32, offset_x: i32, offset_y: i32, color: Rgba<u8>) -> Self {
+        Self {
+            blur_radius,
+            offset_x,
+            offset_y,
+            color,
+        }
+    }
+}
+
+//a length in pixels, or in percent of the shorter side of the canvas so spacing keeps
+//its proportion when the same options render at another size,
+//the canvas is the area the images cover before any gap or margin is added around them
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Unit {
+    Px(u32),
+    Percent(f32),
+}
+
+impl Unit {
+    fn resolve(self, (width, height): (u32, u32)) -> u32 {
+        match self {
+            Unit::Px(px) => px,
+            Unit::Percent(percent) => {
+                geometry::round_px(width.min(height) as f64 * percent as f64 / 100.0)
+            }
+        }
+    }
+}
+
+//outer margin sides in any unit, resolved into a Padding once the canvas size is known
+#[derive(Debug, Clone, Copy, PartialEq)]
+struct Margin {
+    top: Unit,
+    right: Unit,
+    bottom: Unit,
+    left: Unit,
+}
+
+impl Margin {
+    fn uniform(unit: Unit) -> Self {
+        Self {
+            top: unit,
+            right: unit,
+            bottom: unit,
+            left: unit,
+        }
+    }
+
+    fn resolve(self, canvas: (u32, u32)) -> Padding {
+        Padding::new(
+            self.top.resolve(canvas),
+            self.right.resolve(canvas),
+            self.bottom.resolve(canvas),
+            self.left.resolve(canvas),
+        )
+    }
+}
+
+impl From<Padding> for Margin {
+    fn from(padding: Padding) -> Self {
+        Self {
+            top: Unit::Px(padding.top),
+            right: Unit::Px(padding.right),
+            bottom: Unit::Px(padding.bottom),
+            left: Unit::Px(padding.left),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Default)]
+pub struct Padding {
+    pub top: u32,
+    pub right: u32,
+    pub bottom: u32,
+    pub left: u32,
+}
+
+impl Padding {
+    pub fn new(top: u32, right: u32, bottom: u32, left: u32) -> Self {
+        Self {
+            top,
+            right,
+            bottom,
+            left,
+        }
+    }
+
+    pub fn uniform(padding: u32) -> Self {
+        Self::new(padding, padding, padding, padding)
+    }
+}
+
+#[derive(Clone)]
+pub struct CreateBundledImageOptions {
+    dimension: Option<(u32, u32)>,
+    padding: Unit,
+    outer_margin: Option<Margin>,
+    gap: Option<Unit>,
+    //None when the column count comes from rows or auto layout
+    column: Option<u32>,
+    rows: Option<u32>,
+    auto_layout: Option<f32>,
+    normalize_orientation: bool,
+    order: Option<Vec<usize>>,
+    sort: SortOrder,
+    transforms: Vec<(usize, Transform)>,
+    reserved_cells: Vec<(u32, u32)>,
+    fill_order: FillOrder,
+    auto_shrink: bool,
+    layout_mode: LayoutMode,
+    physical_scale: bool,
+    max_canvas: Option<(u32, u32)>,
+    allow_upscale: bool,
+    crop_aspect: Option<(u32, u32)>,
+    trim_background: Option<u8>,
+    dedupe: Option<u32>,
+    cell_alignment: CellAlignment,
+    fit: Fit,
+    background: Rgba<u8>,
+    theme: Theme,
+    cell_border: Option<(u32, Rgba<u8>)>,
+    corner_radius: u32,
+    canvas_corner_radius: u32,
+    drop_shadow: Option<DropShadow>,
+    cell_style: CellStyle,
+    badge: Option<NumberBadge>,
+    ruler: Option<Ruler>,
+    header: Option<Band>,
+    footer: Option<Band>,
+    overlays: Vec<OverlayLayer>,
+    step_arrows: Option<ArrowStyle>,
+    thumbnail_widths: Vec<u32>,
+    linear_light: bool,
+    render_quality: RenderQuality,
+    max_output_bytes: Option<u64>,
+    target_file_size: Option<u64>,
+    sha256: bool,
+    seed: Option<u64>,
+    signing_key: Option<Vec<u8>>,
+    low_memory: bool,
+    exif_orientation: bool,
+    on_decode_error: DecodeErrorPolicy,
+    output_format: OutputFormat,
+    encode_options: EncodeOptions,
+    worker_pool: Option<WorkerPool>,
+    cache_dir: Option<CacheDir>,
+    metrics: Option<Arc<dyn MetricsExporter>>,
+}
+
+impl CreateBundledImageOptions {
+    pub fn new(dimension: Option<(u32, u32)>, padding: u32, column: u32) -> Self {
+        Self {
+            dimension,
+            padding: Unit::Px(padding),
+            outer_margin: None,
+            gap: None,
+            column: Some(column),
+            rows: None,
+            auto_layout: None,
+            normalize_orientation: false,
+            order: None,
+            sort: SortOrder::InputOrder,
+            transforms: Vec::new(),
+            reserved_cells: Vec::new(),
+            fill_order: FillOrder::RowMajor,
+            auto_shrink: false,
+            layout_mode: LayoutMode::Grid,
+            physical_scale: false,
+            max_canvas: None,
+            allow_upscale: false,
+            crop_aspect: None,
+            trim_background: None,
+            dedupe: None,
+            cell_alignment: CellAlignment::Center,
+            fit: Fit::Contain,
+            background: WHITE_COLOR,
+            theme: Theme::light(),
+            cell_border: None,
+            corner_radius: 0,
+            canvas_corner_radius: 0,
+            drop_shadow: None,
+            cell_style: CellStyle::Plain,
+            badge: None,
+            ruler: None,
+            header: None,
+            footer: None,
+            overlays: Vec::new(),
+            step_arrows: None,
+            thumbnail_widths: Vec::new(),
+            linear_light: false,
+            render_quality: RenderQuality::Final,
+            max_output_bytes: None,
+            target_file_size: None,
+            sha256: false,
+            seed: None,
+            signing_key: None,
+            low_memory: false,
+            exif_orientation: true,
+            on_decode_error: DecodeErrorPolicy::FailFast,
+            output_format: OutputFormat::Jpeg,
+            encode_options: EncodeOptions::default(),
+            worker_pool: None,
+            cache_dir: None,
+            metrics: None,
+        }
+    }
+
+    //(gap between cells, margin around the grid), padding is the gap when gap is not set,
+    //percent units are taken from content, the area the images cover without gaps and margins
+    fn spacing(&self, content: (u32, u32)) -> (u32, Padding) {
+        (
+            self.gap.unwrap_or(self.padding).resolve(content),
+            self.outer_margin
+                .map_or_else(Padding::default, |margin| margin.resolve(content)),
+        )
+    }
+
+    //every input keeps its own cell in input order only in the plain grid,
+    //the other layouts and orders move tiles around when one input changes
+    fn keeps_input_cells(&self) -> bool {
+        self.layout_mode != LayoutMode::Masonry
+            && !self.physical_scale
+            && self.sort == SortOrder::InputOrder
+            && self.order.is_none()
+            && self.dedupe.is_none()
+    }
+
+    //Preview draws text, rounded corners and shadows with hard edges
+    fn antialias(&self) -> bool {
+        self.render_quality == RenderQuality::Final
+    }
+
+    fn resampling(&self) -> Resampling {
+        match self.render_quality {
+            RenderQuality::Final => Resampling {
+                filter: image::imageops::FilterType::Lanczos3,
+                linear_light: self.linear_light,
+            },
+            RenderQuality::Preview => Resampling {
+                filter: image::imageops::FilterType::Nearest,
+                linear_light: false,
+            },
+        }
+    }
+
+    //decode options of the input at this index, with its transform if one was set
+    fn decode_options(&self, index: usize) -> DecodeOptions {
+        let transform = self
+            .transforms
+            .iter()
+            .rev()
+            .find(|(i, _)| *i == index)
+            .map(|(_, transform)| *transform);
+        DecodeOptions::new(self.exif_orientation)
+            .with_transform(transform)
+            .with_trim_background(self.trim_background)
+            .with_crop_aspect(self.crop_aspect)
+    }
+
+    fn logo_decode_options(&self, index: usize) -> DecodeOptions {
+        self.decode_options(index).for_logo()
+    }
+
+    //the render parameters a signature vouches for, each under its own name so adding an option
+    //does not change the record of renders that do not use it
+    fn signed_params(&self) -> String {
+        let unit = |unit: Unit| match unit {
+            Unit::Px(px) => format!("{}px", px),
+            Unit::Percent(percent) => format!("{}%", percent),
+        };
+        let pair = |(a, b): (u32, u32)| format!("{}x{}", a, b);
+        let or_none = |value: Option<String>| value.unwrap_or_else(|| "none".to_string());
+        let [red, green, blue, alpha] = self.background.0;
+        signature::params_record(vec![
+            ("member_dimension", or_none(self.dimension.map(pair))),
+            ("column", or_none(self.column.map(|c| c.to_string()))),
+            ("rows", or_none(self.rows.map(|r| r.to_string()))),
+            (
+                "auto_layout",
+                or_none(self.auto_layout.map(|a| a.to_string())),
+            ),
+            ("padding", unit(self.padding)),
+            ("gap", or_none(self.gap.map(unit))),
+            (
+                "outer_margin",
+                or_none(self.outer_margin.map(|margin| {
+                    [margin.top, margin.right, margin.bottom, margin.left]
+                        .map(unit)
+                        .join(" ")
+                })),
+            ),
+            ("layout_mode", format!("{:?}", self.layout_mode)),
+            ("fill_order", format!("{:?}", self.fill_order)),
+            ("sort", format!("{:?}", self.sort)),
+            ("auto_shrink", self.auto_shrink.to_string()),
+            ("max_canvas", or_none(self.max_canvas.map(pair))),
+            ("fit", format!("{:?}", self.fit)),
+            ("cell_alignment", format!("{:?}", self.cell_alignment)),
+            ("allow_upscale", self.allow_upscale.to_string()),
+            ("crop_aspect", or_none(self.crop_aspect.map(pair))),
+            (
+                "trim_background",
+                or_none(self.trim_background.map(|t| t.to_string())),
+            ),
+            ("dedupe", or_none(self.dedupe.map(|d| d.to_string()))),
+            (
+                "background",
+                format!("#{:02x}{:02x}{:02x}{:02x}", red, green, blue, alpha),
+            ),
+            ("linear_light", self.linear_light.to_string()),
+            ("render_quality", format!("{:?}", self.render_quality)),
+            ("output_format", format!("{:?}", self.output_format)),
+            ("quality", self.encode_options.quality().to_string()),
+            ("seed", or_none(self.seed.map(|s| s.to_string()))),
+        ])
+    }
+
+    //every option that changes the rendered bytes, worker_pool and cache_dir do not
+    fn cache_fingerprint(&self) -> String {
+        format!(
+            "{:?}|{:?}|{:?}|{:?}|{:?}|{:?}|{:?}|{}|{:?}|{:?}|{:?}|{:?}|{:?}|{}|{:?}|{}|{:?}|{}|{:?}|{:?}|{:?}|{:?}|{:?}|{:?}|{:?}|{:?}|{}|{}|{:?}|{}|{:?}|{:?}|{:?}|{:?}|{:?}|{:?}|{:?}|{}|{:?}|{:?}|{}|{:?}|{:?}|{:?}|{:?}",
+            self.dimension,
+            self.padding,
+            self.outer_margin,
+            self.gap,
+            self.column,
+            self.rows,
+            self.auto_layout,
+            self.normalize_orientation,
+            self.order,
+            self.sort,
+            self.transforms,
+            self.reserved_cells,
+            self.fill_order,
+            self.auto_shrink,
+            self.layout_mode,
+            self.physical_scale,
+            self.max_canvas,
+            self.allow_upscale,
+            self.crop_aspect,
+            self.trim_background,
+            self.dedupe,
+            self.cell_alignment,
+            self.fit,
+            self.background,
+            self.theme,
+            self.cell_border,
+            self.corner_radius,
+            self.canvas_corner_radius,
+            self.drop_shadow,
+            self.cell_style.fingerprint(),
+            self.badge.as_ref().map(|badge| (
+                badge.style.color,
+                badge.style.font_size,
+                badge.background,
+                badge.position,
+                badge.offset
+            )),
+            self.ruler.as_ref().map(|ruler| (
+                ruler.pixels_per_cm,
+                ruler.unit,
+                ruler.edge,
+                ruler.style.color,
+                ruler.style.font_size,
+                ruler.background
+            )),
+            self.header.as_ref().map(Band::fingerprint),
+            self.footer.as_ref().map(Band::fingerprint),
+            self.overlays
+                .iter()
+                .map(OverlayLayer::fingerprint)
+                .collect::<Vec<_>>(),
+            self.step_arrows,
+            self.thumbnail_widths,
+            self.linear_light,
+            self.max_output_bytes,
+            self.target_file_size,
+            self.exif_orientation,
+            self.on_decode_error,
+            self.output_format,
+            self.encode_options,
+            self.render_quality,
+        )
+    }
+}
+#[derive(Default)]
+pub struct CreateBundledImageOptionsBuilder {
+    member_dimension: Option<(u32, u32)>,
+    column: Option<u32>,
+    rows: Option<u32>,
+    auto_layout: Option<f32>,
+    padding: Option<Unit>,
+    outer_margin: Option<Margin>,
+    gap: Option<Unit>,
+    normalize_orientation: bool,
+    order: Option<Vec<usize>>,
+    sort: Option<SortOrder>,
+    transforms: Vec<(usize, Transform)>,
+    reserved_cells: Vec<(u32, u32)>,
+    fill_order: Option<FillOrder>,
+    auto_shrink: bool,
+    layout_mode: Option<LayoutMode>,
+    physical_scale: bool,
+    max_canvas: Option<(u32, u32)>,
+    allow_upscale: bool,
+    crop_aspect: Option<(u32, u32)>,
+    trim_background: Option<u8>,
+    dedupe: Option<u32>,
+    cell_alignment: Option<CellAlignment>,
+    fit: Option<Fit>,
+    background: Option<Rgba<u8>>,
+    theme: Option<Theme>,
+    cell_border: Option<(u32, Rgba<u8>)>,
+    corner_radius: u32,
+    canvas_corner_radius: u32,
+    drop_shadow: Option<DropShadow>,
+    cell_style: Option<CellStyle>,
+    badge: Option<NumberBadge>,
+    ruler: Option<Ruler>,
+    header: Option<Band>,
+    footer: Option<Band>,
+    overlays: Vec<OverlayLayer>,
+    step_arrows: Option<ArrowStyle>,
+    thumbnail_widths: Vec<u32>,
+    linear_light: Option<bool>,
+    preset: Option<Preset>,
+    render_quality: Option<RenderQuality>,
+    max_output_bytes: Option<u64>,
+    target_file_size: Option<u64>,
+    sha256: bool,
+    seed: Option<u64>,
+    signing_key: Option<Vec<u8>>,
+    low_memory: bool,
+    exif_orientation: Option<bool>,
+    on_decode_error: Option<DecodeErrorPolicy>,
+    output_format: Option<OutputFormat>,
+    encode_options: Option<EncodeOptions>,
+    worker_pool: Option<WorkerPool>,
+    cache_dir: Option<CacheDir>,
+    metrics: Option<Arc<dyn MetricsExporter>>,
+}
+
+impl CreateBundledImageOptionsBuilder {
+    pub fn new() -> Self {
+        Self {
+            member_dimension: None,
+            column: None,
+            rows: None,
+            auto_layout: None,
+            padding: None,
+            outer_margin: None,
+            gap: None,
+            normalize_orientation: false,
+            order: None,
+            sort: None,
+            transforms: Vec::new(),
+            reserved_cells: Vec::new(),
+            fill_order: None,
+            auto_shrink: false,
+            layout_mode: None,
+            physical_scale: false,
+            max_canvas: None,
+            allow_upscale: false,
+            crop_aspect: None,
+            trim_background: None,
+            dedupe: None,
+            cell_alignment: None,
+            fit: None,
+            background: None,
+            theme: None,
+            cell_border: None,
+            corner_radius: 0,
+            canvas_corner_radius: 0,
+            drop_shadow: None,
+            cell_style: None,
+            badge: None,
+            ruler: None,
+            header: None,
+            footer: None,
+            overlays: Vec::new(),
+            step_arrows: None,
+            thumbnail_widths: Vec::new(),
+            linear_light: None,
+            preset: None,
+            render_quality: None,
+            max_output_bytes: None,
+            target_file_size: None,
+            sha256: false,
+            seed: None,
+            signing_key: None,
+            low_memory: false,
+            exif_orientation: None,
+            on_decode_error: None,
+            output_format: None,
+            encode_options: None,
+            worker_pool: None,
+            cache_dir: None,
+            metrics: None,
+        }
+    }
+
+    pub fn set_member_dimension(mut self, width: u32, height: u32) -> Self {
+        self.member_dimension = Some((width, height));
+        self
+    }
+
+    pub fn set_column(mut self, column: u32) -> Self {
+        self.column = Some(column);
+        self
+    }
+
+    //fixed row count with as many columns as needed, can not be combined with set_column
+    pub fn set_rows(mut self, rows: u32) -> Self {
+        self.rows = Some(rows);
+        self
+    }
+
+    //pick the column count that brings the canvas closest to width:height, can not be combined with set_column or set_rows
+    pub fn set_auto_layout(mut self, width: u32, height: u32) -> Self {
+        self.auto_layout = Some(width as f32 / height as f32);
+        self
+    }
+
+    pub fn set_padding(mut self, padding: u32) -> Self {
+        self.padding = Some(Unit::Px(padding));
+        self
+    }
+
+    pub fn set_padding_unit(mut self, padding: Unit) -> Self {
+        self.padding = Some(padding);
+        self
+    }
+
+    //space around the grid, setting it or gap spaces the grid symmetrically instead of trailing padding
+    pub fn set_outer_margin(mut self, outer_margin: u32) -> Self {
+        self.outer_margin = Some(Margin::uniform(Unit::Px(outer_margin)));
+        self
+    }
+
+    pub fn set_outer_margin_unit(mut self, outer_margin: Unit) -> Self {
+        self.outer_margin = Some(Margin::uniform(outer_margin));
+        self
+    }
+
+    //outer margin set per side, e.g. extra room below the grid with tight sides
+    pub fn set_side_padding(mut self, side_padding: Padding) -> Self {
+        self.outer_margin = Some(side_padding.into());
+        self
+    }
+
+    //space between neighbouring cells, defaults to padding
+    pub fn set_gap(mut self, gap: u32) -> Self {
+        self.gap = Some(Unit::Px(gap));
+        self
+    }
+
+    pub fn set_gap_unit(mut self, gap: Unit) -> Self {
+        self.gap = Some(gap);
+        self
+    }
+
+    //rotate images whose orientation differs from the majority of the batch by 90 degrees
+    pub fn set_normalize_orientation(mut self, normalize_orientation: bool) -> Self {
+        self.normalize_orientation = normalize_orientation;
+        self
+    }
+
+    //order[i] is the index of the input image placed at position i
+    pub fn set_order(mut self, order: Vec<usize>) -> Self {
+        self.order = Some(order);
+        self
+    }
+
+    //group similar shapes before layout, cannot be combined with set_order
+    pub fn set_sort(mut self, sort: SortOrder) -> Self {
+        self.sort = Some(sort);
+        self
+    }
+
+    //(input index, transform) pairs applied when the image is decoded, before any resizing,
+    //the last pair wins when an index is listed twice
+    pub fn set_transforms(mut self, transforms: Vec<(usize, Transform)>) -> Self {
+        self.transforms = transforms;
+        self
+    }
+
+    //(row, column) grid positions skipped by the layout and left as background
+    pub fn set_reserved_cells(mut self, reserved_cells: Vec<(u32, u32)>) -> Self {
+        self.reserved_cells = reserved_cells;
+        self
+    }
+
+    //ColumnMajor fills each column top to bottom before moving to the next one
+    pub fn set_fill_order(mut self, fill_order: FillOrder) -> Self {
+        self.fill_order = Some(fill_order);
+        self
+    }
+
+    //drop trailing columns no image or reserved cell reaches, so 3 images with column 4 are 3 columns wide
+    pub fn set_auto_shrink(mut self, auto_shrink: bool) -> Self {
+        self.auto_shrink = auto_shrink;
+        self
+    }
+
+    //Masonry uses the member width as the column width and the column count as the number of columns
+    pub fn set_layout_mode(mut self, layout_mode: LayoutMode) -> Self {
+        self.layout_mode = Some(layout_mode);
+        self
+    }
+
+    //scale every image by its embedded dpi so sizes on the sheet are true to each other,
+    //images without a resolution count as 72 dpi and are never stretched to fill their cell
+    pub fn set_physical_scale(mut self, physical_scale: bool) -> Self {
+        self.physical_scale = physical_scale;
+        self
+    }
+
+    //the member dimension is scaled down until the grid fits in width x height, keeping its aspect ratio,
+    //only grid layouts can be limited so masonry, strips, matrices and templates reject it
+    pub fn set_max_canvas(mut self, width: u32, height: u32) -> Self {
+        self.max_canvas = Some((width, height));
+        self
+    }
+
+    //images smaller than their cell are enlarged to fill it only when set,
+    //otherwise they keep their size and are placed by the cell alignment
+    pub fn set_allow_upscale(mut self, allow_upscale: bool) -> Self {
+        self.allow_upscale = allow_upscale;
+        self
+    }
+
+    //center crop every image to width:height before it is resized, so all cells share one shape
+    pub fn set_crop_aspect(mut self, width: u32, height: u32) -> Self {
+        self.crop_aspect = Some((width, height));
+        self
+    }
+
+    //crop borders of the corner color from every image before it is laid out,
+    //pixels whose channels all differ by at most tolerance count as border
+    pub fn set_trim_background(mut self, tolerance: u8) -> Self {
+        self.trim_background = Some(tolerance);
+        self
+    }
+
+    //drop images that look like an earlier one before layout, the indices are reported in BundledImage::duplicates,
+    //max_distance is how many of the 64 hash bits may differ, 0 to 10 catches re-encoded and resized copies
+    pub fn set_dedupe(mut self, max_distance: u32) -> Self {
+        self.dedupe = Some(max_distance);
+        self
+    }
+
+    //where images smaller than their cell are placed, centered by default
+    pub fn set_cell_alignment(mut self, cell_alignment: CellAlignment) -> Self {
+        self.cell_alignment = Some(cell_alignment);
+        self
+    }
+
+    //how images are sized into their cell, Cover and CropCenter fill it edge to edge
+    pub fn set_fit(mut self, fit: Fit) -> Self {
+        self.fit = Some(fit);
+        self
+    }
+
+    //canvas color behind and between the tiles, white by default
+    pub fn set_background(mut self, background: Rgba<u8>) -> Self {
+        self.background = Some(background);
+        self
+    }
+
+    //an explicit background still wins over the theme background
+    pub fn set_theme(mut self, theme: Theme) -> Self {
+        self.theme = Some(theme);
+        self
+    }
+
+    //border of the given width drawn inside the edge of every image cell
+    pub fn set_cell_border(mut self, width: u32, color: Rgba<u8>) -> Self {
+        self.cell_border = Some((width, color));
+        self
+    }
+
+    //round the corners of every placed image, the background shows through the cut off parts
+    pub fn set_corner_radius(mut self, corner_radius: u32) -> Self {
+        self.corner_radius = corner_radius;
+        self
+    }
+
+    //round the corners of the whole canvas, they become transparent in formats with alpha
+    pub fn set_canvas_corner_radius(mut self, canvas_corner_radius: u32) -> Self {
+        self.canvas_corner_radius = canvas_corner_radius;
+        self
+    }
+
+    //the shadow stays within the gap around its own cell so it never falls on a neighbouring image
+    pub fn set_drop_shadow(mut self, drop_shadow: DropShadow) -> Self {
+        self.drop_shadow = Some(drop_shadow);
+        self
+    }
+
+    //Frame wraps every image in a white frame, cells grow by the frame so images keep their size
+    pub fn set_cell_style(mut self, cell_style: CellStyle) -> Self {
+        self.cell_style = Some(cell_style);
+        self
+    }
+
+    //draw 1, 2, 3, ... over the images so they can be referred to by number
+    pub fn set_badge(mut self, badge: NumberBadge) -> Self {
+        self.badge = Some(badge);
+        self
+    }
+
+    //centimeter or inch scale along one edge, drawn over the images it overlaps
+    pub fn set_ruler(mut self, ruler: Ruler) -> Self {
+        self.ruler = Some(ruler);
+        self
+    }
+
+    //band across the top of the canvas, the images move down by its height
+    pub fn set_header(mut self, header: Band) -> Self {
+        self.header = Some(header);
+        self
+    }
+
+    //band across the bottom of the canvas
+    pub fn set_footer(mut self, footer: Band) -> Self {
+        self.footer = Some(footer);
+        self
+    }
+
+    //composited over the whole canvas, bands included, each over the result of the ones before it
+    pub fn set_overlays(mut self, overlays: Vec<OverlayLayer>) -> Self {
+        self.overlays = overlays;
+        self
+    }
+
+    //connect consecutive tiles with arrows for step by step guides, pair it with set_badge to number the steps
+    pub fn set_step_arrows(mut self, step_arrows: ArrowStyle) -> Self {
+        self.step_arrows = Some(step_arrows);
+        self
+    }
+
+    //also return the bundle scaled down to each of these widths, widths not below the canvas width are left out
+    pub fn set_thumbnail_widths(mut self, thumbnail_widths: Vec<u32>) -> Self {
+        self.thumbnail_widths = thumbnail_widths;
+        self
+    }
+
+    //resize in linear light instead of sRGB, slower but keeps the brightness of fine patterns
+    pub fn set_linear_light(mut self, linear_light: bool) -> Self {
+        self.linear_light = Some(linear_light);
+        self
+    }
+
+    //defaults for options that are not set explicitly
+    pub fn set_preset(mut self, preset: Preset) -> Self {
+        self.preset = Some(preset);
+        self
+    }
+
+    pub fn set_render_quality(mut self, render_quality: RenderQuality) -> Self {
+        self.render_quality = Some(render_quality);
+        self
+    }
+
+    //encode jpeg again at lower quality until it fits, fails when it can not be made small enough
+    pub fn set_max_output_bytes(mut self, max_output_bytes: u64) -> Self {
+        self.max_output_bytes = Some(max_output_bytes);
+        self
+    }
+
+    //pick the highest jpeg quality that keeps the output at or below this size, png is not affected
+    pub fn set_target_file_size(mut self, target_file_size: u64) -> Self {
+        self.target_file_size = Some(target_file_size);
+        self
+    }
+
+    //return the SHA-256 of the output bytes as hex
+    pub fn set_sha256(mut self, sha256: bool) -> Self {
+        self.sha256 = sha256;
+        self
+    }
+
+    //seeds the generator the randomized features draw from, so far the retry jitter of url and s3 sources,
+    //recorded on the bundle and in the signed parameters, it does not change the pixels or the cache entry a render uses
+    pub fn set_seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    //signs the normalized render parameters into a jpeg comment, see verify_signature
+    pub fn set_signing_key(mut self, key: Vec<u8>) -> Self {
+        self.signing_key = Some(key);
+        self
+    }
+
+    //keep only encoded inputs in memory, each image is spilled to a temp file as it is decoded,
+    //before the next decode starts, and mapped back one at a time to be resized and drawn
+    pub fn set_low_memory(mut self, low_memory: bool) -> Self {
+        self.low_memory = low_memory;
+        self
+    }
+
+    //rotate and flip images according to their EXIF orientation tag, enabled by default
+    pub fn set_exif_orientation(mut self, exif_orientation: bool) -> Self {
+        self.exif_orientation = Some(exif_orientation);
+        self
+    }
+
+    pub fn set_on_decode_error(mut self, on_decode_error: DecodeErrorPolicy) -> Self {
+        self.on_decode_error = Some(on_decode_error);
+        self
+    }
+
+    pub fn set_output_format(mut self, output_format: OutputFormat) -> Self {
+        self.output_format = Some(output_format);
+        self
+    }
+
+    pub fn set_encode_options(mut self, encode_options: EncodeOptions) -> Self {
+        self.encode_options = Some(encode_options);
+        self
+    }
+
+    //run decode, resize and encode on this pool instead of the tokio runtime
+    pub fn set_worker_pool(mut self, worker_pool: WorkerPool) -> Self {
+        self.worker_pool = Some(worker_pool);
+        self
+    }
+
+    //reuse earlier outputs stored under a hash of the inputs and options
+    pub fn set_cache_dir(mut self, cache_dir: CacheDir) -> Self {
+        self.cache_dir = Some(cache_dir);
+        self
+    }
+
+    //reports every create_bundled_image call, and the stages the worker pool runs when one is set
+    pub fn set_metrics(mut self, metrics: Arc<dyn MetricsExporter>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    pub fn build(&self) -> Result<CreateBundledImageOptions, ImageCombinerError> {
+        if self.column == Some(0) || self.rows == Some(0) {
+            return Err(ImageCombinerError::InvalidOptions(format!(
+                "column and rows must be at least 1 column:{:?},rows:{:?}",
+                self.column, self.rows
+            )));
+        }
+        let layouts = [
+            self.column.is_some(),
+            self.rows.is_some(),
+            self.auto_layout.is_some(),
+        ];
+        if layouts.iter().filter(|&&set| set).count() > 1 {
+            return Err(ImageCombinerError::InvalidOptions(format!(
+                "only one of column, rows and auto layout can be set column:{:?},rows:{:?},auto layout:{:?}",
+                self.column, self.rows, self.auto_layout
+            )));
+        }
+        if self.max_canvas.is_some() && self.layout_mode == Some(LayoutMode::Masonry) {
+            return Err(unsupported_max_canvas("masonry"));
+        }
+        let mut options = CreateBundledImageOptions::new(self.member_dimension, 20, 1);
+        //the layout derives the column count from rows or auto layout
+        options.column = if self.rows.is_some() || self.auto_layout.is_some() {
+            None
+        } else {
+            Some(self.column.unwrap_or(1))
+        };
+        options.padding = self.padding.unwrap_or(Unit::Px(20));
+        options.rows = self.rows;
+        options.outer_margin = self.outer_margin;
+        options.gap = self.gap;
+        options.auto_layout = self.auto_layout;
+        options.normalize_orientation = self.normalize_orientation;
+        options.order = self.order.clone();
+        options.sort = self.sort.unwrap_or(SortOrder::InputOrder);
+        options.transforms = self.transforms.clone();
+        options.reserved_cells = self.reserved_cells.clone();
+        options.fill_order = self.fill_order.unwrap_or(FillOrder::RowMajor);
+        options.auto_shrink = self.auto_shrink;
+        options.layout_mode = self.layout_mode.unwrap_or(LayoutMode::Grid);
+        options.physical_scale = self.physical_scale;
+        options.max_canvas = self.max_canvas;
+        options.allow_upscale = self.allow_upscale;
+        options.crop_aspect = self.crop_aspect;
+        options.trim_background = self.trim_background;
+        options.dedupe = self.dedupe;
+        options.cell_alignment = self.cell_alignment.unwrap_or(CellAlignment::Center);
+        options.fit = self.fit.unwrap_or(Fit::Contain);
+        let theme = self.theme.unwrap_or_default();
+        options.theme = theme;
+        options.background = self.background.unwrap_or(theme.background);
+        options.cell_border = self.cell_border;
+        options.corner_radius = self.corner_radius;
+        options.canvas_corner_radius = self.canvas_corner_radius;
+        options.drop_shadow = self.drop_shadow;
+        options.cell_style = self.cell_style.clone().unwrap_or(CellStyle::Plain);
+        options.badge = self.badge.clone();
+        options.ruler = self.ruler.clone();
+        options.header = self.header.clone();
+        options.footer = self.footer.clone();
+        options.overlays = self.overlays.clone();
+        options.step_arrows = self.step_arrows;
+        options.thumbnail_widths = self.thumbnail_widths.clone();
+        let quality = self.preset == Some(Preset::Quality);
+        options.linear_light = self.linear_light.unwrap_or(quality);
+        options.render_quality = self.render_quality.unwrap_or(RenderQuality::Final);
+        options.max_output_bytes = self.max_output_bytes;
+        options.target_file_size = self.target_file_size;
+        options.sha256 = self.sha256;
+        options.seed = self.seed;
+        options.signing_key = self.signing_key.clone();
+        options.low_memory = self.low_memory;
+        options.exif_orientation = self.exif_orientation.unwrap_or(true);
+        options.on_decode_error = self.on_decode_error.unwrap_or(DecodeErrorPolicy::FailFast);
+        options.output_format = self.output_format.unwrap_or(OutputFormat::Jpeg);
+        options.encode_options = self.encode_options.clone().unwrap_or_default();
+        options.worker_pool = match (&self.worker_pool, &self.metrics) {
+            (Some(worker_pool), Some(metrics)) => {
+                Some(worker_pool.clone().with_metrics(Arc::clone(metrics)))
+            }
+            (worker_pool, _) => worker_pool.clone(),
+        };
+        options.cache_dir = self.cache_dir.clone();
+        options.metrics = self.metrics.clone();
+        Ok(options)
+    }
+}
+
+pub struct LayoutConstraints {
+    max_column: u32,
+    target_aspect_ratio: f32,
+    padding: u32,
+}
+
+impl LayoutConstraints {
+    pub fn new(max_column: u32, target_aspect_ratio: f32, padding: u32) -> Self {
+        Self {
+            max_column,
+            target_aspect_ratio,
+            padding,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct LayoutConstraintsBuilder {
+    max_column: Option<u32>,
+    target_aspect_ratio: Option<f32>,
+    padding: Option<u32>,
+}
+
+impl LayoutConstraintsBuilder {
+    pub fn new() -> Self {
+        Self {
+            max_column: None,
+            target_aspect_ratio: None,
+            padding: None,
+        }
+    }
+
+    pub fn set_max_column(mut self, max_column: u32) -> Self {
+        self.max_column = Some(max_column);
+        self
+    }
+
+    pub fn set_target_aspect_ratio(mut self, width: u32, height: u32) -> Self {
+        self.target_aspect_ratio = Some(width as f32 / height as f32);
+        self
+    }
+
+    pub fn set_padding(mut self, padding: u32) -> Self {
+        self.padding = Some(padding);
+        self
+    }
+
+    pub fn build(&self) -> LayoutConstraints {
+        let max_column = self.max_column.unwrap_or(4);
+        let target_aspect_ratio = self.target_aspect_ratio.unwrap_or(1.0);
+        let padding = self.padding.unwrap_or(20);
+        LayoutConstraints::new(max_column, target_aspect_ratio, padding)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SuggestedOptions {
+    pub column: u32,
+    pub member_dimension: (u32, u32),
+    pub fit: Fit,
+}
+
+impl SuggestedOptions {
+    pub fn to_options_builder(&self) -> CreateBundledImageOptionsBuilder {
+        CreateBundledImageOptionsBuilder::new()
+            .set_column(self.column)
+            .set_member_dimension(self.member_dimension.0, self.member_dimension.1)
+            .set_fit(self.fit)
+    }
+}
+
+//widest over narrowest aspect ratio up to which Cover crops little enough from any image
+const COVER_ASPECT_SPREAD: f32 = 1.25;
+
+//Cover fills every cell when the inputs are about the same shape, Contain keeps mixed shapes whole
+fn suggest_fit(dimensions: &[(u32, u32)]) -> Fit {
+    let aspects = dimensions
+        .iter()
+        .map(|&(width, height)| width as f32 / height as f32);
+    let narrowest = aspects.clone().fold(f32::MAX, f32::min);
+    let widest = aspects.fold(0.0, f32::max);
+    if widest / narrowest <= COVER_ASPECT_SPREAD {
+        Fit::Cover
+    } else {
+        Fit::Contain
+    }
+}
+
+pub fn suggest_layout(
+    dimensions: &[(u32, u32)],
+    constraints: &LayoutConstraints,
+) -> SuggestedOptions {
+    let dimensions: Vec<(u32, u32)> = dimensions
+        .iter()
+        .copied()
+        .filter(|&(width, height)| width > 0 && height > 0)
+        .collect();
+    let member_dimension = find_optical_dimension(&dimensions);
+    let (member_width, member_height) = member_dimension;
+    if dimensions.is_empty() {
+        return SuggestedOptions {
+            column: 1,
+            member_dimension,
+            fit: Fit::Contain,
+        };
+    }
+    //area actually covered by images once each one is scaled to fit its cell
+    let used_area = dimensions.iter().fold(0.0, |acc, &(width, height)| {
+        let scale = (member_width as f32 / width as f32).min(member_height as f32 / height as f32);
+        acc + width as f32 * scale * height as f32 * scale
+    });
+    let cell_width = (member_width + constraints.padding) as f32;
+    let cell_height = (member_height + constraints.padding) as f32;
+    let image_count = dimensions.len() as u32;
+    let mut best_column = 1;
+    let mut best_score = f32::MAX;
+    //score is the wasted canvas fraction plus how far the canvas drifts from the target aspect ratio
+    for column in 1..=constraints.max_column.clamp(1, image_count) {
+        let row = (image_count as f32 / column as f32).ceil();
+        let canvas_width = column as f32 * cell_width;
+        let canvas_height = row * cell_height;
+        let wasted = 1.0 - used_area / (canvas_width * canvas_height);
+        let aspect_penalty =
+            aspect_penalty(canvas_width, canvas_height, constraints.target_aspect_ratio);
+        let score = wasted + aspect_penalty;
+        debug!("column {} wasted {} score {}", column, wasted, score);
+        if score < best_score {
+            best_column = column;
+            best_score = score;
+        }
+    }
+    SuggestedOptions {
+        column: best_column,
+        member_dimension,
+        fit: suggest_fit(&dimensions),
+    }
+}
+
+fn aspect_penalty(canvas_width: f32, canvas_height: f32, target_aspect_ratio: f32) -> f32 {
+    (canvas_width / canvas_height / target_aspect_ratio)
+        .ln()
+        .abs()
+}
+
+struct PreparedBundle {
+    tiles: Vec<PreparedTile>,
+    layout: BundleLayout,
+    skipped: Vec<usize>,
+}
+
+struct PreparedTile {
+    content: TileContent,
+    alignment: CellAlignment,
+}
+
+enum TileContent {
+    Ready(DynamicImage),
+    Spilled(SpilledTile),
+    //keeps its cell without drawing anything
+    Empty,
+    //decoded, resized and fitted only when the tile is drawn
+    Pending(usize, Tile),
+}
+
+#[derive(PartialEq)]
+struct BundleLayout {
+    member_width: u32,
+    member_height: u32,
+    //(row, column) of each image
+    cells: Vec<(u32, u32)>,
+    cell_width: u32,
+    cell_height: u32,
+    //space around the whole grid, cells start after it
+    margin: Padding,
+    //frame around each image inside its cell
+    frame: Padding,
+    width: u32,
+    height: u32,
+}
+
+//member area of a tile on the canvas
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TilePlacement {
+    pub row: u32,
+    pub column: u32,
+    pub x: u32,
+    pub y: u32,
+    pub width: u32,
+    pub height: u32,
+}
+
+//canvas size and tile placements of a render, see plan_layout_geometry
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayoutGeometry {
+    pub width: u32,
+    pub height: u32,
+    pub tiles: Vec<TilePlacement>,
+}
+
+impl BundleLayout {
+    //in placement order, inside the frame when there is one
+    fn placements(&self) -> Vec<TilePlacement> {
+        self.cells
+            .iter()
+            .map(|&(row, column)| TilePlacement {
+                row,
+                column,
+                x: self.margin.left + column * self.cell_width + self.frame.left,
+                y: self.margin.top + row * self.cell_height + self.frame.top,
+                width: self.member_width,
+                height: self.member_height,
+            })
+            .collect()
+    }
+
+    //member size plus the frame around it
+    fn slot_dimension(&self) -> (u32, u32) {
+        (
+            self.member_width + self.frame.left + self.frame.right,
+            self.member_height + self.frame.top + self.frame.bottom,
+        )
+    }
+}
+
+async fn prepare_bundle(
+    buffers: impl IntoImageSource,
+    options: &CreateBundledImageOptions,
+) -> Result<PreparedBundle, ImageCombinerError> {
+    let mut source = open_source(buffers, options);
+    if options.dimension.is_none() || options.low_memory {
+        //member size comes from every image header, so the whole batch has to arrive first
+        let mut tiles = Vec::new();
+        while let Some(buffer) = source.next_image().await {
+            tiles.push(Tile::Image(buffer?));
+        }
+        return prepare_tiles(tiles, options).await;
+    }
+    //start decoding each image as soon as the source yields it, so slow sources overlap with decoding,
+    //the next image is only pulled once a full size decode has been resized down to its member size
+    let resize_to = early_resize_dimension(options.dimension, options);
+    let in_flight = Arc::new(Semaphore::new(in_flight_limit()));
+    let mut jobs = Vec::new();
+    loop {
+        let permit = Arc::clone(&in_flight)
+            .acquire_owned()
+            .await
+            .map_err(|e| ImageCombinerError::WorkerPool(e.to_string()))?;
+        let Some(buffer) = source.next_image().await else {
+            break;
+        };
+        let index = jobs.len();
+        let decode = decode_job(index, Tile::Image(buffer?), options, resize_to);
+        let job = spawn_job(options, Stage::Decode, index, move || {
+            let tile = decode();
+            drop(permit);
+            tile
+        });
+        jobs.push(Box::pin(async move { job.await? }) as DecodeJob);
+    }
+    prepare_decoded(jobs, options.dimension, options).await
+}
+
+async fn prepare_tiles(
+    tiles: Vec<Tile>,
+    options: &CreateBundledImageOptions,
+) -> Result<PreparedBundle, ImageCombinerError> {
+    let member_dimension = options
+        .dimension
+        .or_else(|| probe_member_dimension(&tiles, options))
+        .map(|member_dimension| limit_member_dimension(tiles.len(), member_dimension, options))
+        .transpose()?;
+    //a skipped input gives up its cell, which is only known once every image is decoded
+    if let Some(member_dimension) = member_dimension {
+        if !options.normalize_orientation && options.on_decode_error != DecodeErrorPolicy::Skip {
+            return plan_pending_tiles(tiles, member_dimension, options);
+        }
+    }
+    let resize_to = early_resize_dimension(member_dimension, options);
+    let mut jobs = Vec::with_capacity(tiles.len());
+    for (index, tile) in tiles.into_iter().enumerate() {
+        jobs.push(pace_decode(spawn_decode(index, tile, options, resize_to), options).await?);
+    }
+    prepare_decoded(jobs, member_dimension, options).await
+}
+
+//pick the member size from image headers so decoding and resizing can run as one job per image
+fn probe_member_dimension(
+    tiles: &[Tile],
+    options: &CreateBundledImageOptions,
+) -> Option<(u32, u32)> {
+    //the trimmed size is only known from the pixels, so the member size is picked after the one decode
+    if options.normalize_orientation || options.trim_background.is_some() {
+        return None;
+    }
+    let probe = |logo: bool| -> Vec<(u32, u32)> {
+        tiles
+            .iter()
+            .enumerate()
+            .filter_map(|(index, tile)| match tile {
+                Tile::Image(buf) if !logo => {
+                    probe_decoded_dimensions(buf, &options.decode_options(index))
+                }
+                Tile::Logo(buf, _) if logo => {
+                    probe_decoded_dimensions(buf, &options.logo_decode_options(index))
+                }
+                _ => None,
+            })
+            .collect()
+    };
+    //logos only decide the member size when there is no image
+    let mut dimensions = probe(false);
+    if dimensions.is_empty() {
+        dimensions = probe(true);
+    }
+    if dimensions.is_empty() {
+        return None;
+    }
+    Some(find_optical_dimension(&dimensions))
+}
+
+//plan the layout from probed headers only, tiles are decoded when they are drawn
+fn plan_pending_tiles(
+    tiles: Vec<Tile>,
+    member_dimension: (u32, u32),
+    options: &CreateBundledImageOptions,
+) -> Result<PreparedBundle, ImageCombinerError> {
+    let mut tiles: Vec<(usize, Tile)> = tiles.into_iter().enumerate().collect();
+    if let Some(order) = &options.order {
+        tiles = apply_order(tiles, order)?;
+    }
+    debug!("plan {} tiles into 1", tiles.len());
+    let mut prepared = Vec::new();
+    let mut skipped = Vec::new();
+    let mut failures = Vec::new();
+    for (index, tile) in tiles {
+        let probed = match &tile {
+            Tile::Image(buf) | Tile::Logo(buf, _) => {
+                probe_dimensions(buf)
+                    .map(drop)
+                    .map_err(|error| DecodeFailure {
+                        index,
+                        size: buf.len(),
+                        error,
+                    })
+            }
+            Tile::TextCard { .. } | Tile::Placeholder => Ok(()),
+            Tile::Empty => {
+                prepared.push(PreparedTile {
+                    content: TileContent::Empty,
+                    alignment: CellAlignment::Center,
+                });
+                continue;
+            }
+        };
+        let alignment = match tile {
+            Tile::Image(_) => options.cell_alignment,
+            _ => CellAlignment::Center,
+        };
+        let failure = match probed {
+            Ok(_) => {
+                prepared.push(PreparedTile {
+                    content: TileContent::Pending(index, tile),
+                    alignment,
+                });
+                continue;
+            }
+            Err(failure) => failure,
+        };
+        match options.on_decode_error {
+            DecodeErrorPolicy::FailFast => {
+                debug!("image no {} failed to probe: {}", index + 1, failure.error);
+                failures.push(failure);
+            }
+            DecodeErrorPolicy::Skip => {
+                debug!(
+                    "skip image no {} failed to probe: {}",
+                    index + 1,
+                    failure.error
+                );
+                skipped.push(index);
+            }
+            DecodeErrorPolicy::Placeholder => {
+                debug!(
+                    "placeholder for image no {} failed to probe: {}",
+                    index + 1,
+                    failure.error
+                );
+                skipped.push(index);
+                prepared.push(PreparedTile {
+                    content: TileContent::Ready(placeholder_image(
+                        member_dimension.0,
+                        member_dimension.1,
+                    )),
+                    alignment: CellAlignment::Center,
+                });
+            }
+        }
+    }
+    if !failures.is_empty() {
+        failures.sort_by_key(|failure| failure.index);
+        return Err(ImageCombinerError::Decode(failures));
+    }
+    let layout = plan_layout(prepared.len(), member_dimension, options)?;
+    Ok(PreparedBundle {
+        tiles: prepared,
+        layout,
+        skipped,
+    })
+}
+
+//decode a pending tile into its final cell sized image, Err inside Ok is a decode failure
+fn resolve_tile(
+    index: usize,
+    tile: Tile,
+    decode_options: &DecodeOptions,
+    member_dimension: (u32, u32),
+    (image_fit, allow_upscale): (Fit, bool),
+    resampling: Resampling,
+    antialias: bool,
+) -> Result<Result<DynamicImage, DecodeFailure>, ImageCombinerError> {
+    let (width, height) = member_dimension;
+    let failure = |size: usize| move |error: ImageError| DecodeFailure { index, size, error };
+    match tile {
+        Tile::Image(buf) => Ok(decode(&buf, decode_options)
+            .map(|image| {
+                resize_member(
+                    image,
+                    index,
+                    width,
+                    height,
+                    image_fit,
+                    allow_upscale,
+                    resampling,
+                )
+            })
+            .map_err(failure(buf.len()))),
+        Tile::Logo(buf, fit) => Ok(decode(&buf, &decode_options.for_logo())
+            .map(|logo| fit_logo(logo, width, height, fit))
+            .map_err(failure(buf.len()))),
+        Tile::TextCard {
+            text,
+            style,
+            background,
+        } => render_text_card(&text, style, background, width, height, antialias).map(Ok),
+        Tile::Placeholder => Ok(Ok(placeholder_image(width, height))),
+        Tile::Empty => unreachable!("empty tiles are never pending"),
+    }
+}
+
+//normalize_orientation has to see the whole decoded batch before anything is resized
+fn early_resize_dimension(
+    member_dimension: Option<(u32, u32)>,
+    options: &CreateBundledImageOptions,
+) -> Option<(u32, u32)> {
+    member_dimension.filter(|_| !options.normalize_orientation)
+}
+
+async fn prepare_decoded(
+    jobs: Vec<DecodeJob>,
+    member_dimension: Option<(u32, u32)>,
+    options: &CreateBundledImageOptions,
+) -> Result<PreparedBundle, ImageCombinerError> {
+    let mut tiles = Vec::with_capacity(jobs.len());
+    for (index, job) in jobs.into_iter().enumerate() {
+        tiles.push((index, job.await?));
+    }
+    if let Some(order) = &options.order {
+        tiles = apply_order(tiles, order)?;
+    }
+    debug!("process {} tiles into 1", tiles.len());
+    let mut position = 0;
+    let mut image_positions = Vec::new();
+    let mut origin_images = Vec::new();
+    let mut logos = Vec::new();
+    let mut placeholders = Vec::new();
+    let mut text_cards = Vec::new();
+    let mut empties = Vec::new();
+    let mut skipped = Vec::new();
+    let mut failures = Vec::new();
+    for (index, tile) in tiles {
+        let (decoded, size, fit) = match tile {
+            DecodedTile::Image(decoded, size) => (decoded, size, None),
+            DecodedTile::Logo(decoded, size, fit) => (decoded, size, Some(fit)),
+            DecodedTile::TextCard {
+                text,
+                style,
+                background,
+            } => {
+                text_cards.push((position, text, style, background));
+                position += 1;
+                continue;
+            }
+            DecodedTile::Empty => {
+                empties.push(position);
+                position += 1;
+                continue;
+            }
+            DecodedTile::Placeholder => {
+                placeholders.push(position);
+                position += 1;
+                continue;
+            }
+        };
+        match (decoded, fit) {
+            (Ok(image), None) => {
+                image_positions.push(position);
+                origin_images.push(image);
+            }
+            (Ok(logo), Some(fit)) => logos.push((position, logo, fit)),
+            (Err(e), _) => match options.on_decode_error {
+                DecodeErrorPolicy::FailFast => {
+                    debug!("image no {} failed to decode: {}", index + 1, e);
+                    failures.push(DecodeFailure {
+                        index,
+                        size,
+                        error: e,
+                    });
+                }
+                DecodeErrorPolicy::Skip => {
+                    debug!("skip image no {} failed to decode: {}", index + 1, e);
+                    skipped.push(index);
+                    continue;
+                }
+                DecodeErrorPolicy::Placeholder => {
+                    debug!(
+                        "placeholder for image no {} failed to decode: {}",
+                        index + 1,
+                        e
+                    );
+                    skipped.push(index);
+                    placeholders.push(position);
+                }
+            },
+        }
+        position += 1;
+    }
+    if !failures.is_empty() {
+        failures.sort_by_key(|failure| failure.index);
+        return Err(ImageCombinerError::Decode(failures));
+    }
+    if options.normalize_orientation {
+        origin_images = normalize_orientation(origin_images)?;
+    }
+    let (width, height) = match member_dimension {
+        Some(member_dimension) => member_dimension,
+        None if origin_images.is_empty() => limit_member_dimension(
+            position,
+            find_optical_dimension(
+                &logos
+                    .iter()
+                    .map(|(_, logo, _)| logo.dimensions())
+                    .collect::<Vec<_>>(),
+            ),
+            options,
+        )?,
+        None => limit_member_dimension(
+            position,
+            find_optical_dimension(&image_dimensions(&origin_images)),
+            options,
+        )?,
+    };
+    let resize_images = resize_images(origin_images, width, height, options).await?;
+    let mut prepared: Vec<Option<PreparedTile>> = (0..position).map(|_| None).collect();
+    for (i, content) in image_positions.into_iter().zip(resize_images) {
+        prepared[i] = Some(PreparedTile {
+            content,
+            alignment: options.cell_alignment,
+        });
+    }
+    for (i, logo, fit) in logos {
+        debug!("fit logo no {}", i + 1);
+        prepared[i] = Some(PreparedTile {
+            content: TileContent::Ready(fit_logo(logo.load()?, width, height, fit)),
+            alignment: CellAlignment::Center,
+        });
+    }
+    for i in placeholders {
+        prepared[i] = Some(PreparedTile {
+            content: TileContent::Ready(placeholder_image(width, height)),
+            alignment: CellAlignment::Center,
+        });
+    }
+    for i in empties {
+        prepared[i] = Some(PreparedTile {
+            content: TileContent::Empty,
+            alignment: CellAlignment::Center,
+        });
+    }
+    for (i, text, style, background) in text_cards {
+        debug!("render text card no {}", i + 1);
+        prepared[i] = Some(PreparedTile {
+            content: TileContent::Ready(render_text_card(
+                &text,
+                style,
+                background,
+                width,
+                height,
+                options.antialias(),
+            )?),
+            alignment: CellAlignment::Center,
+        });
+    }
+    let prepared: Vec<PreparedTile> = prepared.into_iter().flatten().collect();
+    let layout = plan_layout(prepared.len(), (width, height), options)?;
+    Ok(PreparedBundle {
+        tiles: prepared,
+        layout,
+        skipped,
+    })
+}
+
+//decodes every buffer at once, None stands for a placeholder under DecodeErrorPolicy::Placeholder
+async fn decode_images(
+    buffers: Vec<Vec<u8>>,
+    options: &CreateBundledImageOptions,
+) -> Result<(Vec<Option<DynamicImage>>, Vec<usize>), ImageCombinerError> {
+    let mut tasks = TileTasks::new(Stage::Decode);
+    for (index, buffer) in buffers.into_iter().enumerate() {
+        let decode_options = options.decode_options(index);
+        tasks.spawn(index, async move {
+            Ok(
+                decode(&buffer, &decode_options).map_err(|error| DecodeFailure {
+                    index,
+                    size: buffer.len(),
+                    error,
+                }),
+            )
+        });
+    }
+    let mut images = Vec::new();
+    let mut skipped = Vec::new();
+    let mut failures = Vec::new();
+    for decoded in tasks.join_all().await? {
+        match decoded {
+            Ok(image) => images.push(Some(image)),
+            Err(failure) => match options.on_decode_error {
+                DecodeErrorPolicy::FailFast => failures.push(failure),
+                DecodeErrorPolicy::Skip => skipped.push(failure.index),
+                DecodeErrorPolicy::Placeholder => {
+                    skipped.push(failure.index);
+                    images.push(None);
+                }
+            },
+        }
+    }
+    if !failures.is_empty() {
+        return Err(ImageCombinerError::Decode(failures));
+    }
+    Ok((images, skipped))
+}
+
+fn render_text_card(
+    text: &str,
+    style: TextCardStyle,
+    background: Rgba<u8>,
+    width: u32,
+    height: u32,
+    antialias: bool,
+) -> Result<DynamicImage, ImageCombinerError> {
+    let fonts = FontSet::new(&style)?.with_antialias(antialias);
+    let font_size = style.font_size.unwrap_or(width as f32 * 0.1);
+    let padding = font_size * 0.5;
+    let line_height = font_size * 1.2;
+    let mut lines = wrap_text(text, font_size, width as f32 - padding * 2.0);
+    let max_lines = ((height as f32 - padding * 2.0) / line_height).max(1.0) as usize;
+    if lines.len() > max_lines {
+        debug!(
+            "text card overflows cell, drop {} lines",
+            lines.len() - max_lines
+        );
+        lines.truncate(max_lines);
+    }
+    let mut card = ImageBuffer::from_pixel(width, height, background);
+    let mut top = (height as f32 - lines.len() as f32 * line_height) * 0.5;
+    for line in lines.iter() {
+        let left = (width as f32 - fonts.width(line, Scale::uniform(font_size))) * 0.5;
+        fonts.draw(
+            &mut card,
+            style.color,
+            left.max(0.0) as u32,
+            top.max(0.0) as u32,
+            Scale::uniform(font_size),
+            line,
+        );
+        top += line_height;
+    }
+    Ok(DynamicImage::ImageRgba8(card))
+}
+
+//gray cell crossed out, drawn in place of images that failed to decode
+fn placeholder_image(width: u32, height: u32) -> DynamicImage {
+    let mut placeholder = ImageBuffer::from_pixel(width, height, GRAY_COLOR);
+    let (right, bottom) = (width as f32 - 1.0, height as f32 - 1.0);
+    draw_line_segment_mut(
+        &mut placeholder,
+        (0.0, 0.0),
+        (right, bottom),
+        DARK_GRAY_COLOR,
+    );
+    draw_line_segment_mut(
+        &mut placeholder,
+        (right, 0.0),
+        (0.0, bottom),
+        DARK_GRAY_COLOR,
+    );
+    DynamicImage::ImageRgba8(placeholder)
+}
+
+//logos are only ever scaled down, never beyond their native resolution
+fn fit_logo(logo: DynamicImage, width: u32, height: u32, fit: Fit) -> DynamicImage {
+    if logo.width() <= width && logo.height() <= height {
+        return logo;
+    }
+    let filter = image::imageops::FilterType::Lanczos3;
+    match fit {
+        Fit::Contain => logo.resize(width, height, filter),
+        Fit::Cover | Fit::SmartCover => {
+            let scale = (width as f64 / logo.width() as f64)
+                .max(height as f64 / logo.height() as f64)
+                .min(1.0);
+            let scaled = logo.resize_exact(
+                geometry::round_px(logo.width() as f64 * scale),
+                geometry::round_px(logo.height() as f64 * scale),
+                filter,
+            );
+            match fit {
+                Fit::SmartCover => crop_smart(scaled, width, height),
+                _ => crop_center(scaled, width, height),
+            }
+        }
+        Fit::Stretch => {
+            logo.resize_exact(logo.width().min(width), logo.height().min(height), filter)
+        }
+        Fit::CropCenter => crop_center(logo, width, height),
+        Fit::SmartCrop => crop_smart(logo, width, height),
+    }
+}
+
+fn crop_center(image: DynamicImage, width: u32, height: u32) -> DynamicImage {
+    let crop_width = image.width().min(width);
+    let crop_height = image.height().min(height);
+    image.crop_imm(
+        geometry::center_px(image.width() - crop_width),
+        geometry::center_px(image.height() - crop_height),
+        crop_width,
+        crop_height,
+    )
+}
+
+//keeps the window with the most luma edges so a subject off center is not cut off,
+//equally busy windows go to the one nearest the center
+fn crop_smart(image: DynamicImage, width: u32, height: u32) -> DynamicImage {
+    let (image_width, image_height) = image.dimensions();
+    let crop_width = image_width.min(width);
+    let crop_height = image_height.min(height);
+    if (crop_width, crop_height) == (image_width, image_height) {
+        return image;
+    }
+    let luma = image.to_luma8();
+    let stride = image_width as usize + 1;
+    //integral of the edge strength, one row and column larger than the image
+    let mut integral = vec![0u64; stride * (image_height as usize + 1)];
+    for y in 0..image_height {
+        let mut row = 0u64;
+        for x in 0..image_width {
+            let value = luma.get_pixel(x, y)[0];
+            let right = luma.get_pixel((x + 1).min(image_width - 1), y)[0];
+            let below = luma.get_pixel(x, (y + 1).min(image_height - 1))[0];
+            row += (value.abs_diff(right) as u64) + (value.abs_diff(below) as u64);
+            let at = (y as usize + 1) * stride + x as usize + 1;
+            integral[at] = integral[at - stride] + row;
+        }
+    }
+    let energy = |x: u32, y: u32| {
+        let (left, top) = (x as usize, y as usize);
+        let (right, bottom) = (left + crop_width as usize, top + crop_height as usize);
+        integral[bottom * stride + right] + integral[top * stride + left]
+            - integral[top * stride + right]
+            - integral[bottom * stride + left]
+    };
+    let center = (
+        (image_width - crop_width) / 2,
+        (image_height - crop_height) / 2,
+    );
+    let distance = |(x, y): (u32, u32)| x.abs_diff(center.0) + y.abs_diff(center.1);
+    let mut best = (center, energy(center.0, center.1));
+    for y in 0..=image_height - crop_height {
+        for x in 0..=image_width - crop_width {
+            let candidate = energy(x, y);
+            if candidate > best.1 || (candidate == best.1 && distance((x, y)) < distance(best.0)) {
+                best = ((x, y), candidate);
+            }
+        }
+    }
+    let ((x, y), _) = best;
+    debug!("smart crop at {},{}", x, y);
+    image.crop_imm(x, y, crop_width, crop_height)
+}
+
+enum DecodedTile {
+    Image(Result<OriginImage, ImageError>, usize),
+    Logo(Result<OriginImage, ImageError>, usize, Fit),
+    TextCard {
+        text: String,
+        style: TextCardStyle,
+        background: Rgba<u8>,
+    },
+    Empty,
+    Placeholder,
+}
+
+//a decoded image before it is fitted to its cell, spilled right after the decode under low_memory
+enum OriginImage {
+    Ready(DynamicImage),
+    Spilled(SpilledTile),
+}
+
+impl OriginImage {
+    fn new(image: DynamicImage, spill: bool) -> Result<Self, ImageCombinerError> {
+        if spill && image.width() > 0 && image.height() > 0 {
+            return SpilledTile::spill(&image).map(Self::Spilled);
+        }
+        Ok(Self::Ready(image))
+    }
+
+    fn dimensions(&self) -> (u32, u32) {
+        match self {
+            Self::Ready(image) => image.dimensions(),
+            Self::Spilled(spilled) => spilled.dimensions(),
+        }
+    }
+
+    fn load(self) -> Result<DynamicImage, ImageCombinerError> {
+        match self {
+            Self::Ready(image) => Ok(image),
+            Self::Spilled(spilled) => spilled.load(),
+        }
+    }
+
+    //a spilled image is spilled again once changed, so only the one being changed is in memory
+    fn map(self, f: impl FnOnce(DynamicImage) -> DynamicImage) -> Result<Self, ImageCombinerError> {
+        let spill = matches!(self, Self::Spilled(_));
+        Self::new(f(self.load()?), spill)
+    }
+}
+
+type Job<T> = Pin<Box<dyn Future<Output = Result<T, ImageCombinerError>> + Send>>;
+type DecodeJob = Job<DecodedTile>;
+
+fn spawn_decode(
+    index: usize,
+    tile: Tile,
+    options: &CreateBundledImageOptions,
+    resize_to: Option<(u32, u32)>,
+) -> DecodeJob {
+    let job = spawn_job(
+        options,
+        Stage::Decode,
+        index,
+        decode_job(index, tile, options, resize_to),
+    );
+    Box::pin(async move { job.await? })
+}
+
+//under low_memory a decode is finished and spilled before the next one is spawned
+async fn pace_decode<T: Send + 'static>(
+    job: Job<T>,
+    options: &CreateBundledImageOptions,
+) -> Result<Job<T>, ImageCombinerError> {
+    if !options.low_memory {
+        return Ok(job);
+    }
+    let decoded = job.await?;
+    Ok(Box::pin(std::future::ready(Ok(decoded))))
+}
+
+fn decode_job(
+    index: usize,
+    tile: Tile,
+    options: &CreateBundledImageOptions,
+    resize_to: Option<(u32, u32)>,
+) -> impl FnOnce() -> Result<DecodedTile, ImageCombinerError> + Send + 'static {
+    let decode_options = options.decode_options(index);
+    let fit = options.fit;
+    let allow_upscale = options.allow_upscale;
+    let resampling = options.resampling();
+    let spill = options.low_memory;
+    move || {
+        decode_tile(
+            tile,
+            &decode_options,
+            resize_to,
+            (fit, allow_upscale),
+            resampling,
+            spill,
+        )
+    }
+}
+
+//how many full size images are decoded at once, so peak memory follows in-flight tiles
+fn in_flight_limit() -> usize {
+    std::thread::available_parallelism().map_or(4, |n| n.get())
+}
+
+//on the worker pool when one is set, otherwise on a task of its own, so the caller is never blocked
+fn spawn_job<T, F>(
+    options: &CreateBundledImageOptions,
+    stage: Stage,
+    index: usize,
+    job: F,
+) -> Job<T>
+where
+    T: Send + 'static,
+    F: FnOnce() -> T + Send + 'static,
+{
+    match &options.worker_pool {
+        Some(worker_pool) => Box::pin(worker_pool.spawn(stage, Some(index), job)),
+        None => {
+            let handle = tokio::spawn(async move { job() });
+            Box::pin(async move { handle.await.map_err(|e| join_error(e, stage, Some(index))) })
+        }
+    }
+}
+
+fn decode_tile(
+    tile: Tile,
+    decode_options: &DecodeOptions,
+    resize_to: Option<(u32, u32)>,
+    (fit, allow_upscale): (Fit, bool),
+    resampling: Resampling,
+    spill: bool,
+) -> Result<DecodedTile, ImageCombinerError> {
+    let origin = |decoded: Result<DynamicImage, ImageError>| match decoded {
+        Ok(image) => OriginImage::new(image, spill).map(Ok),
+        Err(e) => Ok(Err(e)),
+    };
+    Ok(match tile {
+        Tile::Image(buf) => {
+            let decoded = decode(&buf, decode_options).map(|image| match resize_to {
+                Some((width, height)) => {
+                    resize_member(image, 0, width, height, fit, allow_upscale, resampling)
+                }
+                None => image,
+            });
+            DecodedTile::Image(origin(decoded)?, buf.len())
+        }
+        Tile::Logo(buf, fit) => DecodedTile::Logo(
+            origin(decode(&buf, &decode_options.for_logo()))?,
+            buf.len(),
+            fit,
+        ),
+        Tile::TextCard {
+            text,
+            style,
+            background,
+        } => DecodedTile::TextCard {
+            text,
+            style,
+            background,
+        },
+        Tile::Empty => DecodedTile::Empty,
+        Tile::Placeholder => DecodedTile::Placeholder,
+    })
+}
+
+async fn encode_bundle(
+    dyn_image: DynamicImage,
+    options: &CreateBundledImageOptions,
+) -> Result<Vec<u8>, ImageCombinerError> {
+    Ok(encode_bundle_with_quality(dyn_image, options).await?.0)
+}
+
+async fn encode_bundle_with_quality(
+    dyn_image: DynamicImage,
+    options: &CreateBundledImageOptions,
+) -> Result<(Vec<u8>, u8), ImageCombinerError> {
+    let output_format = options.output_format;
+    let encode_options = match options.render_quality {
+        RenderQuality::Final => options.encode_options.clone(),
+        RenderQuality::Preview => options
+            .encode_options
+            .clone()
+            .with_max_quality(PREVIEW_QUALITY),
+    };
+    let signature = match (&options.signing_key, output_format) {
+        (Some(key), OutputFormat::Jpeg) => {
+            Some(signature::signature_segment(&options.signed_params(), key)?)
+        }
+        _ => None,
+    };
+    //the signature is added after encoding, so it takes its share of the size limits up front
+    let reserved = signature.as_ref().map_or(0, |segment| segment.len() as u64);
+    let max_output_bytes = options
+        .max_output_bytes
+        .map(|bytes| bytes.saturating_sub(reserved));
+    let target_file_size = options
+        .target_file_size
+        .map(|bytes| bytes.saturating_sub(reserved));
+    let job = move || {
+        encode_within(
+            &dyn_image,
+            output_format,
+            &encode_options,
+            max_output_bytes,
+            target_file_size,
+        )
+    };
+    let (mut bytes, quality) = match &options.worker_pool {
+        Some(worker_pool) => worker_pool.spawn(Stage::Encode, None, job).await??,
+        None => job()?,
+    };
+    if let Some(segment) = signature {
+        signature::sign_jpeg(&mut bytes, segment)?;
+    }
+    Ok((bytes, quality))
+}
+
+async fn render_bundle(
+    bundle: PreparedBundle,
+    options: &CreateBundledImageOptions,
+) -> Result<BundledImage, ImageCombinerError> {
+    let (dyn_image, skipped) = compose_bundle(bundle, options).await?;
+    finish_bundle(dyn_image, skipped, options).await
+}
+
+async fn compose_bundle(
+    bundle: PreparedBundle,
+    options: &CreateBundledImageOptions,
+) -> Result<(DynamicImage, Vec<usize>), ImageCombinerError> {
+    let (image_buf, layout, skipped) = draw_tile_layer(bundle, options).await?;
+    Ok((finish_canvas(image_buf, &layout, options)?, skipped))
+}
+
+//every tile on the background, before anything drawn across cells
+async fn draw_tile_layer(
+    bundle: PreparedBundle,
+    options: &CreateBundledImageOptions,
+) -> Result<(ImageBuffer<Rgba<u8>, Vec<u8>>, BundleLayout, Vec<usize>), ImageCombinerError> {
+    let PreparedBundle {
+        tiles,
+        layout,
+        mut skipped,
+    } = bundle;
+    let bundled_image_canvas_height = layout.height;
+    let bundled_image_canvas_width = layout.width;
+    debug!(
+        "create image buf {}x{}",
+        bundled_image_canvas_width, bundled_image_canvas_height
+    );
+    let image_buf = ImageBuffer::from_fn(
+        bundled_image_canvas_width,
+        bundled_image_canvas_height,
+        |_, _| options.background,
+    );
+    let image_buf_threaded = Arc::new(Mutex::new(image_buf));
+    skipped.extend(
+        draw_bundled_image(Arc::clone(&image_buf_threaded), tiles, &layout, 0, options).await?,
+    );
+    skipped.sort_unstable();
+    let image_buf = image_buf_threaded.lock_owned().await.to_owned();
+    Ok((image_buf, layout, skipped))
+}
+
+fn finish_canvas(
+    mut image_buf: ImageBuffer<Rgba<u8>, Vec<u8>>,
+    layout: &BundleLayout,
+    options: &CreateBundledImageOptions,
+) -> Result<DynamicImage, ImageCombinerError> {
+    if let Some(style) = &options.step_arrows {
+        let color = style.color.unwrap_or(options.theme.accent);
+        draw_step_arrows(&mut image_buf, layout, style, color);
+    }
+    decorate_canvas(&mut image_buf, options)?;
+    Ok(DynamicImage::ImageRgba8(image_buf))
+}
+
+//the tile layer of a render, kept so the next render can redraw only the inputs that changed
+#[cfg(feature = "project")]
+pub(crate) struct TileLayer {
+    canvas: ImageBuffer<Rgba<u8>, Vec<u8>>,
+    layout: BundleLayout,
+    skipped: Vec<usize>,
+}
+
+#[cfg(feature = "project")]
+impl TileLayer {
+    pub(crate) async fn render(
+        tiles: Vec<Tile>,
+        options: &CreateBundledImageOptions,
+    ) -> Result<Self, ImageCombinerError> {
+        Self::draw(prepare_tiles(tiles, options).await?, options).await
+    }
+
+    async fn draw(
+        bundle: PreparedBundle,
+        options: &CreateBundledImageOptions,
+    ) -> Result<Self, ImageCombinerError> {
+        let (canvas, layout, skipped) = draw_tile_layer(bundle, options).await?;
+        Ok(Self {
+            canvas,
+            layout,
+            skipped,
+        })
+    }
+
+    //draws only the changed inputs over a copy of this layer, or the whole layer when any cell moved,
+    //the flag tells whether this layer was reused
+    pub(crate) async fn redraw(
+        &self,
+        tiles: Vec<Tile>,
+        changed: &[usize],
+        options: &CreateBundledImageOptions,
+    ) -> Result<(Self, bool), ImageCombinerError> {
+        let count = tiles.len();
+        let mut bundle = prepare_tiles(tiles, options).await?;
+        if bundle.layout != self.layout || bundle.tiles.len() != count || !bundle.skipped.is_empty()
+        {
+            debug!("layout changed, draw every tile");
+            return Ok((Self::draw(bundle, options).await?, false));
+        }
+        let mut canvas = self.canvas.clone();
+        let layout = bundle.layout;
+        let slot = layout.slot_dimension();
+        let gap = (layout.cell_width - slot.0, layout.cell_height - slot.1);
+        for (index, tile) in bundle.tiles.iter_mut().enumerate() {
+            if changed.contains(&index) {
+                //the shadow of a cell reaches into half of the gap on every side
+                let (row, column) = layout.cells[index];
+                let cell = (
+                    layout.margin.left + column * layout.cell_width,
+                    layout.margin.top + row * layout.cell_height,
+                );
+                let (left, top, right, bottom) = cell_clip(cell, slot, gap);
+                let (right, bottom) = (right.min(canvas.width()), bottom.min(canvas.height()));
+                if right > left && bottom > top {
+                    draw_filled_rect_mut(
+                        &mut canvas,
+                        Rect::at(left as i32, top as i32).of_size(right - left, bottom - top),
+                        options.background,
+                    );
+                }
+            } else {
+                tile.content = TileContent::Empty;
+            }
+        }
+        debug!("redraw {} of {} tiles", changed.len(), count);
+        let canvas_threaded = Arc::new(Mutex::new(canvas));
+        let redrawn_skipped = draw_bundled_image(
+            Arc::clone(&canvas_threaded),
+            bundle.tiles,
+            &layout,
+            0,
+            options,
+        )
+        .await?;
+        let mut skipped: Vec<usize> = self
+            .skipped
+            .iter()
+            .copied()
+            .filter(|index| !changed.contains(index))
+            .chain(redrawn_skipped)
+            .collect();
+        skipped.sort_unstable();
+        let canvas = canvas_threaded.lock_owned().await.to_owned();
+        Ok((
+            Self {
+                canvas,
+                layout,
+                skipped,
+            },
+            true,
+        ))
+    }
+
+    //step arrows, bands, overlays and the rest drawn over a copy of the layer, then encoded
+    pub(crate) async fn finish(
+        &self,
+        options: &CreateBundledImageOptions,
+    ) -> Result<BundledImage, ImageCombinerError> {
+        let dyn_image = finish_canvas(self.canvas.clone(), &self.layout, options)?;
+        finish_bundle(dyn_image, self.skipped.clone(), options).await
+    }
+}
+
+//drawn over the whole canvas once every tile is placed
+fn decorate_canvas(
+    canvas: &mut ImageBuffer<Rgba<u8>, Vec<u8>>,
+    options: &CreateBundledImageOptions,
+) -> Result<(), ImageCombinerError> {
+    band::attach_bands(canvas, options)?;
+    overlay::composite_layers(canvas, options)?;
+    if let Some(ruler) = &options.ruler {
+        draw_ruler(canvas, ruler, options)?;
+    }
+    if options.canvas_corner_radius > 0 {
+        round_canvas_corners(canvas, options.canvas_corner_radius, options.antialias());
+    }
+    Ok(())
+}
+
+//splits the batch into pages of at most max_rows_per_page rows, every page keeps the member size of the whole batch
+async fn compose_pages(
+    tiles: Vec<Tile>,
+    options: &CreateBundledImageOptions,
+    max_rows_per_page: u32,
+) -> Result<Vec<(DynamicImage, Vec<usize>)>, ImageCombinerError> {
+    let column = options.column.unwrap_or(0);
+    if column == 0 || max_rows_per_page == 0 || options.order.is_some() {
+        return Err(ImageCombinerError::InvalidOptions(format!(
+            "pages need a fixed column, at least one row per page and no order column:{:?},max rows per page:{}",
+            options.column, max_rows_per_page
+        )));
+    }
+    let slots = (column * max_rows_per_page) as usize;
+    let per_page = slots.saturating_sub(options.reserved_cells.len());
+    if per_page == 0 {
+        return Err(ImageCombinerError::InvalidOptions(format!(
+            "reserved cells leave no room on a page reserved:{},slots:{}",
+            options.reserved_cells.len(),
+            slots
+        )));
+    }
+    let mut page_options = options.clone();
+    //every page shares the member size of a full page
+    page_options.dimension = options
+        .dimension
+        .or_else(|| probe_member_dimension(&tiles, options))
+        .map(|member_dimension| {
+            limit_member_dimension(per_page.min(tiles.len()), member_dimension, options)
+        })
+        .transpose()?;
+    let mut pages = Vec::new();
+    let mut tiles = tiles.into_iter().peekable();
+    let mut first = 0;
+    while tiles.peek().is_some() {
+        let page: Vec<Tile> = tiles.by_ref().take(per_page).collect();
+        let count = page.len();
+        debug!("compose page {} with {} tiles", pages.len() + 1, count);
+        let bundle = prepare_tiles(page, &page_options).await?;
+        let (image, skipped) = compose_bundle(bundle, &page_options).await?;
+        pages.push((image, skipped.into_iter().map(|i| i + first).collect()));
+        first += count;
+    }
+    Ok(pages)
+}
+
+async fn finish_bundle(
+    dyn_image: DynamicImage,
+    skipped: Vec<usize>,
+    options: &CreateBundledImageOptions,
+) -> Result<BundledImage, ImageCombinerError> {
+    let mut thumbnails = BTreeMap::new();
+    for width in thumbnail_widths(options, dyn_image.width()) {
+        let height = (dyn_image.height() as u64 * width as u64 / dyn_image.width() as u64).max(1);
+        debug!("create thumbnail {}x{}", width, height);
+        let thumbnail = dyn_image.resize_exact(width, height as u32, options.resampling().filter);
+        thumbnails.insert(width, encode_bundle(thumbnail, options).await?);
+    }
+    let (image_bytes, quality) = encode_bundle_with_quality(dyn_image, options).await?;
+    Ok(BundledImage {
+        sha256: options.sha256.then(|| sha256_hex(&image_bytes)),
+        quality: (options.output_format == OutputFormat::Jpeg).then_some(quality),
+        seed: options.seed,
+        bytes: image_bytes,
+        skipped,
+        duplicates: Vec::new(),
+        fetch_attempts: Vec::new(),
+        thumbnails,
+    })
+}
+
+fn thumbnail_widths(options: &CreateBundledImageOptions, canvas_width: u32) -> Vec<u32> {
+    let mut widths: Vec<u32> = options
+        .thumbnail_widths
+        .iter()
+        .copied()
+        .filter(|&width| width > 0 && width < canvas_width)
+        .collect();
+    widths.sort_unstable();
+    widths.dedup();
+    widths
+}
+
+fn plan_layout(
+    tile_count: usize,
+    member_dimension: (u32, u32),
+    options: &CreateBundledImageOptions,
+) -> Result<BundleLayout, ImageCombinerError> {
+    let frame = options.cell_style.frame_padding();
+    let (width, height) = (
+        member_dimension.0 + frame.left + frame.right,
+        member_dimension.1 + frame.top + frame.bottom,
+    );
+    let mut column = grid_column(options, tile_count, (width, height))?;
+    let cells = plan_cells(
+        tile_count,
+        column,
+        &options.reserved_cells,
+        options.fill_order,
+    )?;
+    let row = cells
+        .iter()
+        .chain(options.reserved_cells.iter())
+        .map(|&(row, _)| row + 1)
+        .max()
+        .unwrap_or(0);
+    if options.auto_shrink {
+        column = cells
+            .iter()
+            .chain(options.reserved_cells.iter())
+            .map(|&(_, column)| column + 1)
+            .max()
+            .unwrap_or(0);
+    }
+    let (gap, margin) = options.spacing((column * width, row * height));
+    if options.gap.is_none() && options.outer_margin.is_none() {
+        //padding trails every cell, including the last column and row
+        let cell_height = if row >= 1 { height + gap } else { height };
+        let cell_width = if column >= 1 { width + gap } else { width };
+        return Ok(BundleLayout {
+            member_width: member_dimension.0,
+            member_height: member_dimension.1,
+            cells,
+            cell_width,
+            cell_height,
+            margin,
+            frame,
+            width: column * cell_width,
+            height: row * cell_height,
+        });
+    }
+    let span = |count: u32, member: u32| count * member + count.saturating_sub(1) * gap;
+    Ok(BundleLayout {
+        member_width: member_dimension.0,
+        member_height: member_dimension.1,
+        cells,
+        cell_width: width + gap,
+        cell_height: height + gap,
+        margin,
+        frame,
+        width: span(column, width) + margin.left + margin.right,
+        height: span(row, height) + margin.top + margin.bottom,
+    })
+}
+
+//layouts whose size is not planned from the member dimension cannot be fit into max_canvas
+pub(crate) fn unsupported_max_canvas(layout: &str) -> ImageCombinerError {
+    ImageCombinerError::InvalidOptions(format!(
+        "max canvas is not supported by the {} layout",
+        layout
+    ))
+}
+
+//shrinks the member dimension until the planned grid fits in max_canvas,
+//with the header and footer bands taken out of the height first since they never shrink
+fn limit_member_dimension(
+    tile_count: usize,
+    member_dimension: (u32, u32),
+    options: &CreateBundledImageOptions,
+) -> Result<(u32, u32), ImageCombinerError> {
+    let Some((max_width, max_canvas_height)) = options.max_canvas else {
+        return Ok(member_dimension);
+    };
+    let bands = band::header_height(options) + band::footer_height(options);
+    let Some(max_height) = max_canvas_height.checked_sub(bands).filter(|&h| h > 0) else {
+        return Err(ImageCombinerError::InvalidOptions(format!(
+            "header and footer bands of {}px leave no room in max canvas {}x{}",
+            bands, max_width, max_canvas_height
+        )));
+    };
+    let mut dimension = member_dimension;
+    loop {
+        let layout = plan_layout(tile_count, dimension, options)?;
+        if layout.width <= max_width && layout.height <= max_height {
+            if dimension != member_dimension {
+                debug!(
+                    "scale member dimension {:?} down to {:?} to fit max canvas {}x{}",
+                    member_dimension, dimension, max_width, max_canvas_height
+                );
+            }
+            return Ok(dimension);
+        }
+        if dimension == (1, 1) {
+            return Err(ImageCombinerError::InvalidOptions(format!(
+                "grid does not fit in max canvas {}x{} even with 1px members",
+                max_width, max_canvas_height
+            )));
+        }
+        //gaps, margins and frames do not shrink, so the scale is retried until the grid fits
+        let scale =
+            (max_width as f64 / layout.width as f64).min(max_height as f64 / layout.height as f64);
+        let (width, height) = dimension;
+        let scaled = (
+            geometry::fit_px(width as f64 * scale).clamp(1, width),
+            geometry::fit_px(height as f64 * scale).clamp(1, height),
+        );
+        dimension = if scaled == dimension {
+            (
+                width.saturating_sub(1).max(1),
+                height.saturating_sub(1).max(1),
+            )
+        } else {
+            scaled
+        };
+    }
+}
+
+fn grid_column(
+    options: &CreateBundledImageOptions,
+    tile_count: usize,
+    member_dimension: (u32, u32),
+) -> Result<u32, ImageCombinerError> {
+    if let Some(column) = options.column {
+        //CreateBundledImageOptions::new takes the column count without the checks of build
+        if column == 0 {
+            return Err(ImageCombinerError::InvalidOptions(
+                "column must be at least 1".to_string(),
+            ));
+        }
+        return Ok(column);
+    }
+    let slots = (tile_count + options.reserved_cells.len()) as u32;
+    if let Some(rows) = options.rows {
+        return Ok(slots.div_ceil(rows).max(1));
+    }
+    let target_aspect_ratio = options.auto_layout.unwrap_or(1.0);
+    if !(target_aspect_ratio.is_finite() && target_aspect_ratio > 0.0) {
+        return Err(ImageCombinerError::InvalidOptions(format!(
+            "auto layout aspect ratio must be positive aspect ratio:{}",
+            target_aspect_ratio
+        )));
+    }
+    let (width, height) = member_dimension;
+    let mut best_column = 1;
+    let mut best_penalty = f32::MAX;
+    for column in 1..=slots.max(1) {
+        let row = slots.div_ceil(column).max(1);
+        //the same canvas plan_layout takes percent units from
+        let (gap, _) = options.spacing((column * width, row * height));
+        let cell_width = (width + gap) as f32;
+        let cell_height = (height + gap) as f32;
+        let penalty = aspect_penalty(
+            column as f32 * cell_width,
+            row as f32 * cell_height,
+            target_aspect_ratio,
+        );
+        if penalty < best_penalty {
+            best_column = column;
+            best_penalty = penalty;
+        }
+    }
+    debug!("auto layout picked {} columns", best_column);
+    Ok(best_column)
+}
+
+fn plan_cells(
+    image_count: usize,
+    column: u32,
+    reserved_cells: &[(u32, u32)],
+    fill_order: FillOrder,
+) -> Result<Vec<(u32, u32)>, ImageCombinerError> {
+    if let Some(&(row, reserved_column)) = reserved_cells.iter().find(|&&(_, c)| c >= column) {
+        return Err(ImageCombinerError::InvalidOptions(format!(
+            "reserved cell is out of column range row:{},column:{},column count:{}",
+            row, reserved_column, column
+        )));
+    }
+    //column major needs the row count up front, grow it until the free cells fit every image
+    let free_cells = |row: u32| {
+        let reserved = reserved_cells.iter().filter(|&&(r, _)| r < row).count() as u32;
+        (row * column).saturating_sub(reserved)
+    };
+    let mut row = (image_count as u32).div_ceil(column.max(1));
+    while fill_order == FillOrder::ColumnMajor && free_cells(row) < image_count as u32 {
+        row += 1;
+    }
+    let mut cells = Vec::with_capacity(image_count);
+    let mut slot = 0;
+    while cells.len() < image_count {
+        let cell = match fill_order {
+            FillOrder::RowMajor => (slot / column, slot % column),
+            FillOrder::ColumnMajor => (slot % row, slot / row),
+        };
+        if !reserved_cells.contains(&cell) {
+            cells.push(cell);
+        }
+        slot += 1;
+    }
+    Ok(cells)
+}
+
+//the sort as an order, so skipped indices still refer to the input
+fn sort_order(
+    tiles: &[Tile],
+    options: &CreateBundledImageOptions,
+) -> Result<Vec<usize>, ImageCombinerError> {
+    if options.order.is_some() {
+        return Err(ImageCombinerError::InvalidOptions(
+            "only one of order and sort can be set".to_string(),
+        ));
+    }
+    let mut keyed: Vec<(usize, Option<f32>)> = tiles
+        .iter()
+        .enumerate()
+        .map(|(index, tile)| {
+            let dimension = match tile {
+                Tile::Image(buf) => probe_decoded_dimensions(buf, &options.decode_options(index)),
+                Tile::Logo(buf, _) => {
+                    probe_decoded_dimensions(buf, &options.logo_decode_options(index))
+                }
+                _ => None,
+            };
+            let key = dimension.map(|(width, height)| match options.sort {
+                SortOrder::InputOrder => 0.0,
+                SortOrder::ByHeight => -(height as f32),
+                SortOrder::ByWidth => -(width as f32),
+                SortOrder::ByAspect => width as f32 / height.max(1) as f32,
+            });
+            (index, key)
+        })
+        .collect();
+    //stable, so equal keys keep their input order
+    keyed.sort_by(|a, b| match (a.1, b.1) {
+        (Some(a), Some(b)) => a.total_cmp(&b),
+        (Some(_), None) => std::cmp::Ordering::Less,
+        (None, Some(_)) => std::cmp::Ordering::Greater,
+        (None, None) => std::cmp::Ordering::Equal,
+    });
+    Ok(keyed.into_iter().map(|(index, _)| index).collect())
+}
+
+fn apply_order<T>(items: Vec<T>, order: &[usize]) -> Result<Vec<T>, ImageCombinerError> {
+    if order.len() != items.len() {
+        return Err(ImageCombinerError::InvalidOptions(format!(
+            "order length is not equal to image count order:{},images:{}",
+            order.len(),
+            items.len()
+        )));
+    }
+    let mut slots: Vec<Option<T>> = items.into_iter().map(Some).collect();
+    order
+        .iter()
+        .map(|&index| {
+            slots.get_mut(index).and_then(Option::take).ok_or_else(|| {
+                ImageCombinerError::InvalidOptions(format!(
+                    "order is not a permutation index {} is out of range or repeated",
+                    index
+                ))
+            })
+        })
+        .collect()
+}
+
+fn normalize_orientation(images: Vec<OriginImage>) -> Result<Vec<OriginImage>, ImageCombinerError> {
+    let dimensions = image_dimensions(&images);
+    let portrait_count = dimensions.iter().filter(|(w, h)| h > w).count();
+    let landscape_count = dimensions.iter().filter(|(w, h)| w > h).count();
+    if portrait_count == landscape_count {
+        return Ok(images);
+    }
+    let majority_portrait = portrait_count > landscape_count;
+    images
+        .into_iter()
+        .enumerate()
+        .zip(dimensions)
+        .map(|((i, image), (width, height))| {
+            let is_minority = if majority_portrait {
+                width > height
+            } else {
+                height > width
+            };
+            if is_minority {
+                debug!("rotate image no {} to match majority orientation", i + 1);
+                image.map(|image| image.rotate90())
+            } else {
+                Ok(image)
+            }
+        })
+        .collect()
+}
+
+fn image_dimensions(images: &[OriginImage]) -> Vec<(u32, u32)> {
+    images.iter().map(|i| i.dimensions()).collect()
+}
+
+fn calc_chars_len(s: &str) -> usize {
+    calc_chars_width(s) as usize
+}
+
+fn calc_chars_width(s: &str) -> f32 {
+    s.chars().fold(0.0, |acc, c| {
+        if c.is_ascii() {
+            return acc + 0.5;
+        }
+        acc + 1.0
+    })
+}
+
+//greedy wrap that breaks between ascii words or between any two non-ascii characters
+fn wrap_text(text: &str, font_size: f32, max_width: f32) -> Vec<String> {
+    let mut lines = Vec::new();
+    for paragraph in text.lines() {
+        let mut tokens: Vec<String> = Vec::new();
+        for c in paragraph.chars() {
+            match tokens.last_mut() {
+                Some(word)
+                    if c.is_ascii()
+                        && c != ' '
+                        && word.chars().all(|w| w.is_ascii() && w != ' ') =>
+                {
+                    word.push(c)
+                }
+                _ => tokens.push(c.to_string()),
+            }
+        }
+        let mut line = String::new();
+        for token in tokens {
+            let pieces = if calc_chars_width(&token) * font_size > max_width {
+                token.chars().map(|c| c.to_string()).collect()
+            } else {
+                vec![token]
+            };
+            for piece in pieces {
+                let line_width = calc_chars_width(&line) + calc_chars_width(&piece);
+                if line.is_empty() || line_width * font_size <= max_width {
+                    if !(line.is_empty() && piece == " ") {
+                        line.push_str(&piece);
+                    }
+                } else {
+                    lines.push(line.trim_end().to_string());
+                    line = if piece == " " { String::new() } else { piece };
+                }
+            }
+        }
+        lines.push(line.trim_end().to_string());
+    }
+    lines
+}
+
+fn resize_member(
+    origin_image: DynamicImage,
+    i: usize,
+    target_image_width: u32,
+    target_image_height: u32,
+    fit: Fit,
+    allow_upscale: bool,
+    resampling: Resampling,
+) -> DynamicImage {
+    let (mut target_image_width, mut target_image_height) =
+        (target_image_width, target_image_height);
+    if !allow_upscale {
+        //a smaller image keeps its size and is letterboxed in its cell by the cell alignment
+        let (width, height) = origin_image.dimensions();
+        if width <= target_image_width && height <= target_image_height {
+            return origin_image;
+        }
+        if fit != Fit::Contain {
+            target_image_width = target_image_width.min(width);
+            target_image_height = target_image_height.min(height);
+        }
+    }
+    if origin_image.dimensions() == (target_image_width, target_image_height)
+        || (fit == Fit::Contain && origin_image.height() == target_image_height)
+    {
+        return origin_image;
+    }
+    debug!("resize image no {} with {:?}", i + 1, fit);
+    match fit {
+        Fit::CropCenter => {
+            return crop_center(origin_image, target_image_width, target_image_height)
+        }
+        Fit::SmartCrop => return crop_smart(origin_image, target_image_width, target_image_height),
+        _ => {}
+    }
+    //filtering sRGB values darkens fine light and dark patterns, linear light keeps their brightness
+    let origin_image = if resampling.linear_light {
+        to_linear_light(&origin_image)
+    } else {
+        origin_image
+    };
+    let filter = resampling.filter;
+    let resized = match fit {
+        Fit::Contain => origin_image.resize(target_image_width, target_image_height, filter),
+        Fit::Cover => origin_image.resize_to_fill(target_image_width, target_image_height, filter),
+        Fit::SmartCover => {
+            let (width, height) = origin_image.dimensions();
+            let scale = (target_image_width as f64 / width as f64)
+                .max(target_image_height as f64 / height as f64);
+            let scaled = origin_image.resize_exact(
+                geometry::round_px(width as f64 * scale).max(target_image_width),
+                geometry::round_px(height as f64 * scale).max(target_image_height),
+                filter,
+            );
+            crop_smart(scaled, target_image_width, target_image_height)
+        }
+        Fit::Stretch | Fit::CropCenter | Fit::SmartCrop => {
+            origin_image.resize_exact(target_image_width, target_image_height, filter)
+        }
+    };
+    if resampling.linear_light {
+        return from_linear_light(&resized);
+    }
+    resized
+}
+
+//16 bit linear light keeps enough precision for the dark end of the sRGB curve
+fn to_linear_light(image: &DynamicImage) -> DynamicImage {
+    let table: Vec<u16> = (0..=255u16)
+        .map(|v| {
+            let c = v as f32 / 255.0;
+            let linear = if c <= 0.04045 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            };
+            (linear * 65535.0).round() as u16
+        })
+        .collect();
+    let rgba = image.to_rgba8();
+    let linear = ImageBuffer::from_fn(rgba.width(), rgba.height(), |x, y| {
+        let Rgba([r, g, b, a]) = *rgba.get_pixel(x, y);
+        Rgba([
+            table[r as usize],
+            table[g as usize],
+            table[b as usize],
+            a as u16 * 257,
+        ])
+    });
+    DynamicImage::ImageRgba16(linear)
+}
+
+fn from_linear_light(image: &DynamicImage) -> DynamicImage {
+    let encode = |v: u16| {
+        let linear = v as f32 / 65535.0;
+        let c = if linear <= 0.0031308 {
+            linear * 12.92
+        } else {
+            1.055 * linear.powf(1.0 / 2.4) - 0.055
+        };
+        (c * 255.0).round().clamp(0.0, 255.0) as u8
+    };
+    let linear = image.to_rgba16();
+    let rgba = ImageBuffer::from_fn(linear.width(), linear.height(), |x, y| {
+        let Rgba([r, g, b, a]) = *linear.get_pixel(x, y);
+        Rgba([
+            encode(r),
+            encode(g),
+            encode(b),
+            (a as f32 / 257.0).round() as u8,
+        ])
+    });
+    DynamicImage::ImageRgba8(rgba)
+}
+
+async fn resize_images(
+    images: Vec<OriginImage>,
+    target_image_width: u32,
+    target_image_height: u32,
+    options: &CreateBundledImageOptions,
+) -> Result<Vec<TileContent>, ImageCombinerError> {
+    let fit = options.fit;
+    let allow_upscale = options.allow_upscale;
+    let resampling = options.resampling();
+    let spill = options.low_memory;
+    let resize = move |i: usize, origin_image: OriginImage| {
+        let image = resize_member(
+            origin_image.load()?,
+            i,
+            target_image_width,
+            target_image_height,
+            fit,
+            allow_upscale,
+            resampling,
+        );
+        Ok(match OriginImage::new(image, spill)? {
+            OriginImage::Ready(image) => TileContent::Ready(image),
+            OriginImage::Spilled(spilled) => TileContent::Spilled(spilled),
+        })
+    };
+    let mut resize_images = Vec::new();
+    if options.low_memory {
+        //spilled images are mapped back one at a time
+        for (i, origin_image) in images.into_iter().enumerate() {
+            let job = spawn_job(options, Stage::Resize, i, move || resize(i, origin_image));
+            resize_images.push(job.await??);
+        }
+        return Ok(resize_images);
+    }
+    if let Some(worker_pool) = &options.worker_pool {
+        let jobs: Vec<_> = images
+            .into_iter()
+            .enumerate()
+            .map(|(i, origin_image)| {
+                worker_pool.spawn(Stage::Resize, Some(i), move || resize(i, origin_image))
+            })
+            .collect();
+        for job in jobs {
+            resize_images.push(job.await??)
+        }
+        return Ok(resize_images);
+    }
+    let mut tasks = TileTasks::new(Stage::Resize);
+    for (i, origin_image) in images.into_iter().enumerate() {
+        tasks.spawn(i, async move { resize(i, origin_image) });
+    }
+    tasks.join_all().await
+}
+
+//everything drawn with or over each tile, shared by the draw tasks
+struct CellRenderer {
+    decoration: CellDecoration,
+    frame: Option<FrameRenderer>,
+    badge: Option<BadgeRenderer>,
+}
+
+//position is the placement order of the tile, captions and badge numbers follow it
+fn place_member<I: GenericImageView<Pixel = Rgba<u8>>>(
+    canvas: &mut ImageBuffer<Rgba<u8>, Vec<u8>>,
+    image: &I,
+    alignment: CellAlignment,
+    cell: (u32, u32),
+    member_dimension: (u32, u32),
+    renderer: &CellRenderer,
+    position: usize,
+) -> Result<(), ImageCombinerError> {
+    let style = &renderer.decoration;
+    let image_cell = match &renderer.frame {
+        Some(frame) => {
+            let framed = frame.render(image, alignment, member_dimension, position)?;
+            let framed_dimension = framed.dimensions();
+            place_tile(
+                canvas,
+                &framed,
+                CellAlignment::TopLeft,
+                cell,
+                framed_dimension,
+                style,
+            )?;
+            (cell.0 + frame.padding.left, cell.1 + frame.padding.top)
+        }
+        None => {
+            place_tile(canvas, image, alignment, cell, member_dimension, style)?;
+            cell
+        }
+    };
+    if let Some(badge) = &renderer.badge {
+        let (buf_x, buf) = alignment.offset(
+            member_dimension.0.saturating_sub(image.width()),
+            member_dimension.1.saturating_sub(image.height()),
+        );
+        badge.draw(
+            canvas,
+            (image_cell.0 + buf_x, image_cell.1 + buf),
+            image.dimensions(),
+            position + 1,
+        );
+    }
+    Ok(())
+}
+
+struct BadgeRenderer {
+    font: Font<'static>,
+    text_color: Rgba<u8>,
+    font_size: Option<f32>,
+    background: Rgba<u8>,
+    position: Anchor,
+    offset: Option<(i32, i32)>,
+    antialias: bool,
+}
+
+impl BadgeRenderer {
+    fn new(
+        badge: &NumberBadge,
+        options: &CreateBundledImageOptions,
+    ) -> Result<Self, ImageCombinerError> {
+        let font = Font::try_from_vec(badge.style.font_bytes.clone())
+            .ok_or_else(|| ImageCombinerError::InvalidText("invalid font bytes".to_string()))?;
+        Ok(Self {
+            font,
+            text_color: badge.style.color,
+            font_size: badge.style.font_size,
+            background: badge.background.unwrap_or(options.theme.accent),
+            position: badge.position,
+            offset: badge.offset,
+            antialias: options.antialias(),
+        })
+    }
+
+    //a filled circle with the number centered in it, inset from the chosen corner or edge of the image
+    fn draw(
+        &self,
+        canvas: &mut ImageBuffer<Rgba<u8>, Vec<u8>>,
+        origin: (u32, u32),
+        dimension: (u32, u32),
+        number: usize,
+    ) {
+        let font_size = self
+            .font_size
+            .unwrap_or(dimension.0.min(dimension.1) as f32 * 0.08)
+            .max(1.0);
+        let diameter = (font_size * 1.6).ceil() as u32;
+        let position = match self.offset {
+            Some((offset_x, offset_y)) => {
+                OverlayPosition::new(self.position).with_offset(offset_x, offset_y)
+            }
+            None => OverlayPosition::inset(self.position, (font_size * 0.4) as u32),
+        };
+        let (offset_x, offset_y) = position.place(dimension, (diameter, diameter));
+        let radius = diameter / 2;
+        let center = (
+            (origin.0 + offset_x + radius) as i32,
+            (origin.1 + offset_y + radius) as i32,
+        );
+        draw_filled_circle_mut(canvas, center, radius as i32, self.background);
+        let text = number.to_string();
+        let text_width = calc_chars_width(&text) * font_size;
+        draw_text(
+            canvas,
+            self.text_color,
+            (
+                (center.0 as f32 - text_width * 0.5).max(0.0) as u32,
+                (center.1 as f32 - font_size * 0.5).max(0.0) as u32,
+            ),
+            Scale::uniform(font_size),
+            &self.font,
+            &text,
+            self.antialias,
+        );
+    }
+}
+
+//straight through the gap between neighbours, otherwise bent through the gap after the first cell
+fn draw_step_arrows(
+    canvas: &mut ImageBuffer<Rgba<u8>, Vec<u8>>,
+    layout: &BundleLayout,
+    style: &ArrowStyle,
+    color: Rgba<u8>,
+) {
+    let (slot_width, slot_height) = layout.slot_dimension();
+    let gap = (
+        (layout.cell_width - slot_width) as i32,
+        (layout.cell_height - slot_height) as i32,
+    );
+    //(left, top, right, bottom) of the slot at a cell
+    let slot = |(row, column): (u32, u32)| {
+        let left = (layout.margin.left + column * layout.cell_width) as i32;
+        let top = (layout.margin.top + row * layout.cell_height) as i32;
+        (
+            left,
+            top,
+            left + slot_width as i32,
+            top + slot_height as i32,
+        )
+    };
+    for pair in layout.cells.windows(2) {
+        let (from, to) = (pair[0], pair[1]);
+        let (from_left, from_top, from_right, from_bottom) = slot(from);
+        let (to_left, to_top, to_right, to_bottom) = slot(to);
+        let from_center = ((from_left + from_right) / 2, (from_top + from_bottom) / 2);
+        let to_center = ((to_left + to_right) / 2, (to_top + to_bottom) / 2);
+        let path = if from.0 == to.0 && to.1 > from.1 {
+            vec![(from_right, from_center.1), (to_left, to_center.1)]
+        } else if from.1 == to.1 && to.0 > from.0 {
+            vec![(from_center.0, from_bottom), (to_center.0, to_top)]
+        } else if to.0 > from.0 {
+            let middle = from_bottom + gap.1 / 2;
+            vec![
+                (from_center.0, from_bottom),
+                (from_center.0, middle),
+                (to_center.0, middle),
+                (to_center.0, to_top),
+            ]
+        } else {
+            let middle = from_right + gap.0 / 2;
+            vec![
+                (from_right, from_center.1),
+                (middle, from_center.1),
+                (middle, to_center.1),
+                (to_left, to_center.1),
+            ]
+        };
+        draw_arrow(canvas, &path, style, color);
+    }
+}
+
+//path legs are horizontal or vertical, the head ends exactly at the last point
+fn draw_arrow(
+    canvas: &mut ImageBuffer<Rgba<u8>, Vec<u8>>,
+    path: &[(i32, i32)],
+    style: &ArrowStyle,
+    color: Rgba<u8>,
+) {
+    let (&tip, &before) = match (path.last(), path.iter().rev().nth(1)) {
+        (Some(tip), Some(before)) => (tip, before),
+        _ => return,
+    };
+    let direction = ((tip.0 - before.0).signum(), (tip.1 - before.1).signum());
+    let head = style.head_size as i32;
+    let base = (tip.0 - direction.0 * head, tip.1 - direction.1 * head);
+    let thickness = style.thickness.max(1);
+    let half = thickness as i32 / 2;
+    let legs = path.len() - 1;
+    for (i, leg) in path.windows(2).enumerate() {
+        let end = if i + 1 == legs { base } else { leg[1] };
+        let (left, top) = (leg[0].0.min(end.0) - half, leg[0].1.min(end.1) - half);
+        let width = leg[0].0.abs_diff(end.0) + thickness;
+        let height = leg[0].1.abs_diff(end.1) + thickness;
+        draw_filled_rect_mut(canvas, Rect::at(left, top).of_size(width, height), color);
+    }
+    if head > 0 {
+        let spread = (direction.1 * head / 2, direction.0 * head / 2);
+        draw_polygon_mut(
+            canvas,
+            &[
+                Point::new(tip.0, tip.1),
+                Point::new(base.0 + spread.0, base.1 + spread.1),
+                Point::new(base.0 - spread.0, base.1 - spread.1),
+            ],
+            color,
+        );
+    }
+}
+
+//major ticks every unit with a label, minor ticks every millimeter or eighth of an inch
+fn draw_ruler(
+    canvas: &mut ImageBuffer<Rgba<u8>, Vec<u8>>,
+    ruler: &Ruler,
+    options: &CreateBundledImageOptions,
+) -> Result<(), ImageCombinerError> {
+    let antialias = options.antialias();
+    let font = Font::try_from_vec(ruler.style.font_bytes.clone())
+        .ok_or_else(|| ImageCombinerError::InvalidText("invalid font bytes".to_string()))?;
+    let (unit_length, divisions) = match ruler.unit {
+        RulerUnit::Centimeter => (ruler.pixels_per_cm, 10),
+        RulerUnit::Inch => (ruler.pixels_per_cm * 2.54, 8),
+    };
+    if unit_length.is_nan() || unit_length < 1.0 {
+        return Err(ImageCombinerError::InvalidOptions(format!(
+            "ruler needs at least one pixel per unit pixels per cm:{}",
+            ruler.pixels_per_cm
+        )));
+    }
+    let font_size = ruler.style.font_size.unwrap_or(12.0);
+    let thickness = (font_size * 2.0).ceil() as u32;
+    let (width, height) = canvas.dimensions();
+    let horizontal = matches!(ruler.edge, Edge::Top | Edge::Bottom);
+    let (length, depth) = if horizontal {
+        (width, thickness.min(height))
+    } else {
+        (height, thickness.min(width))
+    };
+    //(along, across) measured from the ruler origin and its edge to canvas coordinates
+    let to_canvas = |along: f32, across: f32| match ruler.edge {
+        Edge::Top => (along, across),
+        Edge::Bottom => (along, height as f32 - 1.0 - across),
+        Edge::Left => (across, along),
+        Edge::Right => (width as f32 - 1.0 - across, along),
+    };
+    let band = match ruler.edge {
+        Edge::Top => Rect::at(0, 0).of_size(width, depth),
+        Edge::Bottom => Rect::at(0, (height - depth) as i32).of_size(width, depth),
+        Edge::Left => Rect::at(0, 0).of_size(depth, height),
+        Edge::Right => Rect::at((width - depth) as i32, 0).of_size(depth, height),
+    };
+    draw_filled_rect_mut(
+        canvas,
+        band,
+        ruler.background.unwrap_or(options.theme.banner),
+    );
+    let step = unit_length / divisions as f32;
+    //ticks closer than two pixels would merge into a solid bar
+    let minor = step >= 2.0;
+    let mut tick = 0;
+    loop {
+        let along = tick as f32 * step;
+        if along > length as f32 - 1.0 {
+            break;
+        }
+        let major = tick % divisions == 0;
+        let tick_depth = if major {
+            depth as f32 * 0.5
+        } else if divisions % 2 == 0 && tick % (divisions / 2) == 0 {
+            depth as f32 * 0.375
+        } else {
+            depth as f32 * 0.25
+        };
+        if major || minor {
+            draw_line_segment_mut(
+                canvas,
+                to_canvas(along, 0.0),
+                to_canvas(along, tick_depth),
+                ruler.style.color,
+            );
+        }
+        if major {
+            let label = (tick / divisions).to_string();
+            let label_width = calc_chars_width(&label) * font_size;
+            //labels sit past the tip of the major tick, just after the tick itself
+            let (x, y) = to_canvas(along + 2.0, tick_depth);
+            let (x, y) = match ruler.edge {
+                Edge::Top | Edge::Left => (x, y),
+                Edge::Bottom => (x, y - font_size),
+                Edge::Right => (x - label_width, y),
+            };
+            draw_text(
+                canvas,
+                ruler.style.color,
+                (x.max(0.0) as u32, y.max(0.0) as u32),
+                Scale::uniform(font_size),
+                &font,
+                &label,
+                antialias,
+            );
+        }
+        tick += 1;
+    }
+    Ok(())
+}
+
+struct FrameRenderer {
+    padding: Padding,
+    color: Rgba<u8>,
+    captions: Vec<String>,
+    font: Option<(FontSet, Rgba<u8>, Option<f32>)>,
+}
+
+impl FrameRenderer {
+    fn new(
+        cell_style: &CellStyle,
+        color: Rgba<u8>,
+        antialias: bool,
+    ) -> Result<Option<Self>, ImageCombinerError> {
+        let CellStyle::Frame { caption, .. } = cell_style else {
+            return Ok(None);
+        };
+        let (captions, font) = match caption {
+            Some(caption) => (
+                caption.captions.clone(),
+                Some((
+                    FontSet::new(&caption.style)?.with_antialias(antialias),
+                    caption.style.color,
+                    caption.style.font_size,
+                )),
+            ),
+            None => (Vec::new(), None),
+        };
+        Ok(Some(Self {
+            padding: cell_style.frame_padding(),
+            color,
+            captions,
+            font,
+        }))
+    }
+
+    fn render<I: GenericImageView<Pixel = Rgba<u8>>>(
+        &self,
+        image: &I,
+        alignment: CellAlignment,
+        member_dimension: (u32, u32),
+        position: usize,
+    ) -> Result<ImageBuffer<Rgba<u8>, Vec<u8>>, ImageCombinerError> {
+        let padding = self.padding;
+        let width = member_dimension.0 + padding.left + padding.right;
+        let height = member_dimension.1 + padding.top + padding.bottom;
+        let mut framed = ImageBuffer::from_pixel(width, height, self.color);
+        let (buf_x, buf) = alignment.offset(
+            member_dimension.0.saturating_sub(image.width()),
+            member_dimension.1.saturating_sub(image.height()),
+        );
+        framed.copy_from(image, padding.left + buf_x, padding.top + buf)?;
+        let caption = self
+            .captions
+            .get(position)
+            .filter(|caption| !caption.is_empty());
+        if let (Some(caption), Some((font, color, font_size))) = (caption, &self.font) {
+            let band_top = padding.top + member_dimension.1;
+            let font_size = font_size.unwrap_or(padding.bottom as f32 * 0.4);
+            let left = (width as f32 - font.width(caption, Scale::uniform(font_size))) * 0.5;
+            let top = band_top as f32 + (padding.bottom as f32 - font_size) * 0.5;
+            font.draw(
+                &mut framed,
+                *color,
+                left.max(0.0) as u32,
+                top.max(0.0) as u32,
+                Scale::uniform(font_size),
+                caption,
+            );
+        }
+        Ok(framed)
+    }
+}
+
+//how each placed image is decorated, the same for every cell of a bundle
+#[derive(Clone, Copy)]
+struct CellDecoration {
+    border: Option<(u32, Rgba<u8>)>,
+    corner_radius: u32,
+    drop_shadow: Option<DropShadow>,
+    //horizontal and vertical space between neighbouring cells
+    gap: (u32, u32),
+    antialias: bool,
+}
+
+fn place_tile<I: GenericImageView<Pixel = Rgba<u8>>>(
+    canvas: &mut ImageBuffer<Rgba<u8>, Vec<u8>>,
+    image: &I,
+    alignment: CellAlignment,
+    cell: (u32, u32),
+    member_dimension: (u32, u32),
+    style: &CellDecoration,
+) -> Result<(), ImageCombinerError> {
+    let (buf_x, buf) = alignment.offset(
+        member_dimension.0.saturating_sub(image.width()),
+        member_dimension.1.saturating_sub(image.height()),
+    );
+    let (x, y) = (cell.0 + buf_x, cell.1 + buf);
+    let corner_radius = style.corner_radius;
+    if let Some(drop_shadow) = style.drop_shadow {
+        let clip = cell_clip(cell, member_dimension, style.gap);
+        draw_drop_shadow(
+            canvas,
+            (x, y),
+            image.dimensions(),
+            (corner_radius, style.antialias),
+            drop_shadow,
+            clip,
+        );
+    }
+    if corner_radius == 0 {
+        canvas.copy_from(image, x, y)?;
+    } else {
+        let (width, height) = image.dimensions();
+        if x + width > canvas.width() || y + height > canvas.height() {
+            return Err(
+                ImageError::Parameter(image::error::ParameterError::from_kind(
+                    image::error::ParameterErrorKind::DimensionMismatch,
+                ))
+                .into(),
+            );
+        }
+        for (image_x, image_y, pixel) in image.pixels() {
+            let coverage = snap_coverage(
+                corner_coverage(image_x, image_y, width, height, corner_radius),
+                style.antialias,
+            );
+            let target = canvas.get_pixel_mut(x + image_x, y + image_y);
+            *target = blend_pixel(*target, pixel, coverage);
+        }
+    }
+    if let Some((width, color)) = style.border {
+        draw_cell_border(canvas, cell, member_dimension, width, color);
+    }
+    Ok(())
+}
+
+//everything drawn for a cell stays in this (left, top, right, bottom) rect, right and bottom exclusive,
+//half of the gap on each side belongs to the cell, so the draw order of tiles does not matter
+fn cell_clip(cell: (u32, u32), dimension: (u32, u32), gap: (u32, u32)) -> (u32, u32, u32, u32) {
+    let (gap_x, gap_y) = gap;
+    (
+        cell.0.saturating_sub(gap_x / 2),
+        cell.1.saturating_sub(gap_y / 2),
+        cell.0 + dimension.0 + (gap_x - gap_x / 2),
+        cell.1 + dimension.1 + (gap_y - gap_y / 2),
+    )
+}
+
+//clip is (left, top, right, bottom) with right and bottom exclusive,
+//without antialias the shadow is the hard edged shape of the image, not blurred
+fn draw_drop_shadow(
+    canvas: &mut ImageBuffer<Rgba<u8>, Vec<u8>>,
+    position: (u32, u32),
+    dimension: (u32, u32),
+    (corner_radius, antialias): (u32, bool),
+    drop_shadow: DropShadow,
+    clip: (u32, u32, u32, u32),
+) {
+    let (width, height) = dimension;
+    let spread = drop_shadow.blur_radius * 2;
+    let mut mask = image::GrayImage::new(width + spread * 2, height + spread * 2);
+    for y in 0..height {
+        for x in 0..width {
+            let coverage = snap_coverage(
+                corner_coverage(x, y, width, height, corner_radius),
+                antialias,
+            );
+            mask.put_pixel(
+                x + spread,
+                y + spread,
+                Luma([(coverage * 255.0).round() as u8]),
+            );
+        }
+    }
+    if drop_shadow.blur_radius > 0 && antialias {
+        mask = imageproc::filter::gaussian_blur_f32(&mask, drop_shadow.blur_radius as f32 / 2.0);
+    }
+    let origin_x = position.0 as i64 + drop_shadow.offset_x as i64 - spread as i64;
+    let origin_y = position.1 as i64 + drop_shadow.offset_y as i64 - spread as i64;
+    let right = clip.2.min(canvas.width()) as i64;
+    let bottom = clip.3.min(canvas.height()) as i64;
+    for (mask_x, mask_y, value) in mask.enumerate_pixels() {
+        let (x, y) = (origin_x + mask_x as i64, origin_y + mask_y as i64);
+        if value.0[0] == 0 || x < clip.0 as i64 || y < clip.1 as i64 || x >= right || y >= bottom {
+            continue;
+        }
+        let coverage = value.0[0] as f32 / 255.0 * drop_shadow.color.0[3] as f32 / 255.0;
+        let target = canvas.get_pixel_mut(x as u32, y as u32);
+        let mut color = drop_shadow.color;
+        color.0[3] = 255;
+        *target = blend_pixel(*target, color, coverage);
+    }
+}
+
+//share of the pixel inside a rectangle whose corners are rounded by radius, for antialiased edges
+fn corner_coverage(x: u32, y: u32, width: u32, height: u32, radius: u32) -> f32 {
+    let radius = radius.min(width / 2).min(height / 2);
+    if radius == 0 {
+        return 1.0;
+    }
+    let radius = radius as f32;
+    let (px, py) = (x as f32 + 0.5, y as f32 + 0.5);
+    let cx = px.clamp(radius, width as f32 - radius);
+    let cy = py.clamp(radius, height as f32 - radius);
+    let distance = ((px - cx).powi(2) + (py - cy).powi(2)).sqrt();
+    (radius - distance + 0.5).clamp(0.0, 1.0)
+}
+
+//coverage as is with antialias, otherwise a pixel is either fully covered or not at all
+fn snap_coverage(coverage: f32, antialias: bool) -> f32 {
+    match antialias {
+        true => coverage,
+        false if coverage >= 0.5 => 1.0,
+        false => 0.0,
+    }
+}
+
+fn blend_pixel(below: Rgba<u8>, above: Rgba<u8>, coverage: f32) -> Rgba<u8> {
+    if coverage >= 1.0 {
+        return above;
+    }
+    let mut blended = below;
+    for (channel, &value) in blended.0.iter_mut().zip(above.0.iter()) {
+        *channel = (*channel as f32 + (value as f32 - *channel as f32) * coverage).round() as u8;
+    }
+    blended
+}
+
+fn round_canvas_corners(canvas: &mut ImageBuffer<Rgba<u8>, Vec<u8>>, radius: u32, antialias: bool) {
+    let (width, height) = canvas.dimensions();
+    for (x, y, pixel) in canvas.enumerate_pixels_mut() {
+        let coverage = snap_coverage(corner_coverage(x, y, width, height, radius), antialias);
+        if coverage < 1.0 {
+            pixel.0[3] = (pixel.0[3] as f32 * coverage).round() as u8;
+        }
+    }
+}
+
+//drawn inside the cell so borders of neighbouring cells never overlap
+fn draw_cell_border(
+    canvas: &mut ImageBuffer<Rgba<u8>, Vec<u8>>,
+    cell: (u32, u32),
+    member_dimension: (u32, u32),
+    width: u32,
+    color: Rgba<u8>,
+) {
+    let (cell_width, cell_height) = member_dimension;
+    let width = width.min(cell_width).min(cell_height);
+    if width == 0 {
+        return;
+    }
+    let (x, y) = (cell.0 as i32, cell.1 as i32);
+    let (right, bottom) = (
+        x + (cell_width - width) as i32,
+        y + (cell_height - width) as i32,
+    );
+    for rect in [
+        Rect::at(x, y).of_size(cell_width, width),
+        Rect::at(x, bottom).of_size(cell_width, width),
+        Rect::at(x, y).of_size(width, cell_height),
+        Rect::at(right, y).of_size(width, cell_height),
+    ] {
+        draw_filled_rect_mut(canvas, rect, color);
+    }
+}
+
+enum DrawnTile {
+    Drawn,
+    Skipped(usize),
+    Failed(DecodeFailure),
+}
+
+//returns the input indices of pending tiles that failed to decode and were skipped or replaced
+async fn draw_bundled_image(
+    image_buf_threaded: Arc<Mutex<ImageBuffer<Rgba<u8>, Vec<u8>>>>,
+    tiles: Vec<PreparedTile>,
+    layout: &BundleLayout,
+    bundled_image_canvas_y: u32,
+    options: &CreateBundledImageOptions,
+) -> Result<Vec<usize>, ImageCombinerError> {
+    let in_flight = Arc::new(Semaphore::new(in_flight_limit()));
+    let (slot_width, slot_height) = layout.slot_dimension();
+    let renderer = Arc::new(CellRenderer {
+        decoration: CellDecoration {
+            border: options.cell_border,
+            corner_radius: options.corner_radius,
+            drop_shadow: options.drop_shadow,
+            gap: (
+                layout.cell_width - slot_width,
+                layout.cell_height - slot_height,
+            ),
+            antialias: options.antialias(),
+        },
+        frame: FrameRenderer::new(
+            &options.cell_style,
+            options.theme.banner,
+            options.antialias(),
+        )?,
+        badge: options
+            .badge
+            .as_ref()
+            .map(|badge| BadgeRenderer::new(badge, options))
+            .transpose()?,
+    });
+    let mut tasks = TileTasks::new(Stage::Draw);
+    for (i, tile) in tiles.into_iter().enumerate() {
+        let cloned_image_buf = Arc::clone(&image_buf_threaded);
+        let in_flight = Arc::clone(&in_flight);
+        let worker_pool = options.worker_pool.clone();
+        let on_decode_error = options.on_decode_error;
+        let fit = options.fit;
+        let allow_upscale = options.allow_upscale;
+        let resampling = options.resampling();
+        let antialias = options.antialias();
+        let decode_options = match &tile.content {
+            TileContent::Pending(index, _) => options.decode_options(*index),
+            _ => DecodeOptions::new(options.exif_orientation),
+        };
+        let (current_row, current_column) = layout.cells[i];
+        let image_width = layout.member_width;
+        let image_height = layout.member_height;
+        let image_canvas_width = layout.cell_width;
+        let image_canvas_height = layout.cell_height;
+        let margin = layout.margin;
+        let renderer = Arc::clone(&renderer);
+        tasks.spawn(i, async move {
+            let _permit = in_flight
+                .acquire_owned()
+                .await
+                .map_err(|e| ImageCombinerError::WorkerPool(e.to_string()))?;
+            let mut drawn = DrawnTile::Drawn;
+            let cell_x = margin.left + current_column * image_canvas_width;
+            let cell_y = margin.top + current_row * image_canvas_height + bundled_image_canvas_y;
+            let member_dimension = (image_width, image_height);
+            let (image, alignment) = match tile.content {
+                TileContent::Empty => return Ok(drawn),
+                TileContent::Ready(image) => (image, tile.alignment),
+                TileContent::Spilled(spilled) => {
+                    debug!("write spilled image no {}", i);
+                    let image = spilled.map()?;
+                    let mut image_buf = cloned_image_buf.lock().await;
+                    place_member(
+                        &mut image_buf,
+                        &image,
+                        tile.alignment,
+                        (cell_x, cell_y),
+                        member_dimension,
+                        &renderer,
+                        i,
+                    )?;
+                    return Ok(drawn);
+                }
+                TileContent::Pending(index, pending) => {
+                    let resolve = move || {
+                        resolve_tile(
+                            index,
+                            pending,
+                            &decode_options,
+                            member_dimension,
+                            (fit, allow_upscale),
+                            resampling,
+                            antialias,
+                        )
+                    };
+                    let resolved = match worker_pool {
+                        Some(worker_pool) => {
+                            worker_pool.spawn(Stage::Decode, Some(i), resolve).await??
+                        }
+                        None => resolve()?,
+                    };
+                    match (resolved, on_decode_error) {
+                        (Ok(image), _) => (image, tile.alignment),
+                        (Err(failure), DecodeErrorPolicy::FailFast) => {
+                            debug!("image no {} failed to decode: {}", index + 1, failure.error);
+                            return Ok(DrawnTile::Failed(failure));
+                        }
+                        (Err(failure), DecodeErrorPolicy::Skip) => {
+                            debug!(
+                                "skip image no {} failed to decode: {}",
+                                index + 1,
+                                failure.error
+                            );
+                            return Ok(DrawnTile::Skipped(index));
+                        }
+                        (Err(failure), DecodeErrorPolicy::Placeholder) => {
+                            debug!(
+                                "placeholder for image no {} failed to decode: {}",
+                                index + 1,
+                                failure.error
+                            );
+                            drawn = DrawnTile::Skipped(index);
+                            (
+                                placeholder_image(image_width, image_height),
+                                CellAlignment::Center,
+                            )
+                        }
+                    }
+                }
+            };
+            debug!("write image no {}", i);
+            let image = image.to_rgba8();
+            let mut image_buf = cloned_image_buf.lock().await;
+            place_member(
+                &mut image_buf,
+                &image,
+                alignment,
+                (cell_x, cell_y),
+                member_dimension,
+                &renderer,
+                i,
+            )?;
+            Ok(drawn)
+        });
+    }
+
+    let mut skipped = Vec::new();
+    let mut failures = Vec::new();
+    for drawn in tasks.join_all().await? {
+        match drawn {
+            DrawnTile::Drawn => {}
+            DrawnTile::Skipped(index) => skipped.push(index),
+            DrawnTile::Failed(failure) => failures.push(failure),
+        }
+    }
+    if !failures.is_empty() {
+        failures.sort_by_key(|failure| failure.index);
+        return Err(ImageCombinerError::Decode(failures));
+    }
+    Ok(skipped)
+}
+
+fn find_optical_dimension(dimensions: &[(u32, u32)]) -> (u32, u32) {
+    let mut dimension_map: HashMap<(u32, u32), u32> = std::collections::HashMap::new();
     let mut max_dimension = (0, 0);
     let mut max_count = 0;
     let mut most_frequent_dimension = (0, 0);
-    for origin_image in origin_images {
-        let dimension_sum = origin_image.width() + origin_image.height();
+    for &(width, height) in dimensions {
+        let dimension_sum = width + height;
         if dimension_sum > (max_dimension.0 + max_dimension.1) {
-            max_dimension = (origin_image.width(), origin_image.height());
+            max_dimension = (width, height);
             debug!(
                 "update max_dimension : width:{}, height:{}",
                 max_dimension.0, max_dimension.1
             );
         }
-        let count = dimension_map
-            .entry((origin_image.width(), origin_image.height()))
-            .or_insert(0);
+        let count = dimension_map.entry((width, height)).or_insert(0);
         *count += 1;
         if *count > max_count {
-            most_frequent_dimension = (origin_image.width(), origin_image.height());
+            most_frequent_dimension = (width, height);
             max_count = *count;
             debug!(
                 "update most_frequent_dimension : width:{}, height:{} count:{}",
@@ -0,0 +1,153 @@
+use crate::tasks::TileTasks;
+use crate::{
+    decode_images, decorate_canvas, find_optical_dimension, place_tile, placeholder_image,
+    resize_member, CellDecoration, CreateBundledImageOptions, Fit, ImageCombinerError, Stage, Tile,
+};
+use image::{DynamicImage, GenericImageView, ImageBuffer};
+use log::debug;
+
+//every image keeps its aspect ratio at the column width and goes to the currently shortest column
+pub(crate) async fn compose_masonry(
+    tiles: Vec<Tile>,
+    options: &CreateBundledImageOptions,
+) -> Result<(DynamicImage, Vec<usize>), ImageCombinerError> {
+    let Some(column) = options.column.filter(|&column| column > 0) else {
+        return Err(ImageCombinerError::InvalidOptions(
+            "masonry layout needs a column count".to_string(),
+        ));
+    };
+    if options.order.is_some() || !options.reserved_cells.is_empty() {
+        return Err(ImageCombinerError::InvalidOptions(
+            "masonry layout does not support order or reserved cells".to_string(),
+        ));
+    }
+    let mut buffers = Vec::with_capacity(tiles.len());
+    for tile in tiles {
+        match tile {
+            Tile::Image(buffer) => buffers.push(buffer),
+            _ => {
+                return Err(ImageCombinerError::InvalidOptions(
+                    "masonry layout only places images".to_string(),
+                ))
+            }
+        }
+    }
+    let (images, skipped) = decode_images(buffers, options).await?;
+    let column_width = match options.dimension {
+        Some((width, _)) => width,
+        None => {
+            let dimensions = images
+                .iter()
+                .flatten()
+                .map(|i| i.dimensions())
+                .collect::<Vec<_>>();
+            if dimensions.is_empty() {
+                return Err(ImageCombinerError::InvalidOptions(
+                    "masonry layout needs at least one image".to_string(),
+                ));
+            }
+            find_optical_dimension(&dimensions).0
+        }
+    };
+    //placeholders have no aspect ratio of their own and take a square cell
+    let heights = images
+        .iter()
+        .map(|image| match image {
+            Some(image) => {
+                let (width, height) = image.dimensions();
+                ((height as u64 * column_width as u64 + width as u64 / 2) / width as u64).max(1)
+                    as u32
+            }
+            None => column_width,
+        })
+        .collect::<Vec<_>>();
+    let columns = if options.auto_shrink {
+        column.min(heights.len().max(1) as u32)
+    } else {
+        column
+    };
+    //(column, top) of every image and the height of every column with a gap after each image
+    let stack = |gap: u32| {
+        let mut column_heights = vec![0u32; columns as usize];
+        let mut slots = Vec::with_capacity(heights.len());
+        for &height in heights.iter() {
+            //the leftmost of the shortest columns keeps the order readable row by row
+            let (column, &top) = column_heights
+                .iter()
+                .enumerate()
+                .min_by_key(|&(column, &top)| (top, column))
+                .unwrap();
+            slots.push((column as u32, top));
+            column_heights[column] = top + height + gap;
+        }
+        (slots, column_heights)
+    };
+    //percent units are taken from the columns stacked without gaps
+    let (_, gapless) = stack(0);
+    let (gap, margin) = options.spacing((
+        columns * column_width,
+        gapless.into_iter().max().unwrap_or(0),
+    ));
+    let (slots, column_heights) = stack(gap);
+    let origins: Vec<(u32, u32)> = slots
+        .into_iter()
+        .map(|(column, top)| {
+            (
+                margin.left + column * (column_width + gap),
+                margin.top + top,
+            )
+        })
+        .collect();
+    let canvas_width = columns * column_width + (columns - 1) * gap + margin.left + margin.right;
+    let canvas_height =
+        column_heights.iter().max().unwrap().saturating_sub(gap) + margin.top + margin.bottom;
+    debug!(
+        "create masonry image buf {}x{}",
+        canvas_width, canvas_height
+    );
+    let mut tasks = TileTasks::new(Stage::Resize);
+    for (i, (image, &height)) in images.into_iter().zip(heights.iter()).enumerate() {
+        let resampling = options.resampling();
+        let allow_upscale = options.allow_upscale;
+        tasks.spawn(i, async move {
+            Ok(match image {
+                Some(image) => resize_member(
+                    image,
+                    i,
+                    column_width,
+                    height,
+                    Fit::Stretch,
+                    allow_upscale,
+                    resampling,
+                ),
+                None => placeholder_image(column_width, height),
+            })
+        });
+    }
+    let mut canvas = ImageBuffer::from_pixel(canvas_width, canvas_height, options.background);
+    let decoration = CellDecoration {
+        border: options.cell_border,
+        corner_radius: options.corner_radius,
+        drop_shadow: options.drop_shadow,
+        antialias: options.antialias(),
+        gap: (gap, gap),
+    };
+    for ((image, origin), &height) in tasks
+        .join_all()
+        .await?
+        .into_iter()
+        .zip(origins)
+        .zip(heights.iter())
+    {
+        place_tile(
+            &mut canvas,
+            &image.to_rgba8(),
+            options.cell_alignment,
+            origin,
+            (column_width, height),
+            &decoration,
+        )?;
+    }
+    decorate_canvas(&mut canvas, options)?;
+    Ok((DynamicImage::ImageRgba8(canvas), skipped))
+}
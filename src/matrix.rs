@@ -0,0 +1,198 @@
+use crate::fonts::FontSet;
+use crate::tasks::TileTasks;
+use crate::{
+    decode_images, decorate_canvas, find_optical_dimension, finish_bundle, place_tile,
+    placeholder_image, resize_member, unsupported_max_canvas, BundledImage, CellDecoration,
+    CreateBundledImageOptions, DecodeErrorPolicy, ImageCombinerError, Stage, TextCardStyle,
+};
+use image::{DynamicImage, GenericImageView, ImageBuffer, Rgba};
+use log::debug;
+use rusttype::Scale;
+
+//labels of a comparison matrix, an empty list leaves out that band
+pub struct MatrixLabels {
+    rows: Vec<String>,
+    columns: Vec<String>,
+    style: TextCardStyle,
+    themed: bool,
+}
+
+impl MatrixLabels {
+    pub fn new(rows: Vec<String>, columns: Vec<String>, style: TextCardStyle) -> Self {
+        Self {
+            rows,
+            columns,
+            style,
+            themed: false,
+        }
+    }
+
+    //labels in the text color of the options theme instead of the style color
+    pub fn themed(rows: Vec<String>, columns: Vec<String>, style: TextCardStyle) -> Self {
+        Self {
+            rows,
+            columns,
+            style,
+            themed: true,
+        }
+    }
+
+    fn color(&self, options: &CreateBundledImageOptions) -> Rgba<u8> {
+        match self.themed {
+            true => options.theme.text,
+            false => self.style.color,
+        }
+    }
+}
+
+//images[row][column] in a grid with the row labels along the left edge and the column labels along the top,
+//shorter rows leave their last cells blank
+pub async fn create_labeled_matrix(
+    images: Vec<Vec<Vec<u8>>>,
+    labels: MatrixLabels,
+    options: CreateBundledImageOptions,
+) -> Result<BundledImage, ImageCombinerError> {
+    if options.max_canvas.is_some() {
+        return Err(unsupported_max_canvas("matrix"));
+    }
+    let rows = images.len() as u32;
+    let columns = images.iter().map(Vec::len).max().unwrap_or(0) as u32;
+    if rows == 0 || columns == 0 {
+        return Err(ImageCombinerError::InvalidOptions(
+            "matrix needs at least one image".to_string(),
+        ));
+    }
+    if (!labels.rows.is_empty() && labels.rows.len() as u32 != rows)
+        || (!labels.columns.is_empty() && labels.columns.len() as u32 != columns)
+    {
+        return Err(ImageCombinerError::InvalidOptions(format!(
+            "matrix labels do not match the grid rows:{},row labels:{},columns:{},column labels:{}",
+            rows,
+            labels.rows.len(),
+            columns,
+            labels.columns.len()
+        )));
+    }
+    let font = FontSet::new(&labels.style)?.with_antialias(options.antialias());
+    let cells: Vec<(u32, u32)> = images
+        .iter()
+        .enumerate()
+        .flat_map(|(row, images)| (0..images.len()).map(move |column| (row as u32, column as u32)))
+        .collect();
+    let (images, skipped) = decode_images(images.into_iter().flatten().collect(), &options).await?;
+    //skipped images leave their cell blank, placeholders keep theirs
+    let dropped = matches!(options.on_decode_error, DecodeErrorPolicy::Skip);
+    let cells: Vec<(u32, u32)> = cells
+        .into_iter()
+        .enumerate()
+        .filter(|(index, _)| !(dropped && skipped.contains(index)))
+        .map(|(_, cell)| cell)
+        .collect();
+    let (member_width, member_height) = options.dimension.unwrap_or_else(|| {
+        find_optical_dimension(
+            &images
+                .iter()
+                .flatten()
+                .map(|image| image.dimensions())
+                .collect::<Vec<_>>(),
+        )
+    });
+    let font_size = labels
+        .style
+        .font_size
+        .unwrap_or(member_height as f32 * 0.1)
+        .max(1.0);
+    let label_width = |label: &str| font.width(label, Scale::uniform(font_size));
+    let left_band = labels
+        .rows
+        .iter()
+        .map(|label| (label_width(label) + font_size).ceil() as u32)
+        .max()
+        .unwrap_or(0);
+    let top_band = if labels.columns.is_empty() {
+        0
+    } else {
+        (font_size * 2.0).ceil() as u32
+    };
+    let (gap, margin) = options.spacing((columns * member_width, rows * member_height));
+    let grid_left = margin.left + left_band + if left_band > 0 { gap } else { 0 };
+    let grid_top = margin.top + top_band + if top_band > 0 { gap } else { 0 };
+    let width = grid_left + columns * member_width + (columns - 1) * gap + margin.right;
+    let height = grid_top + rows * member_height + (rows - 1) * gap + margin.bottom;
+    debug!("create matrix image buf {}x{}", width, height);
+    let mut tasks = TileTasks::new(Stage::Resize);
+    for (i, image) in images.into_iter().enumerate() {
+        let fit = options.fit;
+        let allow_upscale = options.allow_upscale;
+        let resampling = options.resampling();
+        tasks.spawn(i, async move {
+            Ok(match image {
+                Some(image) => resize_member(
+                    image,
+                    i,
+                    member_width,
+                    member_height,
+                    fit,
+                    allow_upscale,
+                    resampling,
+                ),
+                None => placeholder_image(member_width, member_height),
+            })
+        });
+    }
+    let mut canvas = ImageBuffer::from_pixel(width, height, options.background);
+    let decoration = CellDecoration {
+        border: options.cell_border,
+        corner_radius: options.corner_radius,
+        drop_shadow: options.drop_shadow,
+        antialias: options.antialias(),
+        gap: (gap, gap),
+    };
+    let cell_origin = |row: u32, column: u32| {
+        (
+            grid_left + column * (member_width + gap),
+            grid_top + row * (member_height + gap),
+        )
+    };
+    for (image, (row, column)) in tasks.join_all().await?.into_iter().zip(cells) {
+        place_tile(
+            &mut canvas,
+            &image.to_rgba8(),
+            options.cell_alignment,
+            cell_origin(row, column),
+            (member_width, member_height),
+            &decoration,
+        )?;
+    }
+    //labels are centered on their row or column
+    let scale = Scale::uniform(font_size);
+    let color = labels.color(&options);
+    for (row, label) in labels.rows.iter().enumerate() {
+        let (_, top) = cell_origin(row as u32, 0);
+        let left = margin.left as f32 + (left_band as f32 - label_width(label)) * 0.5;
+        let top = top as f32 + (member_height as f32 - font_size) * 0.5;
+        font.draw(
+            &mut canvas,
+            color,
+            left.max(0.0) as u32,
+            top.max(0.0) as u32,
+            scale,
+            label,
+        );
+    }
+    for (column, label) in labels.columns.iter().enumerate() {
+        let (left, _) = cell_origin(0, column as u32);
+        let left = left as f32 + (member_width as f32 - label_width(label)) * 0.5;
+        let top = margin.top as f32 + (top_band as f32 - font_size) * 0.5;
+        font.draw(
+            &mut canvas,
+            color,
+            left.max(0.0) as u32,
+            top.max(0.0) as u32,
+            scale,
+            label,
+        );
+    }
+    decorate_canvas(&mut canvas, &options)?;
+    finish_bundle(DynamicImage::ImageRgba8(canvas), skipped, &options).await
+}
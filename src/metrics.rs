@@ -0,0 +1,133 @@
+use crate::{ImageCombinerError, Stage};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+use std::sync::Mutex;
+use std::time::Duration;
+
+//receives what a render did, set with set_metrics on the options,
+//stage latencies are reported for the stages a worker pool runs
+pub trait MetricsExporter: Send + Sync {
+    fn job_finished(&self, elapsed: Duration, error: Option<&ImageCombinerError>);
+
+    fn stage_finished(&self, stage: Stage, elapsed: Duration);
+}
+
+//upper bounds in seconds of the latency histogram buckets
+const LATENCY_BUCKETS: [f64; 11] = [
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0,
+];
+
+#[derive(Default)]
+struct Histogram {
+    //cumulative count per bucket, the last one is +Inf
+    buckets: [u64; LATENCY_BUCKETS.len() + 1],
+    sum: f64,
+    count: u64,
+}
+
+impl Histogram {
+    fn observe(&mut self, elapsed: Duration) {
+        let seconds = elapsed.as_secs_f64();
+        let first = LATENCY_BUCKETS
+            .iter()
+            .position(|bound| seconds <= *bound)
+            .unwrap_or(LATENCY_BUCKETS.len());
+        for bucket in self.buckets[first..].iter_mut() {
+            *bucket += 1;
+        }
+        self.sum += seconds;
+        self.count += 1;
+    }
+
+    fn write(&self, out: &mut String, name: &str, labels: &str) {
+        let bounds = LATENCY_BUCKETS
+            .iter()
+            .map(|bound| bound.to_string())
+            .chain(std::iter::once("+Inf".to_string()));
+        for (bound, count) in bounds.zip(self.buckets.iter()) {
+            let _ = writeln!(
+                out,
+                "{}_bucket{{{}le=\"{}\"}} {}",
+                name, labels, bound, count
+            );
+        }
+        let labels = match labels.trim_end_matches(',') {
+            "" => String::new(),
+            labels => format!("{{{}}}", labels),
+        };
+        let _ = writeln!(out, "{}_sum{} {}", name, labels, self.sum);
+        let _ = writeln!(out, "{}_count{} {}", name, labels, self.count);
+    }
+}
+
+#[derive(Default)]
+struct Counters {
+    jobs: u64,
+    job_latency: Histogram,
+    errors: BTreeMap<&'static str, u64>,
+    stages: BTreeMap<&'static str, Histogram>,
+}
+
+//keeps the metrics in memory and renders them in the prometheus text format for a scrape endpoint
+#[derive(Default)]
+pub struct PrometheusExporter {
+    counters: Mutex<Counters>,
+}
+
+impl PrometheusExporter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn render(&self) -> String {
+        let counters = self.counters.lock().unwrap_or_else(|e| e.into_inner());
+        let mut out = String::new();
+        out.push_str("# HELP image_combiner_jobs_total Renders finished, including failed ones.\n");
+        out.push_str("# TYPE image_combiner_jobs_total counter\n");
+        let _ = writeln!(out, "image_combiner_jobs_total {}", counters.jobs);
+        out.push_str("# HELP image_combiner_job_seconds Time a render took.\n");
+        out.push_str("# TYPE image_combiner_job_seconds histogram\n");
+        counters
+            .job_latency
+            .write(&mut out, "image_combiner_job_seconds", "");
+        out.push_str("# HELP image_combiner_errors_total Failed renders by error code.\n");
+        out.push_str("# TYPE image_combiner_errors_total counter\n");
+        for (code, count) in counters.errors.iter() {
+            let _ = writeln!(
+                out,
+                "image_combiner_errors_total{{code=\"{}\"}} {}",
+                code, count
+            );
+        }
+        out.push_str("# HELP image_combiner_stage_seconds Time a worker pool job took by stage.\n");
+        out.push_str("# TYPE image_combiner_stage_seconds histogram\n");
+        for (stage, histogram) in counters.stages.iter() {
+            histogram.write(
+                &mut out,
+                "image_combiner_stage_seconds",
+                &format!("stage=\"{}\",", stage),
+            );
+        }
+        out
+    }
+}
+
+impl MetricsExporter for PrometheusExporter {
+    fn job_finished(&self, elapsed: Duration, error: Option<&ImageCombinerError>) {
+        let mut counters = self.counters.lock().unwrap_or_else(|e| e.into_inner());
+        counters.jobs += 1;
+        counters.job_latency.observe(elapsed);
+        if let Some(error) = error {
+            *counters.errors.entry(error.code()).or_insert(0) += 1;
+        }
+    }
+
+    fn stage_finished(&self, stage: Stage, elapsed: Duration) {
+        let mut counters = self.counters.lock().unwrap_or_else(|e| e.into_inner());
+        counters
+            .stages
+            .entry(stage.name())
+            .or_default()
+            .observe(elapsed);
+    }
+}
@@ -0,0 +1,134 @@
+use crate::{
+    decode, CreateBundledImageOptions, DecodeOptions, ImageCombinerError, OverlayPosition,
+};
+use image::{GenericImageView, ImageBuffer, Rgba};
+
+//how an overlay pixel combines with the canvas below it, as in design tools
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum BlendMode {
+    Normal,
+    //darkens, white in the overlay leaves the canvas unchanged
+    Multiply,
+    //lightens, black in the overlay leaves the canvas unchanged
+    Screen,
+    //multiply in the shadows and screen in the highlights of the canvas
+    Overlay,
+}
+
+impl BlendMode {
+    fn blend(self, below: f32, above: f32) -> f32 {
+        match self {
+            BlendMode::Normal => above,
+            BlendMode::Multiply => below * above,
+            BlendMode::Screen => 1.0 - (1.0 - below) * (1.0 - above),
+            BlendMode::Overlay => {
+                if below < 0.5 {
+                    2.0 * below * above
+                } else {
+                    1.0 - 2.0 * (1.0 - below) * (1.0 - above)
+                }
+            }
+        }
+    }
+}
+
+//an image composited over the finished canvas, such as a watermark or a paper texture
+#[derive(Clone)]
+pub struct OverlayLayer {
+    image: Vec<u8>,
+    blend_mode: BlendMode,
+    position: OverlayPosition,
+    opacity: u8,
+    tiled: bool,
+}
+
+impl OverlayLayer {
+    pub fn new(image: Vec<u8>, blend_mode: BlendMode) -> Self {
+        Self {
+            image,
+            blend_mode,
+            position: OverlayPosition::new(crate::Anchor::Center),
+            opacity: 255,
+            tiled: false,
+        }
+    }
+
+    pub fn with_position(mut self, position: OverlayPosition) -> Self {
+        self.position = position;
+        self
+    }
+
+    //multiplies the alpha of the overlay image
+    pub fn with_opacity(mut self, opacity: u8) -> Self {
+        self.opacity = opacity;
+        self
+    }
+
+    //repeat the image over the whole canvas from the top left, the position is ignored
+    pub fn with_tiled(mut self, tiled: bool) -> Self {
+        self.tiled = tiled;
+        self
+    }
+
+    pub(crate) fn fingerprint(&self) -> String {
+        format!(
+            "{:?}|{:?}|{}|{}",
+            self.blend_mode, self.position, self.opacity, self.tiled
+        )
+    }
+
+    pub(crate) fn image(&self) -> &[u8] {
+        &self.image
+    }
+}
+
+//layers in order, each over the result of the previous ones
+pub(crate) fn composite_layers(
+    canvas: &mut ImageBuffer<Rgba<u8>, Vec<u8>>,
+    options: &CreateBundledImageOptions,
+) -> Result<(), ImageCombinerError> {
+    for layer in options.overlays.iter() {
+        let image = decode(&layer.image, &DecodeOptions::new(options.exif_orientation))?;
+        let image = image.to_rgba8();
+        let (width, height) = canvas.dimensions();
+        if layer.tiled {
+            for top in (0..height).step_by(image.height().max(1) as usize) {
+                for left in (0..width).step_by(image.width().max(1) as usize) {
+                    composite(canvas, &image, (left, top), layer);
+                }
+            }
+        } else {
+            let at = layer.position.place((width, height), image.dimensions());
+            composite(canvas, &image, at, layer);
+        }
+    }
+    Ok(())
+}
+
+//clipped to the canvas, alpha is combined like normal alpha compositing
+fn composite(
+    canvas: &mut ImageBuffer<Rgba<u8>, Vec<u8>>,
+    image: &ImageBuffer<Rgba<u8>, Vec<u8>>,
+    (left, top): (u32, u32),
+    layer: &OverlayLayer,
+) {
+    let opacity = layer.opacity as f32 / 255.0;
+    for (x, y, above) in image.enumerate_pixels() {
+        let (canvas_x, canvas_y) = (left + x, top + y);
+        if !canvas.in_bounds(canvas_x, canvas_y) {
+            continue;
+        }
+        let alpha = above[3] as f32 / 255.0 * opacity;
+        if alpha <= 0.0 {
+            continue;
+        }
+        let below = canvas.get_pixel_mut(canvas_x, canvas_y);
+        for channel in 0..3 {
+            let b = below[channel] as f32 / 255.0;
+            let blended = layer.blend_mode.blend(b, above[channel] as f32 / 255.0);
+            below[channel] = ((b + (blended - b) * alpha) * 255.0).round() as u8;
+        }
+        let below_alpha = below[3] as f32 / 255.0;
+        below[3] = ((alpha + below_alpha * (1.0 - alpha)) * 255.0).round() as u8;
+    }
+}
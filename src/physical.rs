@@ -0,0 +1,171 @@
+use crate::codec::decoded_dpi;
+use crate::tasks::TileTasks;
+use crate::{
+    apply_order, decode, fit_logo, limit_member_dimension, placeholder_image, plan_layout,
+    render_text_card, resize_member, CellAlignment, CreateBundledImageOptions, DecodeErrorPolicy,
+    DecodeFailure, Fit, ImageCombinerError, PreparedBundle, PreparedTile, Stage, TextCardStyle,
+    Tile, TileContent,
+};
+use image::{DynamicImage, GenericImageView, Rgba};
+use log::debug;
+
+//assumed for inputs that carry no resolution, the usual default of image editors
+const DEFAULT_DPI: f32 = 72.0;
+
+enum PhysicalTile {
+    //decoded image and its size in inches
+    Image(DynamicImage, (f32, f32)),
+    Logo(DynamicImage, Fit),
+    Placeholder,
+    Empty,
+    TextCard {
+        text: String,
+        style: TextCardStyle,
+        background: Rgba<u8>,
+    },
+}
+
+//every image is drawn at one common pixels per inch so their sizes on the sheet compare physically,
+//without a member dimension the cell is the physically largest image at the highest input dpi
+pub(crate) async fn prepare_physical(
+    tiles: Vec<Tile>,
+    options: &CreateBundledImageOptions,
+) -> Result<PreparedBundle, ImageCombinerError> {
+    let mut tiles: Vec<(usize, Tile)> = tiles.into_iter().enumerate().collect();
+    if let Some(order) = &options.order {
+        tiles = apply_order(tiles, order)?;
+    }
+    let mut tasks = TileTasks::new(Stage::Decode);
+    for (position, (index, tile)) in tiles.into_iter().enumerate() {
+        let decode_options = options.decode_options(index);
+        tasks.spawn(position, async move {
+            let failure = |size: usize| move |error| DecodeFailure { index, size, error };
+            Ok(match tile {
+                Tile::Image(buffer) => decode(&buffer, &decode_options)
+                    .map(|image| {
+                        let (dpi_x, dpi_y) = decoded_dpi(&buffer, &decode_options)
+                            .unwrap_or((DEFAULT_DPI, DEFAULT_DPI));
+                        let inches = (image.width() as f32 / dpi_x, image.height() as f32 / dpi_y);
+                        PhysicalTile::Image(image, inches)
+                    })
+                    .map_err(failure(buffer.len())),
+                Tile::Logo(buffer, fit) => decode(&buffer, &decode_options.for_logo())
+                    .map(|logo| PhysicalTile::Logo(logo, fit))
+                    .map_err(failure(buffer.len())),
+                Tile::TextCard {
+                    text,
+                    style,
+                    background,
+                } => Ok(PhysicalTile::TextCard {
+                    text,
+                    style,
+                    background,
+                }),
+                Tile::Empty => Ok(PhysicalTile::Empty),
+                Tile::Placeholder => Ok(PhysicalTile::Placeholder),
+            })
+        });
+    }
+    let mut decoded = Vec::new();
+    let mut skipped = Vec::new();
+    let mut failures = Vec::new();
+    for tile in tasks.join_all().await? {
+        match tile {
+            Ok(tile) => decoded.push(tile),
+            Err(failure) => match options.on_decode_error {
+                DecodeErrorPolicy::FailFast => failures.push(failure),
+                DecodeErrorPolicy::Skip => skipped.push(failure.index),
+                DecodeErrorPolicy::Placeholder => {
+                    skipped.push(failure.index);
+                    decoded.push(PhysicalTile::Placeholder);
+                }
+            },
+        }
+    }
+    if !failures.is_empty() {
+        failures.sort_by_key(|failure| failure.index);
+        return Err(ImageCombinerError::Decode(failures));
+    }
+    //the highest input dpi keeps the sharpest image at its native resolution
+    let mut dpi = 0f32;
+    let mut largest = (0f32, 0f32);
+    for tile in decoded.iter() {
+        if let PhysicalTile::Image(image, (width, height)) = tile {
+            dpi = dpi.max(image.width() as f32 / width);
+            largest = (largest.0.max(*width), largest.1.max(*height));
+        }
+    }
+    if dpi == 0.0 {
+        return Err(ImageCombinerError::InvalidOptions(
+            "physical scale needs at least one image".to_string(),
+        ));
+    }
+    let natural = (
+        (largest.0 * dpi).round().max(1.0) as u32,
+        (largest.1 * dpi).round().max(1.0) as u32,
+    );
+    let member_dimension =
+        limit_member_dimension(decoded.len(), options.dimension.unwrap_or(natural), options)?;
+    //pixels per inch on the sheet, the largest image just fits its cell
+    let scale = (member_dimension.0 as f32 / largest.0).min(member_dimension.1 as f32 / largest.1);
+    debug!("physical scale {} px per inch", scale);
+    let (width, height) = member_dimension;
+    let mut tasks = TileTasks::new(Stage::Resize);
+    for (i, tile) in decoded.into_iter().enumerate() {
+        let resampling = options.resampling();
+        let allow_upscale = options.allow_upscale;
+        let antialias = options.antialias();
+        let cell_alignment = options.cell_alignment;
+        tasks.spawn(i, async move {
+            let (image, alignment) = match tile {
+                PhysicalTile::Empty => {
+                    return Ok(PreparedTile {
+                        content: TileContent::Empty,
+                        alignment: CellAlignment::Center,
+                    })
+                }
+                PhysicalTile::Image(image, inches) => {
+                    let target = (
+                        ((inches.0 * scale).round() as u32).clamp(1, width),
+                        ((inches.1 * scale).round() as u32).clamp(1, height),
+                    );
+                    let image = resize_member(
+                        image,
+                        i,
+                        target.0,
+                        target.1,
+                        Fit::Stretch,
+                        allow_upscale,
+                        resampling,
+                    );
+                    (image, cell_alignment)
+                }
+                PhysicalTile::Logo(logo, fit) => {
+                    (fit_logo(logo, width, height, fit), CellAlignment::Center)
+                }
+                PhysicalTile::Placeholder => {
+                    (placeholder_image(width, height), CellAlignment::Center)
+                }
+                PhysicalTile::TextCard {
+                    text,
+                    style,
+                    background,
+                } => (
+                    render_text_card(&text, style, background, width, height, antialias)?,
+                    CellAlignment::Center,
+                ),
+            };
+            Ok(PreparedTile {
+                content: TileContent::Ready(image),
+                alignment,
+            })
+        });
+    }
+    let prepared = tasks.join_all().await?;
+    let layout = plan_layout(prepared.len(), member_dimension, options)?;
+    Ok(PreparedBundle {
+        tiles: prepared,
+        layout,
+        skipped,
+    })
+}
@@ -0,0 +1,62 @@
+use crate::tasks::panic_message;
+use crate::{ImageCombinerError, MetricsExporter, Stage};
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::Arc;
+
+#[derive(Clone)]
+pub struct WorkerPool {
+    pool: Arc<rayon::ThreadPool>,
+    metrics: Option<Arc<dyn MetricsExporter>>,
+}
+
+impl WorkerPool {
+    pub fn new(blocking_threads: usize) -> Result<Self, ImageCombinerError> {
+        let pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(blocking_threads)
+            .thread_name(|i| format!("image-combiner-worker-{}", i))
+            .build()
+            .map_err(|e| ImageCombinerError::WorkerPool(e.to_string()))?;
+        Ok(Self {
+            pool: Arc::new(pool),
+            metrics: None,
+        })
+    }
+
+    pub(crate) fn with_metrics(mut self, metrics: Arc<dyn MetricsExporter>) -> Self {
+        self.metrics = Some(metrics);
+        self
+    }
+
+    //submits the job right away, the returned future only waits for its result
+    pub(crate) fn spawn<F, R>(
+        &self,
+        stage: Stage,
+        image_index: Option<usize>,
+        job: F,
+    ) -> impl Future<Output = Result<R, ImageCombinerError>>
+    where
+        F: FnOnce() -> R + Send + 'static,
+        R: Send + 'static,
+    {
+        let (tx, rx) = tokio::sync::oneshot::channel();
+        let metrics = self.metrics.clone();
+        self.pool.spawn(move || {
+            let started = std::time::Instant::now();
+            let result = std::panic::catch_unwind(AssertUnwindSafe(job));
+            if let Some(metrics) = metrics {
+                metrics.stage_finished(stage, started.elapsed());
+            }
+            let _ = tx.send(result);
+        });
+        async move {
+            rx.await
+                .map_err(|e| ImageCombinerError::WorkerPool(e.to_string()))?
+                .map_err(|payload| ImageCombinerError::InternalPanic {
+                    stage,
+                    image_index,
+                    message: panic_message(payload),
+                })
+        }
+    }
+}
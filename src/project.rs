@@ -0,0 +1,576 @@
+use crate::cache::sha256_hex;
+use crate::{
+    create_bundled_image, Anchor, ArrowStyle, BlendMode, BundledImage, CellAlignment,
+    CreateBundledImageOptions, CreateBundledImageOptionsBuilder, DecodeErrorPolicy, DropShadow,
+    FileImageSource, FillOrder, Fit, ImageCombinerError, LayoutMode, Margin, OutputFormat,
+    OverlayLayer, OverlayPosition, Preset, RenderQuality, SortOrder, Theme, Tile, TileLayer,
+    Transform, Unit,
+};
+use image::Rgba;
+use log::debug;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Map, Value};
+use std::path::{Path, PathBuf};
+
+const PROJECT_VERSION: u64 = 1;
+
+//an image file composited over the canvas, read again every time the project renders
+#[derive(Debug, Clone, PartialEq)]
+pub struct ProjectOverlay {
+    pub path: PathBuf,
+    pub blend_mode: BlendMode,
+    pub position: OverlayPosition,
+    pub opacity: u8,
+    pub tiled: bool,
+}
+
+impl ProjectOverlay {
+    pub fn new(path: PathBuf, blend_mode: BlendMode) -> Self {
+        Self {
+            path,
+            blend_mode,
+            position: OverlayPosition::new(Anchor::Center),
+            opacity: 255,
+            tiled: false,
+        }
+    }
+}
+
+//a render that keeps what render_incremental needs to redraw only the inputs that changed
+pub struct ProjectRender {
+    pub output: BundledImage,
+    //inputs drawn by this render, every input when nothing could be reused
+    pub redrawn_inputs: Vec<usize>,
+    //sha-256 of each input file, so an input edited in place counts as changed
+    input_hashes: Vec<String>,
+    options: Value,
+    layer: Option<TileLayer>,
+}
+
+//a composition saved as a .icproj JSON file, inputs and overlays are kept as paths,
+//options holding fonts or other bytes (text styles, captions, badges, rulers, bands) are not saved
+pub struct Project {
+    pub inputs: Vec<PathBuf>,
+    pub overlays: Vec<ProjectOverlay>,
+    pub options: CreateBundledImageOptionsBuilder,
+}
+
+impl Project {
+    pub fn new(inputs: Vec<PathBuf>, options: CreateBundledImageOptionsBuilder) -> Self {
+        Self {
+            inputs,
+            overlays: Vec::new(),
+            options,
+        }
+    }
+
+    pub fn with_overlay(mut self, overlay: ProjectOverlay) -> Self {
+        self.overlays.push(overlay);
+        self
+    }
+
+    pub fn to_json(&self) -> String {
+        let overlays: Vec<Value> = self
+            .overlays
+            .iter()
+            .map(|overlay| {
+                json!({
+                    "path": overlay.path,
+                    "blend_mode": overlay.blend_mode.name(),
+                    "anchor": overlay.position.anchor.name(),
+                    "offset": [overlay.position.offset_x, overlay.position.offset_y],
+                    "opacity": overlay.opacity,
+                    "tiled": overlay.tiled,
+                })
+            })
+            .collect();
+        let project = json!({
+            "version": PROJECT_VERSION,
+            "inputs": self.inputs,
+            "overlays": overlays,
+            "options": options_to_json(&self.options),
+        });
+        serde_json::to_string_pretty(&project).expect("a json value always serializes")
+    }
+
+    pub fn from_json(json: &str) -> Result<Self, ImageCombinerError> {
+        let project: Value = serde_json::from_str(json)
+            .map_err(|e| invalid(format!("project is not json {}", e)))?;
+        let version = project["version"].as_u64().unwrap_or(0);
+        if version != PROJECT_VERSION {
+            return Err(invalid(format!("unsupported project version:{}", version)));
+        }
+        let inputs = project["inputs"]
+            .as_array()
+            .ok_or_else(|| invalid("project has no inputs".to_string()))?
+            .iter()
+            .map(|input| path(input, "inputs"))
+            .collect::<Result<_, _>>()?;
+        let overlays = match project["overlays"].as_array() {
+            Some(overlays) => overlays
+                .iter()
+                .map(overlay_from_json)
+                .collect::<Result<_, _>>()?,
+            None => Vec::new(),
+        };
+        let options = match project["options"].as_object() {
+            Some(options) => options_from_json(options)?,
+            None => CreateBundledImageOptionsBuilder::new(),
+        };
+        Ok(Self {
+            inputs,
+            overlays,
+            options,
+        })
+    }
+
+    pub async fn save(&self, path: impl AsRef<Path>) -> Result<(), ImageCombinerError> {
+        tokio::fs::write(path, self.to_json()).await?;
+        Ok(())
+    }
+
+    pub async fn load(path: impl AsRef<Path>) -> Result<Self, ImageCombinerError> {
+        Self::from_json(&tokio::fs::read_to_string(path).await?)
+    }
+
+    pub async fn render(&self) -> Result<BundledImage, ImageCombinerError> {
+        let options = self.render_options().await?;
+        create_bundled_image(FileImageSource::new(self.inputs.clone()), options).await
+    }
+
+    //renders again reusing the tile layer of the previous render, only inputs whose content changed are decoded
+    //and drawn, the tile layer is the only part reused, overlays, bands and the other canvas decorations
+    //are always drawn again over it, a change in the saved options, the input count or the grid draws everything
+    pub async fn render_incremental(
+        &self,
+        previous: Option<&ProjectRender>,
+    ) -> Result<ProjectRender, ImageCombinerError> {
+        let options = self.render_options().await?;
+        let saved_options = options_to_json(&self.options);
+        let every_input: Vec<usize> = (0..self.inputs.len()).collect();
+        let mut buffers = Vec::new();
+        let mut input_hashes = Vec::new();
+        for input in self.inputs.iter() {
+            let bytes = tokio::fs::read(input).await?;
+            input_hashes.push(sha256_hex(&bytes));
+            buffers.push(bytes);
+        }
+        if !options.keeps_input_cells() {
+            debug!("layout can not be redrawn in part, render the whole project");
+            return Ok(ProjectRender {
+                output: create_bundled_image(buffers, options).await?,
+                redrawn_inputs: every_input,
+                input_hashes,
+                options: saved_options,
+                layer: None,
+            });
+        }
+        let tiles = buffers.into_iter().map(Tile::Image).collect();
+        let reusable = previous.and_then(|previous| {
+            let layer = previous.layer.as_ref()?;
+            (previous.options == saved_options && previous.input_hashes.len() == input_hashes.len())
+                .then_some((previous, layer))
+        });
+        let (layer, redrawn_inputs) = match reusable {
+            Some((previous, layer)) => {
+                let changed: Vec<usize> = input_hashes
+                    .iter()
+                    .zip(previous.input_hashes.iter())
+                    .enumerate()
+                    .filter(|(_, (hash, previous))| hash != previous)
+                    .map(|(index, _)| index)
+                    .collect();
+                match layer.redraw(tiles, &changed, &options).await? {
+                    (layer, true) => (layer, changed),
+                    (layer, false) => (layer, every_input),
+                }
+            }
+            None => (TileLayer::render(tiles, &options).await?, every_input),
+        };
+        Ok(ProjectRender {
+            output: layer.finish(&options).await?,
+            redrawn_inputs,
+            input_hashes,
+            options: saved_options,
+            layer: Some(layer),
+        })
+    }
+
+    async fn render_options(&self) -> Result<CreateBundledImageOptions, ImageCombinerError> {
+        let mut layers = Vec::new();
+        for overlay in self.overlays.iter() {
+            layers.push(
+                OverlayLayer::new(tokio::fs::read(&overlay.path).await?, overlay.blend_mode)
+                    .with_position(overlay.position)
+                    .with_opacity(overlay.opacity)
+                    .with_tiled(overlay.tiled),
+            );
+        }
+        let mut options = self.options.build()?;
+        options.overlays.extend(layers);
+        Ok(options)
+    }
+}
+
+fn invalid(message: String) -> ImageCombinerError {
+    ImageCombinerError::InvalidOptions(message)
+}
+
+//unit variants are saved by name
+trait Named: Sized + Copy + 'static {
+    const ALL: &'static [(&'static str, Self)];
+
+    fn name(&self) -> &'static str
+    where
+        Self: PartialEq,
+    {
+        Self::ALL
+            .iter()
+            .find(|(_, value)| value == self)
+            .map_or("", |(name, _)| name)
+    }
+
+    fn parse(value: &Value, key: &str) -> Result<Self, ImageCombinerError> {
+        let name = value.as_str().unwrap_or_default();
+        Self::ALL
+            .iter()
+            .find(|(n, _)| *n == name)
+            .map(|(_, value)| *value)
+            .ok_or_else(|| invalid(format!("unknown {} in project:{}", key, value)))
+    }
+}
+
+macro_rules! named {
+    ($ty:ident { $($variant:ident),* $(,)? }) => {
+        impl Named for $ty {
+            const ALL: &'static [(&'static str, Self)] = &[$((stringify!($variant), $ty::$variant)),*];
+        }
+    };
+}
+
+named!(CellAlignment {
+    TopLeft,
+    Top,
+    TopRight,
+    Left,
+    Center,
+    Right,
+    BottomLeft,
+    Bottom,
+    BottomRight
+});
+named!(SortOrder {
+    InputOrder,
+    ByHeight,
+    ByWidth,
+    ByAspect
+});
+named!(FillOrder {
+    RowMajor,
+    ColumnMajor
+});
+named!(LayoutMode { Grid, Masonry });
+named!(Fit {
+    Contain,
+    Cover,
+    Stretch,
+    CropCenter,
+    SmartCover,
+    SmartCrop
+});
+named!(Transform {
+    Rotate90,
+    Rotate180,
+    Rotate270,
+    FlipHorizontal,
+    FlipVertical
+});
+named!(DecodeErrorPolicy {
+    FailFast,
+    Skip,
+    Placeholder
+});
+named!(OutputFormat { Jpeg, Png });
+named!(Preset { Fast, Quality });
+named!(RenderQuality { Final, Preview });
+named!(BlendMode {
+    Normal,
+    Multiply,
+    Screen,
+    Overlay
+});
+
+fn color(color: Rgba<u8>) -> Value {
+    json!(color.0)
+}
+
+fn unit(unit: Unit) -> Value {
+    match unit {
+        Unit::Px(px) => json!({ "px": px }),
+        Unit::Percent(percent) => json!({ "percent": percent }),
+    }
+}
+
+fn options_to_json(builder: &CreateBundledImageOptionsBuilder) -> Value {
+    let mut options = Map::new();
+    let mut set = |key: &str, value: Value| {
+        if !value.is_null() {
+            options.insert(key.to_string(), value);
+        }
+    };
+    set("member_dimension", json!(builder.member_dimension));
+    set("column", json!(builder.column));
+    set("rows", json!(builder.rows));
+    set("auto_layout", json!(builder.auto_layout));
+    set("padding", builder.padding.map_or(Value::Null, unit));
+    set(
+        "outer_margin",
+        builder.outer_margin.map_or(Value::Null, |margin| {
+            json!([
+                unit(margin.top),
+                unit(margin.right),
+                unit(margin.bottom),
+                unit(margin.left)
+            ])
+        }),
+    );
+    set("gap", builder.gap.map_or(Value::Null, unit));
+    set(
+        "normalize_orientation",
+        json!(builder.normalize_orientation),
+    );
+    set("order", json!(builder.order));
+    set("sort", json!(builder.sort.map(|sort| sort.name())));
+    set(
+        "transforms",
+        json!(builder
+            .transforms
+            .iter()
+            .map(|(index, transform)| json!([index, transform.name()]))
+            .collect::<Vec<_>>()),
+    );
+    set("reserved_cells", json!(builder.reserved_cells));
+    set("fill_order", json!(builder.fill_order.map(|f| f.name())));
+    set("auto_shrink", json!(builder.auto_shrink));
+    set("layout_mode", json!(builder.layout_mode.map(|m| m.name())));
+    set("physical_scale", json!(builder.physical_scale));
+    set("max_canvas", json!(builder.max_canvas));
+    set("allow_upscale", json!(builder.allow_upscale));
+    set("crop_aspect", json!(builder.crop_aspect));
+    set("trim_background", json!(builder.trim_background));
+    set("dedupe", json!(builder.dedupe));
+    set(
+        "cell_alignment",
+        json!(builder.cell_alignment.map(|a| a.name())),
+    );
+    set("fit", json!(builder.fit.map(|fit| fit.name())));
+    set("background", builder.background.map_or(Value::Null, color));
+    set(
+        "theme",
+        builder.theme.map_or(Value::Null, |theme| {
+            json!({
+                "background": color(theme.background),
+                "text": color(theme.text),
+                "border": color(theme.border),
+                "accent": color(theme.accent),
+                "banner": color(theme.banner),
+            })
+        }),
+    );
+    set(
+        "cell_border",
+        builder
+            .cell_border
+            .map_or(Value::Null, |(width, c)| json!([width, color(c)])),
+    );
+    set("corner_radius", json!(builder.corner_radius));
+    set("canvas_corner_radius", json!(builder.canvas_corner_radius));
+    set(
+        "drop_shadow",
+        builder.drop_shadow.map_or(Value::Null, |shadow| {
+            json!({
+                "blur_radius": shadow.blur_radius,
+                "offset": [shadow.offset_x, shadow.offset_y],
+                "color": color(shadow.color),
+            })
+        }),
+    );
+    set(
+        "step_arrows",
+        builder.step_arrows.map_or(Value::Null, |arrows| {
+            json!({
+                "color": arrows.color.map_or(Value::Null, color),
+                "thickness": arrows.thickness,
+                "head_size": arrows.head_size,
+            })
+        }),
+    );
+    set("thumbnail_widths", json!(builder.thumbnail_widths));
+    set("linear_light", json!(builder.linear_light));
+    set("preset", json!(builder.preset.map(|preset| preset.name())));
+    set(
+        "render_quality",
+        json!(builder.render_quality.map(|quality| quality.name())),
+    );
+    set("max_output_bytes", json!(builder.max_output_bytes));
+    set("target_file_size", json!(builder.target_file_size));
+    set("sha256", json!(builder.sha256));
+    set("seed", json!(builder.seed));
+    set("low_memory", json!(builder.low_memory));
+    set("exif_orientation", json!(builder.exif_orientation));
+    set(
+        "on_decode_error",
+        json!(builder.on_decode_error.map(|policy| policy.name())),
+    );
+    set(
+        "output_format",
+        json!(builder.output_format.map(|format| format.name())),
+    );
+    Value::Object(options)
+}
+
+fn path(value: &Value, key: &str) -> Result<PathBuf, ImageCombinerError> {
+    value
+        .as_str()
+        .map(PathBuf::from)
+        .ok_or_else(|| invalid(format!("{} must be paths in project:{}", key, value)))
+}
+
+fn overlay_from_json(value: &Value) -> Result<ProjectOverlay, ImageCombinerError> {
+    let mut overlay = ProjectOverlay::new(
+        path(&value["path"], "overlays")?,
+        BlendMode::parse(&value["blend_mode"], "blend_mode")?,
+    );
+    if !value["anchor"].is_null() {
+        overlay.position = OverlayPosition::new(Anchor::parse(&value["anchor"], "anchor")?);
+    }
+    if let Some((offset_x, offset_y)) = decode::<Option<(i32, i32)>>(&value["offset"], "offset")? {
+        overlay.position = overlay.position.with_offset(offset_x, offset_y);
+    }
+    overlay.opacity = decode::<Option<u8>>(&value["opacity"], "opacity")?.unwrap_or(255);
+    overlay.tiled = decode::<Option<bool>>(&value["tiled"], "tiled")?.unwrap_or(false);
+    Ok(overlay)
+}
+
+//plain numbers, arrays and booleans
+fn decode<T: DeserializeOwned>(value: &Value, key: &str) -> Result<T, ImageCombinerError> {
+    serde_json::from_value(value.clone())
+        .map_err(|e| invalid(format!("invalid {} in project {}", key, e)))
+}
+
+fn parse_color(value: &Value, key: &str) -> Result<Rgba<u8>, ImageCombinerError> {
+    Ok(Rgba(decode::<[u8; 4]>(value, key)?))
+}
+
+fn parse_unit(value: &Value, key: &str) -> Result<Unit, ImageCombinerError> {
+    if let Some(px) = value["px"].as_u64() {
+        return Ok(Unit::Px(px as u32));
+    }
+    if let Some(percent) = value["percent"].as_f64() {
+        return Ok(Unit::Percent(percent as f32));
+    }
+    Err(invalid(format!("invalid {} in project:{}", key, value)))
+}
+
+fn options_from_json(
+    options: &Map<String, Value>,
+) -> Result<CreateBundledImageOptionsBuilder, ImageCombinerError> {
+    let mut builder = CreateBundledImageOptionsBuilder::new();
+    for (key, value) in options.iter() {
+        let key = key.as_str();
+        match key {
+            "member_dimension" => builder.member_dimension = decode(value, key)?,
+            "column" => builder.column = decode(value, key)?,
+            "rows" => builder.rows = decode(value, key)?,
+            "auto_layout" => builder.auto_layout = decode(value, key)?,
+            "padding" => builder.padding = Some(parse_unit(value, key)?),
+            "outer_margin" => {
+                let sides: Vec<Value> = decode(value, key)?;
+                let [top, right, bottom, left] = sides.as_slice() else {
+                    return Err(invalid(format!("outer_margin needs 4 sides:{}", value)));
+                };
+                builder.outer_margin = Some(Margin {
+                    top: parse_unit(top, key)?,
+                    right: parse_unit(right, key)?,
+                    bottom: parse_unit(bottom, key)?,
+                    left: parse_unit(left, key)?,
+                });
+            }
+            "gap" => builder.gap = Some(parse_unit(value, key)?),
+            "normalize_orientation" => builder.normalize_orientation = decode(value, key)?,
+            "order" => builder.order = decode(value, key)?,
+            "sort" => builder.sort = Some(SortOrder::parse(value, key)?),
+            "transforms" => {
+                let transforms: Vec<(usize, Value)> = decode(value, key)?;
+                builder.transforms = transforms
+                    .iter()
+                    .map(|(index, transform)| Ok((*index, Transform::parse(transform, key)?)))
+                    .collect::<Result<_, ImageCombinerError>>()?;
+            }
+            "reserved_cells" => builder.reserved_cells = decode(value, key)?,
+            "fill_order" => builder.fill_order = Some(FillOrder::parse(value, key)?),
+            "auto_shrink" => builder.auto_shrink = decode(value, key)?,
+            "layout_mode" => builder.layout_mode = Some(LayoutMode::parse(value, key)?),
+            "physical_scale" => builder.physical_scale = decode(value, key)?,
+            "max_canvas" => builder.max_canvas = decode(value, key)?,
+            "allow_upscale" => builder.allow_upscale = decode(value, key)?,
+            "crop_aspect" => builder.crop_aspect = decode(value, key)?,
+            "trim_background" => builder.trim_background = decode(value, key)?,
+            "dedupe" => builder.dedupe = decode(value, key)?,
+            "cell_alignment" => builder.cell_alignment = Some(CellAlignment::parse(value, key)?),
+            "fit" => builder.fit = Some(Fit::parse(value, key)?),
+            "background" => builder.background = Some(parse_color(value, key)?),
+            "theme" => {
+                builder.theme = Some(Theme::new(
+                    parse_color(&value["background"], key)?,
+                    parse_color(&value["text"], key)?,
+                    parse_color(&value["border"], key)?,
+                    parse_color(&value["accent"], key)?,
+                    parse_color(&value["banner"], key)?,
+                ))
+            }
+            "cell_border" => {
+                let (width, border_color): (u32, Value) = decode(value, key)?;
+                builder.cell_border = Some((width, parse_color(&border_color, key)?));
+            }
+            "corner_radius" => builder.corner_radius = decode(value, key)?,
+            "canvas_corner_radius" => builder.canvas_corner_radius = decode(value, key)?,
+            "drop_shadow" => {
+                let (offset_x, offset_y) = decode(&value["offset"], key)?;
+                builder.drop_shadow = Some(DropShadow::new(
+                    decode(&value["blur_radius"], key)?,
+                    offset_x,
+                    offset_y,
+                    parse_color(&value["color"], key)?,
+                ));
+            }
+            "step_arrows" => {
+                let mut arrows = ArrowStyle::themed(
+                    decode(&value["thickness"], key)?,
+                    decode(&value["head_size"], key)?,
+                );
+                if !value["color"].is_null() {
+                    arrows.color = Some(parse_color(&value["color"], key)?);
+                }
+                builder.step_arrows = Some(arrows);
+            }
+            "thumbnail_widths" => builder.thumbnail_widths = decode(value, key)?,
+            "linear_light" => builder.linear_light = decode(value, key)?,
+            "preset" => builder.preset = Some(Preset::parse(value, key)?),
+            "render_quality" => builder.render_quality = Some(RenderQuality::parse(value, key)?),
+            "max_output_bytes" => builder.max_output_bytes = decode(value, key)?,
+            "target_file_size" => builder.target_file_size = decode(value, key)?,
+            "sha256" => builder.sha256 = decode(value, key)?,
+            "seed" => builder.seed = decode(value, key)?,
+            "low_memory" => builder.low_memory = decode(value, key)?,
+            "exif_orientation" => builder.exif_orientation = decode(value, key)?,
+            "on_decode_error" => {
+                builder.on_decode_error = Some(DecodeErrorPolicy::parse(value, key)?)
+            }
+            "output_format" => builder.output_format = Some(OutputFormat::parse(value, key)?),
+            //written by a newer version, ignored so the rest of the project still loads
+            _ => debug!("skip unknown project option {}", key),
+        }
+    }
+    Ok(builder)
+}
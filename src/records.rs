@@ -0,0 +1,56 @@
+use crate::ImageCombinerError;
+use serde::Serialize;
+use serde_json::Value;
+
+//head from the field names of the first record in declaration order, one body row per record,
+//a field a record leaves out is an empty cell
+pub(crate) fn record_rows<T: Serialize>(
+    records: &[T],
+) -> Result<(Vec<String>, Vec<Vec<String>>), ImageCombinerError> {
+    let mut head: Option<Vec<String>> = None;
+    let mut body = Vec::new();
+    for (index, record) in records.iter().enumerate() {
+        let fields = match serde_json::to_value(record) {
+            Ok(Value::Object(fields)) => fields,
+            Ok(value) => {
+                return Err(ImageCombinerError::InvalidTable(format!(
+                    "record is not a struct or map no:{},value:{}",
+                    index + 1,
+                    value
+                )))
+            }
+            Err(e) => {
+                return Err(ImageCombinerError::InvalidTable(format!(
+                    "record does not serialize no:{},{}",
+                    index + 1,
+                    e
+                )))
+            }
+        };
+        let head = head.get_or_insert_with(|| fields.keys().cloned().collect());
+        if let Some(key) = fields.keys().find(|key| !head.contains(key)) {
+            return Err(ImageCombinerError::InvalidTable(format!(
+                "record has a field the first record does not no:{},field:{}",
+                index + 1,
+                key
+            )));
+        }
+        body.push(
+            head.iter()
+                .map(|key| fields.get(key).map(cell_text).unwrap_or_default())
+                .collect(),
+        );
+    }
+    let head =
+        head.ok_or_else(|| ImageCombinerError::InvalidTable("table has no records".to_string()))?;
+    Ok((head, body))
+}
+
+//strings as they are, null as an empty cell and anything else as json
+fn cell_text(value: &Value) -> String {
+    match value {
+        Value::String(text) => text.clone(),
+        Value::Null => String::new(),
+        value => value.to_string(),
+    }
+}
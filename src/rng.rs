@@ -0,0 +1,39 @@
+use std::collections::hash_map::RandomState;
+use std::hash::BuildHasher;
+
+//splitmix64 seeded with the seed of the job or at random when there is none,
+//fork gives every input a stream of its own so the draws do not depend on which task runs first
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct SeededRng {
+    state: u64,
+}
+
+impl SeededRng {
+    pub(crate) fn new(seed: Option<u64>) -> Self {
+        Self {
+            state: seed.unwrap_or_else(|| RandomState::new().hash_one(0u8)),
+        }
+    }
+
+    pub(crate) fn fork(&self, stream: u64) -> Self {
+        let mut rng = Self {
+            state: self.state ^ stream.wrapping_mul(0xD1B5_4A32_D192_ED03),
+        };
+        Self {
+            state: rng.next_u64(),
+        }
+    }
+
+    pub(crate) fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    //uniform in [0, 1)
+    pub(crate) fn next_unit(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+}
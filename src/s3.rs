@@ -0,0 +1,186 @@
+use crate::fetch::{fetch_all, FetchError, FetchOptions, FetchOptionsBuilder};
+use crate::{
+    create_bundled_image_from_bytes, CreateBundledImageOptions, ImageCombinerError, ImageSource,
+    Storage, UploadOptions,
+};
+use log::debug;
+use rusoto_core::RusotoError;
+use rusoto_s3::{GetObjectRequest, PutObjectRequest, S3Client, S3};
+use std::collections::VecDeque;
+use tokio::io::AsyncReadExt;
+
+pub struct S3ImageSource {
+    client: S3Client,
+    bucket: String,
+    keys: Vec<String>,
+    fetch_options: FetchOptions,
+    fetched: Option<VecDeque<Vec<u8>>>,
+    attempts: Vec<u32>,
+}
+
+impl S3ImageSource {
+    pub fn new(client: S3Client, bucket: String, keys: Vec<String>) -> Self {
+        Self {
+            client,
+            bucket,
+            keys,
+            fetch_options: FetchOptionsBuilder::new().build(),
+            fetched: None,
+            attempts: Vec::new(),
+        }
+    }
+
+    pub fn with_fetch_options(mut self, fetch_options: FetchOptions) -> Self {
+        self.fetch_options = fetch_options;
+        self
+    }
+
+    pub async fn fetch(
+        self,
+        fetch_options: &FetchOptions,
+    ) -> Result<Vec<Vec<u8>>, ImageCombinerError> {
+        let (buffers, _) =
+            fetch_objects(self.client, self.bucket, self.keys, fetch_options).await?;
+        Ok(buffers)
+    }
+}
+
+impl ImageSource for S3ImageSource {
+    async fn next_image(&mut self) -> Option<Result<Vec<u8>, ImageCombinerError>> {
+        if self.fetched.is_none() {
+            //download everything up front so the fetches run concurrently
+            let keys = std::mem::take(&mut self.keys);
+            let fetched = fetch_objects(
+                self.client.clone(),
+                self.bucket.clone(),
+                keys,
+                &self.fetch_options,
+            )
+            .await;
+            match fetched {
+                Ok((buffers, attempts)) => {
+                    self.fetched = Some(buffers.into());
+                    self.attempts = attempts;
+                }
+                Err(e) => {
+                    self.fetched = Some(VecDeque::new());
+                    return Some(Err(e));
+                }
+            }
+        }
+        self.fetched.as_mut()?.pop_front().map(Ok)
+    }
+
+    fn fetch_attempts(&self) -> Vec<u32> {
+        self.attempts.clone()
+    }
+
+    fn seed(&mut self, seed: u64) {
+        self.fetch_options.seed = Some(seed);
+    }
+}
+
+//keys are object keys in one bucket
+#[derive(Clone)]
+pub struct S3Storage {
+    client: S3Client,
+    bucket: String,
+}
+
+impl S3Storage {
+    pub fn new(client: S3Client, bucket: String) -> Self {
+        Self { client, bucket }
+    }
+}
+
+impl Storage for S3Storage {
+    async fn get(&self, key: &str) -> Result<Vec<u8>, ImageCombinerError> {
+        get_object_bytes(&self.client, self.bucket.clone(), key.to_string())
+            .await
+            .map_err(|e| ImageCombinerError::Fetch(e.to_string()))
+    }
+
+    async fn put(
+        &self,
+        key: &str,
+        bytes: Vec<u8>,
+        options: &UploadOptions,
+    ) -> Result<(), ImageCombinerError> {
+        debug!("put s3://{}/{}", self.bucket, key);
+        let metadata = &options.metadata;
+        let request = PutObjectRequest {
+            bucket: self.bucket.clone(),
+            key: key.to_string(),
+            content_length: Some(bytes.len() as i64),
+            content_type: options.content_type.clone(),
+            cache_control: options.cache_control.clone(),
+            metadata: (!metadata.is_empty()).then(|| metadata.clone().into_iter().collect()),
+            body: Some(bytes.into()),
+            ..Default::default()
+        };
+        self.client
+            .put_object(request)
+            .await
+            .map_err(|e| ImageCombinerError::Upload(e.to_string()))?;
+        Ok(())
+    }
+}
+
+pub async fn create_bundled_image_from_s3(
+    source: S3ImageSource,
+    options: CreateBundledImageOptions,
+    fetch_options: FetchOptions,
+) -> Result<Vec<u8>, ImageCombinerError> {
+    create_bundled_image_from_bytes(source.with_fetch_options(fetch_options), options).await
+}
+
+async fn fetch_objects(
+    client: S3Client,
+    bucket: String,
+    keys: Vec<String>,
+    fetch_options: &FetchOptions,
+) -> Result<(Vec<Vec<u8>>, Vec<u32>), ImageCombinerError> {
+    debug!("fetch {} objects from s3://{}", keys.len(), bucket);
+    let host = bucket.clone();
+    fetch_all(
+        keys,
+        fetch_options,
+        move |_| host.clone(),
+        move |key: String| {
+            let client = client.clone();
+            let bucket = bucket.clone();
+            async move { get_object_bytes(&client, bucket, key).await }
+        },
+    )
+    .await
+}
+
+async fn get_object_bytes(
+    client: &S3Client,
+    bucket: String,
+    key: String,
+) -> Result<Vec<u8>, FetchError> {
+    let request = GetObjectRequest {
+        bucket,
+        key,
+        ..Default::default()
+    };
+    let res = client.get_object(request).await.map_err(|e| {
+        //a missing key or bad credentials fail the same way on every attempt
+        let retryable = match &e {
+            RusotoError::HttpDispatch(_) => true,
+            RusotoError::Unknown(res) => res.status.is_server_error() || res.status == 429,
+            _ => false,
+        };
+        FetchError::new(e, retryable)
+    })?;
+    let body = res
+        .body
+        .ok_or_else(|| FetchError::new("object has no body", false))?;
+    let mut bytes = Vec::new();
+    body.into_async_read()
+        .read_to_end(&mut bytes)
+        .await
+        .map_err(|e| FetchError::new(e, true))?;
+    Ok(bytes)
+}
@@ -0,0 +1,112 @@
+use crate::ImageCombinerError;
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+
+const SIGNATURE_PREFIX: &str = "image-combiner-signature:";
+const BLOCK_SIZE: usize = 64;
+//bumped whenever a field of the signed record changes meaning
+const PARAMS_VERSION: u32 = 1;
+
+//"key=value" lines sorted by key after the version line, so the record does not depend
+//on the order fields are listed in
+pub(crate) fn params_record(fields: Vec<(&str, String)>) -> String {
+    let fields: BTreeMap<&str, String> = fields.into_iter().collect();
+    let mut record = format!("version={}\n", PARAMS_VERSION);
+    for (key, value) in fields {
+        record.push_str(&format!("{}={}\n", key, value));
+    }
+    record
+}
+
+//rfc 2104 over sha256
+fn hmac_sha256(key: &[u8], message: &[u8]) -> [u8; 32] {
+    let mut block = [0u8; BLOCK_SIZE];
+    if key.len() > BLOCK_SIZE {
+        block[..32].copy_from_slice(&Sha256::digest(key));
+    } else {
+        block[..key.len()].copy_from_slice(key);
+    }
+    let inner_pad: Vec<u8> = block.iter().map(|byte| byte ^ 0x36).collect();
+    let outer_pad: Vec<u8> = block.iter().map(|byte| byte ^ 0x5c).collect();
+    let inner = Sha256::new()
+        .chain_update(&inner_pad)
+        .chain_update(message)
+        .finalize();
+    Sha256::new()
+        .chain_update(&outer_pad)
+        .chain_update(inner)
+        .finalize()
+        .into()
+}
+
+//"image-combiner-signature:{base64 params}.{base64 hmac}"
+fn signature_comment(params: &str, key: &[u8]) -> String {
+    format!(
+        "{}{}.{}",
+        SIGNATURE_PREFIX,
+        STANDARD.encode(params),
+        STANDARD.encode(hmac_sha256(key, params.as_bytes()))
+    )
+}
+
+//the COM segment carrying the signed parameters, built before encoding so its size is known up front
+pub(crate) fn signature_segment(params: &str, key: &[u8]) -> Result<Vec<u8>, ImageCombinerError> {
+    let comment = signature_comment(params, key);
+    //the length field counts itself
+    let length = u16::try_from(comment.len() + 2).map_err(|_| {
+        ImageCombinerError::Encode("render parameters are too long to sign".to_string())
+    })?;
+    let mut segment = vec![0xFF, 0xFE];
+    segment.extend_from_slice(&length.to_be_bytes());
+    segment.extend_from_slice(comment.as_bytes());
+    Ok(segment)
+}
+
+//adds a signature segment right after SOI
+pub(crate) fn sign_jpeg(bytes: &mut Vec<u8>, segment: Vec<u8>) -> Result<(), ImageCombinerError> {
+    if !bytes.starts_with(&[0xFF, 0xD8]) {
+        return Err(ImageCombinerError::Encode(
+            "can only sign jpeg output".to_string(),
+        ));
+    }
+    bytes.splice(2..2, segment);
+    Ok(())
+}
+
+//returns the signed render parameters when the jpeg carries a signature made with `key`
+pub fn verify_signature(bytes: &[u8], key: &[u8]) -> Option<String> {
+    if !bytes.starts_with(&[0xFF, 0xD8]) {
+        return None;
+    }
+    let mut offset = 2;
+    //markers before the scan data all carry a length
+    while offset + 4 <= bytes.len() && bytes[offset] == 0xFF && bytes[offset + 1] != 0xDA {
+        let length = u16::from_be_bytes([bytes[offset + 2], bytes[offset + 3]]) as usize;
+        let payload = bytes.get(offset + 4..offset + 2 + length)?;
+        if bytes[offset + 1] == 0xFE {
+            if let Some(params) = verify_comment(payload, key) {
+                return Some(params);
+            }
+        }
+        offset += 2 + length;
+    }
+    None
+}
+
+fn verify_comment(payload: &[u8], key: &[u8]) -> Option<String> {
+    let comment = std::str::from_utf8(payload).ok()?;
+    let (params, mac) = comment.strip_prefix(SIGNATURE_PREFIX)?.split_once('.')?;
+    let params = String::from_utf8(STANDARD.decode(params).ok()?).ok()?;
+    let mac = STANDARD.decode(mac).ok()?;
+    let expected = hmac_sha256(key, params.as_bytes());
+    //compare every byte so the time taken does not leak how much matched
+    let matches = mac.len() == expected.len()
+        && mac
+            .iter()
+            .zip(expected.iter())
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0;
+    matches.then_some(params)
+}
@@ -0,0 +1,129 @@
+use crate::{CreateBundledImageOptions, ImageCombinerError};
+use futures_core::Stream;
+use std::collections::VecDeque;
+use std::fmt::Display;
+use std::future::Future;
+use std::path::PathBuf;
+use std::pin::Pin;
+
+pub trait ImageSource {
+    fn next_image(
+        &mut self,
+    ) -> impl Future<Output = Option<Result<Vec<u8>, ImageCombinerError>>> + Send;
+
+    //attempts each input took to download, empty for sources that do not fetch
+    fn fetch_attempts(&self) -> Vec<u32> {
+        Vec::new()
+    }
+
+    //the seed of the job, for sources that randomize how they read such as the retry jitter of fetches
+    fn seed(&mut self, _seed: u64) {}
+}
+
+impl<S: ImageSource> ImageSource for &mut S {
+    fn next_image(
+        &mut self,
+    ) -> impl Future<Output = Option<Result<Vec<u8>, ImageCombinerError>>> + Send {
+        (**self).next_image()
+    }
+
+    fn fetch_attempts(&self) -> Vec<u32> {
+        (**self).fetch_attempts()
+    }
+
+    fn seed(&mut self, seed: u64) {
+        (**self).seed(seed)
+    }
+}
+
+//what the bundle functions take, any source as it is and a Vec of encoded images read front to back
+pub trait IntoImageSource {
+    type Source: ImageSource;
+
+    fn into_source(self) -> Self::Source;
+}
+
+//the source with the seed of the job handed to it
+pub(crate) fn open_source<B: IntoImageSource>(
+    buffers: B,
+    options: &CreateBundledImageOptions,
+) -> B::Source {
+    let mut source = buffers.into_source();
+    if let Some(seed) = options.seed {
+        source.seed(seed);
+    }
+    source
+}
+
+impl<S: ImageSource> IntoImageSource for S {
+    type Source = S;
+
+    fn into_source(self) -> S {
+        self
+    }
+}
+
+impl IntoImageSource for Vec<Vec<u8>> {
+    type Source = std::vec::IntoIter<Vec<u8>>;
+
+    fn into_source(self) -> Self::Source {
+        self.into_iter()
+    }
+}
+
+impl ImageSource for std::vec::IntoIter<Vec<u8>> {
+    fn next_image(
+        &mut self,
+    ) -> impl Future<Output = Option<Result<Vec<u8>, ImageCombinerError>>> + Send {
+        std::future::ready(self.next().map(Ok))
+    }
+}
+
+pub struct FileImageSource {
+    paths: VecDeque<PathBuf>,
+}
+
+impl FileImageSource {
+    pub fn new(paths: Vec<PathBuf>) -> Self {
+        Self {
+            paths: paths.into(),
+        }
+    }
+}
+
+impl ImageSource for FileImageSource {
+    async fn next_image(&mut self) -> Option<Result<Vec<u8>, ImageCombinerError>> {
+        let path = self.paths.pop_front()?;
+        Some(
+            tokio::fs::read(path)
+                .await
+                .map_err(ImageCombinerError::from),
+        )
+    }
+}
+
+//pulls images one at a time so a download stream is only polled as fast as bundling consumes it
+pub struct StreamImageSource<S> {
+    stream: S,
+}
+
+impl<S> StreamImageSource<S> {
+    pub fn new(stream: S) -> Self {
+        Self { stream }
+    }
+}
+
+impl<S, B, E> ImageSource for StreamImageSource<S>
+where
+    S: Stream<Item = Result<B, E>> + Unpin + Send,
+    B: Into<Vec<u8>>,
+    E: Display,
+{
+    async fn next_image(&mut self) -> Option<Result<Vec<u8>, ImageCombinerError>> {
+        let item = std::future::poll_fn(|cx| Pin::new(&mut self.stream).poll_next(cx)).await?;
+        Some(
+            item.map(Into::into)
+                .map_err(|e| ImageCombinerError::Fetch(e.to_string())),
+        )
+    }
+}
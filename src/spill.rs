@@ -0,0 +1,48 @@
+use crate::ImageCombinerError;
+use image::buffer::ConvertBuffer;
+use image::{DynamicImage, ImageBuffer, Rgba};
+use log::debug;
+use memmap2::Mmap;
+use std::fs::File;
+use std::io::Write;
+
+//a decoded tile kept as raw RGBA in an unlinked temp file until it is resized or drawn
+pub(crate) struct SpilledTile {
+    file: File,
+    width: u32,
+    height: u32,
+}
+
+impl SpilledTile {
+    pub(crate) fn spill(image: &DynamicImage) -> Result<Self, ImageCombinerError> {
+        let rgba = image.to_rgba8();
+        let mut file = tempfile::tempfile()?;
+        file.write_all(rgba.as_raw())?;
+        debug!("spill tile {}x{}", rgba.width(), rgba.height());
+        Ok(Self {
+            file,
+            width: rgba.width(),
+            height: rgba.height(),
+        })
+    }
+
+    pub(crate) fn dimensions(&self) -> (u32, u32) {
+        (self.width, self.height)
+    }
+
+    //copies the pixels back into memory, for the one image being rotated or resized
+    pub(crate) fn load(&self) -> Result<DynamicImage, ImageCombinerError> {
+        Ok(DynamicImage::ImageRgba8(self.map()?.convert()))
+    }
+
+    pub(crate) fn map(&self) -> Result<ImageBuffer<Rgba<u8>, Mmap>, ImageCombinerError> {
+        //SAFETY: the temp file is unlinked and only owned by this tile, so nothing else can change it while mapped
+        let map = unsafe { Mmap::map(&self.file)? };
+        ImageBuffer::from_raw(self.width, self.height, map).ok_or_else(|| {
+            ImageCombinerError::Io(std::io::Error::new(
+                std::io::ErrorKind::UnexpectedEof,
+                "spilled tile is shorter than its dimensions",
+            ))
+        })
+    }
+}
@@ -0,0 +1,225 @@
+use crate::arc::{render_arc_text, ArcDirection, ArcText};
+use crate::fonts::FontSet;
+use crate::{calc_chars_width, ImageCombinerError, TextCardStyle};
+use image::{ImageBuffer, Rgba};
+use imageproc::drawing::{draw_filled_rect_mut, draw_polygon_mut};
+use imageproc::geometric_transformations::{rotate_about_center, Interpolation};
+use imageproc::point::Point;
+use imageproc::rect::Rect;
+use rusttype::Scale;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StampShape {
+    Circle,
+    Square,
+}
+
+//ring, text and stars are all drawn in the text style color
+#[derive(Clone)]
+pub struct StampStyle {
+    style: TextCardStyle,
+    shape: StampShape,
+    size: u32,
+    ring_width: Option<u32>,
+    ring_text: Option<String>,
+    stars: bool,
+    rotation: f32,
+}
+
+impl StampStyle {
+    pub fn new(style: TextCardStyle, shape: StampShape, size: u32) -> Self {
+        Self {
+            style,
+            shape,
+            size,
+            ring_width: None,
+            ring_text: None,
+            stars: false,
+            rotation: 0.0,
+        }
+    }
+
+    //a twentieth of the size by default
+    pub fn with_ring_width(mut self, ring_width: u32) -> Self {
+        self.ring_width = Some(ring_width);
+        self
+    }
+
+    //follows the top of the ring on a circle, runs along the top border on a square
+    pub fn with_ring_text(mut self, ring_text: &str) -> Self {
+        self.ring_text = Some(ring_text.to_string());
+        self
+    }
+
+    //a star on either side of the center text
+    pub fn with_stars(mut self, stars: bool) -> Self {
+        self.stars = stars;
+        self
+    }
+
+    //degrees clockwise, the canvas grows so the corners of a square are not cut off
+    pub fn with_rotation(mut self, rotation: f32) -> Self {
+        self.rotation = rotation;
+        self
+    }
+}
+
+//a seal on a transparent canvas, encode it on its own or draw it over a bundle,
+//lines of text are separated by '\n' and centered inside the ring
+pub fn create_stamp(
+    text: &str,
+    stamp: StampStyle,
+) -> Result<ImageBuffer<Rgba<u8>, Vec<u8>>, ImageCombinerError> {
+    if stamp.size < 8 {
+        return Err(ImageCombinerError::InvalidOptions(format!(
+            "stamp size must be at least 8 size:{}",
+            stamp.size
+        )));
+    }
+    let fonts = FontSet::new(&stamp.style)?;
+    let color = stamp.style.color;
+    let size = stamp.size;
+    let ring_width = stamp.ring_width.unwrap_or(size / 20).clamp(1, size / 4);
+    let canvas_size = if stamp.rotation % 360.0 == 0.0 {
+        size
+    } else {
+        (size as f32 * std::f32::consts::SQRT_2).ceil() as u32
+    };
+    let offset = (canvas_size - size) / 2;
+    let mut canvas = ImageBuffer::from_pixel(canvas_size, canvas_size, Rgba([0, 0, 0, 0]));
+    let radius = size as f32 * 0.5;
+    let center = canvas_size as f32 * 0.5;
+    match stamp.shape {
+        StampShape::Circle => draw_ring(&mut canvas, center, radius, ring_width as f32, color),
+        StampShape::Square => {
+            let (left, right) = (offset, offset + size - ring_width);
+            for rect in [
+                Rect::at(left as i32, left as i32).of_size(size, ring_width),
+                Rect::at(left as i32, right as i32).of_size(size, ring_width),
+                Rect::at(left as i32, left as i32).of_size(ring_width, size),
+                Rect::at(right as i32, left as i32).of_size(ring_width, size),
+            ] {
+                draw_filled_rect_mut(&mut canvas, rect, color);
+            }
+        }
+    }
+    //room left inside the border, the ring text takes a band of it
+    let mut inner = radius - ring_width as f32 * 2.0;
+    if let Some(ring_text) = &stamp.ring_text {
+        let font_size = stamp.style.font_size.unwrap_or(size as f32 * 0.1);
+        let band = font_size * 1.4;
+        match stamp.shape {
+            StampShape::Circle => {
+                let mut ring_style = stamp.style.clone();
+                ring_style.font_size = Some(font_size);
+                let baseline = inner - font_size;
+                let arc = ArcText::new(baseline, 0.0, ArcDirection::Clockwise);
+                let overlay = render_arc_text(ring_text, &ring_style, arc)?;
+                let at = (center - overlay.width() as f32 * 0.5).round().max(0.0) as u32;
+                image::imageops::overlay(&mut canvas, &overlay, at, at);
+                draw_ring(&mut canvas, center, inner - band, 1.0, color);
+            }
+            StampShape::Square => {
+                let left = center - fonts.width(ring_text, Scale::uniform(font_size)) * 0.5;
+                let top = center - inner + (band - font_size) * 0.5;
+                fonts.draw(
+                    &mut canvas,
+                    color,
+                    left.max(0.0) as u32,
+                    top.max(0.0) as u32,
+                    Scale::uniform(font_size),
+                    ring_text,
+                );
+            }
+        }
+        inner -= band;
+    }
+    let lines: Vec<&str> = text.lines().collect();
+    let widest = lines
+        .iter()
+        .map(|line| calc_chars_width(line))
+        .fold(0f32, f32::max);
+    let star_room = if stamp.stars { 2.5 } else { 0.0 };
+    //the text block fits in the square inscribed in the inner circle
+    let room = match stamp.shape {
+        StampShape::Circle => inner * std::f32::consts::SQRT_2,
+        StampShape::Square => inner * 2.0,
+    };
+    let font_size = stamp.style.font_size.unwrap_or_else(|| {
+        (room / (widest + star_room).max(1.0)).min(room / (lines.len().max(1) as f32 * 1.2))
+    });
+    let line_height = font_size * 1.2;
+    let mut top = center - lines.len() as f32 * line_height * 0.5;
+    for line in lines.iter() {
+        let left = center - fonts.width(line, Scale::uniform(font_size)) * 0.5;
+        fonts.draw(
+            &mut canvas,
+            color,
+            left.max(0.0) as u32,
+            top.max(0.0) as u32,
+            Scale::uniform(font_size),
+            line,
+        );
+        top += line_height;
+    }
+    if stamp.stars {
+        let drawn = lines
+            .iter()
+            .map(|line| fonts.width(line, Scale::uniform(font_size)))
+            .fold(0f32, f32::max);
+        let reach = drawn * 0.5 + 0.75 * font_size;
+        for x in [center - reach, center + reach] {
+            draw_star(&mut canvas, (x, center), font_size * 0.5, color);
+        }
+    }
+    if stamp.rotation % 360.0 != 0.0 {
+        canvas = rotate_about_center(
+            &canvas,
+            stamp.rotation.to_radians(),
+            Interpolation::Bilinear,
+            Rgba([0, 0, 0, 0]),
+        );
+    }
+    Ok(canvas)
+}
+
+//an anti-aliased ring whose outer edge is at radius
+fn draw_ring(
+    canvas: &mut ImageBuffer<Rgba<u8>, Vec<u8>>,
+    center: f32,
+    radius: f32,
+    width: f32,
+    color: Rgba<u8>,
+) {
+    for (x, y, pixel) in canvas.enumerate_pixels_mut() {
+        let distance = (x as f32 + 0.5 - center).hypot(y as f32 + 0.5 - center);
+        let coverage = (radius - distance + 0.5).clamp(0.0, 1.0)
+            * (distance - radius + width + 0.5).clamp(0.0, 1.0);
+        if coverage > 0.0 {
+            let alpha = (color[3] as f32 * coverage).round() as u8;
+            if alpha > pixel[3] {
+                *pixel = Rgba([color[0], color[1], color[2], alpha]);
+            }
+        }
+    }
+}
+
+//five pointed star with its top point up
+fn draw_star(
+    canvas: &mut ImageBuffer<Rgba<u8>, Vec<u8>>,
+    center: (f32, f32),
+    radius: f32,
+    color: Rgba<u8>,
+) {
+    let points: Vec<Point<i32>> = (0..10)
+        .map(|i| {
+            let radius = if i % 2 == 0 { radius } else { radius * 0.4 };
+            let angle = (i as f32 * 36.0).to_radians();
+            Point::new(
+                (center.0 + radius * angle.sin()).round() as i32,
+                (center.1 - radius * angle.cos()).round() as i32,
+            )
+        })
+        .collect();
+    draw_polygon_mut(canvas, &points, color);
+}
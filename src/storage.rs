@@ -0,0 +1,241 @@
+use crate::{
+    create_bundled_image, BundledImage, CreateBundledImageOptions, ImageCombinerError, ImageSource,
+};
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::future::Future;
+use std::io::ErrorKind;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+//objects read and written by key, so inputs and outputs can live anywhere without the caller moving bytes
+pub trait Storage {
+    fn get(&self, key: &str) -> impl Future<Output = Result<Vec<u8>, ImageCombinerError>> + Send;
+
+    fn put(
+        &self,
+        key: &str,
+        bytes: Vec<u8>,
+        options: &UploadOptions,
+    ) -> impl Future<Output = Result<(), ImageCombinerError>> + Send;
+}
+
+impl<S: Storage + Sync> Storage for &S {
+    fn get(&self, key: &str) -> impl Future<Output = Result<Vec<u8>, ImageCombinerError>> + Send {
+        (**self).get(key)
+    }
+
+    fn put(
+        &self,
+        key: &str,
+        bytes: Vec<u8>,
+        options: &UploadOptions,
+    ) -> impl Future<Output = Result<(), ImageCombinerError>> + Send {
+        (**self).put(key, bytes, options)
+    }
+}
+
+//sent along with the object so a CDN serving it needs no extra call, backends without metadata ignore it
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct UploadOptions {
+    pub(crate) content_type: Option<String>,
+    pub(crate) cache_control: Option<String>,
+    pub(crate) metadata: BTreeMap<String, String>,
+}
+
+impl UploadOptions {
+    pub fn content_type(&self) -> Option<&str> {
+        self.content_type.as_deref()
+    }
+
+    pub fn cache_control(&self) -> Option<&str> {
+        self.cache_control.as_deref()
+    }
+
+    pub fn metadata(&self) -> &BTreeMap<String, String> {
+        &self.metadata
+    }
+}
+
+#[derive(Default)]
+pub struct UploadOptionsBuilder {
+    content_type: Option<String>,
+    cache_control: Option<String>,
+    metadata: BTreeMap<String, String>,
+}
+
+impl UploadOptionsBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    //when unset the bundle helpers use the mime type of the output format
+    pub fn set_content_type(mut self, content_type: &str) -> Self {
+        self.content_type = Some(content_type.to_string());
+        self
+    }
+
+    pub fn set_cache_control(mut self, cache_control: &str) -> Self {
+        self.cache_control = Some(cache_control.to_string());
+        self
+    }
+
+    pub fn set_metadata(mut self, metadata: BTreeMap<String, String>) -> Self {
+        self.metadata = metadata;
+        self
+    }
+
+    pub fn build(&self) -> UploadOptions {
+        UploadOptions {
+            content_type: self.content_type.clone(),
+            cache_control: self.cache_control.clone(),
+            metadata: self.metadata.clone(),
+        }
+    }
+}
+
+//keys are paths relative to root, missing parent directories are created on put and upload options are ignored,
+//absolute keys and keys with . or .. components are rejected so nothing outside root is read or written
+#[derive(Debug, Clone)]
+pub struct FsStorage {
+    root: PathBuf,
+}
+
+impl FsStorage {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    fn path(&self, key: &str) -> Result<PathBuf, ImageCombinerError> {
+        let relative = Path::new(key);
+        let normal = relative.components().next().is_some()
+            && relative
+                .components()
+                .all(|component| matches!(component, Component::Normal(_)));
+        if !normal {
+            return Err(ImageCombinerError::InvalidOptions(format!(
+                "storage key must be a relative path inside the root key:{}",
+                key
+            )));
+        }
+        Ok(self.root.join(relative))
+    }
+}
+
+impl Storage for FsStorage {
+    async fn get(&self, key: &str) -> Result<Vec<u8>, ImageCombinerError> {
+        Ok(tokio::fs::read(self.path(key)?).await?)
+    }
+
+    async fn put(
+        &self,
+        key: &str,
+        bytes: Vec<u8>,
+        _options: &UploadOptions,
+    ) -> Result<(), ImageCombinerError> {
+        let path = self.path(key)?;
+        if let Some(parent) = path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        Ok(tokio::fs::write(path, bytes).await?)
+    }
+}
+
+type StoredObject = (Vec<u8>, UploadOptions);
+
+//clones share the same objects
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStorage {
+    objects: Arc<Mutex<HashMap<String, StoredObject>>>,
+}
+
+impl MemoryStorage {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn keys(&self) -> Vec<String> {
+        let mut keys: Vec<String> = self.objects.lock().unwrap().keys().cloned().collect();
+        keys.sort();
+        keys
+    }
+
+    pub fn upload_options(&self, key: &str) -> Option<UploadOptions> {
+        let objects = self.objects.lock().unwrap();
+        objects.get(key).map(|(_, options)| options.clone())
+    }
+}
+
+impl Storage for MemoryStorage {
+    async fn get(&self, key: &str) -> Result<Vec<u8>, ImageCombinerError> {
+        self.objects
+            .lock()
+            .unwrap()
+            .get(key)
+            .map(|(bytes, _)| bytes.clone())
+            .ok_or_else(|| std::io::Error::new(ErrorKind::NotFound, key.to_string()).into())
+    }
+
+    async fn put(
+        &self,
+        key: &str,
+        bytes: Vec<u8>,
+        options: &UploadOptions,
+    ) -> Result<(), ImageCombinerError> {
+        let object = (bytes, options.clone());
+        self.objects.lock().unwrap().insert(key.to_string(), object);
+        Ok(())
+    }
+}
+
+pub struct StorageImageSource<S> {
+    storage: S,
+    keys: VecDeque<String>,
+}
+
+impl<S: Storage> StorageImageSource<S> {
+    pub fn new(storage: S, keys: Vec<String>) -> Self {
+        Self {
+            storage,
+            keys: keys.into(),
+        }
+    }
+}
+
+impl<S: Storage + Send> ImageSource for StorageImageSource<S> {
+    async fn next_image(&mut self) -> Option<Result<Vec<u8>, ImageCombinerError>> {
+        let key = self.keys.pop_front()?;
+        Some(self.storage.get(&key).await)
+    }
+}
+
+//percent encodes everything but unreserved characters, for keys placed in a url
+#[cfg(any(feature = "gcs", feature = "azure"))]
+pub(crate) fn encode_key(key: &str) -> String {
+    key.bytes()
+        .map(|byte| match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                (byte as char).to_string()
+            }
+            _ => format!("%{:02X}", byte),
+        })
+        .collect()
+}
+
+//bundles the objects at keys and writes the result to output_key in the same storage
+pub async fn create_bundled_image_in_storage<S: Storage + Sync>(
+    storage: &S,
+    keys: Vec<String>,
+    output_key: &str,
+    options: CreateBundledImageOptions,
+    upload_options: UploadOptions,
+) -> Result<BundledImage, ImageCombinerError> {
+    let mut upload_options = upload_options;
+    if upload_options.content_type.is_none() {
+        upload_options.content_type = Some(options.output_format.mime_type().to_string());
+    }
+    let bundled = create_bundled_image(StorageImageSource::new(storage, keys), options).await?;
+    storage
+        .put(output_key, bundled.bytes.clone(), &upload_options)
+        .await?;
+    Ok(bundled)
+}
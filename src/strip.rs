@@ -0,0 +1,242 @@
+use crate::fonts::FontSet;
+use crate::tasks::TileTasks;
+use crate::{
+    decode_images, decorate_canvas, finish_bundle, open_source, place_tile, placeholder_image,
+    resize_member, unsupported_max_canvas, BundledImage, CellDecoration, CreateBundledImageOptions,
+    DecodeErrorPolicy, Fit, ImageCombinerError, ImageSource, IntoImageSource, Stage, TextCardStyle,
+};
+use image::{DynamicImage, GenericImageView, ImageBuffer, Rgba};
+use log::debug;
+use rusttype::Scale;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StripDirection {
+    Horizontal,
+    Vertical,
+}
+
+//labels[i] is drawn above the image i, such as a date or a step number
+pub struct StripLabels {
+    labels: Vec<String>,
+    style: TextCardStyle,
+    themed: bool,
+}
+
+impl StripLabels {
+    pub fn new(labels: Vec<String>, style: TextCardStyle) -> Self {
+        Self {
+            labels,
+            style,
+            themed: false,
+        }
+    }
+
+    //labels in the text color of the options theme instead of the style color
+    pub fn themed(labels: Vec<String>, style: TextCardStyle) -> Self {
+        Self {
+            labels,
+            style,
+            themed: true,
+        }
+    }
+
+    fn color(&self, options: &CreateBundledImageOptions) -> Rgba<u8> {
+        match self.themed {
+            true => options.theme.text,
+            false => self.style.color,
+        }
+    }
+}
+
+//images side by side in one row, cell_alignment places shorter images within the row
+pub async fn combine_horizontal(
+    buffers: impl IntoImageSource,
+    options: CreateBundledImageOptions,
+) -> Result<BundledImage, ImageCombinerError> {
+    combine_strip(buffers, &options, StripDirection::Horizontal, None).await
+}
+
+//images stacked in one column, cell_alignment places narrower images within the column
+pub async fn combine_vertical(
+    buffers: impl IntoImageSource,
+    options: CreateBundledImageOptions,
+) -> Result<BundledImage, ImageCombinerError> {
+    combine_strip(buffers, &options, StripDirection::Vertical, None).await
+}
+
+//a strip with a band above every image holding its label
+pub async fn create_labeled_strip(
+    buffers: impl IntoImageSource,
+    direction: StripDirection,
+    labels: StripLabels,
+    options: CreateBundledImageOptions,
+) -> Result<BundledImage, ImageCombinerError> {
+    combine_strip(buffers, &options, direction, Some(&labels)).await
+}
+
+//with a member dimension every image is scaled to its height in a row or its width in a column,
+//otherwise images keep their own size
+async fn combine_strip(
+    buffers: impl IntoImageSource,
+    options: &CreateBundledImageOptions,
+    direction: StripDirection,
+    labels: Option<&StripLabels>,
+) -> Result<BundledImage, ImageCombinerError> {
+    if options.max_canvas.is_some() {
+        return Err(unsupported_max_canvas("strip"));
+    }
+    let mut source = open_source(buffers, options);
+    let mut collected = Vec::new();
+    while let Some(buffer) = source.next_image().await {
+        collected.push(buffer?);
+    }
+    if let Some(labels) = labels {
+        if labels.labels.len() != collected.len() {
+            return Err(ImageCombinerError::InvalidOptions(format!(
+                "strip labels do not match the images labels:{},images:{}",
+                labels.labels.len(),
+                collected.len()
+            )));
+        }
+    }
+    let count = collected.len();
+    let (images, skipped) = decode_images(collected, options).await?;
+    //labels of skipped images go with them
+    let dropped = matches!(options.on_decode_error, DecodeErrorPolicy::Skip);
+    let kept: Vec<usize> = (0..count)
+        .filter(|index| !(dropped && skipped.contains(index)))
+        .collect();
+    let horizontal = direction == StripDirection::Horizontal;
+    //(along the strip, across the strip) so rows and columns share the arithmetic
+    let orient = |(width, height): (u32, u32)| {
+        if horizontal {
+            (width, height)
+        } else {
+            (height, width)
+        }
+    };
+    let across = options
+        .dimension
+        .map(|dimension| orient(dimension).1)
+        .or_else(|| {
+            images
+                .iter()
+                .flatten()
+                .map(|image| orient(image.dimensions()).1)
+                .max()
+        })
+        .ok_or_else(|| {
+            ImageCombinerError::InvalidOptions("strip needs at least one image".to_string())
+        })?;
+    let sizes: Vec<(u32, u32)> = images
+        .iter()
+        .map(|image| match image {
+            Some(image) => {
+                let (along, image_across) = orient(image.dimensions());
+                if options.dimension.is_none() {
+                    return (along, image_across);
+                }
+                let scaled =
+                    (along as u64 * across as u64 + image_across as u64 / 2) / image_across as u64;
+                (scaled.max(1) as u32, across)
+            }
+            None => (across, across),
+        })
+        .collect();
+    let label_font = match labels {
+        Some(labels) => {
+            let font = FontSet::new(&labels.style)?.with_antialias(options.antialias());
+            let font_size = labels
+                .style
+                .font_size
+                .unwrap_or(across as f32 * 0.1)
+                .max(1.0);
+            Some((labels, font, font_size))
+        }
+        None => None,
+    };
+    let band = label_font
+        .as_ref()
+        .map_or(0, |(_, _, font_size)| (font_size * 2.0).ceil() as u32);
+    //percent units are taken from the images side by side, without gaps and label bands
+    let (gap, margin) = options.spacing(orient((
+        sizes.iter().map(|&(along, _)| along).sum(),
+        across,
+    )));
+    //the band sits above each image, in a row it only adds to the height once
+    let slot = |along: u32| if horizontal { along } else { along + band };
+    let length = sizes.iter().map(|&(along, _)| slot(along)).sum::<u32>()
+        + gap * (sizes.len() as u32).saturating_sub(1);
+    let (width, height) = orient((length, across));
+    let height = if horizontal { height + band } else { height };
+    let (width, height) = (
+        width + margin.left + margin.right,
+        height + margin.top + margin.bottom,
+    );
+    debug!("create strip image buf {}x{}", width, height);
+    let mut tasks = TileTasks::new(Stage::Resize);
+    for (i, (image, &size)) in images.into_iter().zip(sizes.iter()).enumerate() {
+        let (image_width, image_height) = orient(size);
+        let allow_upscale = options.allow_upscale;
+        let resampling = options.resampling();
+        tasks.spawn(i, async move {
+            Ok(match image {
+                Some(image) => resize_member(
+                    image,
+                    i,
+                    image_width,
+                    image_height,
+                    Fit::Stretch,
+                    allow_upscale,
+                    resampling,
+                ),
+                None => placeholder_image(image_width, image_height),
+            })
+        });
+    }
+    let mut canvas = ImageBuffer::from_pixel(width, height, options.background);
+    let decoration = CellDecoration {
+        border: options.cell_border,
+        corner_radius: options.corner_radius,
+        drop_shadow: options.drop_shadow,
+        antialias: options.antialias(),
+        gap: (gap, gap),
+    };
+    let mut offset = 0;
+    for ((image, &(along, _)), index) in tasks
+        .join_all()
+        .await?
+        .into_iter()
+        .zip(sizes.iter())
+        .zip(kept)
+    {
+        let (x, y) = orient((offset, 0));
+        let (x, y) = (margin.left + x, margin.top + y);
+        let cell = orient((along, across));
+        place_tile(
+            &mut canvas,
+            &image.to_rgba8(),
+            options.cell_alignment,
+            (x, y + band),
+            cell,
+            &decoration,
+        )?;
+        if let Some((labels, font, font_size)) = &label_font {
+            let label = &labels.labels[index];
+            let left =
+                x as f32 + (cell.0 as f32 - font.width(label, Scale::uniform(*font_size))) * 0.5;
+            let top = y as f32 + (band as f32 - font_size) * 0.5;
+            font.draw(
+                &mut canvas,
+                labels.color(options),
+                left.max(0.0) as u32,
+                top.max(0.0) as u32,
+                Scale::uniform(*font_size),
+                label,
+            );
+        }
+        offset += slot(along) + gap;
+    }
+    decorate_canvas(&mut canvas, options)?;
+    finish_bundle(DynamicImage::ImageRgba8(canvas), skipped, options).await
+}
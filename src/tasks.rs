@@ -0,0 +1,83 @@
+use crate::{ImageCombinerError, Stage};
+use std::any::Any;
+use std::collections::HashMap;
+use std::future::Future;
+use tokio::task::{Id, JoinError, JoinSet};
+
+//tasks keyed by the tile they work on, the first failure aborts the others
+pub(crate) struct TileTasks<T> {
+    stage: Stage,
+    set: JoinSet<Result<T, ImageCombinerError>>,
+    indices: HashMap<Id, usize>,
+}
+
+impl<T: Send + 'static> TileTasks<T> {
+    pub(crate) fn new(stage: Stage) -> Self {
+        Self {
+            stage,
+            set: JoinSet::new(),
+            indices: HashMap::new(),
+        }
+    }
+
+    pub(crate) fn spawn<F>(&mut self, index: usize, task: F)
+    where
+        F: Future<Output = Result<T, ImageCombinerError>> + Send + 'static,
+    {
+        let handle = self.set.spawn(task);
+        self.indices.insert(handle.id(), index);
+    }
+
+    //results in index order
+    pub(crate) async fn join_all(mut self) -> Result<Vec<T>, ImageCombinerError> {
+        let mut results = Vec::with_capacity(self.indices.len());
+        while let Some(joined) = self.set.join_next_with_id().await {
+            let (index, result) = match joined {
+                Ok((id, result)) => (self.indices[&id], result),
+                Err(e) => {
+                    let index = self.indices[&e.id()];
+                    self.set.abort_all();
+                    return Err(join_error(e, self.stage, Some(index)));
+                }
+            };
+            match result {
+                Ok(value) => results.push((index, value)),
+                Err(error) => {
+                    self.set.abort_all();
+                    return Err(ImageCombinerError::Tile {
+                        index,
+                        error: Box::new(error),
+                    });
+                }
+            }
+        }
+        results.sort_by_key(|&(index, _)| index);
+        Ok(results.into_iter().map(|(_, value)| value).collect())
+    }
+}
+
+//a panicked task becomes InternalPanic, anything else such as cancellation stays a runtime error
+pub(crate) fn join_error(
+    e: JoinError,
+    stage: Stage,
+    image_index: Option<usize>,
+) -> ImageCombinerError {
+    if !e.is_panic() {
+        return ImageCombinerError::Runtime(e);
+    }
+    ImageCombinerError::InternalPanic {
+        stage,
+        image_index,
+        message: panic_message(e.into_panic()),
+    }
+}
+
+pub(crate) fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => match payload.downcast::<&'static str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => "unknown panic".to_string(),
+        },
+    }
+}
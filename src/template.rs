@@ -0,0 +1,186 @@
+use crate::tasks::TileTasks;
+use crate::{
+    decode_images, decorate_canvas, find_optical_dimension, finish_bundle, open_source, place_tile,
+    placeholder_image, resize_member, unsupported_max_canvas, BundledImage, CellDecoration,
+    CreateBundledImageOptions, DecodeErrorPolicy, ImageCombinerError, ImageSource, IntoImageSource,
+    Stage,
+};
+use image::{DynamicImage, GenericImageView, ImageBuffer};
+use log::debug;
+
+//a grid where cells may span several rows and columns, images fill the cells in the order they were added
+#[derive(Debug, Clone, PartialEq)]
+pub struct LayoutTemplate {
+    columns: u32,
+    rows: u32,
+    cells: Vec<TemplateCell>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct TemplateCell {
+    pub row: u32,
+    pub column: u32,
+    pub row_span: u32,
+    pub column_span: u32,
+}
+
+impl LayoutTemplate {
+    pub fn new(columns: u32, rows: u32) -> Self {
+        Self {
+            columns,
+            rows,
+            cells: Vec::new(),
+        }
+    }
+
+    pub fn add_cell(mut self, row: u32, column: u32, row_span: u32, column_span: u32) -> Self {
+        self.cells.push(TemplateCell {
+            row,
+            column,
+            row_span,
+            column_span,
+        });
+        self
+    }
+
+    fn validate(&self) -> Result<(), ImageCombinerError> {
+        let invalid = |message: String| Err(ImageCombinerError::InvalidOptions(message));
+        if self.columns == 0 || self.rows == 0 {
+            return invalid(format!(
+                "template needs at least one row and column columns:{},rows:{}",
+                self.columns, self.rows
+            ));
+        }
+        let mut taken = vec![false; (self.columns * self.rows) as usize];
+        for cell in self.cells.iter() {
+            if cell.row_span == 0
+                || cell.column_span == 0
+                || cell.row + cell.row_span > self.rows
+                || cell.column + cell.column_span > self.columns
+            {
+                return invalid(format!("template cell out of the grid {:?}", cell));
+            }
+            for row in cell.row..cell.row + cell.row_span {
+                for column in cell.column..cell.column + cell.column_span {
+                    let slot = &mut taken[(row * self.columns + column) as usize];
+                    if *slot {
+                        return invalid(format!("template cell overlaps another {:?}", cell));
+                    }
+                    *slot = true;
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+//the member dimension is the size of a single unit cell, spanning cells also cover the gaps they span,
+//the cell of an input skipped under DecodeErrorPolicy::Skip is left blank
+pub async fn create_bundled_image_with_template(
+    buffers: impl IntoImageSource,
+    template: LayoutTemplate,
+    options: CreateBundledImageOptions,
+) -> Result<BundledImage, ImageCombinerError> {
+    template.validate()?;
+    if options.max_canvas.is_some() {
+        return Err(unsupported_max_canvas("template"));
+    }
+    let mut source = open_source(buffers, &options);
+    let mut collected = Vec::new();
+    while let Some(buffer) = source.next_image().await {
+        collected.push(buffer?);
+    }
+    let count = collected.len();
+    if count > template.cells.len() {
+        return Err(ImageCombinerError::InvalidOptions(format!(
+            "more images than template cells images:{},cells:{}",
+            count,
+            template.cells.len()
+        )));
+    }
+    let (decoded, skipped) = decode_images(collected, &options).await?;
+    //skipped inputs are missing from decoded, line the rest up with their cell again
+    let mut decoded = decoded.into_iter();
+    let images: Vec<Option<Option<DynamicImage>>> = (0..count)
+        .map(|index| {
+            if options.on_decode_error == DecodeErrorPolicy::Skip && skipped.contains(&index) {
+                None
+            } else {
+                decoded.next()
+            }
+        })
+        .collect();
+    let (unit_width, unit_height) = options.dimension.unwrap_or_else(|| {
+        find_optical_dimension(
+            &images
+                .iter()
+                .flatten()
+                .flatten()
+                .map(|i| i.dimensions())
+                .collect::<Vec<_>>(),
+        )
+    });
+    let (gap, margin) =
+        options.spacing((template.columns * unit_width, template.rows * unit_height));
+    let span = |count: u32, unit: u32| count * unit + count.saturating_sub(1) * gap;
+    let width = span(template.columns, unit_width) + margin.left + margin.right;
+    let height = span(template.rows, unit_height) + margin.top + margin.bottom;
+    debug!("create template image buf {}x{}", width, height);
+    let mut canvas = ImageBuffer::from_pixel(width, height, options.background);
+    let decoration = CellDecoration {
+        border: options.cell_border,
+        corner_radius: options.corner_radius,
+        drop_shadow: options.drop_shadow,
+        antialias: options.antialias(),
+        gap: (gap, gap),
+    };
+    let cells: Vec<(Option<DynamicImage>, &TemplateCell)> = images
+        .into_iter()
+        .zip(template.cells.iter())
+        .filter_map(|(image, cell)| Some((image?, cell)))
+        .collect();
+    let mut tasks = TileTasks::new(Stage::Resize);
+    for (i, (image, cell)) in cells.iter().cloned().enumerate() {
+        let cell_dimension = (
+            span(cell.column_span, unit_width),
+            span(cell.row_span, unit_height),
+        );
+        let fit = options.fit;
+        let allow_upscale = options.allow_upscale;
+        let resampling = options.resampling();
+        tasks.spawn(i, async move {
+            Ok(match image {
+                Some(image) => resize_member(
+                    image,
+                    i,
+                    cell_dimension.0,
+                    cell_dimension.1,
+                    fit,
+                    allow_upscale,
+                    resampling,
+                ),
+                None => placeholder_image(cell_dimension.0, cell_dimension.1),
+            })
+        });
+    }
+    for (image, (_, cell)) in tasks.join_all().await?.into_iter().zip(cells.iter()) {
+        let origin = (
+            margin.left + cell.column * (unit_width + gap),
+            margin.top + cell.row * (unit_height + gap),
+        );
+        let cell_dimension = (
+            span(cell.column_span, unit_width),
+            span(cell.row_span, unit_height),
+        );
+        place_tile(
+            &mut canvas,
+            &image.to_rgba8(),
+            options.cell_alignment,
+            origin,
+            cell_dimension,
+            &decoration,
+        )?;
+    }
+    decorate_canvas(&mut canvas, &options)?;
+    finish_bundle(DynamicImage::ImageRgba8(canvas), skipped, &options).await
+}
@@ -34,7 +34,8 @@ async fn test_processor() {
     let option = CreateBundledImageOptionsBuilder::new()
         .set_column(2)
         .set_padding(20)
-        .build();
+        .build()
+        .unwrap();
     let image_bytes = create_bundled_image_from_bytes(image_bytes, option)
         .await
         .unwrap();
@@ -127,7 +128,8 @@ async fn test_create_bundle_with_table() {
     let option = CreateBundledImageOptionsBuilder::new()
         .set_column(2)
         .set_padding(20)
-        .build();
+        .build()
+        .unwrap();
     let head = vec![
         "SIZE".to_string(),
         "裙长".to_string(),
@@ -209,7 +211,8 @@ async fn test_create_bundle_with_text() {
     let option = CreateBundledImageOptionsBuilder::new()
         .set_column(2)
         .set_padding(20)
-        .build();
+        .build()
+        .unwrap();
     let font_bytes = std::fs::read("./test/TaipeiSansTCBeta-Light.ttf").unwrap();
     let image_bytes = create_bundled_image_from_bytes_with_text(
         image_bytes,
@@ -279,3 +282,4238 @@ async fn test_create_text_image() {
     // };
     // s3_client.put_object(put_request).await.unwrap();
 }
+
+#[cfg(test)]
+fn sample_image_bytes(width: u32, height: u32, color: [u8; 4]) -> Vec<u8> {
+    let image = image::DynamicImage::ImageRgba8(image::ImageBuffer::from_pixel(
+        width,
+        height,
+        image::Rgba(color),
+    ));
+    let mut bytes = Vec::new();
+    image
+        .write_to(&mut bytes, image::ImageOutputFormat::Png)
+        .unwrap();
+    bytes
+}
+
+//pixels that do not compress, so the encoded size follows the jpeg quality
+#[cfg(test)]
+fn noise_image_bytes(width: u32, height: u32) -> Vec<u8> {
+    let image =
+        image::DynamicImage::ImageRgba8(image::ImageBuffer::from_fn(width, height, |x, y| {
+            let v = ((x * 7919) ^ (y * 104729)).wrapping_mul(2654435761) >> 24;
+            image::Rgba([v as u8, (v * 3) as u8, (v * 7) as u8, 255])
+        }));
+    let mut bytes = Vec::new();
+    image
+        .write_to(&mut bytes, image::ImageOutputFormat::Png)
+        .unwrap();
+    bytes
+}
+
+#[cfg(test)]
+fn test_font_bytes() -> Vec<u8> {
+    std::fs::read("./test/DejaVuSans.ttf").unwrap()
+}
+
+#[cfg(all(test, any(feature = "url", feature = "s3")))]
+async fn serve_image(body: Vec<u8>, failures: usize) -> std::net::SocketAddr {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+    let addr = listener.local_addr().unwrap();
+    tokio::spawn(async move {
+        let mut served = 0;
+        loop {
+            let (mut socket, _) = listener.accept().await.unwrap();
+            let mut request = Vec::new();
+            let mut chunk = [0u8; 1024];
+            while !request.windows(4).any(|w| w == b"\r\n\r\n") {
+                match socket.read(&mut chunk).await {
+                    Ok(0) | Err(_) => break,
+                    Ok(n) => request.extend_from_slice(&chunk[..n]),
+                }
+            }
+            served += 1;
+            let response = if served <= failures {
+                b"HTTP/1.1 500 Internal Server Error\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"
+                    .to_vec()
+            } else {
+                let mut response = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: image/png\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+                    body.len()
+                )
+                .into_bytes();
+                response.extend_from_slice(&body);
+                response
+            };
+            socket.write_all(&response).await.unwrap();
+        }
+    });
+    addr
+}
+
+#[cfg(feature = "url")]
+#[tokio::test(flavor = "multi_thread", worker_threads = 20)]
+async fn test_create_bundled_image_from_urls() {
+    use super::*;
+    let addr = serve_image(sample_image_bytes(40, 60, [200, 0, 0, 255]), 1).await;
+    let urls = (1..=3)
+        .map(|no| format!("http://{}/{}.png", addr, no))
+        .collect();
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_column(3)
+        .set_padding(0)
+        .build()
+        .unwrap();
+    let fetch_options = FetchOptionsBuilder::new()
+        .set_concurrency(2)
+        .set_retries(2)
+        .build();
+    let image_bytes = create_bundled_image_from_urls(urls, option, fetch_options)
+        .await
+        .unwrap();
+    let bundled = image::load_from_memory(&image_bytes).unwrap();
+    assert_eq!((bundled.width(), bundled.height()), (120, 60));
+}
+
+#[cfg(feature = "url")]
+#[tokio::test(flavor = "multi_thread", worker_threads = 20)]
+async fn test_retry_policy() {
+    use super::*;
+    use std::time::Duration;
+    let addr = serve_image(sample_image_bytes(40, 60, [200, 0, 0, 255]), 2).await;
+    let urls = (1..=2)
+        .map(|no| format!("http://{}/{}.png", addr, no))
+        .collect();
+    let fetch_options = FetchOptionsBuilder::new()
+        .set_concurrency(1)
+        .set_retry_policy(RetryPolicy::new(3, Duration::from_millis(10)).with_jitter(0.5))
+        .build();
+    let source = UrlImageSource::new(urls).with_fetch_options(fetch_options);
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_column(2)
+        .build()
+        .unwrap();
+    let bundled = create_bundled_image(source, option).await.unwrap();
+    //the first image took both failures, one at a time
+    assert_eq!(bundled.fetch_attempts, vec![3, 1]);
+
+    let addr = serve_image(sample_image_bytes(40, 60, [200, 0, 0, 255]), 3).await;
+    let fetch_options = FetchOptionsBuilder::new()
+        .set_retry_policy(RetryPolicy::new(3, Duration::from_millis(10)))
+        .build();
+    let source = UrlImageSource::new(vec![format!("http://{}/1.png", addr)])
+        .with_fetch_options(fetch_options);
+    let option = CreateBundledImageOptionsBuilder::new().build().unwrap();
+    assert!(matches!(
+        create_bundled_image(source, option).await,
+        Err(ImageCombinerError::Fetch(_))
+    ));
+}
+
+#[cfg(feature = "url")]
+#[tokio::test(flavor = "multi_thread", worker_threads = 20)]
+async fn test_rate_limit() {
+    use super::*;
+    let addr = serve_image(sample_image_bytes(40, 60, [200, 0, 0, 255]), 0).await;
+    let urls: Vec<String> = (1..=4)
+        .map(|no| format!("http://{}/{}.png", addr, no))
+        .collect();
+    let fetch = |fetch_options: FetchOptions| {
+        let urls = urls.clone();
+        async move {
+            let started = std::time::Instant::now();
+            let source = UrlImageSource::new(urls).with_fetch_options(fetch_options);
+            let option = CreateBundledImageOptionsBuilder::new()
+                .set_column(4)
+                .build()
+                .unwrap();
+            create_bundled_image(source, option).await.unwrap();
+            started.elapsed()
+        }
+    };
+    //the wait itself is checked on a paused clock in test_rate_limiter
+    let limited = FetchOptionsBuilder::new()
+        .set_rate_limit(RateLimit::new(20.0, 1))
+        .build();
+    fetch(limited).await;
+    //a limit for another host leaves this one alone
+    let other_host = FetchOptionsBuilder::new()
+        .set_host_rate_limits(vec![("example.com".to_string(), RateLimit::new(0.1, 1))])
+        .build();
+    assert!(fetch(other_host).await < std::time::Duration::from_secs(5));
+}
+
+#[cfg(any(feature = "url", feature = "s3"))]
+#[tokio::test]
+async fn test_rate_limiter() {
+    use super::*;
+    use std::time::Duration;
+    tokio::time::pause();
+    let limiter = FetchOptionsBuilder::new()
+        .set_rate_limit(RateLimit::new(20.0, 1))
+        .set_host_rate_limits(vec![("Example.com".to_string(), RateLimit::new(2.0, 2))])
+        .build()
+        .rate_limiter
+        .unwrap();
+    let waited = |started: tokio::time::Instant, expected: u64| {
+        let elapsed = started.elapsed();
+        assert!(
+            elapsed >= Duration::from_millis(expected) - Duration::from_micros(1)
+                && elapsed < Duration::from_millis(expected + 5),
+            "waited {:?} instead of {}ms",
+            elapsed,
+            expected
+        );
+    };
+    //one request at once and then one every 50ms
+    let started = tokio::time::Instant::now();
+    for _ in 0..4 {
+        limiter.acquire("127.0.0.1:8080").await;
+    }
+    waited(started, 150);
+    //hosts that differ only in case share one bucket, two at once and then one every 500ms
+    let started = tokio::time::Instant::now();
+    for host in ["example.com", "EXAMPLE.COM", "Example.Com"] {
+        limiter.acquire(host).await;
+    }
+    waited(started, 500);
+}
+
+#[cfg(any(feature = "url", feature = "s3"))]
+#[test]
+fn test_retry_jitter() {
+    use super::rng::SeededRng;
+    use super::*;
+    use std::time::Duration;
+    let policy = RetryPolicy::new(4, Duration::from_millis(100)).with_jitter(0.5);
+    let delays = |seed: Option<u64>, input: u64| {
+        let mut rng = SeededRng::new(seed).fork(input);
+        (0..3)
+            .map(|attempt| policy.delay(&mut rng, attempt))
+            .collect::<Vec<_>>()
+    };
+    //a seed repeats the same delays for each input, the inputs still spread out
+    assert_eq!(delays(Some(42), 0), delays(Some(42), 0));
+    assert_ne!(delays(Some(42), 0), delays(Some(42), 1));
+    for (attempt, delay) in delays(None, 0).into_iter().enumerate() {
+        let full = Duration::from_millis(100 << attempt);
+        assert!(delay > full / 2 && delay <= full);
+    }
+}
+
+#[cfg(any(feature = "url", feature = "s3"))]
+#[tokio::test]
+async fn test_fetch_all_abort() {
+    use super::fetch::{fetch_all, FetchError};
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Arc;
+    struct Dropped(Arc<AtomicBool>);
+    impl Drop for Dropped {
+        fn drop(&mut self) {
+            self.0.store(true, Ordering::SeqCst);
+        }
+    }
+    let dropped = Arc::new(AtomicBool::new(false));
+    let fetch_options = FetchOptionsBuilder::new().set_concurrency(3).build();
+    let fetch = {
+        let dropped = Arc::clone(&dropped);
+        move |target: usize| {
+            let dropped = Arc::clone(&dropped);
+            async move {
+                match target {
+                    //the first fetch never finishes until it is aborted
+                    0 => {
+                        let _guard = Dropped(dropped);
+                        std::future::pending::<()>().await;
+                        unreachable!()
+                    }
+                    1 => Ok(sample_image_bytes(4, 4, [0, 0, 0, 255])),
+                    _ => Err(FetchError::new("gone", false)),
+                }
+            }
+        }
+    };
+    //the failure of the last target is seen without waiting on the first
+    let result = fetch_all(vec![0, 1, 2], &fetch_options, |_| "host".to_string(), fetch).await;
+    assert!(matches!(result, Err(ImageCombinerError::Fetch(_))));
+    tokio::task::yield_now().await;
+    assert!(dropped.load(Ordering::SeqCst));
+}
+
+#[cfg(feature = "s3")]
+#[tokio::test(flavor = "multi_thread", worker_threads = 20)]
+async fn test_create_bundled_image_from_s3() {
+    use super::s3::*;
+    use super::*;
+    use rusoto_core::credential::StaticProvider;
+    use rusoto_core::{HttpClient, Region};
+    use rusoto_s3::S3Client;
+    let addr = serve_image(sample_image_bytes(40, 60, [200, 0, 0, 255]), 1).await;
+    let client = S3Client::new_with(
+        HttpClient::new().unwrap(),
+        StaticProvider::new_minimal("key".to_string(), "secret".to_string()),
+        Region::Custom {
+            name: "local".to_string(),
+            endpoint: format!("http://{}", addr),
+        },
+    );
+    let keys = (1..=2).map(|no| format!("item_{}.jpeg", no)).collect();
+    let source = S3ImageSource::new(client, "phitemspics".to_string(), keys);
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_column(2)
+        .set_padding(0)
+        .build()
+        .unwrap();
+    let image_bytes =
+        create_bundled_image_from_s3(source, option, FetchOptionsBuilder::new().build())
+            .await
+            .unwrap();
+    let bundled = image::load_from_memory(&image_bytes).unwrap();
+    assert_eq!((bundled.width(), bundled.height()), (80, 60));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 20)]
+async fn test_normalize_orientation() {
+    use super::*;
+    let mut buffers = vec![sample_image_bytes(40, 60, [200, 0, 0, 255]); 3];
+    buffers.push(sample_image_bytes(60, 40, [0, 0, 200, 255]));
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_column(4)
+        .set_padding(0)
+        .set_normalize_orientation(true)
+        .build()
+        .unwrap();
+    let image_bytes = create_bundled_image_from_bytes(buffers, option)
+        .await
+        .unwrap();
+    let bundled = image::load_from_memory(&image_bytes).unwrap().to_rgba8();
+    assert_eq!(bundled.dimensions(), (160, 60));
+    //rotated landscape image fills its whole cell instead of being letterboxed
+    let pixel = bundled.get_pixel(140, 5);
+    assert!(pixel[2] > 150 && pixel[0] < 100);
+}
+
+#[test]
+fn test_suggest_layout() {
+    use super::*;
+    let constraints = LayoutConstraintsBuilder::new().set_padding(0).build();
+    let suggested = suggest_layout(&[(100, 100); 7], &constraints);
+    assert_eq!(
+        suggested,
+        SuggestedOptions {
+            column: 3,
+            member_dimension: (100, 100),
+            fit: Fit::Cover,
+        }
+    );
+    //inputs of about the same shape fill their cells, mixed shapes are kept whole
+    assert_eq!(
+        suggest_layout(&[(100, 100), (110, 100), (100, 90)], &constraints).fit,
+        Fit::Cover
+    );
+    assert_eq!(
+        suggest_layout(&[(160, 90), (90, 160), (100, 100)], &constraints).fit,
+        Fit::Contain
+    );
+    assert_eq!(suggest_layout(&[], &constraints).fit, Fit::Contain);
+    let constraints = LayoutConstraintsBuilder::new()
+        .set_padding(0)
+        .set_target_aspect_ratio(4, 1)
+        .build();
+    assert_eq!(suggest_layout(&[(100, 100); 7], &constraints).column, 4);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 20)]
+async fn test_create_bundled_image_from_file_source() {
+    use super::*;
+    let dir = std::env::temp_dir().join("image_combiner_file_source");
+    std::fs::create_dir_all(&dir).unwrap();
+    let paths: Vec<std::path::PathBuf> = (1..=3)
+        .map(|no| {
+            let path = dir.join(format!("{}.png", no));
+            std::fs::write(&path, sample_image_bytes(40, 60, [200, 0, 0, 255])).unwrap();
+            path
+        })
+        .collect();
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_column(3)
+        .set_padding(0)
+        .build()
+        .unwrap();
+    let image_bytes = create_bundled_image_from_bytes(FileImageSource::new(paths), option)
+        .await
+        .unwrap();
+    let bundled = image::load_from_memory(&image_bytes).unwrap();
+    assert_eq!((bundled.width(), bundled.height()), (120, 60));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 20)]
+async fn test_order() {
+    use super::*;
+    let buffers = vec![
+        sample_image_bytes(40, 40, [200, 0, 0, 255]),
+        sample_image_bytes(40, 40, [0, 0, 200, 255]),
+    ];
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_column(2)
+        .set_padding(0)
+        .set_order(vec![1, 0])
+        .build()
+        .unwrap();
+    let image_bytes = create_bundled_image_from_bytes(buffers.clone(), option)
+        .await
+        .unwrap();
+    let bundled = image::load_from_memory(&image_bytes).unwrap().to_rgba8();
+    assert!(bundled.get_pixel(10, 10)[2] > 150);
+    assert!(bundled.get_pixel(50, 10)[0] > 150);
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_order(vec![0, 0])
+        .build()
+        .unwrap();
+    assert!(matches!(
+        create_bundled_image_from_bytes(buffers, option).await,
+        Err(ImageCombinerError::InvalidOptions(_))
+    ));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 20)]
+async fn test_reserved_cells() {
+    use super::*;
+    let buffers = vec![sample_image_bytes(40, 40, [200, 0, 0, 255]); 3];
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_column(2)
+        .set_padding(0)
+        .set_reserved_cells(vec![(0, 1)])
+        .build()
+        .unwrap();
+    let image_bytes = create_bundled_image_from_bytes(buffers, option)
+        .await
+        .unwrap();
+    let bundled = image::load_from_memory(&image_bytes).unwrap().to_rgba8();
+    assert_eq!(bundled.dimensions(), (80, 80));
+    assert!(bundled.get_pixel(10, 10)[0] > 150);
+    assert!(bundled.get_pixel(60, 10)[1] > 200);
+    assert!(bundled.get_pixel(60, 60)[1] < 100);
+}
+
+#[cfg(feature = "zip")]
+#[tokio::test(flavor = "multi_thread", worker_threads = 20)]
+async fn test_create_bundled_image_from_zip() {
+    use super::*;
+    use std::io::Write;
+    let mut writer = zip::ZipWriter::new(std::io::Cursor::new(Vec::new()));
+    let entries = vec![
+        (
+            "photos/item_10.png",
+            sample_image_bytes(40, 40, [0, 0, 200, 255]),
+        ),
+        (
+            "photos/item_2.png",
+            sample_image_bytes(40, 40, [200, 0, 0, 255]),
+        ),
+        ("photos/readme.txt", b"not an image".to_vec()),
+        ("__MACOSX/photos/._item_2.png", b"resource fork".to_vec()),
+    ];
+    for (name, bytes) in entries {
+        writer
+            .start_file(name, zip::write::SimpleFileOptions::default())
+            .unwrap();
+        writer.write_all(&bytes).unwrap();
+    }
+    let zip_bytes = writer.finish().unwrap().into_inner();
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_column(2)
+        .set_padding(0)
+        .build()
+        .unwrap();
+    let image_bytes = create_bundled_image_from_zip(zip_bytes, option)
+        .await
+        .unwrap();
+    let bundled = image::load_from_memory(&image_bytes).unwrap().to_rgba8();
+    assert_eq!(bundled.dimensions(), (80, 40));
+    assert!(bundled.get_pixel(10, 10)[0] > 150);
+    assert!(bundled.get_pixel(50, 10)[2] > 150);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 20)]
+async fn test_exif_orientation() {
+    use super::*;
+    let image = image::DynamicImage::ImageRgb8(image::ImageBuffer::from_pixel(
+        40,
+        60,
+        image::Rgb([200u8, 0, 0]),
+    ));
+    let mut jpeg = Vec::new();
+    image
+        .write_to(&mut jpeg, image::ImageOutputFormat::Jpeg(90))
+        .unwrap();
+    //big endian TIFF header with a single IFD entry: Orientation(0x0112) = 6
+    let tiff: [u8; 26] = [
+        0x4d, 0x4d, 0x00, 0x2a, 0x00, 0x00, 0x00, 0x08, 0x00, 0x01, 0x01, 0x12, 0x00, 0x03, 0x00,
+        0x00, 0x00, 0x01, 0x00, 0x06, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+    ];
+    let mut app1 = vec![0xff, 0xe1];
+    app1.extend_from_slice(&((2 + 6 + tiff.len()) as u16).to_be_bytes());
+    app1.extend_from_slice(b"Exif\0\0");
+    app1.extend_from_slice(&tiff);
+    jpeg.splice(2..2, app1);
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_padding(0)
+        .build()
+        .unwrap();
+    let image_bytes = create_bundled_image_from_bytes(vec![jpeg.clone()], option)
+        .await
+        .unwrap();
+    let bundled = image::load_from_memory(&image_bytes).unwrap();
+    assert_eq!((bundled.width(), bundled.height()), (60, 40));
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_padding(0)
+        .set_exif_orientation(false)
+        .build()
+        .unwrap();
+    let image_bytes = create_bundled_image_from_bytes(vec![jpeg], option)
+        .await
+        .unwrap();
+    let bundled = image::load_from_memory(&image_bytes).unwrap();
+    assert_eq!((bundled.width(), bundled.height()), (40, 60));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 20)]
+async fn test_create_bundled_image_from_tiles() {
+    use super::*;
+    let tiles = vec![
+        Tile::Image(sample_image_bytes(40, 40, [200, 0, 0, 255])),
+        Tile::Logo(sample_image_bytes(20, 20, [0, 0, 200, 255]), Fit::Contain),
+        Tile::Logo(sample_image_bytes(200, 100, [0, 200, 0, 255]), Fit::Contain),
+    ];
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_column(3)
+        .set_padding(0)
+        .build()
+        .unwrap();
+    let image_bytes = create_bundled_image_from_tiles(tiles, option)
+        .await
+        .unwrap();
+    let bundled = image::load_from_memory(&image_bytes).unwrap().to_rgba8();
+    assert_eq!(bundled.dimensions(), (120, 40));
+    //small logo keeps its native size and sits in the middle of the cell
+    assert!(bundled.get_pixel(60, 20)[2] > 150);
+    assert!(bundled.get_pixel(44, 4)[1] > 200);
+    //large logo is scaled down to 40x20 and centered vertically
+    assert!(bundled.get_pixel(100, 20)[1] > 150);
+    assert!(bundled.get_pixel(100, 4)[0] > 200);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 20)]
+async fn test_on_decode_error() {
+    use super::*;
+    let buffers = vec![
+        sample_image_bytes(40, 40, [200, 0, 0, 255]),
+        b"not an image".to_vec(),
+        sample_image_bytes(40, 40, [200, 0, 0, 255]),
+    ];
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_column(3)
+        .set_padding(0)
+        .build()
+        .unwrap();
+    assert!(create_bundled_image(buffers.clone(), option).await.is_err());
+
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_column(3)
+        .set_padding(0)
+        .set_on_decode_error(DecodeErrorPolicy::Skip)
+        .build()
+        .unwrap();
+    let bundled = create_bundled_image(buffers.clone(), option).await.unwrap();
+    assert_eq!(bundled.skipped, vec![1]);
+    let image = image::load_from_memory(&bundled.bytes).unwrap().to_rgba8();
+    assert!(image.get_pixel(60, 20)[1] < 100);
+
+    //a header that probes fine over a body that fails to decode drops its cell whether the member size is given or not
+    let mut truncated = sample_image_bytes(40, 40, [0, 200, 0, 255]);
+    truncated.truncate(truncated.len() / 2);
+    let buffers_with_truncated = vec![buffers[0].clone(), truncated, buffers[2].clone()];
+    let skip = |builder: CreateBundledImageOptionsBuilder| {
+        builder
+            .set_column(3)
+            .set_padding(0)
+            .set_output_format(OutputFormat::Png)
+            .set_on_decode_error(DecodeErrorPolicy::Skip)
+            .build()
+            .unwrap()
+    };
+    let eager = create_bundled_image(
+        buffers_with_truncated.clone(),
+        skip(CreateBundledImageOptionsBuilder::new()),
+    )
+    .await
+    .unwrap();
+    let sized = create_bundled_image(
+        buffers_with_truncated,
+        skip(CreateBundledImageOptionsBuilder::new().set_member_dimension(40, 40)),
+    )
+    .await
+    .unwrap();
+    assert_eq!(eager.skipped, vec![1]);
+    assert_eq!(sized.skipped, vec![1]);
+    assert_eq!(eager.bytes, sized.bytes);
+    let image = image::load_from_memory(&sized.bytes).unwrap().to_rgba8();
+    assert_eq!(image.get_pixel(60, 20), &Rgba([200, 0, 0, 255]));
+
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_column(3)
+        .set_padding(0)
+        .set_on_decode_error(DecodeErrorPolicy::Placeholder)
+        .build()
+        .unwrap();
+    let bundled = create_bundled_image(buffers, option).await.unwrap();
+    assert_eq!(bundled.skipped, vec![1]);
+    let image = image::load_from_memory(&bundled.bytes).unwrap().to_rgba8();
+    let pixel = image.get_pixel(50, 30);
+    assert!(pixel[0] > 180 && pixel[0] < 240 && pixel[1] > 180);
+    assert!(image.get_pixel(100, 20)[1] < 100);
+}
+
+#[test]
+fn test_wrap_text() {
+    use super::*;
+    assert_eq!(
+        wrap_text("他のカラーはこちら", 10.0, 45.0),
+        vec!["他のカラ", "ーはこち", "ら"]
+    );
+    assert_eq!(
+        wrap_text("hello world foo", 10.0, 30.0),
+        vec!["hello", "world", "foo"]
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 20)]
+async fn test_text_card_tile() {
+    use super::*;
+    let tiles = vec![
+        Tile::Image(sample_image_bytes(80, 80, [200, 0, 0, 255])),
+        Tile::TextCard {
+            text: "other colors here".to_string(),
+            style: TextCardStyle::new(test_font_bytes(), Rgba([255, 255, 255, 255]), None),
+            background: Rgba([0, 0, 0, 255]),
+        },
+    ];
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_column(2)
+        .set_padding(0)
+        .build()
+        .unwrap();
+    let image_bytes = create_bundled_image_from_tiles(tiles, option)
+        .await
+        .unwrap();
+    let bundled = image::load_from_memory(&image_bytes).unwrap().to_rgba8();
+    assert_eq!(bundled.dimensions(), (160, 80));
+    let card_pixels: Vec<_> = (80..160)
+        .flat_map(|x| (0..80).map(move |y| (x, y)))
+        .map(|(x, y)| bundled.get_pixel(x, y)[0])
+        .collect();
+    assert!(card_pixels.iter().filter(|&&v| v < 50).count() > card_pixels.len() / 2);
+    assert!(card_pixels.iter().any(|&v| v > 200));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 20)]
+async fn test_decode_failures() {
+    use super::*;
+    let buffers = vec![
+        b"broken".to_vec(),
+        sample_image_bytes(40, 40, [200, 0, 0, 255]),
+        b"also broken".to_vec(),
+    ];
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_order(vec![2, 1, 0])
+        .build()
+        .unwrap();
+    match create_bundled_image_from_bytes(buffers, option).await {
+        Err(ImageCombinerError::Decode(failures)) => {
+            let failed: Vec<(usize, usize)> = failures.iter().map(|f| (f.index, f.size)).collect();
+            assert_eq!(failed, vec![(0, 6), (2, 11)]);
+        }
+        _ => panic!("expected decode failures"),
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 20)]
+async fn test_encode_decode() {
+    use super::*;
+    let image = decode(
+        &sample_image_bytes(40, 30, [200, 0, 0, 255]),
+        &DecodeOptions::default(),
+    )
+    .unwrap();
+    let icc = b"fake icc profile".to_vec();
+    let encode_options = EncodeOptionsBuilder::new()
+        .set_quality(80)
+        .set_subsampling(Subsampling::Yuv420)
+        .set_icc_profile(icc.clone())
+        .build();
+    let jpeg = encode(&image, OutputFormat::Jpeg, &encode_options).unwrap();
+    assert_eq!(
+        image::guess_format(&jpeg).unwrap(),
+        image::ImageFormat::Jpeg
+    );
+    assert_eq!(icc_profile(&jpeg), Some(icc));
+    assert_eq!(
+        decode(&jpeg, &DecodeOptions::new(false))
+            .unwrap()
+            .dimensions(),
+        (40, 30)
+    );
+
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_output_format(OutputFormat::Png)
+        .build()
+        .unwrap();
+    let image_bytes =
+        create_bundled_image_from_bytes(vec![sample_image_bytes(40, 30, [200, 0, 0, 255])], option)
+            .await
+            .unwrap();
+    assert_eq!(
+        image::guess_format(&image_bytes).unwrap(),
+        image::ImageFormat::Png
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 20)]
+async fn test_worker_pool() {
+    use super::*;
+    let worker_pool = WorkerPool::new(2).unwrap();
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_column(2)
+        .set_padding(0)
+        .set_on_decode_error(DecodeErrorPolicy::Skip)
+        .set_output_format(OutputFormat::Png)
+        .set_worker_pool(worker_pool.clone())
+        .build()
+        .unwrap();
+    let buffers = vec![
+        sample_image_bytes(40, 30, [200, 0, 0, 255]),
+        b"not an image".to_vec(),
+        sample_image_bytes(80, 60, [0, 200, 0, 255]),
+        sample_image_bytes(40, 30, [0, 0, 200, 255]),
+    ];
+    let bundled = create_bundled_image(buffers, option).await.unwrap();
+    assert_eq!(bundled.skipped, vec![1]);
+    let image = image::load_from_memory(&bundled.bytes).unwrap();
+    assert_eq!(image.dimensions(), (80, 60));
+
+    let panicked = worker_pool
+        .spawn(Stage::Resize, Some(3), || panic!("boom"))
+        .await;
+    match panicked {
+        Err(ImageCombinerError::InternalPanic {
+            stage,
+            image_index,
+            message,
+        }) => {
+            assert_eq!(stage, Stage::Resize);
+            assert_eq!(image_index, Some(3));
+            assert_eq!(message, "boom");
+        }
+        _ => panic!("expected an internal panic"),
+    }
+    assert_eq!(
+        worker_pool
+            .spawn(Stage::Encode, None, || 1 + 1)
+            .await
+            .unwrap(),
+        2
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 20)]
+async fn test_rows() {
+    use super::*;
+    let buffers: Vec<Vec<u8>> = (0..5)
+        .map(|_| sample_image_bytes(40, 30, [200, 0, 0, 255]))
+        .collect();
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_rows(2)
+        .set_padding(0)
+        .set_output_format(OutputFormat::Png)
+        .build()
+        .unwrap();
+    let image_bytes = create_bundled_image_from_bytes(buffers.clone(), option)
+        .await
+        .unwrap();
+    let image = image::load_from_memory(&image_bytes).unwrap();
+    assert_eq!(image.dimensions(), (120, 60));
+
+    let result = CreateBundledImageOptionsBuilder::new()
+        .set_rows(2)
+        .set_column(3)
+        .build();
+    assert!(matches!(result, Err(ImageCombinerError::InvalidOptions(_))));
+    //0 is a caller error, not an unset column
+    for builder in [
+        CreateBundledImageOptionsBuilder::new().set_column(0),
+        CreateBundledImageOptionsBuilder::new().set_rows(0),
+        CreateBundledImageOptionsBuilder::new()
+            .set_column(0)
+            .set_rows(2),
+    ] {
+        assert!(matches!(
+            builder.build(),
+            Err(ImageCombinerError::InvalidOptions(_))
+        ));
+    }
+    let option = CreateBundledImageOptions::new(None, 0, 0);
+    let result = create_bundled_image_from_bytes(buffers, option).await;
+    assert!(matches!(result, Err(ImageCombinerError::InvalidOptions(_))));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 20)]
+async fn test_auto_layout() {
+    use super::*;
+    let buffers: Vec<Vec<u8>> = (0..7)
+        .map(|_| sample_image_bytes(40, 40, [200, 0, 0, 255]))
+        .collect();
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_auto_layout(1, 1)
+        .set_padding(0)
+        .set_output_format(OutputFormat::Png)
+        .build()
+        .unwrap();
+    let image_bytes = create_bundled_image_from_bytes(buffers.clone(), option)
+        .await
+        .unwrap();
+    let image = image::load_from_memory(&image_bytes).unwrap();
+    assert_eq!(image.dimensions(), (120, 120));
+
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_auto_layout(4, 1)
+        .set_padding(0)
+        .set_output_format(OutputFormat::Png)
+        .build()
+        .unwrap();
+    let image_bytes = create_bundled_image_from_bytes(buffers.clone(), option)
+        .await
+        .unwrap();
+    let image = image::load_from_memory(&image_bytes).unwrap();
+    assert_eq!(image.dimensions(), (240, 80));
+
+    let result = CreateBundledImageOptionsBuilder::new()
+        .set_auto_layout(1, 1)
+        .set_rows(2)
+        .build();
+    assert!(matches!(result, Err(ImageCombinerError::InvalidOptions(_))));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 20)]
+async fn test_stream_image_source() {
+    use super::*;
+    let items: Vec<Result<bytes::Bytes, String>> = vec![
+        Ok(sample_image_bytes(80, 60, [200, 0, 0, 255]).into()),
+        Ok(sample_image_bytes(40, 30, [0, 200, 0, 255]).into()),
+        Ok(sample_image_bytes(40, 30, [0, 0, 200, 255]).into()),
+    ];
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_member_dimension(40, 30)
+        .set_column(3)
+        .set_padding(0)
+        .set_output_format(OutputFormat::Png)
+        .build()
+        .unwrap();
+    let source = StreamImageSource::new(futures_util::stream::iter(items));
+    let image_bytes = create_bundled_image_from_bytes(source, option)
+        .await
+        .unwrap();
+    let image = image::load_from_memory(&image_bytes).unwrap().to_rgba8();
+    assert_eq!(image.dimensions(), (120, 30));
+    assert_eq!(image.get_pixel(20, 15), &Rgba([200, 0, 0, 255]));
+    assert_eq!(image.get_pixel(100, 15), &Rgba([0, 0, 200, 255]));
+
+    let items: Vec<Result<bytes::Bytes, String>> = vec![
+        Ok(sample_image_bytes(40, 30, [200, 0, 0, 255]).into()),
+        Err("connection reset".to_string()),
+    ];
+    let option = CreateBundledImageOptionsBuilder::new().build().unwrap();
+    let source = StreamImageSource::new(futures_util::stream::iter(items));
+    let result = create_bundled_image_from_bytes(source, option).await;
+    assert!(matches!(result, Err(ImageCombinerError::Fetch(_))));
+
+    //a source is not pulled further while the decodes already in flight wait for a worker
+    struct CountingSource {
+        buffers: Vec<Vec<u8>>,
+        pulled: Arc<std::sync::atomic::AtomicUsize>,
+    }
+    impl ImageSource for CountingSource {
+        async fn next_image(&mut self) -> Option<Result<Vec<u8>, ImageCombinerError>> {
+            let buffer = self.buffers.pop()?;
+            self.pulled
+                .fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Some(Ok(buffer))
+        }
+    }
+    let limit = std::thread::available_parallelism().map_or(4, |n| n.get());
+    let worker_pool = WorkerPool::new(1).unwrap();
+    let (release, blocked) = std::sync::mpsc::channel::<()>();
+    let busy = worker_pool.spawn(Stage::Decode, None, move || blocked.recv().unwrap());
+    let pulled = Arc::new(std::sync::atomic::AtomicUsize::new(0));
+    let source = CountingSource {
+        buffers: vec![sample_image_bytes(40, 30, [200, 0, 0, 255]); limit + 3],
+        pulled: Arc::clone(&pulled),
+    };
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_member_dimension(40, 30)
+        .set_column(limit as u32 + 3)
+        .set_padding(0)
+        .set_worker_pool(worker_pool)
+        .set_output_format(OutputFormat::Png)
+        .build()
+        .unwrap();
+    let render = tokio::spawn(create_bundled_image(source, option));
+    tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+    assert_eq!(pulled.load(std::sync::atomic::Ordering::SeqCst), limit);
+    release.send(()).unwrap();
+    busy.await.unwrap();
+    let bundled = render.await.unwrap().unwrap();
+    assert_eq!(pulled.load(std::sync::atomic::Ordering::SeqCst), limit + 3);
+    let image = image::load_from_memory(&bundled.bytes).unwrap();
+    assert_eq!(image.dimensions(), (40 * (limit as u32 + 3), 30));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 20)]
+async fn test_fill_order() {
+    use super::*;
+    let colors = [
+        [200, 0, 0, 255],
+        [0, 200, 0, 255],
+        [0, 0, 200, 255],
+        [200, 200, 0, 255],
+    ];
+    let buffers: Vec<Vec<u8>> = colors
+        .iter()
+        .map(|&color| sample_image_bytes(40, 30, color))
+        .collect();
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_column(2)
+        .set_padding(0)
+        .set_fill_order(FillOrder::ColumnMajor)
+        .set_output_format(OutputFormat::Png)
+        .build()
+        .unwrap();
+    let image_bytes = create_bundled_image_from_bytes(buffers, option)
+        .await
+        .unwrap();
+    let image = image::load_from_memory(&image_bytes).unwrap().to_rgba8();
+    assert_eq!(image.dimensions(), (80, 60));
+    assert_eq!(image.get_pixel(20, 15), &Rgba(colors[0]));
+    assert_eq!(image.get_pixel(20, 45), &Rgba(colors[1]));
+    assert_eq!(image.get_pixel(60, 15), &Rgba(colors[2]));
+    assert_eq!(image.get_pixel(60, 45), &Rgba(colors[3]));
+}
+
+#[test]
+fn test_probe_dimensions() {
+    use super::*;
+    let (width, height, format) =
+        probe_dimensions(&sample_image_bytes(40, 30, [200, 0, 0, 255])).unwrap();
+    assert_eq!((width, height), (40, 30));
+    assert_eq!(format, image::ImageFormat::Png);
+    assert!(probe_dimensions(b"not an image").is_err());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 20)]
+async fn test_cell_alignment() {
+    use super::*;
+    let red = [200, 0, 0, 255];
+    let buffers = vec![sample_image_bytes(20, 30, red)];
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_member_dimension(40, 30)
+        .set_padding(0)
+        .set_output_format(OutputFormat::Png)
+        .build()
+        .unwrap();
+    let image_bytes = create_bundled_image_from_bytes(buffers.clone(), option)
+        .await
+        .unwrap();
+    let image = image::load_from_memory(&image_bytes).unwrap().to_rgba8();
+    assert_eq!(image.get_pixel(5, 15), &WHITE_COLOR);
+    assert_eq!(image.get_pixel(20, 15), &Rgba(red));
+    assert_eq!(image.get_pixel(35, 15), &WHITE_COLOR);
+
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_member_dimension(40, 30)
+        .set_padding(0)
+        .set_cell_alignment(CellAlignment::BottomRight)
+        .set_output_format(OutputFormat::Png)
+        .build()
+        .unwrap();
+    let image_bytes = create_bundled_image_from_bytes(buffers, option)
+        .await
+        .unwrap();
+    let image = image::load_from_memory(&image_bytes).unwrap().to_rgba8();
+    assert_eq!(image.get_pixel(5, 15), &WHITE_COLOR);
+    assert_eq!(image.get_pixel(35, 15), &Rgba(red));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 20)]
+async fn test_pending_tile_decode_failure() {
+    use super::*;
+    //header is intact so the layout is planned, the pixel data fails once the tile is drawn
+    let mut truncated = sample_image_bytes(40, 30, [0, 200, 0, 255]);
+    truncated.truncate(60);
+    assert!(probe_dimensions(&truncated).is_ok());
+    let buffers = vec![sample_image_bytes(40, 30, [200, 0, 0, 255]), truncated];
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_column(2)
+        .set_padding(0)
+        .set_on_decode_error(DecodeErrorPolicy::Placeholder)
+        .set_output_format(OutputFormat::Png)
+        .build()
+        .unwrap();
+    let bundled = create_bundled_image(buffers.clone(), option).await.unwrap();
+    assert_eq!(bundled.skipped, vec![1]);
+    let image = image::load_from_memory(&bundled.bytes).unwrap().to_rgba8();
+    assert_eq!(image.dimensions(), (80, 30));
+    assert_eq!(image.get_pixel(20, 15), &Rgba([200, 0, 0, 255]));
+    assert_eq!(image.get_pixel(60, 2), &GRAY_COLOR);
+
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_column(2)
+        .build()
+        .unwrap();
+    match create_bundled_image(buffers, option).await {
+        Err(ImageCombinerError::Decode(failures)) => {
+            assert_eq!(failures.len(), 1);
+            assert_eq!(failures[0].index, 1);
+            assert_eq!(failures[0].size, 60);
+        }
+        _ => panic!("expected a decode error"),
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 20)]
+async fn test_fit() {
+    use super::*;
+    let buffers = vec![sample_image_bytes(20, 30, [200, 0, 0, 255])];
+    for fit in [Fit::Cover, Fit::Stretch] {
+        let option = CreateBundledImageOptionsBuilder::new()
+            .set_member_dimension(40, 30)
+            .set_padding(0)
+            .set_fit(fit)
+            .set_allow_upscale(true)
+            .set_output_format(OutputFormat::Png)
+            .build()
+            .unwrap();
+        let image_bytes = create_bundled_image_from_bytes(buffers.clone(), option)
+            .await
+            .unwrap();
+        let image = image::load_from_memory(&image_bytes).unwrap().to_rgba8();
+        assert_eq!(image.dimensions(), (40, 30));
+        assert_eq!(image.get_pixel(1, 15), &Rgba([200, 0, 0, 255]));
+        assert_eq!(image.get_pixel(38, 15), &Rgba([200, 0, 0, 255]));
+    }
+
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_member_dimension(10, 10)
+        .set_padding(0)
+        .set_fit(Fit::CropCenter)
+        .set_output_format(OutputFormat::Png)
+        .build()
+        .unwrap();
+    let image_bytes = create_bundled_image_from_bytes(buffers, option)
+        .await
+        .unwrap();
+    let image = image::load_from_memory(&image_bytes).unwrap();
+    assert_eq!(image.dimensions(), (10, 10));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 20)]
+async fn test_cache_dir() {
+    use super::*;
+    let path = std::env::temp_dir().join(format!("image-combiner-cache-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&path);
+    let option = || {
+        CreateBundledImageOptionsBuilder::new()
+            .set_cache_dir(CacheDir::new(&path))
+            .build()
+            .unwrap()
+    };
+    let buffers = vec![sample_image_bytes(40, 30, [200, 0, 0, 255])];
+    let image_bytes = create_bundled_image_from_bytes(buffers.clone(), option())
+        .await
+        .unwrap();
+    let entries: Vec<_> = std::fs::read_dir(&path)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+    assert_eq!(entries.len(), 1);
+    assert_eq!(std::fs::read(&entries[0]).unwrap(), image_bytes);
+
+    //a hit is served from the file without rendering again
+    std::fs::write(&entries[0], b"cached").unwrap();
+    let cached = create_bundled_image_from_bytes(buffers.clone(), option())
+        .await
+        .unwrap();
+    assert_eq!(cached, b"cached".to_vec());
+
+    let buffers = vec![sample_image_bytes(40, 30, [0, 200, 0, 255])];
+    create_bundled_image_from_bytes(buffers, option())
+        .await
+        .unwrap();
+    assert_eq!(std::fs::read_dir(&path).unwrap().count(), 2);
+    std::fs::remove_dir_all(&path).unwrap();
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 20)]
+async fn test_background() {
+    use super::*;
+    let background = Rgba([20, 30, 40, 255]);
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_member_dimension(40, 30)
+        .set_padding(10)
+        .set_background(background)
+        .set_output_format(OutputFormat::Png)
+        .build()
+        .unwrap();
+    let image_bytes =
+        create_bundled_image_from_bytes(vec![sample_image_bytes(20, 30, [200, 0, 0, 255])], option)
+            .await
+            .unwrap();
+    let image = image::load_from_memory(&image_bytes).unwrap().to_rgba8();
+    assert_eq!(image.get_pixel(2, 15), &background);
+    assert_eq!(image.get_pixel(45, 35), &background);
+    assert_eq!(image.get_pixel(20, 15), &Rgba([200, 0, 0, 255]));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 20)]
+async fn test_low_memory() {
+    use super::*;
+    let colors = [[200, 0, 0, 255], [0, 200, 0, 255], [0, 0, 200, 255]];
+    let buffers: Vec<Vec<u8>> = colors
+        .iter()
+        .map(|&color| sample_image_bytes(40, 30, color))
+        .collect();
+    //normalize_orientation needs every image decoded up front, which is where tiles get spilled
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_column(3)
+        .set_padding(0)
+        .set_normalize_orientation(true)
+        .set_low_memory(true)
+        .set_output_format(OutputFormat::Png)
+        .build()
+        .unwrap();
+    let image_bytes = create_bundled_image_from_bytes(buffers, option.clone())
+        .await
+        .unwrap();
+    let image = image::load_from_memory(&image_bytes).unwrap().to_rgba8();
+    assert_eq!(image.dimensions(), (120, 30));
+    for (i, &color) in colors.iter().enumerate() {
+        assert_eq!(image.get_pixel(i as u32 * 40 + 20, 15), &Rgba(color));
+    }
+
+    //the decode itself is spilled, before the member size is known
+    let decoded = decode_tile(
+        Tile::Image(sample_image_bytes(40, 30, colors[0])),
+        &DecodeOptions::new(false),
+        None,
+        (Fit::Contain, true),
+        option.resampling(),
+        true,
+    )
+    .unwrap();
+    let DecodedTile::Image(Ok(origin @ OriginImage::Spilled(_)), _) = decoded else {
+        panic!("decoded image is kept in memory");
+    };
+    assert_eq!(origin.dimensions(), (40, 30));
+    assert_eq!(
+        origin.load().unwrap().to_rgba8().get_pixel(20, 15),
+        &Rgba(colors[0])
+    );
+
+    //spilling every decode draws the same bundle as keeping them in memory
+    let options = |low_memory: bool| {
+        CreateBundledImageOptionsBuilder::new()
+            .set_column(3)
+            .set_padding(0)
+            .set_on_decode_error(DecodeErrorPolicy::Skip)
+            .set_low_memory(low_memory)
+            .set_output_format(OutputFormat::Png)
+            .build()
+            .unwrap()
+    };
+    let mut inputs: Vec<Vec<u8>> = colors
+        .iter()
+        .map(|&color| sample_image_bytes(40, 30, color))
+        .collect();
+    inputs.insert(1, b"not an image".to_vec());
+    let spilled = create_bundled_image(inputs.clone(), options(true))
+        .await
+        .unwrap();
+    let in_memory = create_bundled_image(inputs, options(false)).await.unwrap();
+    assert_eq!(spilled.skipped, vec![1]);
+    assert_eq!(spilled.bytes, in_memory.bytes);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 20)]
+async fn test_outer_margin_and_gap() {
+    use super::*;
+    let red = [200, 0, 0, 255];
+    let buffers: Vec<Vec<u8>> = (0..4).map(|_| sample_image_bytes(40, 30, red)).collect();
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_column(2)
+        .set_outer_margin(10)
+        .set_gap(4)
+        .set_output_format(OutputFormat::Png)
+        .build()
+        .unwrap();
+    let image_bytes = create_bundled_image_from_bytes(buffers, option)
+        .await
+        .unwrap();
+    let image = image::load_from_memory(&image_bytes).unwrap().to_rgba8();
+    assert_eq!(
+        image.dimensions(),
+        (10 + 40 + 4 + 40 + 10, 10 + 30 + 4 + 30 + 10)
+    );
+    assert_eq!(image.get_pixel(5, 5), &WHITE_COLOR);
+    assert_eq!(image.get_pixel(10, 10), &Rgba(red));
+    assert_eq!(image.get_pixel(52, 20), &WHITE_COLOR);
+    assert_eq!(image.get_pixel(54, 44), &Rgba(red));
+    assert_eq!(image.get_pixel(93, 73), &Rgba(red));
+    assert_eq!(image.get_pixel(94, 74), &WHITE_COLOR);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 20)]
+async fn test_tile_tasks() {
+    use super::*;
+    use crate::tasks::TileTasks;
+    let mut tasks = TileTasks::new(Stage::Draw);
+    for i in 0..3 {
+        tasks.spawn(i, async move { Ok(i * 10) });
+    }
+    assert_eq!(tasks.join_all().await.unwrap(), vec![0, 10, 20]);
+
+    let mut tasks = TileTasks::new(Stage::Draw);
+    tasks.spawn(0, async {
+        tokio::time::sleep(std::time::Duration::from_secs(60)).await;
+        Ok(())
+    });
+    tasks.spawn(1, async {
+        Err(ImageCombinerError::InvalidOptions("broken".to_string()))
+    });
+    let started = std::time::Instant::now();
+    match tasks.join_all().await {
+        Err(ImageCombinerError::Tile { index, error }) => {
+            assert_eq!(index, 1);
+            assert!(matches!(*error, ImageCombinerError::InvalidOptions(_)));
+        }
+        _ => panic!("expected a tile error"),
+    }
+    assert!(started.elapsed() < std::time::Duration::from_secs(10));
+
+    let mut tasks: TileTasks<()> = TileTasks::new(Stage::Resize);
+    tasks.spawn(4, async { panic!("resize exploded") });
+    match tasks.join_all().await {
+        Err(ImageCombinerError::InternalPanic {
+            stage,
+            image_index,
+            message,
+        }) => {
+            assert_eq!(stage, Stage::Resize);
+            assert_eq!(image_index, Some(4));
+            assert_eq!(message, "resize exploded");
+        }
+        _ => panic!("expected an internal panic"),
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 20)]
+async fn test_side_padding() {
+    use super::*;
+    let red = [200, 0, 0, 255];
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_side_padding(Padding::new(2, 4, 50, 6))
+        .set_gap(0)
+        .set_output_format(OutputFormat::Png)
+        .build()
+        .unwrap();
+    let image_bytes =
+        create_bundled_image_from_bytes(vec![sample_image_bytes(40, 30, red)], option)
+            .await
+            .unwrap();
+    let image = image::load_from_memory(&image_bytes).unwrap().to_rgba8();
+    assert_eq!(image.dimensions(), (6 + 40 + 4, 2 + 30 + 50));
+    assert_eq!(image.get_pixel(6, 2), &Rgba(red));
+    assert_eq!(image.get_pixel(5, 2), &WHITE_COLOR);
+    assert_eq!(image.get_pixel(45, 31), &Rgba(red));
+    assert_eq!(image.get_pixel(45, 32), &WHITE_COLOR);
+}
+
+#[tokio::test]
+async fn test_cell_border() {
+    use super::*;
+    let red = [200, 0, 0, 255];
+    let black = Rgba([0, 0, 0, 255]);
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_column(2)
+        .set_gap(0)
+        .set_cell_border(3, black)
+        .set_output_format(OutputFormat::Png)
+        .build()
+        .unwrap();
+    let image_bytes = create_bundled_image_from_bytes(
+        vec![
+            sample_image_bytes(40, 30, red),
+            sample_image_bytes(40, 30, red),
+        ],
+        option,
+    )
+    .await
+    .unwrap();
+    let image = image::load_from_memory(&image_bytes).unwrap().to_rgba8();
+    assert_eq!(image.dimensions(), (80, 30));
+    assert_eq!(image.get_pixel(0, 0), &black);
+    assert_eq!(image.get_pixel(2, 15), &black);
+    assert_eq!(image.get_pixel(3, 15), &Rgba(red));
+    assert_eq!(image.get_pixel(39, 15), &black);
+    assert_eq!(image.get_pixel(40, 15), &black);
+    assert_eq!(image.get_pixel(60, 27), &black);
+    assert_eq!(image.get_pixel(60, 26), &Rgba(red));
+}
+
+#[tokio::test]
+async fn test_transcode() {
+    use super::*;
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_output_format(OutputFormat::Png)
+        .build()
+        .unwrap();
+    let png_bytes =
+        create_bundled_image_from_bytes(vec![sample_image_bytes(40, 30, [200, 0, 0, 255])], option)
+            .await
+            .unwrap();
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_encode_options(EncodeOptionsBuilder::new().set_quality(90).build())
+        .build()
+        .unwrap();
+    let jpeg_bytes = transcode(png_bytes.clone(), OutputFormat::Jpeg, option)
+        .await
+        .unwrap();
+    assert_eq!(
+        probe_dimensions(&jpeg_bytes).unwrap(),
+        (60, 50, image::ImageFormat::Jpeg)
+    );
+    let option = CreateBundledImageOptionsBuilder::new().build().unwrap();
+    assert!(
+        transcode(png_bytes[..20].to_vec(), OutputFormat::Jpeg, option)
+            .await
+            .is_err()
+    );
+}
+
+#[tokio::test]
+async fn test_corner_radius() {
+    use super::*;
+    let red = [200, 0, 0, 255];
+    let black = Rgba([0, 0, 0, 255]);
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_padding(0)
+        .set_background(black)
+        .set_corner_radius(14)
+        .set_canvas_corner_radius(4)
+        .set_output_format(OutputFormat::Png)
+        .build()
+        .unwrap();
+    let image_bytes =
+        create_bundled_image_from_bytes(vec![sample_image_bytes(40, 30, red)], option)
+            .await
+            .unwrap();
+    let image = image::load_from_memory(&image_bytes).unwrap().to_rgba8();
+    assert_eq!(image.dimensions(), (40, 30));
+    assert_eq!(image.get_pixel(20, 15), &Rgba(red));
+    assert_eq!(image.get_pixel(0, 15), &Rgba(red));
+    assert_eq!(image.get_pixel(39, 0).0[3], 0);
+    //inside the canvas corner but outside the wider image corner the background shows
+    assert_eq!(image.get_pixel(1, 5), &black);
+    //antialiased edge between image and background
+    let edge = image.get_pixel(3, 4).0[0];
+    assert!(edge > 0 && edge < 200);
+}
+
+#[tokio::test]
+async fn test_thumbnail_widths() {
+    use super::*;
+    let path =
+        std::env::temp_dir().join(format!("image-combiner-thumbnails-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&path);
+    let option = || {
+        CreateBundledImageOptionsBuilder::new()
+            .set_column(2)
+            .set_padding(0)
+            .set_thumbnail_widths(vec![40, 20, 80, 400])
+            .set_cache_dir(CacheDir::new(&path))
+            .build()
+            .unwrap()
+    };
+    let buffers = vec![
+        sample_image_bytes(40, 30, [200, 0, 0, 255]),
+        sample_image_bytes(40, 30, [0, 200, 0, 255]),
+    ];
+    let bundled = create_bundled_image(buffers.clone(), option())
+        .await
+        .unwrap();
+    assert_eq!(probe_dimensions(&bundled.bytes).unwrap().0, 80);
+    assert_eq!(
+        bundled.thumbnails.keys().copied().collect::<Vec<_>>(),
+        vec![20, 40]
+    );
+    let (width, height, _) = probe_dimensions(&bundled.thumbnails[&40]).unwrap();
+    assert_eq!((width, height), (40, 15));
+    assert_eq!(std::fs::read_dir(&path).unwrap().count(), 3);
+
+    let cached = create_bundled_image(buffers, option()).await.unwrap();
+    assert_eq!(cached.bytes, bundled.bytes);
+    assert_eq!(cached.thumbnails, bundled.thumbnails);
+    std::fs::remove_dir_all(&path).unwrap();
+}
+
+#[tokio::test]
+async fn test_drop_shadow() {
+    use super::*;
+    let red = [200, 0, 0, 255];
+    let black = Rgba([0, 0, 0, 255]);
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_column(2)
+        .set_drop_shadow(DropShadow::new(0, 15, 5, black))
+        .set_output_format(OutputFormat::Png)
+        .build()
+        .unwrap();
+    let buffers = vec![
+        sample_image_bytes(40, 30, red),
+        sample_image_bytes(40, 30, red),
+    ];
+    let image_bytes = create_bundled_image_from_bytes(buffers.clone(), option)
+        .await
+        .unwrap();
+    let image = image::load_from_memory(&image_bytes).unwrap().to_rgba8();
+    assert_eq!(image.dimensions(), (120, 50));
+    assert_eq!(image.get_pixel(20, 15), &Rgba(red));
+    assert_eq!(image.get_pixel(45, 15), &black);
+    assert_eq!(image.get_pixel(20, 32), &black);
+    assert_eq!(image.get_pixel(5, 32), &WHITE_COLOR);
+    //clipped to half of the gap so it does not reach the next cell
+    assert_eq!(image.get_pixel(52, 15), &WHITE_COLOR);
+    assert_eq!(image.get_pixel(62, 15), &Rgba(red));
+
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_column(2)
+        .set_drop_shadow(DropShadow::new(4, 0, 0, black))
+        .set_output_format(OutputFormat::Png)
+        .build()
+        .unwrap();
+    let image_bytes = create_bundled_image_from_bytes(buffers, option)
+        .await
+        .unwrap();
+    let image = image::load_from_memory(&image_bytes).unwrap().to_rgba8();
+    let near = image.get_pixel(41, 15).0[3];
+    let far = image.get_pixel(43, 15).0[3];
+    assert!(near > far && far > 0 && near < 255);
+}
+
+#[tokio::test]
+async fn test_linear_light() {
+    use super::*;
+    //one pixel black and white stripes average to mid gray in linear light
+    let stripes = image::DynamicImage::ImageRgba8(image::ImageBuffer::from_fn(80, 60, |x, _| {
+        if x % 2 == 0 {
+            Rgba([0, 0, 0, 255])
+        } else {
+            Rgba([255, 255, 255, 255])
+        }
+    }));
+    let mut stripes_bytes = Vec::new();
+    stripes
+        .write_to(&mut stripes_bytes, image::ImageOutputFormat::Png)
+        .unwrap();
+    let center = |preset: Option<Preset>| {
+        let stripes_bytes = stripes_bytes.clone();
+        async move {
+            let mut builder = CreateBundledImageOptionsBuilder::new()
+                .set_member_dimension(20, 15)
+                .set_padding(0)
+                .set_output_format(OutputFormat::Png);
+            if let Some(preset) = preset {
+                builder = builder.set_preset(preset);
+            }
+            let image_bytes =
+                create_bundled_image_from_bytes(vec![stripes_bytes], builder.build().unwrap())
+                    .await
+                    .unwrap();
+            let image = image::load_from_memory(&image_bytes).unwrap().to_rgba8();
+            assert_eq!(image.dimensions(), (20, 15));
+            image.get_pixel(10, 7).0[0]
+        }
+    };
+    let srgb = center(None).await;
+    let linear = center(Some(Preset::Quality)).await;
+    assert!((120..=135).contains(&srgb), "{}", srgb);
+    assert!((180..=195).contains(&linear), "{}", linear);
+    assert_eq!(center(Some(Preset::Fast)).await, srgb);
+}
+
+#[test]
+fn test_restart_interval() {
+    use super::*;
+    let image = image::load_from_memory(&sample_image_bytes(64, 32, [200, 0, 0, 255])).unwrap();
+    let has_restart_marker = |bytes: &[u8]| bytes.windows(2).any(|w| w == [0xFF, 0xDD]);
+    let plain = encode(&image, OutputFormat::Jpeg, &EncodeOptions::default()).unwrap();
+    assert!(!has_restart_marker(&plain));
+    let options = EncodeOptionsBuilder::new().set_restart_interval(1).build();
+    let mut streamed = Vec::new();
+    let written = encode_to(&image, OutputFormat::Jpeg, &options, &mut streamed).unwrap();
+    assert_eq!(written, streamed.len() as u64);
+    assert!(has_restart_marker(&streamed));
+    assert_eq!(
+        streamed,
+        encode(&image, OutputFormat::Jpeg, &options).unwrap()
+    );
+    assert_eq!(
+        image::load_from_memory(&streamed).unwrap().dimensions(),
+        (64, 32)
+    );
+}
+
+#[tokio::test]
+async fn test_frame_cell_style() {
+    use super::*;
+    let red = [200, 0, 0, 255];
+    let black = Rgba([0, 0, 0, 255]);
+    let frame = Rgba([255, 255, 255, 255]);
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_column(2)
+        .set_gap(10)
+        .set_background(black)
+        .set_cell_style(CellStyle::Frame {
+            border: 5,
+            bottom_band: 30,
+            caption: Some(FrameCaption::new(
+                vec!["".to_string(), "two".to_string()],
+                TextCardStyle::new(test_font_bytes(), black, Some(20.0)),
+            )),
+        })
+        .set_output_format(OutputFormat::Png)
+        .build()
+        .unwrap();
+    let buffers = vec![
+        sample_image_bytes(40, 30, red),
+        sample_image_bytes(40, 30, red),
+    ];
+    let image_bytes = create_bundled_image_from_bytes(buffers, option)
+        .await
+        .unwrap();
+    let image = image::load_from_memory(&image_bytes).unwrap().to_rgba8();
+    assert_eq!(image.dimensions(), (50 + 10 + 50, 5 + 30 + 30));
+    assert_eq!(image.get_pixel(2, 2), &frame);
+    assert_eq!(image.get_pixel(5, 5), &Rgba(red));
+    assert_eq!(image.get_pixel(44, 34), &Rgba(red));
+    assert_eq!(image.get_pixel(45, 34), &frame);
+    assert_eq!(image.get_pixel(55, 20), &black);
+    assert_eq!(image.get_pixel(65, 5), &Rgba(red));
+    //only the second image has a caption in its band
+    let band = |left: u32| {
+        (left..left + 50)
+            .flat_map(|x| (35..65).map(move |y| (x, y)))
+            .filter(|&(x, y)| image.get_pixel(x, y) != &frame)
+            .count()
+    };
+    assert_eq!(band(0), 0);
+    assert!(band(60) > 0);
+}
+
+#[cfg(feature = "tiff")]
+#[tokio::test]
+async fn test_create_bundled_image_tiff() {
+    use super::*;
+    let buffers: Vec<Vec<u8>> = (0..5)
+        .map(|i| sample_image_bytes(40, 30, [i * 40, 0, 0, 255]))
+        .collect();
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_column(2)
+        .set_padding(0)
+        .build()
+        .unwrap();
+    let tiff_bytes = create_bundled_image_tiff(buffers.clone(), option, 1)
+        .await
+        .unwrap();
+    let mut decoder = tiff::decoder::Decoder::new(std::io::Cursor::new(&tiff_bytes)).unwrap();
+    let mut pages = vec![decoder.dimensions().unwrap()];
+    while decoder.more_images() {
+        decoder.next_image().unwrap();
+        pages.push(decoder.dimensions().unwrap());
+    }
+    assert_eq!(pages, vec![(80, 30), (80, 30), (80, 30)]);
+
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_rows(2)
+        .build()
+        .unwrap();
+    assert!(matches!(
+        create_bundled_image_tiff(buffers, option, 1).await,
+        Err(ImageCombinerError::InvalidOptions(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_create_bundled_image_pages() {
+    use super::*;
+    let buffers: Vec<Vec<u8>> = (0..9)
+        .map(|i| sample_image_bytes(40, 30, [i * 30, 0, 0, 255]))
+        .collect();
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_column(2)
+        .set_padding(0)
+        .set_output_format(OutputFormat::Png)
+        .build()
+        .unwrap();
+    let pages = create_bundled_image_pages(buffers.clone(), option, 2)
+        .await
+        .unwrap();
+    let dimensions: Vec<(u32, u32)> = pages
+        .iter()
+        .map(|page| image::load_from_memory(page).unwrap().dimensions())
+        .collect();
+    //the last page holds the one image left over
+    assert_eq!(dimensions, vec![(80, 60), (80, 60), (80, 30)]);
+    let last = image::load_from_memory(&pages[2]).unwrap().to_rgba8();
+    assert_eq!(last.get_pixel(10, 10), &Rgba([240, 0, 0, 255]));
+
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_column(2)
+        .build()
+        .unwrap();
+    assert!(matches!(
+        create_bundled_image_pages(buffers, option, 0).await,
+        Err(ImageCombinerError::InvalidOptions(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_number_badge() {
+    use super::*;
+    let red = [200, 0, 0, 255];
+    let blue = Rgba([0, 0, 200, 255]);
+    let render = |position: CellAlignment| async move {
+        let option = CreateBundledImageOptionsBuilder::new()
+            .set_column(2)
+            .set_gap(0)
+            .set_badge(NumberBadge::new(
+                TextCardStyle::new(test_font_bytes(), Rgba([255, 255, 255, 255]), Some(10.0)),
+                blue,
+                position,
+            ))
+            .set_output_format(OutputFormat::Png)
+            .build()
+            .unwrap();
+        let buffers = vec![
+            sample_image_bytes(100, 80, red),
+            sample_image_bytes(100, 80, red),
+        ];
+        let image_bytes = create_bundled_image_from_bytes(buffers, option)
+            .await
+            .unwrap();
+        image::load_from_memory(&image_bytes).unwrap().to_rgba8()
+    };
+    let image = render(CellAlignment::TopLeft).await;
+    assert_eq!(image.get_pixel(12, 5), &blue);
+    assert_eq!(image.get_pixel(112, 5), &blue);
+    assert_eq!(image.get_pixel(88, 61), &Rgba(red));
+    //the numbers differ so the badges do not look the same
+    let badge = |left: u32| -> Vec<Rgba<u8>> {
+        (left + 4..left + 20)
+            .flat_map(|x| (4..20).map(move |y| (x, y)))
+            .map(|(x, y)| *image.get_pixel(x, y))
+            .collect()
+    };
+    assert_ne!(badge(0), badge(100));
+
+    let image = render(CellAlignment::BottomRight).await;
+    assert_eq!(image.get_pixel(88, 61), &blue);
+    assert_eq!(image.get_pixel(12, 5), &Rgba(red));
+}
+
+#[cfg(feature = "zip")]
+#[tokio::test]
+async fn test_create_bundled_image_archive() {
+    use super::*;
+    use std::io::Read;
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_column(2)
+        .set_gap(10)
+        .set_thumbnail_widths(vec![45])
+        .build()
+        .unwrap();
+    let buffers = vec![
+        sample_image_bytes(40, 30, [200, 0, 0, 255]),
+        sample_image_bytes(40, 30, [0, 200, 0, 255]),
+    ];
+    let zip_bytes = create_bundled_image_archive(buffers, option).await.unwrap();
+    let mut archive = zip::ZipArchive::new(std::io::Cursor::new(zip_bytes)).unwrap();
+    let mut names: Vec<String> = archive.file_names().map(str::to_string).collect();
+    names.sort();
+    assert_eq!(
+        names,
+        vec![
+            "alt.txt",
+            "bundle-w45.jpeg",
+            "bundle.jpeg",
+            "layout.json",
+            "sprites.css"
+        ]
+    );
+    let mut read = |name: &str| {
+        let mut text = String::new();
+        archive
+            .by_name(name)
+            .unwrap()
+            .read_to_string(&mut text)
+            .unwrap();
+        text
+    };
+    let layout = read("layout.json");
+    assert!(layout.starts_with("{\"image\":\"bundle.jpeg\",\"width\":90,\"height\":30,"));
+    assert!(layout.contains(
+        "{\"index\":1,\"position\":1,\"row\":0,\"column\":1,\"x\":50,\"y\":0,\"width\":40,\"height\":30}"
+    ));
+    assert!(read("alt.txt").starts_with("2 images arranged in 1 rows and 2 columns."));
+    assert!(read("sprites.css").contains(
+        ".bundle-tile-2 {\n  width: 40px;\n  height: 30px;\n  background-position: -50px -0px;\n}"
+    ));
+
+    //sidecars name tiles by input index when inputs are reordered or skipped
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_column(2)
+        .set_padding(0)
+        .set_order(vec![2, 1, 0])
+        .set_on_decode_error(DecodeErrorPolicy::Skip)
+        .build()
+        .unwrap();
+    let buffers = vec![
+        sample_image_bytes(40, 30, [200, 0, 0, 255]),
+        b"not an image".to_vec(),
+        sample_image_bytes(40, 30, [0, 200, 0, 255]),
+    ];
+    let zip_bytes = create_bundled_image_archive(buffers, option).await.unwrap();
+    let mut archive = zip::ZipArchive::new(std::io::Cursor::new(zip_bytes)).unwrap();
+    let mut read = |name: &str| {
+        let mut text = String::new();
+        archive
+            .by_name(name)
+            .unwrap()
+            .read_to_string(&mut text)
+            .unwrap();
+        text
+    };
+    let layout = read("layout.json");
+    assert!(layout.contains("{\"index\":2,\"position\":0,\"row\":0,\"column\":0,"));
+    assert!(layout.contains("{\"index\":0,\"position\":1,\"row\":0,\"column\":1,"));
+    assert!(layout.ends_with("\"skipped\":[1]}"));
+    assert_eq!(
+        read("alt.txt"),
+        "2 images arranged in 1 rows and 2 columns.\nImage 3: row 1, column 1.\nImage 1: row 1, column 2.\n"
+    );
+    assert!(read("sprites.css").contains(
+        ".bundle-tile-3 {\n  width: 40px;\n  height: 30px;\n  background-position: -0px -0px;\n}"
+    ));
+}
+
+#[tokio::test]
+async fn test_max_output_bytes() {
+    use super::*;
+    use sha2::Digest;
+    let noise_bytes = noise_image_bytes(120, 90);
+    let full = create_bundled_image_from_bytes(
+        vec![noise_bytes.clone()],
+        CreateBundledImageOptionsBuilder::new().build().unwrap(),
+    )
+    .await
+    .unwrap();
+    let max_bytes = full.len() as u64 / 2;
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_max_output_bytes(max_bytes)
+        .set_sha256(true)
+        .build()
+        .unwrap();
+    let bundled = create_bundled_image(vec![noise_bytes.clone()], option)
+        .await
+        .unwrap();
+    assert!(bundled.bytes.len() as u64 <= max_bytes);
+    assert_eq!(probe_dimensions(&bundled.bytes).unwrap().0, 140);
+    let expected: String = sha2::Sha256::digest(&bundled.bytes)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    assert_eq!(bundled.sha256, Some(expected));
+
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_max_output_bytes(100)
+        .set_output_format(OutputFormat::Png)
+        .build()
+        .unwrap();
+    assert!(matches!(
+        create_bundled_image(vec![noise_bytes], option).await,
+        Err(ImageCombinerError::Encode(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_layout_template() {
+    use super::*;
+    let (red, green, blue) = ([200, 0, 0, 255], [0, 200, 0, 255], [0, 0, 200, 255]);
+    let template = LayoutTemplate::new(3, 2)
+        .add_cell(0, 0, 2, 2)
+        .add_cell(0, 2, 1, 1)
+        .add_cell(1, 2, 1, 1);
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_member_dimension(40, 30)
+        .set_gap(4)
+        .set_output_format(OutputFormat::Png)
+        .build()
+        .unwrap();
+    let buffers = vec![
+        sample_image_bytes(84, 64, red),
+        sample_image_bytes(40, 30, green),
+        sample_image_bytes(40, 30, blue),
+    ];
+    let bundled = create_bundled_image_with_template(buffers.clone(), template.clone(), option)
+        .await
+        .unwrap();
+    assert!(bundled.skipped.is_empty());
+    let image = image::load_from_memory(&bundled.bytes).unwrap().to_rgba8();
+    assert_eq!(image.dimensions(), (40 * 3 + 8, 30 * 2 + 4));
+    assert_eq!(image.get_pixel(0, 0), &Rgba(red));
+    assert_eq!(image.get_pixel(83, 63), &Rgba(red));
+    assert_eq!(image.get_pixel(86, 10), &WHITE_COLOR);
+    assert_eq!(image.get_pixel(88, 0), &Rgba(green));
+    assert_eq!(image.get_pixel(127, 63), &Rgba(blue));
+
+    //a bad input leaves its cell blank or crossed out and the other cells keep their places
+    let broken = vec![
+        buffers[0].clone(),
+        b"not an image".to_vec(),
+        buffers[2].clone(),
+    ];
+    let render = |on_decode_error: DecodeErrorPolicy| {
+        let option = CreateBundledImageOptionsBuilder::new()
+            .set_member_dimension(40, 30)
+            .set_gap(4)
+            .set_on_decode_error(on_decode_error)
+            .set_output_format(OutputFormat::Png)
+            .build()
+            .unwrap();
+        create_bundled_image_with_template(broken.clone(), template.clone(), option)
+    };
+    assert!(matches!(
+        render(DecodeErrorPolicy::FailFast).await,
+        Err(ImageCombinerError::Decode(_))
+    ));
+    let bundled = render(DecodeErrorPolicy::Skip).await.unwrap();
+    assert_eq!(bundled.skipped, vec![1]);
+    let image = image::load_from_memory(&bundled.bytes).unwrap().to_rgba8();
+    assert_eq!(image.get_pixel(100, 10), &WHITE_COLOR);
+    assert_eq!(image.get_pixel(127, 63), &Rgba(blue));
+    let bundled = render(DecodeErrorPolicy::Placeholder).await.unwrap();
+    assert_eq!(bundled.skipped, vec![1]);
+    let image = image::load_from_memory(&bundled.bytes).unwrap().to_rgba8();
+    assert_eq!(image.get_pixel(100, 10), &GRAY_COLOR);
+    assert_eq!(image.get_pixel(127, 63), &Rgba(blue));
+
+    let overlapping = LayoutTemplate::new(2, 2)
+        .add_cell(0, 0, 2, 2)
+        .add_cell(1, 1, 1, 1);
+    let option = CreateBundledImageOptionsBuilder::new().build().unwrap();
+    assert!(matches!(
+        create_bundled_image_with_template(buffers, overlapping, option).await,
+        Err(ImageCombinerError::InvalidOptions(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_compose() {
+    use super::*;
+    let (red, green) = ([200, 0, 0, 255], [0, 200, 0, 255]);
+    let buffers = vec![
+        sample_image_bytes(40, 40, red),
+        sample_image_bytes(20, 20, green),
+    ];
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_output_format(OutputFormat::Png)
+        .build()
+        .unwrap();
+    let placements = vec![
+        Placement::new(0, 0, 0, 60, 50),
+        Placement::new(1, 50, 40, 30, 30),
+        Placement::new(1, 0, 60, 10, 10),
+    ];
+    let bundled = compose(buffers.clone(), placements, option).await.unwrap();
+    let image = image::load_from_memory(&bundled.bytes).unwrap().to_rgba8();
+    assert_eq!(image.dimensions(), (80, 70));
+    assert_eq!(image.get_pixel(10, 10), &Rgba(red));
+    //later placements draw over earlier ones
+    assert_eq!(image.get_pixel(55, 45), &Rgba(green));
+    assert_eq!(image.get_pixel(5, 65), &Rgba(green));
+    assert_eq!(image.get_pixel(30, 65), &WHITE_COLOR);
+
+    let option = CreateBundledImageOptionsBuilder::new().build().unwrap();
+    assert!(matches!(
+        compose(buffers, vec![Placement::new(2, 0, 0, 10, 10)], option).await,
+        Err(ImageCombinerError::InvalidOptions(_))
+    ));
+
+    //decode options, the decode error policy and canvas decoration apply as in the grid
+    let buffers = vec![sample_image_bytes(20, 10, red), b"not an image".to_vec()];
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_transforms(vec![(0, Transform::Rotate90)])
+        .set_on_decode_error(DecodeErrorPolicy::Skip)
+        .set_canvas_corner_radius(4)
+        .set_output_format(OutputFormat::Png)
+        .build()
+        .unwrap();
+    let placements = vec![
+        Placement::new(0, 0, 0, 10, 20),
+        Placement::new(1, 10, 0, 10, 20),
+    ];
+    let bundled = compose(buffers, placements, option).await.unwrap();
+    assert_eq!(bundled.skipped, vec![1]);
+    let image = image::load_from_memory(&bundled.bytes).unwrap().to_rgba8();
+    assert_eq!(image.dimensions(), (20, 20));
+    assert_eq!(image.get_pixel(5, 10), &Rgba(red));
+    assert_eq!(image.get_pixel(15, 10), &WHITE_COLOR);
+    assert_eq!(image.get_pixel(0, 0).0[3], 0);
+}
+
+#[tokio::test]
+async fn test_masonry_layout() {
+    use super::*;
+    let (red, green, blue, yellow) = (
+        [200, 0, 0, 255],
+        [0, 200, 0, 255],
+        [0, 0, 200, 255],
+        [200, 200, 0, 255],
+    );
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_member_dimension(40, 40)
+        .set_column(2)
+        .set_gap(4)
+        .set_layout_mode(LayoutMode::Masonry)
+        .set_output_format(OutputFormat::Png)
+        .build()
+        .unwrap();
+    let buffers = vec![
+        sample_image_bytes(40, 60, red),
+        sample_image_bytes(40, 20, green),
+        sample_image_bytes(40, 20, blue),
+        sample_image_bytes(80, 40, yellow),
+    ];
+    let bundled = create_bundled_image(buffers, option).await.unwrap();
+    let image = image::load_from_memory(&bundled.bytes).unwrap().to_rgba8();
+    //the tall image fills the first column while the short ones stack in the second
+    assert_eq!(image.dimensions(), (84, 68));
+    assert_eq!(image.get_pixel(0, 59), &Rgba(red));
+    assert_eq!(image.get_pixel(0, 64), &WHITE_COLOR);
+    assert_eq!(image.get_pixel(44, 0), &Rgba(green));
+    assert_eq!(image.get_pixel(44, 26), &Rgba(blue));
+    assert_eq!(image.get_pixel(83, 67), &Rgba(yellow));
+
+    //a narrow image keeps its size in its slot unless upscaling is allowed
+    let render = |allow_upscale: bool| async move {
+        let option = CreateBundledImageOptionsBuilder::new()
+            .set_member_dimension(40, 40)
+            .set_column(2)
+            .set_gap(0)
+            .set_layout_mode(LayoutMode::Masonry)
+            .set_allow_upscale(allow_upscale)
+            .set_output_format(OutputFormat::Png)
+            .build()
+            .unwrap();
+        let buffers = vec![
+            sample_image_bytes(40, 40, red),
+            sample_image_bytes(20, 20, green),
+        ];
+        let bundled = create_bundled_image(buffers, option).await.unwrap();
+        image::load_from_memory(&bundled.bytes).unwrap().to_rgba8()
+    };
+    let image = render(false).await;
+    assert_eq!(image.dimensions(), (80, 40));
+    assert_eq!(image.get_pixel(60, 20), &Rgba(green));
+    assert_eq!(image.get_pixel(42, 2), &WHITE_COLOR);
+    let image = render(true).await;
+    assert_eq!(image.get_pixel(42, 2), &Rgba(green));
+}
+
+#[tokio::test]
+async fn test_physical_scale() {
+    use super::*;
+    let jpeg_at_dpi = |color: [u8; 3], dpi: u16| {
+        let image = image::RgbImage::from_pixel(100, 100, image::Rgb(color));
+        let mut bytes = Vec::new();
+        let mut encoder = image::codecs::jpeg::JpegEncoder::new_with_quality(&mut bytes, 100);
+        encoder.set_pixel_density(image::codecs::jpeg::PixelDensity::dpi(dpi));
+        encoder.encode_image(&image).unwrap();
+        bytes
+    };
+    let one_inch = jpeg_at_dpi([200, 0, 0], 100);
+    let half_inch = jpeg_at_dpi([0, 0, 200], 200);
+    assert_eq!(source_dpi(&half_inch), Some((200.0, 200.0)));
+    assert_eq!(source_dpi(&sample_image_bytes(4, 4, [0, 0, 0, 255])), None);
+    let is_blue = |pixel: &Rgba<u8>| pixel[2] > 150 && pixel[0] < 50;
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_column(2)
+        .set_gap(0)
+        .set_physical_scale(true)
+        .set_output_format(OutputFormat::Png)
+        .build()
+        .unwrap();
+    let bundled = create_bundled_image(vec![one_inch.clone(), half_inch.clone()], option)
+        .await
+        .unwrap();
+    let image = image::load_from_memory(&bundled.bytes).unwrap().to_rgba8();
+    //both are 100px wide but the second one is half an inch, so it takes a quarter of its cell
+    assert_eq!(image.dimensions(), (400, 200));
+    assert!(image.get_pixel(100, 100)[0] > 150);
+    assert!(is_blue(image.get_pixel(300, 100)));
+    assert_eq!(image.get_pixel(260, 40), &WHITE_COLOR);
+    //the one inch image is 100px, it only grows to the 200px its size on the sheet needs when allowed
+    assert_eq!(image.get_pixel(10, 100), &WHITE_COLOR);
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_column(2)
+        .set_gap(0)
+        .set_physical_scale(true)
+        .set_allow_upscale(true)
+        .set_output_format(OutputFormat::Png)
+        .build()
+        .unwrap();
+    let bundled = create_bundled_image(vec![one_inch.clone(), half_inch.clone()], option)
+        .await
+        .unwrap();
+    let image = image::load_from_memory(&bundled.bytes).unwrap().to_rgba8();
+    assert!(image.get_pixel(10, 100)[0] > 150);
+
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_member_dimension(100, 100)
+        .set_column(2)
+        .set_gap(0)
+        .set_physical_scale(true)
+        .set_output_format(OutputFormat::Png)
+        .build()
+        .unwrap();
+    let bundled = create_bundled_image(vec![one_inch, half_inch], option)
+        .await
+        .unwrap();
+    let image = image::load_from_memory(&bundled.bytes).unwrap().to_rgba8();
+    assert_eq!(image.dimensions(), (200, 100));
+    assert!(is_blue(image.get_pixel(150, 50)));
+    assert_eq!(image.get_pixel(120, 20), &WHITE_COLOR);
+}
+
+#[tokio::test]
+async fn test_ruler() {
+    use super::*;
+    let red = [200, 0, 0, 255];
+    let black = Rgba([0, 0, 0, 255]);
+    let style = TextCardStyle::new(test_font_bytes(), black, Some(10.0));
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_member_dimension(100, 100)
+        .set_column(2)
+        .set_gap(0)
+        .set_ruler(Ruler::new(
+            20.0,
+            RulerUnit::Centimeter,
+            Edge::Top,
+            style,
+            WHITE_COLOR,
+        ))
+        .set_output_format(OutputFormat::Png)
+        .build()
+        .unwrap();
+    let buffers = vec![
+        sample_image_bytes(100, 100, red),
+        sample_image_bytes(100, 100, red),
+    ];
+    let bundled = create_bundled_image(buffers, option).await.unwrap();
+    let image = image::load_from_memory(&bundled.bytes).unwrap().to_rgba8();
+    assert_eq!(image.dimensions(), (200, 100));
+    //a centimeter tick every 20px and a millimeter tick every 2px
+    assert_eq!(image.get_pixel(20, 8), &black);
+    assert_eq!(image.get_pixel(22, 3), &black);
+    assert_eq!(image.get_pixel(22, 7), &WHITE_COLOR);
+    assert_eq!(image.get_pixel(51, 19), &WHITE_COLOR);
+    assert_eq!(image.get_pixel(51, 25), &Rgba(red));
+}
+
+#[tokio::test]
+async fn test_combine_strip() {
+    use super::*;
+    let (red, blue) = ([200, 0, 0, 255], [0, 0, 200, 255]);
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_gap(4)
+        .set_output_format(OutputFormat::Png)
+        .build()
+        .unwrap();
+    let buffers = vec![
+        sample_image_bytes(30, 40, red),
+        sample_image_bytes(50, 20, blue),
+    ];
+    let bundled = combine_horizontal(buffers, option).await.unwrap();
+    let image = image::load_from_memory(&bundled.bytes).unwrap().to_rgba8();
+    //images keep their size and the shorter one is centered in the row
+    assert_eq!(image.dimensions(), (84, 40));
+    assert_eq!(image.get_pixel(0, 39), &Rgba(red));
+    assert_eq!(image.get_pixel(40, 5), &WHITE_COLOR);
+    assert_eq!(image.get_pixel(40, 15), &Rgba(blue));
+
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_member_dimension(40, 40)
+        .set_gap(4)
+        .set_allow_upscale(true)
+        .set_output_format(OutputFormat::Png)
+        .build()
+        .unwrap();
+    let buffers = vec![
+        sample_image_bytes(20, 40, red),
+        sample_image_bytes(80, 40, blue),
+    ];
+    let bundled = combine_vertical(buffers, option).await.unwrap();
+    let image = image::load_from_memory(&bundled.bytes).unwrap().to_rgba8();
+    //every image is scaled to the member width
+    assert_eq!(image.dimensions(), (40, 104));
+    assert_eq!(image.get_pixel(39, 79), &Rgba(red));
+    assert_eq!(image.get_pixel(20, 82), &WHITE_COLOR);
+    assert_eq!(image.get_pixel(20, 90), &Rgba(blue));
+}
+
+#[tokio::test]
+async fn test_labeled_matrix() {
+    use super::*;
+    let (red, blue) = ([200, 0, 0, 255], [0, 0, 200, 255]);
+    let black = Rgba([0, 0, 0, 255]);
+    let labels = MatrixLabels::new(
+        vec!["Red".to_string(), "Blue".to_string()],
+        vec!["Front".to_string(), "Side".to_string()],
+        TextCardStyle::new(test_font_bytes(), black, Some(10.0)),
+    );
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_member_dimension(40, 40)
+        .set_gap(4)
+        .set_output_format(OutputFormat::Png)
+        .build()
+        .unwrap();
+    let images = vec![
+        vec![
+            sample_image_bytes(40, 40, red),
+            sample_image_bytes(40, 40, red),
+        ],
+        vec![sample_image_bytes(40, 40, blue)],
+    ];
+    let bundled = create_labeled_matrix(images, labels, option).await.unwrap();
+    let image = image::load_from_memory(&bundled.bytes).unwrap().to_rgba8();
+    //the row label band fits "Blue" at 20px plus half a font size on each side, the column band is 20px
+    assert_eq!(image.dimensions(), (30 + 4 + 84, 20 + 4 + 84));
+    assert_eq!(image.get_pixel(34, 24), &Rgba(red));
+    assert_eq!(image.get_pixel(117, 24), &Rgba(red));
+    assert_eq!(image.get_pixel(34, 107), &Rgba(blue));
+    assert_eq!(image.get_pixel(117, 107), &WHITE_COLOR);
+    let dark = |x0: u32, y0: u32, x1: u32, y1: u32| {
+        (x0..x1).any(|x| (y0..y1).any(|y| image.get_pixel(x, y)[0] < 128))
+    };
+    assert!(dark(0, 24, 30, 64));
+    assert!(dark(34, 0, 74, 20));
+    assert!(!dark(0, 0, 30, 20));
+}
+
+#[tokio::test]
+async fn test_labeled_strip() {
+    use super::*;
+    let (red, blue) = ([200, 0, 0, 255], [0, 0, 200, 255]);
+    let black = Rgba([0, 0, 0, 255]);
+    let labels = StripLabels::new(
+        vec!["Mon".to_string(), "Tue".to_string()],
+        TextCardStyle::new(test_font_bytes(), black, Some(10.0)),
+    );
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_gap(4)
+        .set_output_format(OutputFormat::Png)
+        .build()
+        .unwrap();
+    let buffers = vec![
+        sample_image_bytes(40, 40, red),
+        sample_image_bytes(40, 40, blue),
+    ];
+    let bundled = create_labeled_strip(buffers.clone(), StripDirection::Horizontal, labels, option)
+        .await
+        .unwrap();
+    let image = image::load_from_memory(&bundled.bytes).unwrap().to_rgba8();
+    //one 20px label band above the row
+    assert_eq!(image.dimensions(), (84, 60));
+    assert_eq!(image.get_pixel(0, 20), &Rgba(red));
+    assert_eq!(image.get_pixel(83, 59), &Rgba(blue));
+    let dark = |x0: u32, x1: u32, y0: u32, y1: u32| {
+        (x0..x1).any(|x| (y0..y1).any(|y| image.get_pixel(x, y)[0] < 128))
+    };
+    assert!(dark(0, 40, 0, 20));
+    assert!(dark(44, 84, 0, 20));
+
+    let labels = StripLabels::new(
+        vec!["1".to_string()],
+        TextCardStyle::new(test_font_bytes(), black, Some(10.0)),
+    );
+    let option = CreateBundledImageOptionsBuilder::new().build().unwrap();
+    assert!(matches!(
+        create_labeled_strip(buffers, StripDirection::Vertical, labels, option).await,
+        Err(ImageCombinerError::InvalidOptions(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_auto_shrink() {
+    use super::*;
+    let buffers = vec![sample_image_bytes(40, 40, [200, 0, 0, 255]); 3];
+    let builder = || {
+        CreateBundledImageOptionsBuilder::new()
+            .set_column(4)
+            .set_gap(4)
+            .set_output_format(OutputFormat::Png)
+    };
+    let full = create_bundled_image(buffers.clone(), builder().build().unwrap())
+        .await
+        .unwrap();
+    let full = image::load_from_memory(&full.bytes).unwrap();
+    assert_eq!(full.dimensions(), (4 * 40 + 3 * 4, 40));
+    let shrunk = create_bundled_image(buffers, builder().set_auto_shrink(true).build().unwrap())
+        .await
+        .unwrap();
+    let shrunk = image::load_from_memory(&shrunk.bytes).unwrap();
+    assert_eq!(shrunk.dimensions(), (3 * 40 + 2 * 4, 40));
+}
+
+#[tokio::test]
+async fn test_spacer_tiles() {
+    use super::*;
+    let (red, blue) = ([200, 0, 0, 255], [0, 0, 200, 255]);
+    for normalize_orientation in [false, true] {
+        let tiles = vec![
+            Tile::Image(sample_image_bytes(20, 20, red)),
+            Tile::Empty,
+            Tile::Placeholder,
+            Tile::Image(sample_image_bytes(20, 20, blue)),
+        ];
+        let option = CreateBundledImageOptionsBuilder::new()
+            .set_member_dimension(20, 20)
+            .set_column(4)
+            .set_gap(0)
+            .set_background(Rgba([255, 255, 0, 255]))
+            .set_normalize_orientation(normalize_orientation)
+            .set_output_format(OutputFormat::Png)
+            .build()
+            .unwrap();
+        let image_bytes = create_bundled_image_from_tiles(tiles, option)
+            .await
+            .unwrap();
+        let image = image::load_from_memory(&image_bytes).unwrap().to_rgba8();
+        assert_eq!(image.dimensions(), (80, 20));
+        assert_eq!(image.get_pixel(10, 10), &Rgba(red));
+        assert_eq!(image.get_pixel(30, 10), &Rgba([255, 255, 0, 255]));
+        assert_eq!(image.get_pixel(50, 2), &GRAY_COLOR);
+        assert_eq!(image.get_pixel(70, 10), &Rgba(blue));
+    }
+}
+
+#[tokio::test]
+async fn test_step_arrows() {
+    use super::*;
+    let black = Rgba([0, 0, 0, 255]);
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_member_dimension(20, 20)
+        .set_column(2)
+        .set_gap(20)
+        .set_step_arrows(ArrowStyle::new(black, 2, 8))
+        .set_output_format(OutputFormat::Png)
+        .build()
+        .unwrap();
+    let buffers = vec![sample_image_bytes(20, 20, [200, 0, 0, 255]); 3];
+    let bundled = create_bundled_image(buffers, option).await.unwrap();
+    let image = image::load_from_memory(&bundled.bytes).unwrap().to_rgba8();
+    assert_eq!(image.dimensions(), (60, 60));
+    //straight across the column gap, the head ends at the next tile
+    assert_eq!(image.get_pixel(25, 10), &black);
+    assert_eq!(image.get_pixel(33, 7), &black);
+    assert_eq!(image.get_pixel(25, 5), &WHITE_COLOR);
+    //down from the second tile, across the row gap and down into the third
+    assert_eq!(image.get_pixel(50, 25), &black);
+    assert_eq!(image.get_pixel(30, 30), &black);
+    assert_eq!(image.get_pixel(10, 33), &black);
+    assert_eq!(image.get_pixel(45, 45), &WHITE_COLOR);
+}
+
+#[tokio::test]
+async fn test_sort() {
+    use super::*;
+    let (red, green, blue) = ([200, 0, 0, 255], [0, 200, 0, 255], [0, 0, 200, 255]);
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_member_dimension(20, 60)
+        .set_column(3)
+        .set_gap(0)
+        .set_cell_alignment(CellAlignment::Top)
+        .set_sort(SortOrder::ByHeight)
+        .set_output_format(OutputFormat::Png)
+        .build()
+        .unwrap();
+    let buffers = vec![
+        sample_image_bytes(20, 20, red),
+        sample_image_bytes(20, 60, green),
+        sample_image_bytes(20, 40, blue),
+    ];
+    let bundled = create_bundled_image(buffers.clone(), option).await.unwrap();
+    let image = image::load_from_memory(&bundled.bytes).unwrap().to_rgba8();
+    //tallest first
+    assert_eq!(image.get_pixel(10, 50), &Rgba(green));
+    assert_eq!(image.get_pixel(30, 30), &Rgba(blue));
+    assert_eq!(image.get_pixel(50, 10), &Rgba(red));
+    assert_eq!(image.get_pixel(50, 30), &WHITE_COLOR);
+
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_order(vec![2, 1, 0])
+        .set_sort(SortOrder::ByWidth)
+        .build()
+        .unwrap();
+    assert!(matches!(
+        create_bundled_image(buffers, option).await,
+        Err(ImageCombinerError::InvalidOptions(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_create_bundled_image_dzi() {
+    use super::*;
+    let dir = tempfile::tempdir().unwrap();
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_column(3)
+        .set_gap(0)
+        .set_output_format(OutputFormat::Png)
+        .build()
+        .unwrap();
+    let buffers = vec![sample_image_bytes(40, 40, [200, 0, 0, 255]); 3];
+    let deep_zoom = create_bundled_image_dzi(
+        buffers,
+        option,
+        DeepZoomOptions::new(64, 1),
+        dir.path(),
+        "sheet",
+    )
+    .await
+    .unwrap();
+    assert_eq!((deep_zoom.width, deep_zoom.height), (120, 40));
+    let descriptor = std::fs::read_to_string(&deep_zoom.descriptor).unwrap();
+    assert!(descriptor.contains("TileSize=\"64\""));
+    assert!(descriptor.contains("<Size Width=\"120\" Height=\"40\"/>"));
+    //the full size level is 2 tiles wide, the second one starts one pixel early for the overlap
+    let files = dir.path().join("sheet_files");
+    let tile = image::open(files.join("7/1_0.png")).unwrap();
+    assert_eq!(tile.dimensions(), (57, 40));
+    assert!(!files.join("7/2_0.png").exists());
+    let top = image::open(files.join("0/0_0.png")).unwrap();
+    assert_eq!(top.dimensions(), (1, 1));
+}
+
+#[tokio::test]
+async fn test_theme() {
+    use super::*;
+    let buffers = vec![sample_image_bytes(20, 20, [200, 0, 0, 255]); 2];
+    let builder = || {
+        CreateBundledImageOptionsBuilder::new()
+            .set_member_dimension(20, 20)
+            .set_column(2)
+            .set_gap(4)
+            .set_output_format(OutputFormat::Png)
+    };
+    let dark = Theme::dark();
+    let themed = create_bundled_image(buffers.clone(), builder().set_theme(dark).build().unwrap())
+        .await
+        .unwrap();
+    let themed = image::load_from_memory(&themed.bytes).unwrap();
+    assert_eq!(themed.get_pixel(22, 10), dark.background);
+    //an explicit background overrides the theme one
+    let background = Rgba([0, 120, 0, 255]);
+    let overridden = create_bundled_image(
+        buffers,
+        builder()
+            .set_theme(dark)
+            .set_background(background)
+            .build()
+            .unwrap(),
+    )
+    .await
+    .unwrap();
+    let overridden = image::load_from_memory(&overridden.bytes).unwrap();
+    assert_eq!(overridden.get_pixel(22, 10), background);
+
+    //themed badges and arrows take the accent, a themed ruler the banner and themed labels the text
+    let style = TextCardStyle::new(test_font_bytes(), Rgba([255, 255, 255, 255]), Some(10.0));
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_column(2)
+        .set_gap(20)
+        .set_theme(dark)
+        .set_badge(NumberBadge::themed(style.clone(), CellAlignment::TopLeft))
+        .set_step_arrows(ArrowStyle::themed(2, 8))
+        .set_ruler(Ruler::themed(
+            20.0,
+            RulerUnit::Centimeter,
+            Edge::Bottom,
+            style.clone(),
+        ))
+        .set_output_format(OutputFormat::Png)
+        .build()
+        .unwrap();
+    let bundled = create_bundled_image(
+        vec![sample_image_bytes(100, 80, [200, 0, 0, 255]); 2],
+        option,
+    )
+    .await
+    .unwrap();
+    let image = image::load_from_memory(&bundled.bytes).unwrap().to_rgba8();
+    assert_eq!(image.dimensions(), (220, 80));
+    assert_eq!(image.get_pixel(12, 5), &dark.accent);
+    assert_eq!(image.get_pixel(105, 40), &dark.accent);
+    assert_eq!(image.get_pixel(51, 61), &dark.banner);
+    let style = TextCardStyle::new(test_font_bytes(), Rgba([255, 255, 255, 255]), Some(24.0));
+    let labels = StripLabels::themed(vec!["one".to_string(), "two".to_string()], style);
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_member_dimension(80, 80)
+        .set_theme(dark)
+        .set_output_format(OutputFormat::Png)
+        .build()
+        .unwrap();
+    let bundled = create_labeled_strip(
+        vec![sample_image_bytes(80, 80, [200, 0, 0, 255]); 2],
+        StripDirection::Horizontal,
+        labels,
+        option,
+    )
+    .await
+    .unwrap();
+    let image = image::load_from_memory(&bundled.bytes).unwrap().to_rgba8();
+    assert!(image.pixels().any(|pixel| pixel == &dark.text));
+    assert!(!image
+        .pixels()
+        .any(|pixel| pixel == &Rgba([255, 255, 255, 255])));
+}
+
+#[tokio::test]
+async fn test_transforms() {
+    use super::*;
+    //left half red, right half blue
+    let image = image::ImageBuffer::from_fn(20, 10, |x, _| {
+        if x < 10 {
+            Rgba([200, 0, 0, 255])
+        } else {
+            Rgba([0, 0, 200, 255])
+        }
+    });
+    let mut buffer = Vec::new();
+    DynamicImage::ImageRgba8(image)
+        .write_to(&mut buffer, image::ImageOutputFormat::Png)
+        .unwrap();
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_column(2)
+        .set_gap(0)
+        .set_transforms(vec![
+            (0, Transform::Rotate90),
+            (1, Transform::FlipHorizontal),
+        ])
+        .set_output_format(OutputFormat::Png)
+        .build()
+        .unwrap();
+    let image_bytes = create_bundled_image(vec![buffer.clone(), buffer], option)
+        .await
+        .unwrap();
+    let bundled = image::load_from_memory(&image_bytes.bytes).unwrap();
+    //the member size follows the rotated image
+    assert_eq!(bundled.dimensions(), (20, 20));
+    assert_eq!(bundled.get_pixel(5, 2), Rgba([200, 0, 0, 255]));
+    assert_eq!(bundled.get_pixel(5, 17), Rgba([0, 0, 200, 255]));
+    //the flipped image is scaled into the same cell with blue on the left
+    let (left, right) = (bundled.get_pixel(11, 10), bundled.get_pixel(18, 10));
+    assert!(left[2] > left[0] && right[0] > right[2]);
+}
+
+#[tokio::test]
+async fn test_max_canvas() {
+    use super::*;
+    let buffers = vec![sample_image_bytes(100, 100, [200, 0, 0, 255]); 4];
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_column(2)
+        .set_gap(10)
+        .set_max_canvas(150, 400)
+        .set_output_format(OutputFormat::Png)
+        .build()
+        .unwrap();
+    let image_bytes = create_bundled_image(buffers.clone(), option).await.unwrap();
+    let bundled = image::load_from_memory(&image_bytes.bytes).unwrap();
+    //the gap keeps its size, only the members shrink
+    assert_eq!(bundled.dimensions(), (150, 150));
+    //header and footer bands keep their height, so the grid gets what is left
+    let blank: BandDraw = std::sync::Arc::new(|_| Ok(()));
+    let banded = |max_height| {
+        CreateBundledImageOptionsBuilder::new()
+            .set_column(2)
+            .set_gap(10)
+            .set_max_canvas(150, max_height)
+            .set_header(Band::new(30, BandContent::Custom(blank.clone())))
+            .set_footer(Band::new(20, BandContent::Custom(blank.clone())))
+            .set_output_format(OutputFormat::Png)
+            .build()
+            .unwrap()
+    };
+    let image_bytes = create_bundled_image(buffers.clone(), banded(200))
+        .await
+        .unwrap();
+    let bundled = image::load_from_memory(&image_bytes.bytes).unwrap();
+    assert_eq!(bundled.dimensions(), (150, 200));
+    assert!(matches!(
+        create_bundled_image(buffers.clone(), banded(50)).await,
+        Err(ImageCombinerError::InvalidOptions(_))
+    ));
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_column(2)
+        .set_gap(10)
+        .set_max_canvas(5, 5)
+        .build()
+        .unwrap();
+    assert!(matches!(
+        create_bundled_image(buffers.clone(), option).await,
+        Err(ImageCombinerError::InvalidOptions(_))
+    ));
+    //layouts that are not planned as a grid reject the limit instead of ignoring it
+    assert!(matches!(
+        CreateBundledImageOptionsBuilder::new()
+            .set_layout_mode(LayoutMode::Masonry)
+            .set_max_canvas(150, 150)
+            .build(),
+        Err(ImageCombinerError::InvalidOptions(_))
+    ));
+    let limited = CreateBundledImageOptionsBuilder::new()
+        .set_max_canvas(150, 150)
+        .build()
+        .unwrap();
+    assert!(matches!(
+        combine_horizontal(buffers.clone(), limited.clone()).await,
+        Err(ImageCombinerError::InvalidOptions(_))
+    ));
+    assert!(matches!(
+        create_bundled_image_with_template(
+            buffers.clone(),
+            LayoutTemplate::new(2, 2).add_cell(0, 0, 1, 1),
+            limited.clone()
+        )
+        .await,
+        Err(ImageCombinerError::InvalidOptions(_))
+    ));
+    let labels = MatrixLabels::new(
+        Vec::new(),
+        Vec::new(),
+        TextCardStyle::new(test_font_bytes(), Rgba([0, 0, 0, 255]), None),
+    );
+    assert!(matches!(
+        create_labeled_matrix(vec![buffers], labels, limited).await,
+        Err(ImageCombinerError::InvalidOptions(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_seed() {
+    use super::*;
+    let buffers = vec![sample_image_bytes(20, 20, [200, 0, 0, 255]); 2];
+    let unseeded = create_bundled_image(
+        buffers.clone(),
+        CreateBundledImageOptionsBuilder::new().build().unwrap(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(unseeded.seed, None);
+    let seeded = create_bundled_image(
+        buffers,
+        CreateBundledImageOptionsBuilder::new()
+            .set_seed(42)
+            .build()
+            .unwrap(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(seeded.seed, Some(42));
+    assert_eq!(seeded.bytes, unseeded.bytes);
+
+    //the seed is handed to the source before it is read
+    struct SeededSource {
+        buffers: std::vec::IntoIter<Vec<u8>>,
+        seed: Option<u64>,
+    }
+    impl ImageSource for SeededSource {
+        async fn next_image(&mut self) -> Option<Result<Vec<u8>, ImageCombinerError>> {
+            assert_eq!(self.seed, Some(42));
+            self.buffers.next_image().await
+        }
+
+        fn seed(&mut self, seed: u64) {
+            self.seed = Some(seed);
+        }
+    }
+    let mut source = SeededSource {
+        buffers: vec![sample_image_bytes(20, 20, [200, 0, 0, 255]); 2].into_iter(),
+        seed: None,
+    };
+    create_bundled_image(
+        &mut source,
+        CreateBundledImageOptionsBuilder::new()
+            .set_seed(42)
+            .build()
+            .unwrap(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(source.seed, Some(42));
+    assert_eq!(
+        CreateBundledImageOptionsBuilder::new()
+            .set_seed(42)
+            .build()
+            .unwrap()
+            .cache_fingerprint(),
+        CreateBundledImageOptionsBuilder::new()
+            .build()
+            .unwrap()
+            .cache_fingerprint()
+    );
+}
+
+#[tokio::test]
+async fn test_script_fonts() {
+    use super::*;
+    assert_eq!(
+        fonts::script_runs("Hello, 世界! مرحبا"),
+        vec![
+            (Some(Script::Latin), "Hello, "),
+            (Some(Script::Cjk), "世界! "),
+            (Some(Script::Arabic), "مرحبا"),
+        ]
+    );
+    assert_eq!(fonts::script_runs("2024"), vec![(None, "2024")]);
+    //the script font is loaded with the style, so a broken one fails the render
+    let tiles = vec![Tile::TextCard {
+        text: "caption 日本".to_string(),
+        style: TextCardStyle::new(test_font_bytes(), Rgba([0, 0, 0, 255]), None)
+            .with_script_font(Script::Cjk, vec![0, 1, 2]),
+        background: Rgba([255, 255, 255, 255]),
+    }];
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_member_dimension(80, 80)
+        .build()
+        .unwrap();
+    assert!(matches!(
+        create_bundled_image_from_tiles(tiles, option).await,
+        Err(ImageCombinerError::Tile { index: 0, .. })
+    ));
+
+    //Latin takes the registered font while Arabic falls back to the style font,
+    //and each run starts where the previous one ends in its own font
+    let black = Rgba([0, 0, 0, 255]);
+    let sans = test_font_bytes();
+    let mono = std::fs::read("./test/DejaVuSansMono.ttf").unwrap();
+    let font_set = |bytes: &Vec<u8>| {
+        fonts::FontSet::new(&TextCardStyle::new(bytes.clone(), black, None)).unwrap()
+    };
+    let mixed = fonts::FontSet::new(
+        &TextCardStyle::new(sans.clone(), black, None)
+            .with_script_font(Script::Latin, mono.clone()),
+    )
+    .unwrap();
+    let scale = Scale::uniform(20.0);
+    let (arabic, latin) = ("مرحبا ", "abc");
+    let canvas = || ImageBuffer::from_pixel(200, 30, Rgba([255, 255, 255, 255]));
+    let mut drawn = canvas();
+    mixed.draw(
+        &mut drawn,
+        black,
+        0,
+        0,
+        scale,
+        &format!("{}{}", arabic, latin),
+    );
+    let arabic_width = font_set(&sans).width(arabic, scale);
+    let mut expected = canvas();
+    font_set(&sans).draw(&mut expected, black, 0, 0, scale, arabic);
+    font_set(&mono).draw(&mut expected, black, arabic_width as u32, 0, scale, latin);
+    assert_eq!(drawn, expected);
+    let mut primary_only = canvas();
+    font_set(&sans).draw(&mut primary_only, black, 0, 0, scale, arabic);
+    font_set(&sans).draw(
+        &mut primary_only,
+        black,
+        arabic_width as u32,
+        0,
+        scale,
+        latin,
+    );
+    assert_ne!(drawn, primary_only);
+    assert_eq!(
+        mixed.width(&format!("{}{}", arabic, latin), scale),
+        arabic_width + font_set(&mono).width(latin, scale)
+    );
+}
+
+#[tokio::test]
+async fn test_allow_upscale() {
+    use super::*;
+    let buffers = vec![sample_image_bytes(20, 20, [200, 0, 0, 255])];
+    let builder = || {
+        CreateBundledImageOptionsBuilder::new()
+            .set_member_dimension(40, 40)
+            .set_padding(0)
+            .set_background(Rgba([255, 255, 255, 255]))
+            .set_output_format(OutputFormat::Png)
+    };
+    let image_bytes = create_bundled_image(buffers.clone(), builder().build().unwrap())
+        .await
+        .unwrap();
+    let image = image::load_from_memory(&image_bytes.bytes).unwrap();
+    //kept at 20x20 and centered in the 40x40 cell
+    assert_eq!(image.dimensions(), (40, 40));
+    assert_eq!(image.get_pixel(5, 5), Rgba([255, 255, 255, 255]));
+    assert_eq!(image.get_pixel(20, 20), Rgba([200, 0, 0, 255]));
+    let image_bytes =
+        create_bundled_image(buffers, builder().set_allow_upscale(true).build().unwrap())
+            .await
+            .unwrap();
+    let image = image::load_from_memory(&image_bytes.bytes).unwrap();
+    assert_eq!(image.get_pixel(5, 5), Rgba([200, 0, 0, 255]));
+}
+
+#[tokio::test]
+async fn test_crop_aspect() {
+    use super::*;
+    //blue stripes on the sides are cropped away
+    let wide = image::ImageBuffer::from_fn(40, 20, |x, _| {
+        if (10..30).contains(&x) {
+            Rgba([200, 0, 0, 255])
+        } else {
+            Rgba([0, 0, 200, 255])
+        }
+    });
+    let mut wide_bytes = Vec::new();
+    DynamicImage::ImageRgba8(wide)
+        .write_to(&mut wide_bytes, image::ImageOutputFormat::Png)
+        .unwrap();
+    let buffers = vec![wide_bytes, sample_image_bytes(20, 40, [200, 0, 0, 255])];
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_column(2)
+        .set_gap(0)
+        .set_crop_aspect(1, 1)
+        .set_output_format(OutputFormat::Png)
+        .build()
+        .unwrap();
+    let image_bytes = create_bundled_image(buffers, option).await.unwrap();
+    let image = image::load_from_memory(&image_bytes.bytes).unwrap();
+    assert_eq!(image.dimensions(), (40, 20));
+    for (x, y) in [(0, 0), (19, 19), (20, 0), (39, 19)] {
+        assert_eq!(image.get_pixel(x, y), Rgba([200, 0, 0, 255]));
+    }
+}
+
+#[tokio::test]
+async fn test_trim_background() {
+    use super::*;
+    let framed = image::ImageBuffer::from_fn(40, 40, |x, y| {
+        if (15..25).contains(&x) && (10..30).contains(&y) {
+            Rgba([200, 0, 0, 255])
+        } else {
+            Rgba([250, 252, 255, 255])
+        }
+    });
+    let mut buffer = Vec::new();
+    DynamicImage::ImageRgba8(framed)
+        .write_to(&mut buffer, image::ImageOutputFormat::Png)
+        .unwrap();
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_padding(0)
+        .set_trim_background(8)
+        .set_output_format(OutputFormat::Png)
+        .build()
+        .unwrap();
+    let image_bytes = create_bundled_image(vec![buffer.clone()], option)
+        .await
+        .unwrap();
+    let image = image::load_from_memory(&image_bytes.bytes).unwrap();
+    assert_eq!(image.dimensions(), (10, 20));
+    assert_eq!(image.get_pixel(0, 0), Rgba([200, 0, 0, 255]));
+    //the member size comes from the decoded images instead of a decode just to probe them
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_trim_background(8)
+        .build()
+        .unwrap();
+    assert_eq!(
+        probe_member_dimension(&[Tile::Image(buffer.clone())], &option),
+        None
+    );
+    let option = CreateBundledImageOptionsBuilder::new().build().unwrap();
+    assert_eq!(
+        probe_member_dimension(&[Tile::Image(buffer)], &option),
+        Some((40, 40))
+    );
+}
+
+#[test]
+fn test_arc_text() {
+    use super::*;
+    let style = TextCardStyle::new(test_font_bytes(), Rgba([200, 0, 0, 255]), Some(16.0));
+    for (center_angle, direction, on_top) in [
+        (0.0, ArcDirection::Clockwise, true),
+        (180.0, ArcDirection::CounterClockwise, false),
+    ] {
+        let arc = ArcText::new(40.0, center_angle, direction);
+        let overlay = render_arc_text("STAMP", &style, arc).unwrap();
+        let (width, height) = overlay.dimensions();
+        assert_eq!(width, height);
+        let inked = |top: bool| {
+            overlay
+                .enumerate_pixels()
+                .filter(|(_, y, pixel)| (*y < height / 2) == top && pixel[3] > 0)
+                .count()
+        };
+        assert!(inked(on_top) > 0);
+        assert_eq!(inked(!on_top), 0);
+        //glyph edges are blended rather than cut
+        assert!(overlay.pixels().any(|pixel| pixel[3] > 0 && pixel[3] < 255));
+    }
+    assert!(matches!(
+        render_arc_text(
+            "STAMP",
+            &style,
+            ArcText::new(0.0, 0.0, ArcDirection::Clockwise)
+        ),
+        Err(ImageCombinerError::InvalidOptions(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_smart_crop() {
+    use super::*;
+    //flat gray with a checkered subject at the right edge
+    let subject = image::ImageBuffer::from_fn(120, 40, |x, y| {
+        if x >= 80 && (x / 4 + y / 4) % 2 == 0 {
+            Rgba([200, 0, 0, 255])
+        } else if x >= 80 {
+            Rgba([0, 0, 200, 255])
+        } else {
+            Rgba([128, 128, 128, 255])
+        }
+    });
+    let mut buffer = Vec::new();
+    DynamicImage::ImageRgba8(subject)
+        .write_to(&mut buffer, image::ImageOutputFormat::Png)
+        .unwrap();
+    for (fit, dimension) in [(Fit::SmartCrop, (40, 40)), (Fit::SmartCover, (20, 20))] {
+        let option = CreateBundledImageOptionsBuilder::new()
+            .set_member_dimension(dimension.0, dimension.1)
+            .set_padding(0)
+            .set_fit(fit)
+            .set_output_format(OutputFormat::Png)
+            .build()
+            .unwrap();
+        let image_bytes = create_bundled_image(vec![buffer.clone()], option)
+            .await
+            .unwrap();
+        let image = image::load_from_memory(&image_bytes.bytes)
+            .unwrap()
+            .to_rgba8();
+        assert_eq!(image.dimensions(), dimension);
+        let subject_pixels = image.pixels().filter(|pixel| pixel[1] < 64).count();
+        assert!(subject_pixels * 4 > image.pixels().count() * 3);
+    }
+}
+
+#[test]
+fn test_create_stamp() {
+    use super::*;
+    let style = TextCardStyle::new(test_font_bytes(), Rgba([200, 0, 0, 255]), None);
+    let stamp = create_stamp(
+        "SALE",
+        StampStyle::new(style.clone(), StampShape::Circle, 120)
+            .with_ring_text("LIMITED")
+            .with_stars(true),
+    )
+    .unwrap();
+    assert_eq!(stamp.dimensions(), (120, 120));
+    //outside the ring stays transparent, the ring itself is opaque
+    assert_eq!(stamp.get_pixel(0, 0)[3], 0);
+    assert_eq!(stamp.get_pixel(60, 2), &Rgba([200, 0, 0, 255]));
+    assert!(stamp.pixels().any(|pixel| pixel[3] > 0 && pixel[3] < 255));
+    let rotated = create_stamp(
+        "SALE",
+        StampStyle::new(style.clone(), StampShape::Square, 100).with_rotation(45.0),
+    )
+    .unwrap();
+    //the canvas grows to hold the turned corners
+    assert_eq!(rotated.dimensions(), (142, 142));
+    assert_eq!(rotated.get_pixel(71, 3)[3], 255);
+    //the ring text along the top of a square is centered on its drawn width
+    let square = create_stamp(
+        "SALE",
+        StampStyle::new(style, StampShape::Square, 200).with_ring_text("WWWW"),
+    )
+    .unwrap();
+    let ink: Vec<u32> = (10..190)
+        .filter(|&x| (24..50).any(|y| square.get_pixel(x, y)[3] > 0))
+        .collect();
+    let (left, right) = (ink[0] - 10, 189 - ink[ink.len() - 1]);
+    assert!(left.abs_diff(right) <= 2, "margins {} and {}", left, right);
+}
+
+#[test]
+fn test_gradient_text() {
+    use super::*;
+    let style = TextCardStyle::new(test_font_bytes(), Rgba([255, 0, 0, 255]), None)
+        .with_gradient(TextGradient::new(Rgba([0, 0, 255, 255]), 0.0));
+    let mut canvas = ImageBuffer::from_pixel(200, 40, Rgba([255, 255, 255, 255]));
+    fonts::FontSet::new(&style).unwrap().draw(
+        &mut canvas,
+        style.color,
+        0,
+        0,
+        Scale::uniform(32.0),
+        "HHHHHHHH",
+    );
+    //solid ink runs from red on the left to blue on the right
+    let ink = |range: std::ops::Range<u32>| {
+        let (mut red, mut blue) = (0u32, 0u32);
+        for (x, _, pixel) in canvas.enumerate_pixels() {
+            if range.contains(&x) && pixel[1] < 64 {
+                red += pixel[0] as u32;
+                blue += pixel[2] as u32;
+            }
+        }
+        (red, blue)
+    };
+    let (red, blue) = ink(0..40);
+    assert!(red > blue * 2);
+    let (red, blue) = ink(120..200);
+    assert!(blue > red * 2);
+}
+
+#[tokio::test]
+async fn test_dedupe() {
+    use super::*;
+    let gradient = |width: u32, height: u32, flip: bool| {
+        let image =
+            image::DynamicImage::ImageRgba8(image::ImageBuffer::from_fn(width, height, |x, _| {
+                let v = (x * 255 / width) as u8;
+                Rgba([if flip { 255 - v } else { v }, 0, 0, 255])
+            }));
+        let mut bytes = Vec::new();
+        image
+            .write_to(&mut bytes, image::ImageOutputFormat::Jpeg(80))
+            .unwrap();
+        bytes
+    };
+    //the second input is the first one saved at another size
+    let buffers = vec![
+        gradient(60, 40, false),
+        gradient(90, 60, false),
+        vec![0, 1, 2],
+        gradient(60, 40, true),
+    ];
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_member_dimension(20, 20)
+        .set_padding(0)
+        .set_column(4)
+        .set_on_decode_error(DecodeErrorPolicy::Skip)
+        .set_dedupe(4)
+        .build()
+        .unwrap();
+    let bundled = create_bundled_image(buffers.clone(), option).await.unwrap();
+    assert_eq!(bundled.duplicates, vec![1]);
+    assert_eq!(bundled.skipped, vec![2]);
+
+    //the images decoded for the hash are the ones drawn, every input is decoded once
+    let exporter = Arc::new(PrometheusExporter::new());
+    let options = || {
+        CreateBundledImageOptionsBuilder::new()
+            .set_member_dimension(20, 20)
+            .set_padding(0)
+            .set_column(4)
+            .set_on_decode_error(DecodeErrorPolicy::Skip)
+            .set_output_format(OutputFormat::Png)
+    };
+    let deduped = create_bundled_image(
+        buffers.clone(),
+        options()
+            .set_dedupe(4)
+            .set_worker_pool(WorkerPool::new(2).unwrap())
+            .set_metrics(exporter.clone())
+            .build()
+            .unwrap(),
+    )
+    .await
+    .unwrap();
+    assert!(exporter
+        .render()
+        .contains("\nimage_combiner_stage_seconds_count{stage=\"decode\"} 4\n"));
+    let kept = vec![buffers[0].clone(), buffers[2].clone(), buffers[3].clone()];
+    let expected = create_bundled_image(kept, options().build().unwrap())
+        .await
+        .unwrap();
+    assert_eq!(deduped.bytes, expected.bytes);
+
+    //under low_memory the decodes for the hash run one at a time and are kept resized and spilled
+    let low_memory = options().set_low_memory(true).build().unwrap();
+    let tiles: Vec<Tile> = buffers.iter().cloned().map(Tile::Image).collect();
+    let kept_tiles = dedupe::dedupe_tiles(tiles, &low_memory, 4).await.unwrap();
+    assert_eq!(kept_tiles.duplicates, vec![1]);
+    for decoded in kept_tiles.decoded.iter() {
+        match decoded {
+            Some(DecodedTile::Image(Ok(origin @ OriginImage::Spilled(_)), _)) => {
+                assert_eq!(origin.dimensions(), (20, 13))
+            }
+            Some(DecodedTile::Image(Err(_), _)) => {}
+            _ => panic!("kept image is not resized and spilled"),
+        }
+    }
+    let deduped = create_bundled_image(
+        buffers.clone(),
+        options()
+            .set_dedupe(4)
+            .set_low_memory(true)
+            .build()
+            .unwrap(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(deduped.duplicates, vec![1]);
+    assert_eq!(deduped.bytes, expected.bytes);
+}
+
+#[test]
+fn test_text_pill() {
+    use super::*;
+    assert_eq!(
+        contrast_color(Rgba([255, 255, 255, 255])),
+        Rgba([0, 0, 0, 255])
+    );
+    assert_eq!(
+        contrast_color(Rgba([250, 220, 40, 255])),
+        Rgba([0, 0, 0, 255])
+    );
+    assert_eq!(
+        contrast_color(Rgba([20, 40, 120, 255])),
+        Rgba([255, 255, 255, 255])
+    );
+    //white text over a mid gray photo gets a black pill reaching past the text by the padding
+    let style = TextCardStyle::new(test_font_bytes(), Rgba([255, 255, 255, 255]), None)
+        .with_pill(TextPill::new(6));
+    let mut canvas = ImageBuffer::from_pixel(200, 60, Rgba([128, 128, 128, 255]));
+    fonts::FontSet::new(&style).unwrap().draw(
+        &mut canvas,
+        style.color,
+        30,
+        20,
+        Scale::uniform(20.0),
+        "SALE",
+    );
+    assert_eq!(canvas.get_pixel(40, 16), &Rgba([0, 0, 0, 255]));
+    assert_eq!(canvas.get_pixel(40, 8), &Rgba([128, 128, 128, 255]));
+    //the ends are rounded
+    assert_eq!(canvas.get_pixel(24, 14), &Rgba([128, 128, 128, 255]));
+    let style = style.with_pill(TextPill::new(6).with_color(Rgba([0, 90, 200, 255])));
+    let mut canvas = ImageBuffer::from_pixel(200, 60, Rgba([128, 128, 128, 255]));
+    fonts::FontSet::new(&style).unwrap().draw(
+        &mut canvas,
+        style.color,
+        30,
+        20,
+        Scale::uniform(20.0),
+        "SALE",
+    );
+    assert_eq!(canvas.get_pixel(40, 16), &Rgba([0, 90, 200, 255]));
+}
+
+#[tokio::test]
+async fn test_relative_units() {
+    use super::*;
+    let buffers: Vec<Vec<u8>> = (0..2)
+        .map(|_| sample_image_bytes(40, 40, [200, 0, 0, 255]))
+        .collect();
+    let render = |size: u32| {
+        let buffers = buffers.clone();
+        async move {
+            let option = CreateBundledImageOptionsBuilder::new()
+                .set_member_dimension(size, size)
+                .set_column(2)
+                .set_gap_unit(Unit::Percent(10.0))
+                .set_outer_margin_unit(Unit::Percent(5.0))
+                .build()
+                .unwrap();
+            let bytes = create_bundled_image(buffers, option).await.unwrap().bytes;
+            image::load_from_memory(&bytes).unwrap().dimensions()
+        }
+    };
+    //the spacing grows with the cells, so the proportions stay the same
+    assert_eq!(render(100).await, (220, 110));
+    assert_eq!(render(200).await, (440, 220));
+
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_member_dimension(100, 50)
+        .set_column(2)
+        .set_padding_unit(Unit::Percent(20.0))
+        .build()
+        .unwrap();
+    let bytes = create_bundled_image(buffers.clone(), option.clone())
+        .await
+        .unwrap()
+        .bytes;
+    //percent is of the shorter side of the 200x50 images area, padding trails every cell
+    assert_eq!(
+        image::load_from_memory(&bytes).unwrap().dimensions(),
+        (220, 60)
+    );
+    //a second row makes the images area 200x100, so the padding doubles
+    let bytes = create_bundled_image([buffers.clone(), buffers].concat(), option)
+        .await
+        .unwrap()
+        .bytes;
+    assert_eq!(
+        image::load_from_memory(&bytes).unwrap().dimensions(),
+        (240, 140)
+    );
+}
+
+#[tokio::test]
+async fn test_header_footer_bands() {
+    use super::*;
+    let buffers: Vec<Vec<u8>> = (0..2)
+        .map(|_| sample_image_bytes(40, 40, [200, 0, 0, 255]))
+        .collect();
+    let style = TextCardStyle::new(test_font_bytes(), Rgba([0, 0, 0, 255]), None);
+    let green: BandDraw = std::sync::Arc::new(|canvas| {
+        for pixel in canvas.pixels_mut() {
+            *pixel = Rgba([0, 200, 0, 255]);
+        }
+        Ok(())
+    });
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_member_dimension(40, 40)
+        .set_column(2)
+        .set_padding(0)
+        .set_background(Rgba([255, 255, 255, 255]))
+        .set_header(Band::new(
+            30,
+            BandContent::Text("Spring".to_string(), style),
+        ))
+        .set_footer(Band::new(20, BandContent::Custom(green)))
+        .set_output_format(OutputFormat::Png)
+        .build()
+        .unwrap();
+    let bytes = create_bundled_image(buffers.clone(), option)
+        .await
+        .unwrap()
+        .bytes;
+    let image = image::load_from_memory(&bytes).unwrap().to_rgba8();
+    assert_eq!(image.dimensions(), (80, 90));
+    assert!(image
+        .enumerate_pixels()
+        .any(|(_, y, pixel)| y < 30 && pixel[0] < 64));
+    assert_eq!(image.get_pixel(20, 50), &Rgba([200, 0, 0, 255]));
+    assert_eq!(image.get_pixel(40, 80), &Rgba([0, 200, 0, 255]));
+
+    //a table too large for its band is an error rather than being cut off
+    let table = TableBase::new(
+        vec!["size".to_string(), "color".to_string()],
+        vec![vec!["M".to_string(), "red".to_string()]],
+        1,
+    )
+    .unwrap();
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_member_dimension(40, 40)
+        .set_column(2)
+        .set_padding(0)
+        .set_footer(Band::new(
+            4,
+            BandContent::Table(table.clone(), test_font_bytes()),
+        ))
+        .build()
+        .unwrap();
+    assert!(matches!(
+        create_bundled_image(buffers.clone(), option).await,
+        Err(ImageCombinerError::InvalidTable(_))
+    ));
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_member_dimension(200, 200)
+        .set_column(2)
+        .set_padding(0)
+        .set_footer(Band::new(60, BandContent::Table(table, test_font_bytes())))
+        .build()
+        .unwrap();
+    let bytes = create_bundled_image(buffers, option).await.unwrap().bytes;
+    assert_eq!(
+        image::load_from_memory(&bytes).unwrap().dimensions(),
+        (400, 260)
+    );
+}
+
+#[test]
+fn test_overlay_position() {
+    use super::*;
+    let place = |anchor: Anchor, offset: (i32, i32)| {
+        OverlayPosition::new(anchor)
+            .with_offset(offset.0, offset.1)
+            .place((100, 50), (20, 10))
+    };
+    assert_eq!(place(Anchor::TopLeft, (5, 5)), (5, 5));
+    //offsets move in from the anchored edges
+    assert_eq!(place(Anchor::BottomRight, (5, 5)), (75, 35));
+    assert_eq!(place(Anchor::Center, (3, -2)), (43, 18));
+    assert_eq!(place(Anchor::Right, (4, 4)), (76, 24));
+    //never outside the area
+    assert_eq!(place(Anchor::TopLeft, (-10, 0)), (0, 0));
+    assert_eq!(place(Anchor::BottomLeft, (0, 90)), (0, 0));
+}
+
+#[tokio::test]
+async fn test_table_placement() {
+    use super::*;
+    let table = TableBase::new(
+        vec!["size".to_string(), "color".to_string()],
+        vec![vec!["M".to_string(), "red".to_string()]],
+        1,
+    )
+    .unwrap();
+    let render = |placement: TablePlacement| {
+        let table = table.clone().with_placement(placement);
+        async move {
+            let bytes = add_table_with_theme(
+                sample_image_bytes(400, 300, [200, 0, 0, 255]),
+                table,
+                &test_font_bytes(),
+                Theme::light(),
+            )
+            .await
+            .unwrap();
+            image::load_from_memory(&bytes).unwrap().to_rgba8()
+        }
+    };
+    let image = render(TablePlacement::Bottom).await;
+    assert!(image.height() > 300);
+    assert!(image.get_pixel(200, 10)[0] > 150 && image.get_pixel(200, 10)[1] < 60);
+    let image = render(TablePlacement::Left).await;
+    assert!(image.width() > 400 && image.height() == 300);
+    //the image moves right of the sidebar
+    assert!(image.get_pixel(image.width() - 10, 150)[1] < 60);
+    assert!(image.get_pixel(5, 5)[1] > 200);
+    let image = render(TablePlacement::Overlay {
+        position: OverlayPosition::new(Anchor::TopRight),
+        opacity: 128,
+    })
+    .await;
+    assert_eq!(image.dimensions(), (400, 300));
+    //the panel lightens the image below it without hiding it
+    let panel = image.get_pixel(398, 2);
+    assert!(panel[1] > 90 && panel[1] < 170 && panel[0] > 200);
+    assert!(image.get_pixel(2, 298)[1] < 60);
+
+    //above the image a table only has to fit the width, the padding beside it gives way
+    let wide = |width: u32| {
+        TableBase::new(vec!["size".to_string()], vec![vec!["M".to_string()]], 1)
+            .unwrap()
+            .with_column_widths(vec![ColumnWidth::Fixed(width)])
+    };
+    let image = add_table_with_theme(
+        sample_image_bytes(400, 300, [200, 0, 0, 255]),
+        wide(390),
+        &test_font_bytes(),
+        Theme::light(),
+    )
+    .await
+    .unwrap();
+    let image = image::load_from_memory(&image).unwrap().to_rgba8();
+    assert_eq!(image.width(), 400);
+    assert!(matches!(
+        add_table_with_theme(
+            sample_image_bytes(400, 300, [200, 0, 0, 255]),
+            wide(410),
+            &test_font_bytes(),
+            Theme::light(),
+        )
+        .await,
+        Err(ImageCombinerError::InvalidTable(_))
+    ));
+}
+
+#[tokio::test]
+async fn test_overlay_blend_modes() {
+    use super::*;
+    let render = |overlays: Vec<OverlayLayer>| async move {
+        let option = CreateBundledImageOptionsBuilder::new()
+            .set_member_dimension(40, 40)
+            .set_padding(0)
+            .set_overlays(overlays)
+            .set_output_format(OutputFormat::Png)
+            .build()
+            .unwrap();
+        let buffers = vec![sample_image_bytes(40, 40, [128, 128, 128, 255])];
+        let bytes = create_bundled_image(buffers, option).await.unwrap().bytes;
+        image::load_from_memory(&bytes).unwrap().to_rgba8()
+    };
+    let layer = |blend_mode: BlendMode| {
+        OverlayLayer::new(sample_image_bytes(10, 10, [200, 100, 0, 255]), blend_mode)
+            .with_position(OverlayPosition::new(Anchor::TopLeft))
+    };
+    let image = render(vec![layer(BlendMode::Multiply)]).await;
+    assert_eq!(image.get_pixel(5, 5), &Rgba([100, 50, 0, 255]));
+    assert_eq!(image.get_pixel(20, 20), &Rgba([128, 128, 128, 255]));
+    let image = render(vec![layer(BlendMode::Screen)]).await;
+    assert_eq!(image.get_pixel(5, 5), &Rgba([228, 178, 128, 255]));
+    let image = render(vec![layer(BlendMode::Overlay).with_opacity(0)]).await;
+    assert_eq!(image.get_pixel(5, 5), &Rgba([128, 128, 128, 255]));
+    //a tiled texture covers the canvas
+    let image = render(vec![layer(BlendMode::Normal).with_tiled(true)]).await;
+    assert_eq!(image.get_pixel(35, 35), &Rgba([200, 100, 0, 255]));
+}
+
+#[tokio::test]
+async fn test_table_style() {
+    use super::*;
+    let table = TableBase::new(
+        vec!["size".to_string(), "color".to_string()],
+        vec![
+            vec!["M".to_string(), "red".to_string()],
+            vec!["L".to_string(), "blue".to_string()],
+        ],
+        1,
+    )
+    .unwrap()
+    .with_style(
+        TableStyle::new()
+            .with_header_fill(Rgba([0, 60, 160, 255]))
+            .with_body_fill(Rgba([250, 240, 200, 255]))
+            .with_text_color(Rgba([255, 255, 255, 255]))
+            .with_line_color(Rgba([200, 0, 0, 255]))
+            .with_line_width(3),
+    );
+    let bytes = create_table_image_with_theme(table, &test_font_bytes(), Theme::light())
+        .await
+        .unwrap();
+    let image = image::load_from_memory(&bytes).unwrap().to_rgba8();
+    let count = |check: &dyn Fn(&Rgba<u8>) -> bool| image.pixels().filter(|p| check(p)).count();
+    assert!(count(&|p| p[2] > 130 && p[0] < 30 && p[1] > 40 && p[1] < 80) > 500);
+    assert!(count(&|p| p[0] > 235 && p[2] > 180 && p[2] < 220) > 500);
+    assert!(count(&|p| p[0] > 170 && p[1] < 40 && p[2] < 40) > 200);
+}
+
+#[tokio::test]
+async fn test_table_stripes() {
+    use super::*;
+    let body = (1..=4)
+        .map(|no| vec![no.to_string(), "cm".to_string()])
+        .collect();
+    let table = TableBase::new(vec!["no".to_string(), "unit".to_string()], body, 1)
+        .unwrap()
+        .with_style(
+            TableStyle::new()
+                .with_header_fill(Rgba([0, 60, 160, 255]))
+                .with_stripe_fill(Rgba([200, 230, 200, 255])),
+        );
+    let bytes = create_table_image_with_theme(table, &test_font_bytes(), Theme::light())
+        .await
+        .unwrap();
+    let image = image::load_from_memory(&bytes).unwrap().to_rgba8();
+    //the second and fourth rows are striped, the first and third keep the background
+    let striped_rows: Vec<u32> = (0..image.height())
+        .filter(|y| {
+            (0..image.width())
+                .filter(|x| {
+                    let p = image.get_pixel(*x, *y);
+                    p[1] > 215 && p[0] > 185 && p[0] < 215 && p[2] > 185 && p[2] < 215
+                })
+                .count()
+                > 20
+        })
+        .collect();
+    assert!(!striped_rows.is_empty());
+    let band_count = striped_rows.windows(2).filter(|w| w[1] > w[0] + 1).count() + 1;
+    assert_eq!(band_count, 2);
+    assert!(image
+        .pixels()
+        .any(|p| p[2] > 130 && p[0] < 30 && p[1] > 40 && p[1] < 80));
+}
+
+#[tokio::test]
+async fn test_transposed_table() {
+    use super::*;
+    let table = || {
+        TableBase::new(
+            vec![
+                "size".to_string(),
+                "chest".to_string(),
+                "length".to_string(),
+            ],
+            vec![
+                vec!["S".to_string(), "88".to_string(), "65".to_string()],
+                vec!["M".to_string(), "92".to_string(), "67".to_string()],
+            ],
+            1,
+        )
+        .unwrap()
+        .with_style(TableStyle::new().with_header_fill(Rgba([0, 60, 160, 255])))
+    };
+    let transposed = table().transpose();
+    assert_eq!(transposed.head, vec!["size", "S", "M"]);
+    assert_eq!(
+        transposed.body,
+        vec![vec!["chest", "88", "92"], vec!["length", "65", "67"]]
+    );
+    let render = |table: TableBase| async move {
+        let bytes = create_table_image_with_theme(table, &test_font_bytes(), Theme::light())
+            .await
+            .unwrap();
+        image::load_from_memory(&bytes).unwrap().to_rgba8()
+    };
+    //bounding box of the header fill
+    let header_box = |image: &image::RgbaImage| {
+        let blue: Vec<(u32, u32)> = image
+            .enumerate_pixels()
+            .filter(|(_, _, p)| p[2] > 130 && p[0] < 30 && p[1] > 40 && p[1] < 80)
+            .map(|(x, y, _)| (x, y))
+            .collect();
+        let width =
+            blue.iter().map(|p| p.0).max().unwrap() - blue.iter().map(|p| p.0).min().unwrap();
+        let height =
+            blue.iter().map(|p| p.1).max().unwrap() - blue.iter().map(|p| p.1).min().unwrap();
+        (width, height)
+    };
+    let plain = render(table()).await;
+    let transposed = render(table().with_transposed(true)).await;
+    let (plain_width, plain_height) = header_box(&plain);
+    let (head_width, head_height) = header_box(&transposed);
+    assert!(plain_width > plain_height);
+    //the head is a column as tall as the three rows
+    assert!(head_height > plain_height * 2);
+    assert!(head_width < plain_width);
+}
+
+#[tokio::test]
+async fn test_column_alignment() {
+    use super::*;
+    let render = |alignment: ColumnAlignment| async move {
+        let table = TableBase::new(
+            vec!["measurement".to_string()],
+            vec![vec!["1".to_string()]],
+            1,
+        )
+        .unwrap()
+        .with_column_alignments(vec![alignment])
+        .with_style(
+            TableStyle::new()
+                .with_text_color(Rgba([0, 0, 0, 255]))
+                .with_line_color(Rgba([255, 0, 0, 255])),
+        );
+        let bytes = create_table_image_with_theme(table, &test_font_bytes(), Theme::light())
+            .await
+            .unwrap();
+        let image = image::load_from_memory(&bytes).unwrap().to_rgba8();
+        //leftmost text pixel of the body row
+        (image.height() / 2..image.height())
+            .flat_map(|y| (0..image.width()).map(move |x| (x, y)))
+            .filter(|(x, y)| image.get_pixel(*x, *y).0[..3].iter().all(|c| *c < 80))
+            .map(|(x, _)| x)
+            .min()
+            .unwrap()
+    };
+    let left = render(ColumnAlignment::Left).await;
+    let center = render(ColumnAlignment::Center).await;
+    let right = render(ColumnAlignment::Right).await;
+    assert!(
+        left < center && center < right,
+        "{} {} {}",
+        left,
+        center,
+        right
+    );
+}
+
+#[tokio::test]
+async fn test_column_widths() {
+    use super::*;
+    let table = TableBase::new(
+        vec!["size".to_string(), "note".to_string(), "cm".to_string()],
+        vec![vec!["M".to_string(), "slim".to_string(), "70".to_string()]],
+        1,
+    )
+    .unwrap()
+    .with_column_widths(vec![
+        ColumnWidth::Fixed(300),
+        ColumnWidth::Fill,
+        ColumnWidth::Min(10),
+    ])
+    .with_style(TableStyle::new().with_line_color(Rgba([255, 0, 0, 255])));
+    let bytes = create_table_image_with_theme(table, &test_font_bytes(), Theme::light())
+        .await
+        .unwrap();
+    let image = image::load_from_memory(&bytes).unwrap().to_rgba8();
+    let lines: Vec<u32> = (0..image.width())
+        .filter(|x| {
+            (0..image.height())
+                .filter(|y| {
+                    let p = image.get_pixel(*x, *y);
+                    p[0] > 180 && p[1] < 90 && p[2] < 90
+                })
+                .count()
+                > 30
+        })
+        .collect();
+    let (first, last) = (lines[0], lines[lines.len() - 1]);
+    //the fill column stretches the table over the canvas less its 5% padding
+    assert!((862..=866).contains(&(last - first)), "{:?}", lines);
+    assert!(lines
+        .iter()
+        .any(|x| (first + 299..=first + 301).contains(x)));
+}
+
+#[tokio::test]
+async fn test_table_cell_wrap() {
+    use super::*;
+    let render = |widths: Vec<ColumnWidth>| async move {
+        let table = TableBase::new(
+            vec!["size".to_string(), "note".to_string()],
+            vec![
+                vec!["M".to_string(), "适合身高160-170cm".to_string()],
+                vec!["L".to_string(), "fits most people over 170cm".to_string()],
+            ],
+            1,
+        )
+        .unwrap()
+        .with_column_widths(widths);
+        let bytes = create_table_image_with_theme(table, &test_font_bytes(), Theme::light())
+            .await
+            .unwrap();
+        image::load_from_memory(&bytes).unwrap().dimensions()
+    };
+    let (_, unwrapped_height) = render(vec![]).await;
+    let (_, wrapped_height) = render(vec![ColumnWidth::Auto, ColumnWidth::Max(150)]).await;
+    //the note column wraps onto more lines so the rows grow instead of the table widening
+    assert!(wrapped_height > unwrapped_height);
+
+    //with default widths a long value wraps to the width of the image the table is attached to
+    let attach = |note: &str| {
+        let table = TableBase::new(
+            vec!["size".to_string(), "note".to_string()],
+            vec![vec!["M".to_string(), note.to_string()]],
+            1,
+        )
+        .unwrap();
+        async move {
+            let bytes = add_table_with_theme(
+                sample_image_bytes(200, 100, [200, 0, 0, 255]),
+                table,
+                &test_font_bytes(),
+                Theme::light(),
+            )
+            .await
+            .unwrap();
+            image::load_from_memory(&bytes).unwrap().dimensions()
+        }
+    };
+    let (_, short_height) = attach("fits").await;
+    let (width, long_height) = attach(&"fits most people over 170cm ".repeat(6)).await;
+    assert_eq!(width, 200);
+    assert!(long_height > short_height);
+}
+
+#[tokio::test]
+async fn test_table_spans() {
+    use super::*;
+    let texts = |row: &[&str]| row.iter().map(|text| text.to_string()).collect();
+    let table = TableBase::new(
+        texts(&["size", "cm", "", ""]),
+        vec![texts(&["M", "70", "100", "60"])],
+        1,
+    )
+    .unwrap()
+    .with_spans(vec![CellSpan::new(0, 1, 3)])
+    .with_style(TableStyle::new().with_line_color(Rgba([255, 0, 0, 255])));
+    let bytes = create_table_image_with_theme(table, &test_font_bytes(), Theme::light())
+        .await
+        .unwrap();
+    let image = image::load_from_memory(&bytes).unwrap().to_rgba8();
+    let red = |x: u32, y: u32| {
+        let p = image.get_pixel(x, y);
+        p[0] > 180 && p[1] < 90 && p[2] < 90
+    };
+    //column lines crossing a row, counted across the middle of the row
+    let column_lines = |y: u32| {
+        (1..image.width())
+            .filter(|x| red(*x, y) && !red(x - 1, y))
+            .count()
+    };
+    let rows: Vec<u32> = (0..image.height())
+        .filter(|y| (0..image.width()).filter(|x| red(*x, *y)).count() > 200)
+        .collect();
+    let (header_middle, body_middle) = ((rows[0] + rows[1]) / 2, (rows[1] + rows[2]) / 2);
+    assert_eq!(column_lines(header_middle), 3);
+    assert_eq!(column_lines(body_middle), 5);
+}
+
+#[tokio::test]
+async fn test_table_title_footnote() {
+    use super::*;
+    let render = |table: TableBase| async move {
+        let table = table.with_style(TableStyle::new().with_line_color(Rgba([255, 0, 0, 255])));
+        let bytes = create_table_image_with_theme(table, &test_font_bytes(), Theme::light())
+            .await
+            .unwrap();
+        image::load_from_memory(&bytes).unwrap().to_rgba8()
+    };
+    let table = TableBase::new(
+        vec!["size".to_string(), "cm".to_string()],
+        vec![vec!["M".to_string(), "70".to_string()]],
+        1,
+    )
+    .unwrap();
+    let plain = render(table.clone()).await;
+    let image = render(
+        table
+            .with_title("Size chart".to_string())
+            .with_footnote("*within 2cm".to_string()),
+    )
+    .await;
+    assert!(image.height() > plain.height());
+    //text above the grid's top line and below its bottom line
+    let red_rows: Vec<u32> = (0..image.height())
+        .filter(|y| {
+            (0..image.width())
+                .filter(|x| {
+                    let p = image.get_pixel(*x, *y);
+                    p[0] > 180 && p[1] < 90 && p[2] < 90
+                })
+                .count()
+                > 100
+        })
+        .collect();
+    let dark_rows: Vec<u32> = (0..image.height())
+        .filter(|y| (0..image.width()).any(|x| image.get_pixel(x, *y)[1] < 60))
+        .collect();
+    assert!(dark_rows.iter().any(|y| y < &red_rows[0]));
+    assert!(dark_rows.iter().any(|y| y > red_rows.last().unwrap()));
+}
+
+#[tokio::test]
+async fn test_multiple_tables() {
+    use super::*;
+    let sizes = TableBase::new(
+        vec!["size".to_string(), "cm".to_string()],
+        vec![vec!["M".to_string(), "70".to_string()]],
+        1,
+    )
+    .unwrap()
+    .with_placement(TablePlacement::Bottom);
+    let materials = TableBase::new(
+        vec!["material".to_string(), "%".to_string()],
+        vec![
+            vec!["cotton".to_string(), "80".to_string()],
+            vec!["nylon".to_string(), "20".to_string()],
+        ],
+        1,
+    )
+    .unwrap();
+    let render = |tables: Vec<TableBase>| async move {
+        let bytes = add_tables(
+            sample_image_bytes(400, 300, [200, 0, 0, 255]),
+            tables,
+            &test_font_bytes(),
+        )
+        .await
+        .unwrap();
+        image::load_from_memory(&bytes).unwrap().to_rgba8()
+    };
+    let one = render(vec![sizes.clone()]).await;
+    let both = render(vec![sizes, materials]).await;
+    //stacked below the image in the first table's placement
+    assert_eq!(both.width(), 400);
+    assert!(both.height() > one.height());
+    assert!(both.get_pixel(200, 10)[0] > 150 && both.get_pixel(200, 10)[1] < 60);
+    assert!(add_tables(
+        sample_image_bytes(400, 300, [200, 0, 0, 255]),
+        Vec::new(),
+        &test_font_bytes()
+    )
+    .await
+    .is_err());
+}
+
+#[tokio::test]
+async fn test_signature() {
+    use super::*;
+    let buffers = vec![sample_image_bytes(20, 20, [200, 0, 0, 255]); 2];
+    let unsigned = create_bundled_image(
+        buffers.clone(),
+        CreateBundledImageOptionsBuilder::new().build().unwrap(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(verify_signature(&unsigned.bytes, b"secret"), None);
+    let signed = create_bundled_image(
+        buffers,
+        CreateBundledImageOptionsBuilder::new()
+            .set_seed(7)
+            .set_signing_key(b"secret".to_vec())
+            .build()
+            .unwrap(),
+    )
+    .await
+    .unwrap();
+    let params = verify_signature(&signed.bytes, b"secret").unwrap();
+    assert!(params.starts_with("version=1\n"));
+    assert!(params.contains("\nseed=7\n"));
+    assert!(params.contains("\ncolumn=1\n"));
+    assert_eq!(verify_signature(&signed.bytes, b"other"), None);
+    //the comment does not get in the way of decoding
+    image::load_from_memory(&signed.bytes).unwrap();
+    let mut tampered = signed.bytes.clone();
+    let prefix = b"image-combiner-signature:";
+    let at = tampered
+        .windows(prefix.len())
+        .position(|w| w == prefix.as_slice())
+        .unwrap()
+        + prefix.len();
+    tampered[at] = if tampered[at] == b'A' { b'B' } else { b'A' };
+    assert_eq!(verify_signature(&tampered, b"secret"), None);
+
+    //the signature counts toward the size limit
+    let noise_bytes = noise_image_bytes(120, 90);
+    let unsigned = create_bundled_image_from_bytes(
+        vec![noise_bytes.clone()],
+        CreateBundledImageOptionsBuilder::new().build().unwrap(),
+    )
+    .await
+    .unwrap();
+    let max_bytes = unsigned.len() as u64 + 10;
+    for options in [
+        CreateBundledImageOptionsBuilder::new().set_max_output_bytes(max_bytes),
+        CreateBundledImageOptionsBuilder::new().set_target_file_size(max_bytes),
+    ] {
+        let options = options.set_signing_key(b"secret".to_vec()).build().unwrap();
+        let signed = create_bundled_image(vec![noise_bytes.clone()], options)
+            .await
+            .unwrap();
+        assert!(signed.bytes.len() as u64 <= max_bytes);
+        assert!(verify_signature(&signed.bytes, b"secret").is_some());
+    }
+}
+
+#[cfg(feature = "serde")]
+#[test]
+fn test_table_from_serde() {
+    use super::*;
+    #[derive(serde::Serialize)]
+    struct SizeChart {
+        size: &'static str,
+        chest: u32,
+        length: f32,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        note: Option<&'static str>,
+    }
+    let table = TableBase::from_serde(
+        &[
+            SizeChart {
+                size: "S",
+                chest: 88,
+                length: 65.5,
+                note: Some("slim"),
+            },
+            SizeChart {
+                size: "M",
+                chest: 92,
+                length: 67.0,
+                note: None,
+            },
+        ],
+        2,
+    )
+    .unwrap();
+    assert_eq!(table.head, vec!["size", "chest", "length", "note"]);
+    assert_eq!(
+        table.body,
+        vec![vec!["S", "88", "65.5", "slim"], vec!["M", "92", "67.0", ""]]
+    );
+    assert!(matches!(
+        TableBase::from_serde(&[1, 2], 2),
+        Err(ImageCombinerError::InvalidTable(_))
+    ));
+    assert!(TableBase::from_serde::<SizeChart>(&[], 2).is_err());
+}
+
+#[test]
+fn test_geometry_rounding() {
+    use super::*;
+    assert_eq!(geometry::round_px(2.5), 3);
+    assert_eq!(geometry::round_px(2.49), 2);
+    assert_eq!(geometry::round_px(-1.0), 0);
+    assert_eq!(geometry::round_px(f64::NAN), 0);
+    assert_eq!(geometry::fit_px(2.99), 2);
+    assert_eq!(geometry::center_px(5), 2);
+    assert_eq!(Unit::Percent(12.5).resolve((20, 40)), 3);
+    assert_eq!(CellAlignment::Center.offset(7, 3), (3, 1));
+}
+
+#[tokio::test]
+async fn test_layout_geometry() {
+    use super::*;
+    let colors = [[200, 0, 0, 255], [0, 0, 200, 255], [0, 160, 0, 255]];
+    let buffers = vec![
+        sample_image_bytes(90, 60, colors[0]),
+        sample_image_bytes(61, 87, colors[1]),
+        sample_image_bytes(75, 75, colors[2]),
+    ];
+    let blank: BandDraw = std::sync::Arc::new(|_| Ok(()));
+    let layouts: Vec<Box<dyn Fn() -> CreateBundledImageOptionsBuilder>> = vec![
+        Box::new(|| {
+            CreateBundledImageOptionsBuilder::new()
+                .set_column(3)
+                .set_member_dimension(37, 23)
+                .set_padding_unit(Unit::Percent(7.5))
+                .set_cell_alignment(CellAlignment::BottomRight)
+                .set_fit(Fit::Cover)
+        }),
+        Box::new(move || {
+            CreateBundledImageOptionsBuilder::new()
+                .set_auto_layout(16, 9)
+                .set_gap(3)
+                .set_outer_margin_unit(Unit::Percent(12.5))
+                .set_header(Band::new(15, BandContent::Custom(blank.clone())))
+                .set_footer(Band::new(9, BandContent::Custom(blank.clone())))
+        }),
+        Box::new(|| {
+            CreateBundledImageOptionsBuilder::new()
+                .set_column(2)
+                .set_padding(5)
+                .set_max_canvas(101, 67)
+        }),
+    ];
+    for layout in layouts.iter() {
+        let geometry = plan_layout_geometry(buffers.clone(), &layout().build().unwrap())
+            .await
+            .unwrap();
+        for render_quality in [RenderQuality::Final, RenderQuality::Preview] {
+            let options = layout()
+                .set_render_quality(render_quality)
+                .set_output_format(OutputFormat::Png)
+                .build()
+                .unwrap();
+            assert_eq!(
+                plan_layout_geometry(buffers.clone(), &options)
+                    .await
+                    .unwrap(),
+                geometry
+            );
+            let bytes = create_bundled_image(buffers.clone(), options)
+                .await
+                .unwrap()
+                .bytes;
+            let image = image::load_from_memory(&bytes).unwrap().to_rgba8();
+            assert_eq!(image.dimensions(), (geometry.width, geometry.height));
+            for (tile, color) in geometry.tiles.iter().zip(colors) {
+                let pixel = image.get_pixel(tile.x + tile.width / 2, tile.y + tile.height / 2);
+                assert!(
+                    pixel.0.iter().zip(color).all(|(&a, b)| a.abs_diff(b) <= 8),
+                    "{:?} at {:?} is {:?}",
+                    render_quality,
+                    tile,
+                    pixel
+                );
+            }
+        }
+    }
+    assert!(plan_layout_geometry(
+        buffers,
+        &CreateBundledImageOptionsBuilder::new()
+            .set_layout_mode(LayoutMode::Masonry)
+            .build()
+            .unwrap()
+    )
+    .await
+    .is_err());
+}
+
+#[tokio::test]
+async fn test_render_quality() {
+    use super::*;
+    let buffers = vec![
+        sample_image_bytes(90, 60, [200, 0, 0, 255]),
+        sample_image_bytes(60, 90, [0, 0, 200, 255]),
+        sample_image_bytes(75, 75, [0, 200, 0, 255]),
+    ];
+    let options = || {
+        CreateBundledImageOptionsBuilder::new()
+            .set_column(2)
+            .set_member_dimension(40, 30)
+            .set_padding(6)
+            .set_linear_light(true)
+    };
+    let final_render = create_bundled_image(buffers.clone(), options().build().unwrap())
+        .await
+        .unwrap();
+    let preview = create_bundled_image(
+        buffers.clone(),
+        options()
+            .set_render_quality(RenderQuality::Preview)
+            .build()
+            .unwrap(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(final_render.quality, Some(100));
+    assert_eq!(preview.quality, Some(PREVIEW_QUALITY));
+    assert!(preview.bytes.len() < final_render.bytes.len());
+    let render = |render_quality| {
+        create_bundled_image(
+            buffers.clone(),
+            options()
+                .set_render_quality(render_quality)
+                .set_output_format(OutputFormat::Png)
+                .build()
+                .unwrap(),
+        )
+    };
+    let final_image =
+        image::load_from_memory(&render(RenderQuality::Final).await.unwrap().bytes).unwrap();
+    let preview_image =
+        image::load_from_memory(&render(RenderQuality::Preview).await.unwrap().bytes).unwrap();
+    assert_eq!(final_image.dimensions(), preview_image.dimensions());
+    //every tile lands in the same place, so the background shows in the same pixels
+    let white = |pixel: Rgba<u8>| pixel.0[..3].iter().all(|&c| c > 230);
+    for y in 0..final_image.height() {
+        for x in 0..final_image.width() {
+            let (a, b) = (final_image.get_pixel(x, y), preview_image.get_pixel(x, y));
+            assert_eq!(white(a), white(b), "tiles differ at {},{}", x, y);
+        }
+    }
+    //the planned geometry is the same for both
+    assert_eq!(
+        plan_layout_geometry(buffers.clone(), &options().build().unwrap())
+            .await
+            .unwrap(),
+        plan_layout_geometry(
+            buffers,
+            &options()
+                .set_render_quality(RenderQuality::Preview)
+                .build()
+                .unwrap()
+        )
+        .await
+        .unwrap()
+    );
+
+    //text, rounded corners and shadows keep hard edges in a preview
+    let edged = |render_quality| {
+        let tiles = vec![
+            Tile::Image(sample_image_bytes(40, 30, [200, 0, 0, 255])),
+            Tile::TextCard {
+                text: "Ab".to_string(),
+                style: TextCardStyle::new(test_font_bytes(), Rgba([0, 0, 0, 255]), Some(16.0)),
+                background: Rgba([255, 255, 255, 255]),
+            },
+        ];
+        let options = CreateBundledImageOptionsBuilder::new()
+            .set_column(2)
+            .set_member_dimension(40, 30)
+            .set_padding(6)
+            .set_background(Rgba([255, 255, 255, 255]))
+            .set_corner_radius(8)
+            .set_drop_shadow(DropShadow::new(3, 2, 2, Rgba([0, 0, 0, 128])))
+            .set_render_quality(render_quality)
+            .set_output_format(OutputFormat::Png)
+            .build()
+            .unwrap();
+        async move {
+            let bytes = create_bundled_image_from_tiles(tiles, options)
+                .await
+                .unwrap();
+            image::load_from_memory(&bytes).unwrap().to_rgba8()
+        }
+    };
+    let colors = |image: &image::RgbaImage| {
+        image
+            .pixels()
+            .map(|pixel| pixel.0)
+            .collect::<std::collections::HashSet<_>>()
+    };
+    let final_image = edged(RenderQuality::Final).await;
+    let preview_image = edged(RenderQuality::Preview).await;
+    assert_eq!(final_image.dimensions(), preview_image.dimensions());
+    //background, image, text and one shade of shadow
+    assert_eq!(colors(&preview_image).len(), 4);
+    assert!(colors(&final_image).len() > 4);
+}
+
+#[test]
+fn test_table_from_csv() {
+    use super::*;
+    let csv = "\u{feff}size,chest,\"note\"\r\nS,88,\"fits \"\"small\"\", check\"\r\nM,92,\"two\nlines\"\r\n\r\n";
+    let table = TableBase::from_csv(csv.as_bytes(), 2).unwrap();
+    assert_eq!(table.head, vec!["size", "chest", "note"]);
+    assert_eq!(
+        table.body,
+        vec![
+            vec!["S", "88", "fits \"small\", check"],
+            vec!["M", "92", "two\nlines"],
+        ]
+    );
+    let table = TableBase::from_tsv("size\tchest\nS\t88".as_bytes(), 2).unwrap();
+    assert_eq!(table.head, vec!["size", "chest"]);
+    assert_eq!(table.body, vec![vec!["S", "88"]]);
+    assert!(matches!(
+        TableBase::from_csv("size,chest\nS".as_bytes(), 2),
+        Err(ImageCombinerError::InvalidTable(_))
+    ));
+    assert!(matches!(
+        TableBase::from_csv("size,\"chest\nS,88".as_bytes(), 2),
+        Err(ImageCombinerError::InvalidTable(_))
+    ));
+    assert!(TableBase::from_csv("".as_bytes(), 2).is_err());
+}
+
+#[tokio::test]
+async fn test_prometheus_metrics() {
+    use super::*;
+    let exporter = std::sync::Arc::new(PrometheusExporter::new());
+    let options = || {
+        CreateBundledImageOptionsBuilder::new()
+            .set_column(2)
+            .set_worker_pool(WorkerPool::new(2).unwrap())
+            .set_metrics(exporter.clone())
+            .build()
+            .unwrap()
+    };
+    let buffers = vec![sample_image_bytes(40, 30, [200, 0, 0, 255]); 2];
+    create_bundled_image(buffers, options()).await.unwrap();
+    assert!(
+        create_bundled_image(vec![b"not an image".to_vec()], options())
+            .await
+            .is_err()
+    );
+    let metrics = exporter.render();
+    assert!(metrics.contains("\nimage_combiner_jobs_total 2\n"));
+    assert!(metrics.contains("\nimage_combiner_job_seconds_count 2\n"));
+    assert!(metrics.contains("\nimage_combiner_job_seconds_bucket{le=\"+Inf\"} 2\n"));
+    assert!(metrics.contains("\nimage_combiner_errors_total{code=\"decode\"} 1\n"));
+    assert!(metrics.contains("\nimage_combiner_stage_seconds_count{stage=\"encode\"} 1\n"));
+    assert_eq!(
+        ImageCombinerError::Tile {
+            index: 0,
+            error: Box::new(ImageCombinerError::Fetch("timeout".to_string())),
+        }
+        .code(),
+        "fetch"
+    );
+}
+
+#[cfg(feature = "project")]
+#[tokio::test]
+async fn test_project_round_trip() {
+    use super::*;
+    let dir = std::env::temp_dir().join("image_combiner_project");
+    std::fs::create_dir_all(&dir).unwrap();
+    let inputs: Vec<std::path::PathBuf> = (1..=2)
+        .map(|no| {
+            let path = dir.join(format!("{}.png", no));
+            std::fs::write(&path, sample_image_bytes(40, 40, [128, 128, 128, 255])).unwrap();
+            path
+        })
+        .collect();
+    let overlay = dir.join("overlay.png");
+    std::fs::write(&overlay, sample_image_bytes(10, 10, [200, 100, 0, 255])).unwrap();
+    let options = CreateBundledImageOptionsBuilder::new()
+        .set_column(2)
+        .set_outer_margin_unit(Unit::Percent(10.0))
+        .set_background(Rgba([0, 0, 0, 255]))
+        .set_fit(Fit::Cover)
+        .set_transforms(vec![(1, Transform::FlipHorizontal)])
+        .set_output_format(OutputFormat::Png);
+    let mut layer = ProjectOverlay::new(overlay, BlendMode::Multiply);
+    layer.position = OverlayPosition::new(Anchor::TopLeft);
+    let project = Project::new(inputs, options).with_overlay(layer);
+    let path = dir.join("bundle.icproj");
+    project.save(&path).await.unwrap();
+    let loaded = Project::load(&path).await.unwrap();
+    assert_eq!(loaded.to_json(), project.to_json());
+    let image = image::load_from_memory(&loaded.render().await.unwrap().bytes).unwrap();
+    assert_eq!(image.to_rgba8().get_pixel(5, 5), &Rgba([100, 50, 0, 255]));
+    assert_eq!(
+        image.to_rgba8().get_pixel(2, 2),
+        &Rgba([0, 0, 0, 255]),
+        "outer margin is kept as a percent"
+    );
+    assert!(Project::from_json(r#"{"version":2,"inputs":[]}"#).is_err());
+}
+
+#[cfg(feature = "project")]
+#[tokio::test]
+async fn test_project_incremental() {
+    use super::*;
+    let dir = std::env::temp_dir().join("image_combiner_project_incremental");
+    std::fs::create_dir_all(&dir).unwrap();
+    let write = |name: &str, size: u32, color: [u8; 4]| {
+        let path = dir.join(name);
+        std::fs::write(&path, sample_image_bytes(size, size, color)).unwrap();
+        path
+    };
+    let grey = write("grey.png", 40, [128, 128, 128, 255]);
+    let red = write("red.png", 40, [200, 0, 0, 255]);
+    let small = write("small.png", 24, [0, 0, 200, 255]);
+    let options = || {
+        CreateBundledImageOptionsBuilder::new()
+            .set_column(3)
+            .set_member_dimension(40, 40)
+            .set_output_format(OutputFormat::Png)
+    };
+    let project = Project::new(vec![grey.clone(); 3], options());
+    let first = project.render_incremental(None).await.unwrap();
+    assert_eq!(first.redrawn_inputs, vec![0, 1, 2]);
+
+    let edited = Project::new(vec![grey.clone(), red.clone(), grey.clone()], options());
+    let second = edited.render_incremental(Some(&first)).await.unwrap();
+    assert_eq!(second.redrawn_inputs, vec![1]);
+    assert_eq!(second.output.bytes, edited.render().await.unwrap().bytes);
+
+    //an input edited in place keeps its path but is drawn again
+    std::fs::write(&red, sample_image_bytes(40, 40, [0, 200, 0, 255])).unwrap();
+    let second = edited.render_incremental(Some(&second)).await.unwrap();
+    assert_eq!(second.redrawn_inputs, vec![1]);
+    assert_eq!(second.output.bytes, edited.render().await.unwrap().bytes);
+
+    //the grid changes, so every cell is drawn again
+    let regrid = Project::new(vec![grey.clone(); 3], options().set_column(1));
+    let third = regrid.render_incremental(Some(&second)).await.unwrap();
+    assert_eq!(third.redrawn_inputs, vec![0, 1, 2]);
+    assert_eq!(third.output.bytes, regrid.render().await.unwrap().bytes);
+
+    //shadows reach into the gap on both sides of a cell
+    let shadowed = || {
+        options()
+            .set_gap(12)
+            .set_drop_shadow(DropShadow::new(4, -5, 5, Rgba([0, 0, 0, 160])))
+    };
+    let project = Project::new(vec![grey.clone(), red.clone(), grey.clone()], shadowed());
+    let first = project.render_incremental(None).await.unwrap();
+    let edited = Project::new(vec![grey.clone(), small, grey], shadowed());
+    let second = edited.render_incremental(Some(&first)).await.unwrap();
+    assert_eq!(second.redrawn_inputs, vec![1]);
+    assert_eq!(second.output.bytes, edited.render().await.unwrap().bytes);
+}
+
+#[tokio::test]
+async fn test_storage() {
+    use super::*;
+    let storage = MemoryStorage::new();
+    for no in 1..=2 {
+        storage
+            .put(
+                &format!("in/{}.png", no),
+                sample_image_bytes(40, 40, [200, 0, 0, 255]),
+                &UploadOptions::default(),
+            )
+            .await
+            .unwrap();
+    }
+    let keys = vec!["in/1.png".to_string(), "in/2.png".to_string()];
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_column(2)
+        .set_padding(0)
+        .build()
+        .unwrap();
+    let upload_options = UploadOptionsBuilder::new()
+        .set_cache_control("public, max-age=86400")
+        .set_metadata([("item".to_string(), "A2103".to_string())].into())
+        .build();
+    let bundled =
+        create_bundled_image_in_storage(&storage, keys, "out/bundle.jpeg", option, upload_options)
+            .await
+            .unwrap();
+    assert_eq!(storage.get("out/bundle.jpeg").await.unwrap(), bundled.bytes);
+    //the content type follows the output format when none is given
+    let uploaded = storage.upload_options("out/bundle.jpeg").unwrap();
+    assert_eq!(uploaded.content_type(), Some("image/jpeg"));
+    assert_eq!(uploaded.cache_control(), Some("public, max-age=86400"));
+    assert_eq!(uploaded.metadata()["item"], "A2103");
+    assert!(matches!(
+        storage.get("in/3.png").await,
+        Err(ImageCombinerError::Io(_))
+    ));
+
+    let dir = std::env::temp_dir().join("image_combiner_storage");
+    let storage = FsStorage::new(&dir);
+    storage
+        .put("nested/a.bin", vec![1, 2, 3], &UploadOptions::default())
+        .await
+        .unwrap();
+    assert_eq!(
+        std::fs::read(dir.join("nested/a.bin")).unwrap(),
+        vec![1, 2, 3]
+    );
+    assert_eq!(storage.get("nested/a.bin").await.unwrap(), vec![1, 2, 3]);
+    //keys can not reach outside the root
+    let outside = std::env::temp_dir().join("image_combiner_outside.bin");
+    for key in [
+        "../image_combiner_outside.bin",
+        "nested/../../image_combiner_outside.bin",
+        outside.to_str().unwrap(),
+        "./a.bin",
+        "",
+    ] {
+        assert!(matches!(
+            storage.put(key, vec![1], &UploadOptions::default()).await,
+            Err(ImageCombinerError::InvalidOptions(_))
+        ));
+        assert!(matches!(
+            storage.get(key).await,
+            Err(ImageCombinerError::InvalidOptions(_))
+        ));
+    }
+    assert!(!outside.exists());
+}
+
+#[tokio::test]
+async fn test_target_file_size() {
+    use super::*;
+    let noise_bytes = noise_image_bytes(120, 90);
+    let full = create_bundled_image(
+        vec![noise_bytes.clone()],
+        CreateBundledImageOptionsBuilder::new().build().unwrap(),
+    )
+    .await
+    .unwrap();
+    assert_eq!(full.quality, Some(100));
+    let target = full.bytes.len() as u64 / 3;
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_target_file_size(target)
+        .build()
+        .unwrap();
+    let bundled = create_bundled_image(vec![noise_bytes.clone()], option)
+        .await
+        .unwrap();
+    let quality = bundled.quality.unwrap();
+    assert!(bundled.bytes.len() as u64 <= target);
+    //one step up would no longer fit
+    let option = CreateBundledImageOptionsBuilder::new()
+        .set_encode_options(EncodeOptionsBuilder::new().set_quality(quality + 1).build())
+        .build()
+        .unwrap();
+    let above = create_bundled_image_from_bytes(vec![noise_bytes], option)
+        .await
+        .unwrap();
+    assert!(above.len() as u64 > target);
+}
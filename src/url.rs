@@ -0,0 +1,104 @@
+use crate::fetch::{fetch_all, FetchError, FetchOptions, FetchOptionsBuilder};
+use crate::{
+    create_bundled_image_from_bytes, CreateBundledImageOptions, ImageCombinerError, ImageSource,
+};
+use log::debug;
+use std::collections::VecDeque;
+
+pub async fn create_bundled_image_from_urls(
+    urls: Vec<String>,
+    options: CreateBundledImageOptions,
+    fetch_options: FetchOptions,
+) -> Result<Vec<u8>, ImageCombinerError> {
+    let source = UrlImageSource::new(urls).with_fetch_options(fetch_options);
+    create_bundled_image_from_bytes(source, options).await
+}
+
+pub struct UrlImageSource {
+    urls: Vec<String>,
+    fetch_options: FetchOptions,
+    fetched: Option<VecDeque<Vec<u8>>>,
+    attempts: Vec<u32>,
+}
+
+impl UrlImageSource {
+    pub fn new(urls: Vec<String>) -> Self {
+        Self {
+            urls,
+            fetch_options: FetchOptionsBuilder::new().build(),
+            fetched: None,
+            attempts: Vec::new(),
+        }
+    }
+
+    pub fn with_fetch_options(mut self, fetch_options: FetchOptions) -> Self {
+        self.fetch_options = fetch_options;
+        self
+    }
+}
+
+impl ImageSource for UrlImageSource {
+    async fn next_image(&mut self) -> Option<Result<Vec<u8>, ImageCombinerError>> {
+        if self.fetched.is_none() {
+            //download everything up front so the fetches run concurrently
+            let urls = std::mem::take(&mut self.urls);
+            debug!("fetch {} images", urls.len());
+            let client = reqwest::Client::new();
+            let fetched = fetch_all(
+                urls,
+                &self.fetch_options,
+                |url| url_host(url),
+                move |url: String| {
+                    let client = client.clone();
+                    async move { fetch_once(&client, &url).await }
+                },
+            )
+            .await;
+            match fetched {
+                Ok((buffers, attempts)) => {
+                    self.fetched = Some(buffers.into());
+                    self.attempts = attempts;
+                }
+                Err(e) => {
+                    self.fetched = Some(VecDeque::new());
+                    return Some(Err(e));
+                }
+            }
+        }
+        self.fetched.as_mut()?.pop_front().map(Ok)
+    }
+
+    fn fetch_attempts(&self) -> Vec<u32> {
+        self.attempts.clone()
+    }
+
+    fn seed(&mut self, seed: u64) {
+        self.fetch_options.seed = Some(seed);
+    }
+}
+
+async fn fetch_once(client: &reqwest::Client, url: &str) -> Result<Vec<u8>, FetchError> {
+    let res = client
+        .get(url)
+        .send()
+        .await
+        .and_then(|res| res.error_for_status())
+        .map_err(classify)?;
+    Ok(res.bytes().await.map_err(classify)?.to_vec())
+}
+
+fn url_host(url: &str) -> String {
+    let rest = url.split_once("://").map_or(url, |(_, rest)| rest);
+    let authority = rest.split(['/', '?', '#']).next().unwrap_or_default();
+    //drop any user info before the host
+    authority.rsplit('@').next().unwrap_or_default().to_string()
+}
+
+//client errors other than rate limiting are answered the same way on every attempt
+fn classify(e: reqwest::Error) -> FetchError {
+    let retryable = match e.status() {
+        Some(status) => status.is_server_error() || status.as_u16() == 429,
+        None => !e.is_builder() && !e.is_redirect(),
+    };
+    FetchError::new(e, retryable)
+}